@@ -0,0 +1,37 @@
+/* C API of parity-wasm, enabled by the `capi` cargo feature. */
+
+#ifndef PARITY_WASM_H
+#define PARITY_WASM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Decoded module, owned by the caller. */
+typedef struct pwasm_module pwasm_module;
+
+/* Returns NULL on failure and sets `*error_out` (if not NULL) to a message
+ * to release with `pwasm_error_free`. */
+pwasm_module *pwasm_module_deserialize(const uint8_t *bytes, size_t len, char **error_out);
+/* Returns a buffer to release with `pwasm_bytes_free`, or NULL on failure. */
+uint8_t *pwasm_module_serialize(const pwasm_module *module, size_t *len_out, char **error_out);
+void pwasm_module_free(pwasm_module *module);
+
+size_t pwasm_module_function_count(const pwasm_module *module);
+size_t pwasm_module_export_count(const pwasm_module *module);
+/* UTF-8 name, not nul-terminated, borrowed from the module. NULL if out of range. */
+const uint8_t *pwasm_module_export_name(const pwasm_module *module, size_t index, size_t *len_out);
+/* 0 function, 1 table, 2 memory, 3 global, -1 if out of range. */
+int32_t pwasm_module_export_kind(const pwasm_module *module, size_t index);
+
+void pwasm_bytes_free(uint8_t *bytes, size_t len);
+void pwasm_error_free(char *error);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PARITY_WASM_H */
@@ -0,0 +1,119 @@
+//! Macros embedding WebAssembly modules written in the text format.
+//!
+//! Both macros parse and validate the module when the crate using them is
+//! built, so that a malformed or invalid fixture fails the build, and expand
+//! to the binary module as a `&'static [u8; N]`:
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate parity_wasm_macros;
+//! extern crate parity_wasm;
+//!
+//! let inline: parity_wasm::elements::Module = parity_wasm::deserialize_buffer(
+//! 	wat!(r#"(module (func (export "main")))"#)
+//! ).unwrap();
+//! let fixture = include_wat!("tests/fixtures/add.wat");
+//! ```
+
+extern crate proc_macro;
+extern crate parity_wasm;
+
+use std::fs;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use proc_macro::{TokenStream, TokenTree, Literal};
+use parity_wasm::elements::serialize;
+use parity_wasm::text::from_wat;
+use parity_wasm::validation::{validate, Features};
+
+/// Embeds the module written in the text format in the given string literal.
+#[proc_macro]
+pub fn wat(input: TokenStream) -> TokenStream {
+	expand(input, |literal| Ok(literal))
+}
+
+/// Embeds the module written in the text format in the file at the given
+/// path, relative to the directory of the manifest of the crate being
+/// built.
+#[proc_macro]
+pub fn include_wat(input: TokenStream) -> TokenStream {
+	expand(input, |path| {
+		let path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(path);
+		fs::read_to_string(&path).map_err(|error| format!("cannot read {}: {}", path.display(), error))
+	})
+}
+
+/// Expands to the module whose source is given by `source` from the string
+/// literal of `input`, or to a compile error.
+fn expand<F: FnOnce(String) -> Result<String, String>>(input: TokenStream, source: F) -> TokenStream {
+	let result = string_literal(input)
+		.and_then(source)
+		.and_then(|source| {
+			let module = from_wat(&source).map_err(|error| format!("malformed module: {}", error))?;
+			validate(module.clone(), &Features::default()).map_err(|error| format!("invalid module: {}", error))?;
+			serialize(module).map_err(|error| format!("cannot serialize module: {}", error))
+		});
+	match result {
+		Ok(bytes) => TokenTree::Literal(Literal::byte_string(&bytes)).into(),
+		Err(message) => TokenStream::from_str(&format!("compile_error!({:?})", message))
+			.expect("a call to compile_error! is valid Rust; qed"),
+	}
+}
+
+/// Value of the single string literal, raw or not, of `input`.
+fn string_literal(input: TokenStream) -> Result<String, String> {
+	let mut tokens = input.into_iter();
+	let literal = match (tokens.next(), tokens.next()) {
+		(Some(TokenTree::Literal(literal)), None) => literal.to_string(),
+		_ => return Err("expected a string literal".into()),
+	};
+	if literal.starts_with('r') {
+		let hashes = literal[1..].find('"').ok_or("expected a string literal")?;
+		return Ok(literal[hashes + 2..literal.len() - hashes - 1].to_string());
+	}
+	if !literal.starts_with('"') {
+		return Err("expected a string literal".into());
+	}
+	unescape(&literal[1..literal.len() - 1])
+}
+
+/// Value of the (non-raw) string literal whose content is `literal`.
+fn unescape(literal: &str) -> Result<String, String> {
+	let mut value = String::new();
+	let mut chars = literal.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			value.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => value.push('\n'),
+			Some('t') => value.push('\t'),
+			Some('r') => value.push('\r'),
+			Some('0') => value.push('\0'),
+			Some('\\') => value.push('\\'),
+			Some('"') => value.push('"'),
+			Some('\'') => value.push('\''),
+			Some('\n') => {
+				while chars.peek().is_some_and(|c| c.is_whitespace()) {
+					chars.next();
+				}
+			},
+			Some('x') => {
+				let hex: String = chars.by_ref().take(2).collect();
+				let byte = u8::from_str_radix(&hex, 16).map_err(|_| "invalid escape in string literal")?;
+				value.push(byte as char);
+			},
+			Some('u') => {
+				let code: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+				let c = u32::from_str_radix(&code.replace('_', ""), 16).ok()
+					.and_then(std::char::from_u32)
+					.ok_or("invalid escape in string literal")?;
+				value.push(c);
+			},
+			_ => return Err("invalid escape in string literal".into()),
+		}
+	}
+	Ok(value)
+}
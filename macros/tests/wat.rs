@@ -0,0 +1,30 @@
+#[macro_use]
+extern crate parity_wasm_macros;
+extern crate parity_wasm;
+
+use parity_wasm::deserialize_buffer;
+use parity_wasm::elements::{Internal, Module};
+
+#[test]
+fn inline() {
+	let module: Module = deserialize_buffer(wat!(r#"
+		(module
+			(memory 1)
+			(func (export "main") (result i32)
+				(i32.load (i32.const 0)))
+			(data (i32.const 0) "\2a"))
+	"#)).expect("module to be deserialized");
+	assert_eq!(module.export_section().expect("export section").entries()[0].field(), "main");
+	assert_eq!(module.data_section().expect("data section").entries()[0].value(), &[42]);
+
+	let escaped: Module = deserialize_buffer(wat!("(module (func (export \"a\\tb\")))")).expect("module to be deserialized");
+	assert_eq!(escaped.export_section().expect("export section").entries()[0].field(), "a\tb");
+}
+
+#[test]
+fn include() {
+	let module: Module = deserialize_buffer(include_wat!("tests/fixtures/add.wat")).expect("module to be deserialized");
+	let export = &module.export_section().expect("export section").entries()[0];
+	assert_eq!(export.field(), "add");
+	assert_eq!(*export.internal(), Internal::Function(0));
+}
@@ -0,0 +1,194 @@
+use std::vec::Vec;
+use elements::{Instruction, BlockType, FunctionType, Type};
+
+/// Where a branch transfers control to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchTarget {
+	/// Start of the loop whose `loop` instruction is at this position:
+	/// execution continues with the instruction following it.
+	Loop(usize),
+	/// End of the block (or `if`) whose `end` instruction is at this
+	/// position: execution continues with the instruction following it.
+	End(usize),
+	/// Out of the function (branch to the implicit function block).
+	Return,
+}
+
+/// Resolved target of a branch with the number of values it carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedBranch {
+	/// Target of the branch.
+	pub target: BranchTarget,
+	/// Number of operands transferred to the target.
+	pub arity: u32,
+}
+
+/// Jump table of a `br_table` instruction with its labels resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JumpTable {
+	position: usize,
+	targets: Vec<ResolvedBranch>,
+	default: ResolvedBranch,
+}
+
+impl JumpTable {
+	/// Position of the `br_table` instruction.
+	pub fn position(&self) -> usize { self.position }
+
+	/// Resolved targets, indexed by the operand of the `br_table`.
+	pub fn targets(&self) -> &[ResolvedBranch] { &self.targets }
+
+	/// Target used when the operand is out of range.
+	pub fn default(&self) -> ResolvedBranch { self.default }
+
+	/// Target taken for the operand `value`.
+	pub fn resolve(&self, value: u32) -> ResolvedBranch {
+		self.targets.get(value as usize).cloned().unwrap_or(self.default)
+	}
+}
+
+/// Number of parameters and results of a block of type `block_type`, `None`
+/// if it refers to an unknown type.
+fn block_arity(block_type: BlockType, types: &[Type]) -> Option<(u32, u32)> {
+	match block_type {
+		BlockType::NoResult => Some((0, 0)),
+		BlockType::Value(_) => Some((0, 1)),
+		BlockType::TypeIndex(type_ref) => types.get(type_ref as usize).map(|ty| match *ty {
+			Type::Function(ref signature) => (signature.params().len() as u32, signature.results().len() as u32),
+		}),
+	}
+}
+
+struct Frame {
+	target: BranchTarget,
+	arity: u32,
+}
+
+/// Positions of the `end` instruction matching every block, loop or `if`,
+/// indexed by the position of the instruction opening it.
+fn matching_ends(code: &[Instruction]) -> Option<Vec<Option<usize>>> {
+	let mut ends = vec![None; code.len()];
+	let mut open = Vec::new();
+	for (position, instruction) in code.iter().enumerate() {
+		match *instruction {
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => open.push(position),
+			Instruction::End => match open.pop() {
+				Some(start) => ends[start] = Some(position),
+				// The final `end` closes the function body itself.
+				None => return if position + 1 == code.len() { Some(ends) } else { None },
+			},
+			_ => {},
+		}
+	}
+	None
+}
+
+/// Resolves the labels of every `br_table` in the body `code` of a function
+/// with the given `signature`, in a module with the given `types`.
+///
+/// Returns `None` when the body is malformed (unbalanced blocks, labels out
+/// of range or unknown block types).
+pub fn resolve_jump_tables(code: &[Instruction], signature: &FunctionType, types: &[Type]) -> Option<Vec<JumpTable>> {
+	let ends = matching_ends(code)?;
+	let mut frames = vec![Frame {
+		target: BranchTarget::Return,
+		arity: signature.results().len() as u32,
+	}];
+	let mut tables = Vec::new();
+
+	for (position, instruction) in code.iter().enumerate() {
+		match *instruction {
+			Instruction::Block(block_type) | Instruction::If(block_type) => frames.push(Frame {
+				target: BranchTarget::End(ends[position]?),
+				arity: block_arity(block_type, types)?.1,
+			}),
+			// Branching to a loop restarts it, which takes its parameters.
+			Instruction::Loop(block_type) => frames.push(Frame {
+				target: BranchTarget::Loop(position),
+				arity: block_arity(block_type, types)?.0,
+			}),
+			Instruction::End => {
+				frames.pop();
+			},
+			Instruction::BrTable(ref table) => {
+				let resolve = |depth: u32| {
+					let idx = frames.len().checked_sub(depth as usize + 1)?;
+					let frame = &frames[idx];
+					Some(ResolvedBranch { target: frame.target, arity: frame.arity })
+				};
+				let targets = table.table.iter().map(|&depth| resolve(depth)).collect::<Option<Vec<_>>>()?;
+				let default = resolve(table.default)?;
+				tables.push(JumpTable { position, targets, default });
+			},
+			_ => {},
+		}
+	}
+
+	Some(tables)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{resolve_jump_tables, BranchTarget, ResolvedBranch};
+	use elements::{BlockType, BrTableData, FunctionType, Type, ValueType};
+	use elements::Instruction::*;
+
+	#[test]
+	fn resolve() {
+		let code = vec![
+			Block(BlockType::Value(ValueType::I32)),          // 0
+				Loop(BlockType::NoResult),                    // 1
+					I32Const(1),                              // 2
+					GetLocal(0),                              // 3
+					BrTable(Box::new(BrTableData {            // 4
+						table: Box::new([0, 1]),
+						default: 2,
+					})),
+				End,                                          // 5
+				I32Const(0),                                  // 6
+			End,                                              // 7
+			End,                                              // 8
+		];
+		let signature = FunctionType::with_results(vec![ValueType::I32], vec![ValueType::I32]);
+
+		let tables = resolve_jump_tables(&code, &signature, &[]).expect("body to be well formed");
+		assert_eq!(tables.len(), 1);
+		assert_eq!(tables[0].position(), 4);
+		assert_eq!(tables[0].targets(), &[
+			ResolvedBranch { target: BranchTarget::Loop(1), arity: 0 },
+			ResolvedBranch { target: BranchTarget::End(7), arity: 1 },
+		][..]);
+		assert_eq!(tables[0].resolve(7), ResolvedBranch { target: BranchTarget::Return, arity: 1 });
+	}
+
+	#[test]
+	fn type_index() {
+		let types = [Type::Function(FunctionType::with_results(vec![ValueType::I32], vec![ValueType::I32; 2]))];
+		let code = vec![
+			I32Const(1),
+			Loop(BlockType::TypeIndex(0)),
+				GetLocal(0),
+				BrTable(Box::new(BrTableData { table: Box::new([0]), default: 1 })),
+			End,
+			End,
+		];
+		let signature = FunctionType::with_results(vec![ValueType::I32], vec![ValueType::I32; 2]);
+
+		let tables = resolve_jump_tables(&code, &signature, &types).expect("body to be well formed");
+		assert_eq!(tables[0].targets(), &[ResolvedBranch { target: BranchTarget::Loop(1), arity: 1 }][..]);
+		assert_eq!(tables[0].default(), ResolvedBranch { target: BranchTarget::Return, arity: 2 });
+		assert!(resolve_jump_tables(&code, &signature, &[]).is_none());
+	}
+
+	#[test]
+	fn malformed() {
+		let signature = FunctionType::default();
+		let code = vec![
+			GetLocal(0),
+			BrTable(Box::new(BrTableData { table: Box::new([1]), default: 0 })),
+			End,
+		];
+		assert!(resolve_jump_tables(&code, &signature, &[]).is_none());
+		assert!(resolve_jump_tables(&[Block(BlockType::NoResult), End], &signature, &[]).is_none());
+	}
+}
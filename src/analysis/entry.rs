@@ -0,0 +1,113 @@
+use std::vec::Vec;
+use elements::{Module, Internal};
+
+/// Exports running the constructors of emscripten modules, newest first.
+const EMSCRIPTEN_CTORS: [&str; 3] = ["__wasm_call_ctors", "__post_instantiate", "___wasm_call_ctors"];
+
+/// Convention a module follows to be run by its embedder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryConvention {
+	/// WASI command: `_start` runs the whole program.
+	Command,
+	/// WASI reactor: `_initialize` prepares the instance, whose exports are
+	/// then called as needed.
+	Reactor,
+	/// Emscripten program: the constructors run before `main`.
+	Emscripten,
+	/// `main` is exported without any constructor to call first.
+	Main,
+	/// No entry point: only the start function runs, at instantiation.
+	Library,
+}
+
+/// How to run a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoint<'a> {
+	convention: EntryConvention,
+	calls: Vec<&'a str>,
+}
+
+impl<'a> EntryPoint<'a> {
+	/// Convention of the module.
+	pub fn convention(&self) -> EntryConvention { self.convention }
+
+	/// Exported functions to call after instantiation, in order.
+	pub fn calls(&self) -> &[&'a str] { &self.calls }
+}
+
+/// Classifies the entry point convention of `module` from its exported
+/// functions.
+///
+/// `_start` makes a command and `_initialize` a reactor; WASI forbids
+/// exporting both, and `_start` wins if a module does. Otherwise, a module
+/// exporting `main` (or `_main`, for older emscripten) is an emscripten
+/// program if it exports constructors or imports `emscripten_*` functions.
+pub fn entry_point(module: &Module) -> EntryPoint<'_> {
+	let exports = module.export_section().map_or(&[][..], |es| es.entries());
+	let function = |name: &str| exports.iter()
+		.find(|entry| entry.field() == name && matches!(*entry.internal(), Internal::Function(_)))
+		.map(|entry| entry.field());
+
+	if let Some(start) = function("_start") {
+		return EntryPoint { convention: EntryConvention::Command, calls: vec![start] };
+	}
+	if let Some(initialize) = function("_initialize") {
+		return EntryPoint { convention: EntryConvention::Reactor, calls: vec![initialize] };
+	}
+	let main = match function("main").or_else(|| function("_main")) {
+		Some(main) => main,
+		None => return EntryPoint { convention: EntryConvention::Library, calls: Vec::new() },
+	};
+	let ctors = EMSCRIPTEN_CTORS.iter().filter_map(|&name| function(name)).next();
+	let emscripten_imports = module.import_section().map_or(&[][..], |is| is.entries()).iter()
+		.any(|entry| entry.field().starts_with("emscripten_"));
+	if ctors.is_none() && !emscripten_imports {
+		return EntryPoint { convention: EntryConvention::Main, calls: vec![main] };
+	}
+	EntryPoint {
+		convention: EntryConvention::Emscripten,
+		calls: ctors.into_iter().chain(Some(main)).collect(),
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{entry_point, EntryConvention};
+	use builder::{module, ModuleBuilder};
+
+	fn exporting(names: &[&str]) -> ModuleBuilder {
+		let mut builder = module().function().signature().build().body().build().build();
+		for name in names {
+			builder = builder.export().field(name).internal().func(0).build();
+		}
+		builder
+	}
+
+	#[test]
+	fn classify() {
+		let command = exporting(&["_initialize", "_start"]).build();
+		assert_eq!(entry_point(&command).convention(), EntryConvention::Command);
+		assert_eq!(entry_point(&command).calls(), &["_start"][..]);
+
+		let reactor = exporting(&["_initialize", "work"]).build();
+		assert_eq!(entry_point(&reactor).convention(), EntryConvention::Reactor);
+		assert_eq!(entry_point(&reactor).calls(), &["_initialize"][..]);
+
+		let emscripten = exporting(&["main", "__post_instantiate", "__wasm_call_ctors"]).build();
+		assert_eq!(entry_point(&emscripten).convention(), EntryConvention::Emscripten);
+		assert_eq!(entry_point(&emscripten).calls(), &["__wasm_call_ctors", "main"][..]);
+
+		let old_emscripten = exporting(&["_main"])
+			.import().module("env").field("emscripten_memcpy_big").external().func(0).build()
+			.build();
+		assert_eq!(entry_point(&old_emscripten).calls(), &["_main"][..]);
+		assert_eq!(entry_point(&old_emscripten).convention(), EntryConvention::Emscripten);
+
+		let main = exporting(&["main"]).build();
+		assert_eq!(entry_point(&main).convention(), EntryConvention::Main);
+
+		let library = exporting(&["add"]).export().field("_start").internal().memory(0).build().build();
+		assert_eq!(entry_point(&library).convention(), EntryConvention::Library);
+		assert!(entry_point(&library).calls().is_empty());
+	}
+}
@@ -0,0 +1,287 @@
+use std::vec::Vec;
+use elements::{Module, Instruction, ImportCountType};
+use super::loops::{find_loops, Loop};
+
+/// Static upper bound on the number of instructions executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionBound {
+	/// At most this many instructions are executed.
+	Bounded(u64),
+	/// No bound could be found: a loop without a detectable trip count, an
+	/// indirect call or recursion.
+	Unbounded,
+}
+
+impl InstructionBound {
+	fn add(self, other: InstructionBound) -> InstructionBound {
+		match (self, other) {
+			(InstructionBound::Bounded(a), InstructionBound::Bounded(b)) => a.checked_add(b).map_or(InstructionBound::Unbounded, InstructionBound::Bounded),
+			_ => InstructionBound::Unbounded,
+		}
+	}
+
+	fn times(self, count: u64) -> InstructionBound {
+		match self {
+			InstructionBound::Bounded(a) => a.checked_mul(count).map_or(InstructionBound::Unbounded, InstructionBound::Bounded),
+			InstructionBound::Unbounded => InstructionBound::Unbounded,
+		}
+	}
+
+	fn max(self, other: InstructionBound) -> InstructionBound {
+		match (self, other) {
+			(InstructionBound::Bounded(a), InstructionBound::Bounded(b)) => InstructionBound::Bounded(::std::cmp::max(a, b)),
+			_ => InstructionBound::Unbounded,
+		}
+	}
+}
+
+/// Value of the local `local` set right before `position` by
+/// `i32.const k; set_local local` (or `tee_local`), looking back through
+/// straight-line code only.
+fn initial_value(code: &[Instruction], position: usize, local: u32) -> Option<i32> {
+	for at in (0..position).rev() {
+		match code[at] {
+			Instruction::SetLocal(l) | Instruction::TeeLocal(l) if l == local => {
+				return match code.get(at.checked_sub(1)?) {
+					Some(&Instruction::I32Const(value)) => Some(value),
+					_ => None,
+				};
+			},
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) | Instruction::Else
+				| Instruction::End | Instruction::Br(_) | Instruction::BrIf(_) | Instruction::BrTable(_)
+				| Instruction::Return | Instruction::Unreachable => return None,
+			_ => {},
+		}
+	}
+	None
+}
+
+/// Number of iterations of `lp` in `code` if it is a counted loop.
+///
+/// Recognized loops are entered with an `i32` local set to a constant right
+/// before the loop, and end with the only back edge of the loop:
+///
+/// ```text
+/// get_local $i  i32.const step  i32.add  tee_local $i    (or set_local $i  get_local $i)
+/// i32.const limit  i32.lt_s (or i32.lt_u, i32.ne)  br_if 0
+/// end
+/// ```
+///
+/// where `step` is positive and `$i` is not written anywhere else in the
+/// loop. The body running before the condition is checked, the count is at
+/// least 1. Exits through other branches only make the count an upper bound.
+pub fn trip_count(code: &[Instruction], lp: &Loop) -> Option<u64> {
+	let end = lp.end();
+	if lp.back_edges() != [end.checked_sub(1)?] || code.get(end - 1) != Some(&Instruction::BrIf(0)) {
+		return None;
+	}
+	let condition = code.get(end.checked_sub(2)?)?;
+	let limit = match *code.get(end.checked_sub(3)?)? {
+		Instruction::I32Const(limit) => limit,
+		_ => return None,
+	};
+	let (local, increment) = match (code.get(end.checked_sub(5)?)?, code.get(end - 4)?) {
+		(&Instruction::I32Add, &Instruction::TeeLocal(local)) => (local, end.checked_sub(7)?),
+		(&Instruction::SetLocal(local), &Instruction::GetLocal(other)) if local == other => (local, end.checked_sub(8)?),
+		_ => return None,
+	};
+	if increment <= lp.start() {
+		return None;
+	}
+	let step = match code[increment..end - 3] {
+		[Instruction::GetLocal(l), Instruction::I32Const(step), Instruction::I32Add, Instruction::TeeLocal(_)]
+		| [Instruction::GetLocal(l), Instruction::I32Const(step), Instruction::I32Add, Instruction::SetLocal(_), Instruction::GetLocal(_)]
+			if l == local && step > 0 => i64::from(step),
+		_ => return None,
+	};
+	let written = code[lp.start() + 1..increment].iter().any(|instruction| match *instruction {
+		Instruction::SetLocal(l) | Instruction::TeeLocal(l) => l == local,
+		_ => false,
+	});
+	if written {
+		return None;
+	}
+	let initial = initial_value(code, lp.start(), local)?;
+
+	let (initial, limit, max) = match *condition {
+		Instruction::I32LtS | Instruction::I32Ne => (i64::from(initial), i64::from(limit), i64::from(i32::MAX)),
+		Instruction::I32LtU => (i64::from(initial as u32), i64::from(limit as u32), i64::from(u32::MAX)),
+		_ => return None,
+	};
+	// The counter must not wrap around before reaching the limit.
+	if limit - 1 + step > max {
+		return None;
+	}
+	let remaining = limit - initial;
+	if *condition == Instruction::I32Ne && (remaining <= 0 || remaining % step != 0) {
+		return None;
+	}
+	Some(::std::cmp::max(1, (remaining + step - 1) / step) as u64)
+}
+
+enum State {
+	Pending,
+	InProgress,
+	Done(InstructionBound),
+}
+
+struct Estimator<'a> {
+	module: &'a Module,
+	imported: usize,
+	states: Vec<State>,
+}
+
+impl<'a> Estimator<'a> {
+	fn function(&mut self, func_idx: u32) -> InstructionBound {
+		let body_idx = match (func_idx as usize).checked_sub(self.imported) {
+			Some(body_idx) => body_idx,
+			None => return InstructionBound::Bounded(0),
+		};
+		match self.states.get(body_idx) {
+			Some(&State::Done(bound)) => return bound,
+			Some(&State::InProgress) | None => return InstructionBound::Unbounded,
+			Some(&State::Pending) => {},
+		}
+		self.states[body_idx] = State::InProgress;
+		let module = self.module;
+		let code = module.code_section().expect("states are only created for bodies; qed")
+			.bodies()[body_idx].code().elements();
+		let loops = find_loops(code);
+		let mut position = 0;
+		let bound = self.sequence(code, &loops, &mut position).add(InstructionBound::Bounded(1));
+		self.states[body_idx] = State::Done(bound);
+		bound
+	}
+
+	/// Bound of the instructions from `position` to the `end` or `else`
+	/// closing the enclosing block, left at `position`.
+	fn sequence(&mut self, code: &[Instruction], loops: &[Loop], position: &mut usize) -> InstructionBound {
+		let mut bound = InstructionBound::Bounded(0);
+		while let Some(instruction) = code.get(*position) {
+			let start = *position;
+			*position += 1;
+			let cost = match *instruction {
+				Instruction::End | Instruction::Else => {
+					*position = start;
+					return bound;
+				},
+				Instruction::Block(_) => {
+					let inner = self.sequence(code, loops, position);
+					*position += 1;
+					inner.add(InstructionBound::Bounded(2))
+				},
+				Instruction::If(_) => {
+					let then = self.sequence(code, loops, position);
+					let mut cost = then;
+					if code.get(*position) == Some(&Instruction::Else) {
+						*position += 1;
+						cost = then.max(self.sequence(code, loops, position).add(InstructionBound::Bounded(1)));
+					}
+					*position += 1;
+					cost.add(InstructionBound::Bounded(2))
+				},
+				Instruction::Loop(_) => {
+					let body = self.sequence(code, loops, position);
+					*position += 1;
+					let trips = loops.iter().find(|lp| lp.start() == start).and_then(|lp| trip_count(code, lp));
+					match trips {
+						Some(trips) => body.times(trips).add(InstructionBound::Bounded(2)),
+						None => InstructionBound::Unbounded,
+					}
+				},
+				Instruction::Call(callee) => self.function(callee).add(InstructionBound::Bounded(1)),
+				Instruction::CallIndirect(_, _) => InstructionBound::Unbounded,
+				_ => InstructionBound::Bounded(1),
+			};
+			bound = bound.add(cost);
+		}
+		bound
+	}
+}
+
+/// Upper bound on the instructions executed by a call of each function
+/// body, in the order of the code section.
+///
+/// Every instruction counts for one, including the instructions of the
+/// functions called. Calls of imported functions only count for the `call`
+/// instruction itself.
+pub fn instruction_bounds(module: &Module) -> Vec<InstructionBound> {
+	let bodies = module.code_section().map_or(0, |cs| cs.bodies().len());
+	let imported = module.import_count(ImportCountType::Function);
+	let mut estimator = Estimator {
+		module,
+		imported,
+		states: (0..bodies).map(|_| State::Pending).collect(),
+	};
+	(0..bodies).map(|body_idx| estimator.function((imported + body_idx) as u32)).collect()
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{instruction_bounds, trip_count, InstructionBound};
+	use analysis::find_loops;
+	use builder::module;
+	use elements::{Instructions, Instruction, BlockType, ValueType, Local};
+	use elements::Instruction::*;
+
+	fn counted(initial: i32, step: i32, limit: i32, condition: Instruction) -> Vec<Instruction> {
+		vec![
+			I32Const(initial), SetLocal(0),
+			Loop(BlockType::NoResult),
+				Nop,
+				GetLocal(0), I32Const(step), I32Add, TeeLocal(0),
+				I32Const(limit), condition, BrIf(0),
+			End,
+			End,
+		]
+	}
+
+	#[test]
+	fn trip_counts() {
+		let count = |code: Vec<Instruction>| trip_count(&code, &find_loops(&code)[0]);
+		assert_eq!(count(counted(0, 1, 10, I32LtS)), Some(10));
+		assert_eq!(count(counted(0, 3, 10, I32LtU)), Some(4));
+		assert_eq!(count(counted(20, 1, 10, I32LtS)), Some(1));
+		assert_eq!(count(counted(0, 2, 10, I32Ne)), Some(5));
+		assert_eq!(count(counted(0, 3, 10, I32Ne)), None);
+		assert_eq!(count(counted(0, -1, 10, I32LtS)), None);
+		assert_eq!(count(counted(0, 2, i32::MAX, I32LtS)), None);
+
+		let mut written = counted(0, 1, 10, I32LtS);
+		written[3] = SetLocal(0);
+		assert_eq!(count(written), None);
+	}
+
+	#[test]
+	fn bounds() {
+		let module = module()
+			.function().signature().build()
+				.body()
+					.with_locals(vec![Local::new(1, ValueType::I32)])
+					.with_instructions(Instructions::new(counted(0, 1, 10, I32LtS)))
+					.build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					I32Const(1), If(BlockType::NoResult), Call(0), Else, Nop, Nop, End, End,
+				])).build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Call(2), End])).build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					Loop(BlockType::NoResult), Br(0), End, End,
+				])).build()
+				.build()
+			.build();
+
+		// 2 + loop (2 + 10 * 8) + end
+		assert_eq!(instruction_bounds(&module), vec![
+			InstructionBound::Bounded(85),
+			InstructionBound::Bounded(1 + 2 + 86 + 1),
+			InstructionBound::Unbounded,
+			InstructionBound::Unbounded,
+		]);
+	}
+}
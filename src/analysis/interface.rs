@@ -0,0 +1,253 @@
+use std::fmt::Write;
+use std::string::String;
+use std::vec::Vec;
+use elements::{
+	Module, External, Internal, Type, FunctionType, TableType, MemoryType, GlobalType, ResizableLimits,
+};
+
+/// Type of an item imported or exported by a module.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemType {
+	/// Function with this signature.
+	Function(FunctionType),
+	/// Table.
+	Table(TableType),
+	/// Linear memory.
+	Memory(MemoryType),
+	/// Global.
+	Global(GlobalType),
+}
+
+/// Item a module requires from its embedder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportItem<'a> {
+	module: &'a str,
+	field: &'a str,
+	item_type: ItemType,
+}
+
+impl<'a> ImportItem<'a> {
+	/// Module the item is imported from.
+	pub fn module(&self) -> &'a str { self.module }
+
+	/// Name of the item in its module.
+	pub fn field(&self) -> &'a str { self.field }
+
+	/// Type the item must have.
+	pub fn item_type(&self) -> &ItemType { &self.item_type }
+}
+
+/// Item a module provides to its embedder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportItem<'a> {
+	name: &'a str,
+	item_type: ItemType,
+}
+
+impl<'a> ExportItem<'a> {
+	/// Name the item is exported as.
+	pub fn name(&self) -> &'a str { self.name }
+
+	/// Type of the item.
+	pub fn item_type(&self) -> &ItemType { &self.item_type }
+}
+
+/// Language-neutral description of what a module imports and exports,
+/// in the order of the import and export sections.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interface<'a> {
+	imports: Vec<ImportItem<'a>>,
+	exports: Vec<ExportItem<'a>>,
+}
+
+impl<'a> Interface<'a> {
+	/// Items the module imports.
+	pub fn imports(&self) -> &[ImportItem<'a>] { &self.imports }
+
+	/// Items the module exports.
+	pub fn exports(&self) -> &[ExportItem<'a>] { &self.exports }
+
+	/// The interface as a JSON object with `imports` and `exports` arrays.
+	///
+	/// Each item has its `module` and `field` (imports) or `name` (exports)
+	/// and a `kind`, which is `function` (with `params` and `results`),
+	/// `table` (with `element`, `minimum` and `maximum`), `memory` (with
+	/// `minimum`, `maximum` and `shared`) or `global` (with `type` and
+	/// `mutable`). Missing maximums are `null`.
+	pub fn to_json(&self) -> String {
+		let mut out = String::from("{\"imports\":[");
+		for (idx, import) in self.imports.iter().enumerate() {
+			if idx > 0 {
+				out.push(',');
+			}
+			out.push_str("{\"module\":");
+			json_string(&mut out, import.module);
+			out.push_str(",\"field\":");
+			json_string(&mut out, import.field);
+			json_item_type(&mut out, &import.item_type);
+		}
+		out.push_str("],\"exports\":[");
+		for (idx, export) in self.exports.iter().enumerate() {
+			if idx > 0 {
+				out.push(',');
+			}
+			out.push_str("{\"name\":");
+			json_string(&mut out, export.name);
+			json_item_type(&mut out, &export.item_type);
+		}
+		out.push_str("]}");
+		out
+	}
+}
+
+/// Imports and exports of `module`, with their types.
+///
+/// Returns `None` if a function refers to a missing type or an export
+/// refers to a missing item.
+pub fn interface(module: &Module) -> Option<Interface<'_>> {
+	let types = module.type_section().map_or(&[][..], |ts| ts.types());
+	let signature = |type_ref: u32| types.get(type_ref as usize).map(|ty| match *ty {
+		Type::Function(ref func_type) => func_type.clone(),
+	});
+
+	let mut functions = Vec::new();
+	let mut tables = Vec::new();
+	let mut memories = Vec::new();
+	let mut globals = Vec::new();
+	let mut imports = Vec::new();
+	for entry in module.import_section().map_or(&[][..], |is| is.entries()) {
+		let item_type = match *entry.external() {
+			External::Function(type_ref) => {
+				let func_type = signature(type_ref)?;
+				functions.push(func_type.clone());
+				ItemType::Function(func_type)
+			},
+			External::Table(table_type) => {
+				tables.push(table_type);
+				ItemType::Table(table_type)
+			},
+			External::Memory(memory_type) => {
+				memories.push(memory_type);
+				ItemType::Memory(memory_type)
+			},
+			External::Global(global_type) => {
+				globals.push(global_type);
+				ItemType::Global(global_type)
+			},
+		};
+		imports.push(ImportItem { module: entry.module(), field: entry.field(), item_type });
+	}
+	for func in module.function_section().map_or(&[][..], |fs| fs.entries()) {
+		functions.push(signature(func.type_ref())?);
+	}
+	tables.extend(module.table_section().map_or(&[][..], |ts| ts.entries()).iter().cloned());
+	memories.extend(module.memory_section().map_or(&[][..], |ms| ms.entries()).iter().cloned());
+	globals.extend(module.global_section().map_or(&[][..], |gs| gs.entries()).iter().map(|global| *global.global_type()));
+
+	let mut exports = Vec::new();
+	for entry in module.export_section().map_or(&[][..], |es| es.entries()) {
+		let item_type = match *entry.internal() {
+			Internal::Function(idx) => ItemType::Function(functions.get(idx as usize)?.clone()),
+			Internal::Table(idx) => ItemType::Table(*tables.get(idx as usize)?),
+			Internal::Memory(idx) => ItemType::Memory(*memories.get(idx as usize)?),
+			Internal::Global(idx) => ItemType::Global(*globals.get(idx as usize)?),
+		};
+		exports.push(ExportItem { name: entry.field(), item_type });
+	}
+	Some(Interface { imports, exports })
+}
+
+/// Appends the fields describing `item_type` and closes the object.
+fn json_item_type(out: &mut String, item_type: &ItemType) {
+	match *item_type {
+		ItemType::Function(ref func_type) => {
+			out.push_str(",\"kind\":\"function\",\"params\":[");
+			json_list(out, func_type.params().iter());
+			out.push_str("],\"results\":[");
+			json_list(out, func_type.results().iter());
+			out.push(']');
+		},
+		ItemType::Table(ref table_type) => {
+			let _ = write!(out, ",\"kind\":\"table\",\"element\":\"{}\"", table_type.elem_type());
+			json_limits(out, table_type.limits());
+		},
+		ItemType::Memory(ref memory_type) => {
+			out.push_str(",\"kind\":\"memory\"");
+			json_limits(out, memory_type.limits());
+			let _ = write!(out, ",\"shared\":{}", memory_type.limits().shared());
+		},
+		ItemType::Global(ref global_type) => {
+			let _ = write!(out, ",\"kind\":\"global\",\"type\":\"{}\",\"mutable\":{}",
+				global_type.content_type(), global_type.is_mutable());
+		},
+	}
+	out.push('}');
+}
+
+fn json_list<T: ::std::fmt::Display, I: Iterator<Item = T>>(out: &mut String, items: I) {
+	for (idx, item) in items.enumerate() {
+		if idx > 0 {
+			out.push(',');
+		}
+		let _ = write!(out, "\"{}\"", item);
+	}
+}
+
+fn json_limits(out: &mut String, limits: &ResizableLimits) {
+	let _ = write!(out, ",\"minimum\":{},\"maximum\":", limits.initial());
+	match limits.maximum() {
+		Some(maximum) => { let _ = write!(out, "{}", maximum); },
+		None => out.push_str("null"),
+	}
+}
+
+fn json_string(out: &mut String, value: &str) {
+	out.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{interface, ItemType};
+	use builder::module;
+	use elements::{ExportEntry, Internal, ValueType};
+
+	#[test]
+	fn describe() {
+		let mut built = module()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("env").field("memory").external().memory(1, None).build()
+			.function().signature().with_param(ValueType::I32).build().body().build().build()
+			.function().signature().with_param(ValueType::I64).with_result(ValueType::F32).build().body().build().build()
+			.global().value_type().i32().mutable().init_expr(::elements::Instruction::I32Const(0)).build()
+			.export().field("run\"1\"").internal().func(2).build()
+			.export().field("counter").internal().global(0).build()
+			.build();
+
+		let summary = interface(&built).expect("module to be described");
+		assert_eq!(summary.imports().len(), 2);
+		assert_eq!(summary.imports()[0].field(), "log");
+		match *summary.exports()[0].item_type() {
+			ItemType::Function(ref func_type) => assert_eq!(func_type.results(), &[ValueType::F32][..]),
+			ref other => panic!("unexpected {:?}", other),
+		}
+		assert_eq!(summary.to_json(), concat!(
+			r#"{"imports":[{"module":"env","field":"log","kind":"function","params":["i32"],"results":[]},"#,
+			r#"{"module":"env","field":"memory","kind":"memory","minimum":1,"maximum":null,"shared":false}],"#,
+			r#""exports":[{"name":"run\"1\"","kind":"function","params":["i64"],"results":["f32"]},"#,
+			r#"{"name":"counter","kind":"global","type":"i32","mutable":true}]}"#,
+		));
+
+		built.export_section_mut().expect("export section").entries_mut()
+			.push(ExportEntry::new("missing".into(), Internal::Table(0)));
+		assert!(interface(&built).is_none());
+	}
+}
@@ -0,0 +1,215 @@
+use std::vec::Vec;
+use elements::{self, Module, External, Type, Serialize};
+
+/// Implementation limits of an engine, above which it refuses to compile a
+/// module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+	/// Maximum size of the module binary, in bytes.
+	pub module_size: u64,
+	/// Maximum number of types.
+	pub types: u64,
+	/// Maximum number of functions, imported ones included.
+	pub functions: u64,
+	/// Maximum number of imports.
+	pub imports: u64,
+	/// Maximum number of exports.
+	pub exports: u64,
+	/// Maximum number of globals, imported ones included.
+	pub globals: u64,
+	/// Maximum number of data segments.
+	pub data_segments: u64,
+	/// Maximum number of element segments.
+	pub element_segments: u64,
+	/// Maximum initial (and declared maximum) size of a table.
+	pub table_size: u64,
+	/// Maximum initial (and declared maximum) size of a memory, in pages.
+	pub memory_pages: u64,
+	/// Maximum number of parameters of a function type.
+	pub params: u64,
+	/// Maximum number of locals of a function, parameters included.
+	pub locals: u64,
+	/// Maximum size of a function body, in bytes.
+	pub function_size: u64,
+}
+
+impl Profile {
+	/// Limits shared by the web engines, as specified by the WebAssembly
+	/// JavaScript interface.
+	pub fn web() -> Profile {
+		Profile {
+			module_size: 1_073_741_824,
+			types: 1_000_000,
+			functions: 1_000_000,
+			imports: 100_000,
+			exports: 100_000,
+			globals: 1_000_000,
+			data_segments: 100_000,
+			element_segments: 10_000_000,
+			table_size: 10_000_000,
+			memory_pages: 65_536,
+			params: 1_000,
+			locals: 50_000,
+			function_size: 7_654_321,
+		}
+	}
+}
+
+/// Limit of a `Profile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+	/// `Profile::module_size`.
+	ModuleSize,
+	/// `Profile::types`.
+	Types,
+	/// `Profile::functions`.
+	Functions,
+	/// `Profile::imports`.
+	Imports,
+	/// `Profile::exports`.
+	Exports,
+	/// `Profile::globals`.
+	Globals,
+	/// `Profile::data_segments`.
+	DataSegments,
+	/// `Profile::element_segments`.
+	ElementSegments,
+	/// `Profile::table_size`.
+	TableSize,
+	/// `Profile::memory_pages`.
+	MemoryPages,
+	/// `Profile::params`.
+	Params,
+	/// `Profile::locals`.
+	Locals,
+	/// `Profile::function_size`.
+	FunctionSize,
+}
+
+/// Limit exceeded by a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+	/// Exceeded limit.
+	pub limit: Limit,
+	/// Index of the offending item for per-item limits: the type for
+	/// `Params`, the function for `Locals` and `FunctionSize`.
+	pub index: Option<u32>,
+	/// Actual value found in the module.
+	pub actual: u64,
+	/// Maximum allowed by the profile.
+	pub max: u64,
+}
+
+struct Checker {
+	violations: Vec<Violation>,
+}
+
+impl Checker {
+	fn check(&mut self, limit: Limit, index: Option<u32>, actual: u64, max: u64) {
+		if actual > max {
+			self.violations.push(Violation { limit, index, actual, max });
+		}
+	}
+}
+
+/// Size of a serialized function body, without its length prefix.
+fn body_size(body: &elements::FuncBody) -> Option<u64> {
+	let mut bytes = Vec::new();
+	body.clone().serialize(&mut bytes).ok()?;
+	let prefix = bytes.iter().position(|byte| byte & 0x80 == 0)? + 1;
+	Some((bytes.len() - prefix) as u64)
+}
+
+/// Reports the limits of `profile` the module exceeds, in the order of the
+/// `Limit` variants.
+///
+/// The module size is computed by serializing the module, it is not checked
+/// if serialization fails.
+pub fn check_against_profile(module: &Module, profile: Profile) -> Vec<Violation> {
+	let mut checker = Checker { violations: Vec::new() };
+	let len = |len: usize| len as u64;
+
+	if let Ok(bytes) = elements::serialize(module.clone()) {
+		checker.check(Limit::ModuleSize, None, len(bytes.len()), profile.module_size);
+	}
+	let types = module.type_section().map_or(&[][..], |ts| ts.types());
+	checker.check(Limit::Types, None, len(types.len()), profile.types);
+	checker.check(Limit::Functions, None, len(module.functions_space()), profile.functions);
+	let imports = module.import_section().map_or(&[][..], |is| is.entries());
+	checker.check(Limit::Imports, None, len(imports.len()), profile.imports);
+	let exports = module.export_section().map_or(0, |es| es.entries().len());
+	checker.check(Limit::Exports, None, len(exports), profile.exports);
+	checker.check(Limit::Globals, None, len(module.globals_space()), profile.globals);
+	let data_segments = module.data_section().map_or(0, |ds| ds.entries().len());
+	checker.check(Limit::DataSegments, None, len(data_segments), profile.data_segments);
+	let element_segments = module.elements_section().map_or(0, |es| es.entries().len());
+	checker.check(Limit::ElementSegments, None, len(element_segments), profile.element_segments);
+
+	let mut tables = Vec::new();
+	let mut memories = Vec::new();
+	for import in imports {
+		match *import.external() {
+			External::Table(ref table) => tables.push(*table.limits()),
+			External::Memory(ref memory) => memories.push(*memory.limits()),
+			_ => {},
+		}
+	}
+	tables.extend(module.table_section().map_or(&[][..], |ts| ts.entries()).iter().map(|t| *t.limits()));
+	memories.extend(module.memory_section().map_or(&[][..], |ms| ms.entries()).iter().map(|m| *m.limits()));
+	for limits in tables {
+		let size = ::std::cmp::max(limits.initial(), limits.maximum().unwrap_or(0));
+		checker.check(Limit::TableSize, None, size as u64, profile.table_size);
+	}
+	for limits in memories {
+		let size = ::std::cmp::max(limits.initial(), limits.maximum().unwrap_or(0));
+		checker.check(Limit::MemoryPages, None, size as u64, profile.memory_pages);
+	}
+
+	for (idx, ty) in types.iter().enumerate() {
+		let Type::Function(ref func_type) = *ty;
+		checker.check(Limit::Params, Some(idx as u32), len(func_type.params().len()), profile.params);
+	}
+
+	let imported_functions = module.import_count(elements::ImportCountType::Function);
+	let bodies = module.code_section().map_or(&[][..], |cs| cs.bodies());
+	for body_idx in 0..bodies.len() {
+		let func_idx = (imported_functions + body_idx) as u32;
+		if let Some(locals) = super::function_locals(module, func_idx) {
+			checker.check(Limit::Locals, Some(func_idx), len(locals.len()), profile.locals);
+		}
+	}
+	for (body_idx, body) in bodies.iter().enumerate() {
+		if let Some(size) = body_size(body) {
+			let func_idx = (imported_functions + body_idx) as u32;
+			checker.check(Limit::FunctionSize, Some(func_idx), size, profile.function_size);
+		}
+	}
+
+	checker.violations
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{check_against_profile, Profile, Limit, Violation};
+	use builder::module;
+	use elements::{Local, ValueType};
+
+	#[test]
+	fn violations() {
+		let module = module()
+			.function()
+				.signature().with_params(vec![ValueType::I32; 3]).build()
+				.body().with_locals(vec![Local::new(10, ValueType::I64)]).build()
+				.build()
+			.memory().with_min(2).with_max(Some(20)).build()
+			.build();
+		assert!(check_against_profile(&module, Profile::web()).is_empty());
+
+		let profile = Profile { params: 2, locals: 12, memory_pages: 16, ..Profile::web() };
+		assert_eq!(check_against_profile(&module, profile), vec![
+			Violation { limit: Limit::MemoryPages, index: None, actual: 20, max: 16 },
+			Violation { limit: Limit::Params, index: Some(0), actual: 3, max: 2 },
+			Violation { limit: Limit::Locals, index: Some(0), actual: 13, max: 12 },
+		]);
+	}
+}
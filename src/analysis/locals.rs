@@ -0,0 +1,119 @@
+use std::vec::Vec;
+use elements::{Module, Section, NameSection, ValueType, External, Type};
+
+/// Parameter or local variable of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVariable<'a> {
+	index: u32,
+	value_type: ValueType,
+	param: bool,
+	name: Option<&'a str>,
+}
+
+impl<'a> LocalVariable<'a> {
+	/// Index of the variable, as used by `get_local`/`set_local`.
+	pub fn index(&self) -> u32 { self.index }
+
+	/// Type of the variable.
+	pub fn value_type(&self) -> ValueType { self.value_type }
+
+	/// Whether the variable is a parameter of the function (as opposed to a
+	/// local declared in its body).
+	pub fn is_param(&self) -> bool { self.param }
+
+	/// Name of the variable from the local name section, if any.
+	pub fn name(&self) -> Option<&'a str> { self.name }
+}
+
+/// Type reference of the function `func_idx` and, for functions defined in
+/// the module, the index of its body.
+fn function_entry(module: &Module, func_idx: u32) -> Option<(u32, Option<usize>)> {
+	let imported = module.import_section()
+		.map(|is| is.entries().iter().filter_map(|entry| match *entry.external() {
+			External::Function(type_ref) => Some(type_ref),
+			_ => None,
+		}).collect::<Vec<_>>())
+		.unwrap_or_default();
+	if let Some(&type_ref) = imported.get(func_idx as usize) {
+		return Some((type_ref, None));
+	}
+	let body_idx = func_idx as usize - imported.len();
+	let func = module.function_section()?.entries().get(body_idx)?;
+	Some((func.type_ref(), Some(body_idx)))
+}
+
+/// Parameters and locals of the function `func_idx` (in the function index
+/// space, imports included), in index order.
+///
+/// Names come from the local name section, which is only available once
+/// the module names have been parsed (see `Module::parse_names`). Imported
+/// functions only have their parameters listed.
+///
+/// Returns `None` if there is no such function or its type is missing.
+pub fn function_locals(module: &Module, func_idx: u32) -> Option<Vec<LocalVariable<'_>>> {
+	let (type_ref, body_idx) = function_entry(module, func_idx)?;
+	let Type::Function(ref func_type) = *module.type_section()?.types().get(type_ref as usize)?;
+
+	let names = module.sections().iter()
+		.filter_map(|section| match *section {
+			Section::Name(NameSection::Local(ref local_names)) => local_names.local_names().get(func_idx),
+			_ => None,
+		})
+		.next();
+	let name = |index: u32| names.and_then(|names| names.get(index)).map(|name| name.as_str());
+
+	let mut variables = Vec::new();
+	for &value_type in func_type.params() {
+		let index = variables.len() as u32;
+		variables.push(LocalVariable { index, value_type, param: true, name: name(index) });
+	}
+	if let Some(body_idx) = body_idx {
+		let body = module.code_section()?.bodies().get(body_idx)?;
+		for local in body.locals() {
+			for _ in 0..local.count() {
+				let index = variables.len() as u32;
+				variables.push(LocalVariable { index, value_type: local.value_type(), param: false, name: name(index) });
+			}
+		}
+	}
+	Some(variables)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::function_locals;
+	use builder::module;
+	use elements::{Local, ValueType, Section, NameSection, LocalNameSection, NameMap};
+
+	#[test]
+	fn params_locals_and_names() {
+		let mut module = module()
+			.import().module("env").field("f").external().func(0).build()
+			.function()
+				.signature().with_params(vec![ValueType::I32, ValueType::F64]).build()
+				.body().with_locals(vec![Local::new(2, ValueType::I64)]).build()
+				.build()
+			.build();
+		let mut names = NameMap::default();
+		names.insert(0, "ptr".to_owned());
+		names.insert(3, "tmp".to_owned());
+		let mut local_names = LocalNameSection::default();
+		local_names.local_names_mut().insert(1, names);
+		module.sections_mut().push(Section::Name(NameSection::Local(local_names)));
+
+		let locals = function_locals(&module, 1).expect("function 1 to exist");
+		let summary: Vec<_> = locals.iter().map(|l| (l.index(), l.value_type(), l.is_param(), l.name())).collect();
+		assert_eq!(summary, vec![
+			(0, ValueType::I32, true, Some("ptr")),
+			(1, ValueType::F64, true, None),
+			(2, ValueType::I64, false, None),
+			(3, ValueType::I64, false, Some("tmp")),
+		]);
+
+		// The import shares the signature of the defined function.
+		let imported = function_locals(&module, 0).expect("import to have a type");
+		assert_eq!(imported.len(), 2);
+		assert!(imported.iter().all(|l| l.is_param()));
+		assert!(function_locals(&module, 2).is_none());
+	}
+}
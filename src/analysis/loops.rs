@@ -0,0 +1,160 @@
+use std::vec::Vec;
+use elements::Instruction;
+
+/// Loop found in a function body.
+///
+/// Positions are indices into the instruction sequence that was analysed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+	start: usize,
+	end: usize,
+	nesting: u32,
+	back_edges: Vec<usize>,
+}
+
+impl Loop {
+	/// Position of the `loop` instruction.
+	pub fn start(&self) -> usize { self.start }
+
+	/// Position of the `end` instruction closing the loop.
+	pub fn end(&self) -> usize { self.end }
+
+	/// Number of loops enclosing this one (`0` for an outermost loop).
+	pub fn nesting(&self) -> u32 { self.nesting }
+
+	/// Positions of the branch instructions (`br`, `br_if` or `br_table`)
+	/// jumping back to the start of this loop.
+	///
+	/// These are the points executed once per loop iteration (apart from
+	/// the first one), which makes them the natural place for fuel metering
+	/// or interruption checks.
+	pub fn back_edges(&self) -> &[usize] { &self.back_edges }
+
+	/// Whether `position` lies within the loop (including the `loop` and
+	/// `end` instructions themselves).
+	pub fn contains(&self, position: usize) -> bool {
+		self.start <= position && position <= self.end
+	}
+}
+
+enum Frame {
+	Block,
+	/// Index of the loop in the result.
+	Loop(usize),
+}
+
+/// Finds all loops in the function body `code` and their back edges.
+///
+/// WebAssembly control flow is structured, so every cycle goes through a
+/// `loop` construct: a branch is a back edge exactly when its target label
+/// belongs to a `loop`. In particular a `br_table` can never form an
+/// irreducible cycle; it is a back edge for every loop among its targets.
+///
+/// Loops are returned in the order of their `loop` instructions. Malformed
+/// bodies (unbalanced `end`s, branch depths out of range) are analysed as far
+/// as possible without panicking.
+pub fn find_loops(code: &[Instruction]) -> Vec<Loop> {
+	let mut loops: Vec<Loop> = Vec::new();
+	// The implicit function block.
+	let mut frames = vec![Frame::Block];
+	let mut nesting = 0u32;
+
+	fn mark(loops: &mut [Loop], frames: &[Frame], depth: u32, position: usize) {
+		let target = match frames.len().checked_sub(depth as usize + 1) {
+			Some(target) => target,
+			None => return,
+		};
+		if let Frame::Loop(idx) = frames[target] {
+			let back_edges = &mut loops[idx].back_edges;
+			if back_edges.last() != Some(&position) {
+				back_edges.push(position);
+			}
+		}
+	}
+
+	for (position, instruction) in code.iter().enumerate() {
+		match *instruction {
+			Instruction::Block(_) | Instruction::If(_) => frames.push(Frame::Block),
+			Instruction::Loop(_) => {
+				frames.push(Frame::Loop(loops.len()));
+				loops.push(Loop {
+					start: position,
+					end: position,
+					nesting,
+					back_edges: Vec::new(),
+				});
+				nesting += 1;
+			},
+			Instruction::End => {
+				if let Some(Frame::Loop(idx)) = frames.pop() {
+					loops[idx].end = position;
+					nesting -= 1;
+				}
+			},
+			Instruction::Br(depth) | Instruction::BrIf(depth) => {
+				mark(&mut loops, &frames, depth, position);
+			},
+			Instruction::BrTable(ref table) => {
+				for &depth in table.table.iter().chain(Some(&table.default)) {
+					mark(&mut loops, &frames, depth, position);
+				}
+			},
+			_ => {},
+		}
+	}
+
+	loops
+}
+
+#[cfg(test)]
+mod tests {
+	use super::find_loops;
+	use elements::{BlockType, BrTableData};
+	use elements::Instruction::*;
+
+	#[test]
+	fn nested() {
+		let code = vec![
+			Loop(BlockType::NoResult),          // 0
+				Block(BlockType::NoResult),     // 1
+					Loop(BlockType::NoResult),  // 2
+						GetLocal(0),            // 3
+						BrIf(0),                // 4: inner loop
+						GetLocal(0),            // 5
+						BrIf(2),                // 6: outer loop
+						Br(1),                  // 7: block, not a back edge
+					End,                        // 8
+				End,                            // 9
+				Br(0),                          // 10: outer loop
+			End,                                // 11
+			End,                                // 12
+		];
+
+		let loops = find_loops(&code);
+		assert_eq!(loops.len(), 2);
+
+		assert_eq!((loops[0].start(), loops[0].end(), loops[0].nesting()), (0, 11, 0));
+		assert_eq!(loops[0].back_edges(), &[6, 10][..]);
+
+		assert_eq!((loops[1].start(), loops[1].end(), loops[1].nesting()), (2, 8, 1));
+		assert_eq!(loops[1].back_edges(), &[4][..]);
+		assert!(loops[0].contains(loops[1].start()));
+	}
+
+	#[test]
+	fn br_table() {
+		let code = vec![
+			Block(BlockType::NoResult),
+				Loop(BlockType::NoResult),
+					GetLocal(0),
+					BrTable(Box::new(BrTableData { table: Box::new([0, 1, 0]), default: 1 })),
+				End,
+			End,
+			End,
+		];
+
+		let loops = find_loops(&code);
+		assert_eq!(loops.len(), 1);
+		assert_eq!(loops[0].back_edges(), &[3][..]);
+	}
+}
@@ -0,0 +1,25 @@
+//! Static analyses of function bodies and modules.
+
+mod stack;
+mod loops;
+mod pattern;
+mod branches;
+mod locals;
+mod limits;
+mod names;
+mod estimate;
+mod interface;
+mod size;
+mod entry;
+
+pub use self::loops::{find_loops, Loop};
+pub use self::pattern::{Pattern, Match, match_at, find_all, replace_all};
+pub use self::branches::{resolve_jump_tables, JumpTable, BranchTarget, ResolvedBranch};
+pub use self::locals::{function_locals, LocalVariable};
+pub use self::limits::{check_against_profile, Profile, Limit, Violation};
+pub use self::stack::StackEffects;
+pub use self::names::NameResolver;
+pub use self::estimate::{instruction_bounds, trip_count, InstructionBound};
+pub use self::interface::{interface, Interface, ImportItem, ExportItem, ItemType};
+pub use self::size::{serialize_within, BudgetError};
+pub use self::entry::{entry_point, EntryPoint, EntryConvention};
@@ -0,0 +1,173 @@
+use io;
+use std::vec::Vec;
+use std::string::String;
+use std::borrow::ToOwned;
+use elements::{Module, Section, NameSection, NameDecoding, Internal};
+
+/// Function names of a module, indexed both ways.
+///
+/// Names come from the function names of the name section (parsed or not)
+/// and from the exports. A function can have several names: `name` gives
+/// the name from the name section if there is one, the first export name
+/// otherwise, while `index` accepts any of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameResolver {
+	/// Names sorted by name then index.
+	by_name: Vec<(String, u32)>,
+	/// Preferred name of each named function, sorted by index.
+	by_index: Vec<(u32, String)>,
+}
+
+/// Function names of the name section in `section`, which may be a parsed
+/// or an unparsed name section.
+fn debug_names(module: &Module, section: &Section) -> Vec<(u32, String)> {
+	let names = |name_section: &NameSection| match *name_section {
+		NameSection::Function(ref functions) => functions.names().iter()
+			.map(|(idx, name)| (idx, name.clone()))
+			.collect(),
+		_ => Vec::new(),
+	};
+	match *section {
+		Section::Name(ref name_section) => names(name_section),
+		Section::Custom(ref custom) if custom.name() == "name" => {
+			// Invalid subsections are skipped, names only being debug
+			// information.
+			let mut rdr = io::Cursor::new(custom.payload());
+			let mut found = Vec::new();
+			while rdr.position() < custom.payload().len() {
+				match NameSection::deserialize_with_decoding(module, &mut rdr, NameDecoding::Lossy) {
+					Ok(name_section) => found.extend(names(&name_section)),
+					Err(_) => break,
+				}
+			}
+			found
+		},
+		_ => Vec::new(),
+	}
+}
+
+impl NameResolver {
+	/// Collects the function names of `module`.
+	pub fn new(module: &Module) -> NameResolver {
+		let mut by_index: Vec<(u32, String)> = Vec::new();
+		let mut by_name = Vec::new();
+		for section in module.sections() {
+			for (idx, name) in debug_names(module, section) {
+				by_name.push((name.clone(), idx));
+				by_index.push((idx, name));
+			}
+		}
+		for entry in module.export_section().map_or(&[][..], |es| es.entries()) {
+			if let Internal::Function(idx) = *entry.internal() {
+				by_name.push((entry.field().to_owned(), idx));
+				by_index.push((idx, entry.field().to_owned()));
+			}
+		}
+
+		// The sort is stable, so the name section wins over exports.
+		by_index.sort_by_key(|&(idx, _)| idx);
+		by_index.dedup_by_key(|&mut (idx, _)| idx);
+		by_name.sort();
+		by_name.dedup();
+		NameResolver { by_name, by_index }
+	}
+
+	/// Preferred name of the function `func_idx`.
+	pub fn name(&self, func_idx: u32) -> Option<&str> {
+		self.by_index.binary_search_by_key(&func_idx, |&(idx, _)| idx).ok()
+			.map(|position| self.by_index[position].1.as_str())
+	}
+
+	/// Index of the function named `name`. If several functions have this
+	/// name, the lowest index is returned.
+	pub fn index(&self, name: &str) -> Option<u32> {
+		let position = self.by_name.partition_point(|(n, _)| n.as_str() < name);
+		self.by_name.get(position).filter(|(n, _)| n == name).map(|&(_, idx)| idx)
+	}
+
+	/// All the names of the function `func_idx`, sorted.
+	pub fn names(&self, func_idx: u32) -> Vec<&str> {
+		self.by_name.iter().filter(|&&(_, idx)| idx == func_idx).map(|(name, _)| name.as_str()).collect()
+	}
+
+	/// Names starting with `prefix`, sorted by name, with the function they
+	/// name.
+	pub fn with_prefix(&self, prefix: &str) -> Vec<(&str, u32)> {
+		let start = self.by_name.partition_point(|(n, _)| n.as_str() < prefix);
+		self.by_name[start..].iter()
+			.take_while(|(name, _)| name.starts_with(prefix))
+			.map(|(name, idx)| (name.as_str(), *idx))
+			.collect()
+	}
+
+	/// Names matching `query` approximately, best matches first.
+	///
+	/// A name matches if the characters of `query` appear in it in order,
+	/// ignoring case. Names containing `query` as is rank first, then
+	/// shorter names, then names in order.
+	pub fn fuzzy(&self, query: &str) -> Vec<(&str, u32)> {
+		let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+		let mut matches: Vec<(bool, usize, &str, u32)> = self.by_name.iter()
+			.filter(|(name, _)| {
+				let mut remaining = &query[..];
+				for c in name.chars().flat_map(char::to_lowercase) {
+					if remaining.first() == Some(&c) {
+						remaining = &remaining[1..];
+					}
+				}
+				remaining.is_empty()
+			})
+			.map(|(name, idx)| {
+				let lowercase: String = name.chars().flat_map(char::to_lowercase).collect();
+				let query: String = query.iter().collect();
+				(!lowercase.contains(&query), name.len(), name.as_str(), *idx)
+			})
+			.collect();
+		matches.sort();
+		matches.into_iter().map(|(_, _, name, idx)| (name, idx)).collect()
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::NameResolver;
+	use builder::module;
+	use elements::{Module, Section, CustomSection, FunctionNameSection, NameSection, Serialize};
+
+	fn sample() -> Module {
+		let mut names = FunctionNameSection::default();
+		names.names_mut().insert(0, "alloc".into());
+		names.names_mut().insert(1, "core::fmt::write".into());
+		let mut payload = Vec::new();
+		NameSection::Function(names).serialize(&mut payload).expect("names to serialize");
+		module()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.export().field("malloc").internal().func(0).build()
+			.export().field("main").internal().func(2).build()
+			.with_section(Section::Custom(CustomSection::new("name".into(), payload)))
+			.build()
+	}
+
+	#[test]
+	fn lookups() {
+		let module = sample();
+		for resolver in &[
+			NameResolver::new(&module),
+			NameResolver::new(&module.clone().parse_names().expect("names to be parsed")),
+		] {
+			assert_eq!(resolver.name(0), Some("alloc"));
+			assert_eq!(resolver.name(2), Some("main"));
+			assert_eq!(resolver.name(3), None);
+			assert_eq!(resolver.index("malloc"), Some(0));
+			assert_eq!(resolver.index("core::fmt::write"), Some(1));
+			assert_eq!(resolver.index("mai"), None);
+			assert_eq!(resolver.names(0), vec!["alloc", "malloc"]);
+			assert_eq!(resolver.with_prefix("ma"), vec![("main", 2), ("malloc", 0)]);
+			assert_eq!(resolver.fuzzy("MA"), vec![("main", 2), ("malloc", 0)]);
+			assert_eq!(resolver.fuzzy("fmtw"), vec![("core::fmt::write", 1)]);
+			assert_eq!(resolver.fuzzy("lc"), vec![("alloc", 0), ("malloc", 0)]);
+		}
+	}
+}
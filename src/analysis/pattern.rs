@@ -0,0 +1,228 @@
+use std::mem;
+use std::vec::Vec;
+use std::boxed::Box;
+use elements::Instruction;
+use super::stack::plain_stack_effect;
+
+/// Element of an instruction pattern.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+	/// Any single instruction.
+	Any,
+	/// Exactly this instruction, immediates included.
+	Is(Instruction),
+	/// An instruction of the same kind as the given one, whatever its
+	/// immediates are (e.g. `Op(GetLocal(0))` matches any `get_local`).
+	Op(Instruction),
+	/// A single instruction satisfying the predicate.
+	Where(fn(&Instruction) -> bool),
+	/// A (possibly empty) run of plain instructions (no calls or control
+	/// flow) which only consumes values it pushed itself and leaves the
+	/// operand stack as it found it. The shortest such run is tried first.
+	StackNeutral,
+	/// Matches like the inner pattern, and records the matched instructions
+	/// in `Match::captures`.
+	Capture(Box<Pattern>),
+}
+
+impl Pattern {
+	/// Shortcut for `Pattern::Capture(Box::new(pattern))`.
+	pub fn capture(pattern: Pattern) -> Pattern {
+		Pattern::Capture(Box::new(pattern))
+	}
+}
+
+/// Successful match of a pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'a> {
+	start: usize,
+	end: usize,
+	captures: Vec<&'a [Instruction]>,
+}
+
+impl<'a> Match<'a> {
+	/// Position of the first matched instruction.
+	pub fn start(&self) -> usize { self.start }
+
+	/// Position following the last matched instruction.
+	pub fn end(&self) -> usize { self.end }
+
+	/// Instructions matched by the `Capture` elements, in pattern order.
+	pub fn captures(&self) -> &[&'a [Instruction]] { &self.captures }
+
+	/// Instructions matched by the capture number `idx`, if it matched
+	/// exactly one instruction.
+	pub fn captured(&self, idx: usize) -> Option<&'a Instruction> {
+		match self.captures.get(idx) {
+			Some(captured) if captured.len() == 1 => Some(&captured[0]),
+			_ => None,
+		}
+	}
+}
+
+/// Possible end positions of `pattern` matched at `position`.
+fn ends(pattern: &Pattern, code: &[Instruction], position: usize) -> Vec<usize> {
+	let single = |matches: bool| if matches { vec![position + 1] } else { Vec::new() };
+	let instruction = match code.get(position) {
+		Some(instruction) => instruction,
+		None => return match *pattern {
+			Pattern::StackNeutral => vec![position],
+			Pattern::Capture(ref inner) => ends(inner, code, position),
+			_ => Vec::new(),
+		},
+	};
+	match *pattern {
+		Pattern::Any => single(true),
+		Pattern::Is(ref expected) => single(instruction == expected),
+		Pattern::Op(ref expected) => single(mem::discriminant(instruction) == mem::discriminant(expected)),
+		Pattern::Where(predicate) => single(predicate(instruction)),
+		Pattern::Capture(ref inner) => ends(inner, code, position),
+		Pattern::StackNeutral => {
+			let mut result = vec![position];
+			let mut height = 0u32;
+			for (offset, instruction) in code[position..].iter().enumerate() {
+				let (pops, pushes) = match plain_stack_effect(instruction) {
+					Some(effect) => effect,
+					None => break,
+				};
+				height = match height.checked_sub(pops) {
+					Some(height) => height + pushes,
+					None => break,
+				};
+				if height == 0 {
+					result.push(position + offset + 1);
+				}
+			}
+			result
+		},
+	}
+}
+
+fn match_from<'a>(
+	patterns: &[Pattern],
+	code: &'a [Instruction],
+	position: usize,
+	captures: &mut Vec<&'a [Instruction]>,
+) -> Option<usize> {
+	let (pattern, rest) = match patterns.split_first() {
+		Some(split) => split,
+		None => return Some(position),
+	};
+	for end in ends(pattern, code, position) {
+		let captured = if let Pattern::Capture(_) = *pattern {
+			captures.push(&code[position..end]);
+			true
+		} else {
+			false
+		};
+		if let Some(end) = match_from(rest, code, end, captures) {
+			return Some(end);
+		}
+		if captured {
+			captures.pop();
+		}
+	}
+	None
+}
+
+/// Matches `patterns` against `code` starting exactly at `position`.
+pub fn match_at<'a>(patterns: &[Pattern], code: &'a [Instruction], position: usize) -> Option<Match<'a>> {
+	let mut captures = Vec::new();
+	match_from(patterns, code, position, &mut captures)
+		.map(|end| Match { start: position, end, captures })
+}
+
+/// Finds all non-overlapping matches of `patterns` in `code`, from left to
+/// right. Empty matches are skipped.
+pub fn find_all<'a>(patterns: &[Pattern], code: &'a [Instruction]) -> Vec<Match<'a>> {
+	let mut matches = Vec::new();
+	let mut position = 0;
+	while position < code.len() {
+		match match_at(patterns, code, position) {
+			Some(found) if found.end > found.start => {
+				position = found.end;
+				matches.push(found);
+			},
+			_ => position += 1,
+		}
+	}
+	matches
+}
+
+/// Replaces every non-overlapping match of `patterns` in `code` by the
+/// instructions returned by `replacement`.
+///
+/// Returns the number of replaced matches.
+pub fn replace_all<F>(patterns: &[Pattern], code: &mut Vec<Instruction>, mut replacement: F) -> usize
+	where F: FnMut(&Match) -> Vec<Instruction>
+{
+	let old = mem::take(code);
+	let mut replaced = 0;
+	let mut copied = 0;
+	for found in find_all(patterns, &old) {
+		code.extend_from_slice(&old[copied..found.start]);
+		code.extend(replacement(&found));
+		copied = found.end;
+		replaced += 1;
+	}
+	code.extend_from_slice(&old[copied..]);
+	replaced
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Pattern, match_at, find_all, replace_all};
+	use elements::Instruction;
+	use elements::Instruction::*;
+
+	#[test]
+	fn immediates() {
+		let pattern = [Pattern::capture(Pattern::Op(GetLocal(0))), Pattern::Is(I32Const(0)), Pattern::Op(I32Add)];
+		let code = [GetLocal(3), I32Const(0), I32Add, GetLocal(1), I32Const(1), I32Add, End];
+
+		let matches = find_all(&pattern, &code);
+		assert_eq!(matches.len(), 1);
+		assert_eq!((matches[0].start(), matches[0].end()), (0, 3));
+		assert_eq!(matches[0].captured(0), Some(&GetLocal(3)));
+		assert!(match_at(&pattern, &code, 3).is_none());
+	}
+
+	#[test]
+	fn stack_neutral() {
+		fn is_store(instruction: &Instruction) -> bool {
+			matches!(*instruction, I32Store(..) | I64Store(..))
+		}
+
+		// `get_local 0` ... `i32.store` with an arbitrary computation of the
+		// stored value in between.
+		let pattern = [
+			Pattern::Is(GetLocal(0)),
+			Pattern::capture(Pattern::StackNeutral),
+			Pattern::Any,
+			Pattern::Where(is_store),
+		];
+		let code = [
+			GetLocal(0),
+			GetLocal(1), I32Const(4), I32Add, Drop,
+			I32Const(7),
+			I32Store(2, 0),
+			End,
+		];
+		let found = match_at(&pattern, &code, 0).expect("pattern to match");
+		assert_eq!(found.end(), 7);
+		assert_eq!(found.captures()[0], &code[1..5]);
+
+		// A region consuming values from before it is not neutral.
+		let code = [GetLocal(0), Drop, I32Const(7), I32Store(2, 0), End];
+		let found = match_at(&pattern, &code, 0);
+		assert!(found.is_none());
+	}
+
+	#[test]
+	fn replace() {
+		let pattern = [Pattern::Op(I32Const(0)), Pattern::Is(Drop)];
+		let mut code = vec![I32Const(1), Drop, Nop, I32Const(2), Drop, End];
+		assert_eq!(replace_all(&pattern, &mut code, |_| vec![]), 2);
+		assert_eq!(code, vec![Nop, End]);
+	}
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::vec::Vec;
+use elements::{self, Module, Serialize, Stats, SectionSize, FunctionSize};
+
+/// Number of sections and of functions reported by `BudgetError::Exceeded`.
+const REPORTED: usize = 5;
+
+/// Error of `serialize_within`.
+#[derive(Debug, Clone)]
+pub enum BudgetError {
+	/// The module could not be serialized.
+	Serialize(elements::Error),
+	/// The binary is larger than the budget.
+	Exceeded {
+		/// Size of the binary.
+		size: usize,
+		/// Maximum size allowed.
+		max: usize,
+		/// Largest sections, by decreasing size.
+		sections: Vec<SectionSize>,
+		/// Largest function bodies, by decreasing size.
+		functions: Vec<FunctionSize>,
+	},
+}
+
+impl From<elements::Error> for BudgetError {
+	fn from(err: elements::Error) -> Self {
+		BudgetError::Serialize(err)
+	}
+}
+
+impl fmt::Display for BudgetError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			BudgetError::Serialize(ref e) => write!(f, "Failed to serialize module: {}", e),
+			BudgetError::Exceeded { size, max, ref sections, ref functions } => {
+				write!(f, "Module of {} bytes exceeds the budget of {} bytes; largest sections:", size, max)?;
+				for section in sections {
+					match section.name {
+						Some(ref name) => write!(f, " custom \"{}\" ({} bytes)", name, section.size)?,
+						None => write!(f, " {} ({} bytes)", section.id, section.size)?,
+					}
+				}
+				if !functions.is_empty() {
+					write!(f, "; largest functions:")?;
+					for function in functions {
+						write!(f, " {} ({} bytes)", function.index, function.size)?;
+					}
+				}
+				Ok(())
+			},
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for BudgetError {
+	fn description(&self) -> &str {
+		match *self {
+			BudgetError::Serialize(_) => "Failed to serialize module",
+			BudgetError::Exceeded { .. } => "Module exceeds the size budget",
+		}
+	}
+}
+
+/// Serializes `module`, failing if the binary is larger than `max` bytes.
+///
+/// The error reports the largest sections and function bodies, which are
+/// the ones to look at to bring the module within the budget.
+pub fn serialize_within(module: Module, max: usize) -> Result<Vec<u8>, BudgetError> {
+	let mut binary = Vec::new();
+	module.serialize(&mut binary)?;
+	if binary.len() <= max {
+		return Ok(binary);
+	}
+
+	let stats = Stats::from_bytes(&binary)?;
+	Err(BudgetError::Exceeded {
+		size: binary.len(),
+		max,
+		sections: stats.largest_sections(REPORTED),
+		functions: stats.largest_functions(REPORTED),
+	})
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{serialize_within, BudgetError};
+	use builder::module;
+	use elements::{self, Instructions, Instruction, SectionSize, FunctionSize};
+
+	#[test]
+	fn budget() {
+		let big = Instructions::new(vec![Instruction::Nop; 100].into_iter().chain(Some(Instruction::End)).collect());
+		let built = module()
+			.import().module("env").field("f").external().func(0).build()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().with_instructions(big).build().build()
+			.build();
+		let size = elements::serialize(built.clone()).expect("module to serialize").len();
+
+		assert_eq!(serialize_within(built.clone(), size).expect("module to fit").len(), size);
+		match serialize_within(built, size - 1) {
+			Err(BudgetError::Exceeded { size: actual, max, sections, functions }) => {
+				assert_eq!((actual, max), (size, size - 1));
+				assert_eq!(sections[0], SectionSize { id: 10, name: None, size: 109 });
+				assert_eq!(sections.len(), 4);
+				assert_eq!(functions, vec![FunctionSize { index: 2, size: 102 }, FunctionSize { index: 1, size: 2 }]);
+			},
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+}
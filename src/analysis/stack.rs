@@ -0,0 +1,194 @@
+use std::vec::Vec;
+use elements::{Instruction, BlockType, FunctionType, Module, Type, External};
+use elements::Instruction::*;
+
+/// Number of operands popped and pushed by an instruction whose effect on the
+/// operand stack doesn't depend on the module (no calls) or on the control
+/// flow (no blocks and branches).
+///
+/// Returns `None` for any other instruction.
+pub(crate) fn plain_stack_effect(instruction: &Instruction) -> Option<(u32, u32)> {
+	Some(match *instruction {
+		Nop => (0, 0),
+		Drop => (1, 0),
+		Select | TypedSelect(_) => (3, 1),
+
+		GetLocal(_) | GetGlobal(_) => (0, 1),
+		SetLocal(_) | SetGlobal(_) => (1, 0),
+		TeeLocal(_) => (1, 1),
+
+		I32Load(..) | I64Load(..) | F32Load(..) | F64Load(..)
+			| I32Load8S(..) | I32Load8U(..) | I32Load16S(..) | I32Load16U(..)
+			| I64Load8S(..) | I64Load8U(..) | I64Load16S(..) | I64Load16U(..)
+			| I64Load32S(..) | I64Load32U(..) => (1, 1),
+		I32Store(..) | I64Store(..) | F32Store(..) | F64Store(..)
+			| I32Store8(..) | I32Store16(..)
+			| I64Store8(..) | I64Store16(..) | I64Store32(..) => (2, 0),
+		CurrentMemory(_) => (0, 1),
+		GrowMemory(_) => (1, 1),
+
+		RefNull(_) | RefFunc(_) | TableSize(_) => (0, 1),
+		RefIsNull | TableGet(_) => (1, 1),
+		TableSet(_) => (2, 0),
+		TableGrow(_) => (2, 1),
+		TableFill(_) => (3, 0),
+
+		I32Const(_) | I64Const(_) | F32Const(_) | F64Const(_) => (0, 1),
+
+		I32Eqz | I64Eqz
+			| I32Clz | I32Ctz | I32Popcnt | I64Clz | I64Ctz | I64Popcnt
+			| F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt
+			| F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt
+			| I32WrapI64 | I32TruncSF32 | I32TruncUF32 | I32TruncSF64 | I32TruncUF64
+			| I64ExtendSI32 | I64ExtendUI32
+			| I64TruncSF32 | I64TruncUF32 | I64TruncSF64 | I64TruncUF64
+			| F32ConvertSI32 | F32ConvertUI32 | F32ConvertSI64 | F32ConvertUI64 | F32DemoteF64
+			| F64ConvertSI32 | F64ConvertUI32 | F64ConvertSI64 | F64ConvertUI64 | F64PromoteF32
+			| I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64
+			| I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => (1, 1),
+
+		I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU
+			| I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU
+			| F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge
+			| F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge
+			| I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU
+			| I32And | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr
+			| I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU
+			| I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr
+			| F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign
+			| F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => (2, 1),
+
+		_ => return None,
+	})
+}
+
+fn signature_effect(signature: &FunctionType) -> (u32, u32) {
+	(signature.params().len() as u32, signature.results().len() as u32)
+}
+
+/// Effects of instructions on the operand stack, calls included.
+///
+/// Holds the signatures of the functions and types of a module (the
+/// default value knows none, so only resolves instructions without calls).
+/// It doesn't borrow the module, so it can be used while editing it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StackEffects {
+	functions: Vec<(u32, u32)>,
+	types: Vec<(u32, u32)>,
+}
+
+impl StackEffects {
+	/// Effects of the instructions of functions of `module`.
+	pub fn new(module: &Module) -> StackEffects {
+		let types: Vec<(u32, u32)> = module.type_section()
+			.map_or(&[][..], |ts| ts.types())
+			.iter()
+			.map(|ty| match *ty { Type::Function(ref signature) => signature_effect(signature) })
+			.collect();
+		let imported = module.import_section().map_or(&[][..], |is| is.entries()).iter()
+			.filter_map(|entry| match *entry.external() {
+				External::Function(type_ref) => Some(type_ref),
+				_ => None,
+			});
+		let defined = module.function_section().map_or(&[][..], |fs| fs.entries()).iter()
+			.map(|func| func.type_ref());
+		let functions = imported.chain(defined)
+			.map(|type_ref| types.get(type_ref as usize).cloned().unwrap_or((0, 0)))
+			.collect();
+		StackEffects { functions, types }
+	}
+
+	/// Number of operands popped and pushed by a block of type `block_type`,
+	/// `None` if it refers to an unknown type.
+	fn block(&self, block_type: BlockType) -> Option<(u32, u32)> {
+		match block_type {
+			BlockType::NoResult => Some((0, 0)),
+			BlockType::Value(_) => Some((0, 1)),
+			BlockType::TypeIndex(type_ref) => self.types.get(type_ref as usize).cloned(),
+		}
+	}
+
+	/// Number of operands popped and pushed by `instruction`.
+	///
+	/// Returns `None` for control flow instructions and calls to unknown
+	/// functions or types.
+	pub fn instruction(&self, instruction: &Instruction) -> Option<(u32, u32)> {
+		match *instruction {
+			Call(func) => self.functions.get(func as usize).cloned(),
+			CallIndirect(type_ref, _) => self.types.get(type_ref as usize).map(|&(pops, pushes)| (pops + 1, pushes)),
+			_ => plain_stack_effect(instruction),
+		}
+	}
+
+	/// Number of operands the sequence `code` pops from the stack it starts
+	/// with, and pushes on it once it is done (the least number of operands
+	/// popped, e.g. `(1, 2)` for `get_local 0, i32.add, get_local 0`).
+	///
+	/// Nested blocks are accounted as a whole, by their signature. Returns
+	/// `None` if `code` is not a balanced sequence of blocks, contains a
+	/// branch (or `return`, `unreachable`) outside of nested blocks, or an
+	/// instruction whose effect is unknown.
+	pub fn sequence(&self, code: &[Instruction]) -> Option<(u32, u32)> {
+		let mut height = 0i64;
+		let mut lowest = 0i64;
+		let mut apply = |(pops, pushes): (u32, u32)| {
+			height -= pops as i64;
+			lowest = ::std::cmp::min(lowest, height);
+			height += pushes as i64;
+		};
+		let mut position = 0;
+		while position < code.len() {
+			match code[position] {
+				Block(block_type) | Loop(block_type) | If(block_type) => {
+					let condition = matches!(code[position], If(_)) as u32;
+					let mut depth = 0;
+					let end = code[position..].iter().position(|instruction| {
+						match *instruction {
+							Block(_) | Loop(_) | If(_) => depth += 1,
+							End => depth -= 1,
+							_ => {},
+						}
+						depth == 0
+					})?;
+					let (params, results) = self.block(block_type)?;
+					apply((condition + params, results));
+					position += end;
+				},
+				Else | End | Br(_) | BrIf(_) | BrTable(_) | Return | Unreachable => return None,
+				ref instruction => apply(self.instruction(instruction)?),
+			}
+			position += 1;
+		}
+		Some(((-lowest) as u32, (height - lowest) as u32))
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::StackEffects;
+	use builder::module;
+	use elements::{BlockType, ValueType};
+	use elements::Instruction::*;
+
+	#[test]
+	fn sequences() {
+		let module = module()
+			.function().signature().with_params(vec![ValueType::I32; 2]).with_return_type(Some(ValueType::I32)).build()
+				.body().build().build()
+			.build();
+		let effects = StackEffects::new(&module);
+
+		assert_eq!(effects.sequence(&[GetLocal(0), I32Add, GetLocal(0)]), Some((1, 2)));
+		assert_eq!(effects.sequence(&[I32Const(1), Call(0)]), Some((1, 1)));
+		assert_eq!(effects.sequence(&[
+			GetLocal(0),
+			If(BlockType::Value(ValueType::I32)), I32Const(1), Else, Br(0), End,
+		]), Some((0, 1)));
+		assert_eq!(effects.sequence(&[Block(BlockType::TypeIndex(0)), I32Add, End]), Some((2, 1)));
+		assert_eq!(effects.sequence(&[Block(BlockType::TypeIndex(1)), End]), None);
+		assert_eq!(effects.sequence(&[Call(1)]), None);
+		assert_eq!(StackEffects::default().sequence(&[Call(0)]), None);
+		assert_eq!(effects.sequence(&[Block(BlockType::NoResult)]), None);
+		assert_eq!(effects.sequence(&[Return]), None);
+	}
+}
@@ -45,7 +45,19 @@ impl<F> SignatureBuilder<F> where F: Invoke<elements::FunctionType> {
 
 	/// Override signature return type
 	pub fn with_return_type(mut self, return_type: Option<elements::ValueType>) -> Self {
-		*self.signature.return_type_mut() = return_type;
+		*self.signature.results_mut() = return_type.into_iter().collect();
+		self
+	}
+
+	/// Add result to signature builder
+	pub fn with_result(mut self, value_type: elements::ValueType) -> Self {
+		self.signature.results_mut().push(value_type);
+		self
+	}
+
+	/// Add multiple results to signature builder
+	pub fn with_results(mut self, value_types: Vec<elements::ValueType>) -> Self {
+		self.signature.results_mut().extend(value_types);
 		self
 	}
 
@@ -64,6 +76,11 @@ impl<F> SignatureBuilder<F> where F: Invoke<elements::FunctionType> {
 		OptionalValueTypeBuilder::with_callback(self)
 	}
 
+	/// Start build multiple results
+	pub fn results(self) -> ValueTypesBuilder<SignatureResults<F>> {
+		ValueTypesBuilder::with_callback(SignatureResults(self))
+	}
+
 	/// Finish current builder
 	pub fn build(self) -> F::Result {
 		self.callback.invoke(self.signature)
@@ -105,6 +122,20 @@ impl<F> Invoke<elements::ValueType> for SignatureBuilder<F>
 	}
 }
 
+/// Signature builder adding the value types it is invoked with to the
+/// results.
+pub struct SignatureResults<F>(SignatureBuilder<F>);
+
+impl<F> Invoke<Vec<elements::ValueType>> for SignatureResults<F>
+	where F: Invoke<elements::FunctionType>
+{
+	type Result = SignatureBuilder<F>;
+
+	fn invoke(self, results: Vec<elements::ValueType>) -> SignatureBuilder<F> {
+		self.0.with_results(results)
+	}
+}
+
 /// Type (signature) reference builder (for function/import/indirect call)
 pub struct TypeRefBuilder<F=Identity> {
 	callback: F,
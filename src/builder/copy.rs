@@ -0,0 +1,253 @@
+use std::fmt;
+use std::vec::Vec;
+use std::string::String;
+use elements::{self, Instruction, BlockType, External, Internal, ImportEntry, FuncBody, FunctionType, GlobalEntry, GlobalType, Type};
+
+/// How `ModuleBuilder::import_function_body_from` brings in the functions
+/// and globals a copied function refers to.
+///
+/// Functions and globals the source module imports are imported the same
+/// way whatever the policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyPolicy {
+	/// Copy the referenced functions (and, transitively, the functions and
+	/// globals they refer to) and globals.
+	Copy,
+	/// Import the referenced functions and globals from the given module
+	/// name, under the names the source module exports them with.
+	Import(String),
+}
+
+/// Error reported by `ModuleBuilder::import_function_body_from`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CopyError {
+	/// The function does not exist in the source module.
+	NoFunction(u32),
+	/// The function is imported by the source module, so it has no body.
+	Imported(u32),
+	/// The global does not exist in the source module.
+	NoGlobal(u32),
+	/// The type does not exist in the source module.
+	NoType(u32),
+	/// The function has to be imported but the source module does not
+	/// export it.
+	FunctionNotExported(u32),
+	/// The global has to be imported but the source module does not export
+	/// it.
+	GlobalNotExported(u32),
+	/// The function uses an instruction referring to data or element
+	/// segments, which are not copied.
+	Segments(u32),
+}
+
+impl fmt::Display for CopyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			CopyError::NoFunction(idx) => write!(f, "Function {} does not exist", idx),
+			CopyError::Imported(idx) => write!(f, "Function {} is imported", idx),
+			CopyError::NoGlobal(idx) => write!(f, "Global {} does not exist", idx),
+			CopyError::NoType(idx) => write!(f, "Type {} does not exist", idx),
+			CopyError::FunctionNotExported(idx) => write!(f, "Function {} is not exported", idx),
+			CopyError::GlobalNotExported(idx) => write!(f, "Global {} is not exported", idx),
+			CopyError::Segments(idx) => write!(f, "Function {} refers to segments", idx),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for CopyError {
+	fn description(&self) -> &str {
+		match *self {
+			CopyError::NoFunction(_) => "Function does not exist",
+			CopyError::Imported(_) => "Function is imported",
+			CopyError::NoGlobal(_) => "Global does not exist",
+			CopyError::NoType(_) => "Type does not exist",
+			CopyError::FunctionNotExported(_) => "Function is not exported",
+			CopyError::GlobalNotExported(_) => "Global is not exported",
+			CopyError::Segments(_) => "Function refers to segments",
+		}
+	}
+}
+
+/// Functions and globals of the source module to import and copy, in the
+/// order they are to be added.
+pub(super) struct Dependencies {
+	/// Function imports, with the source function index.
+	pub functions_imported: Vec<(u32, ImportEntry)>,
+	/// Copied functions: source function index, type and body.
+	pub functions_copied: Vec<(u32, FunctionType, FuncBody)>,
+	/// Global imports, with the source global index.
+	pub globals_imported: Vec<(u32, ImportEntry)>,
+	/// Copied globals, with the source global index.
+	pub globals_copied: Vec<(u32, GlobalEntry)>,
+}
+
+fn contains<T>(items: &[(u32, T)], idx: u32) -> bool {
+	items.iter().any(|&(i, _)| i == idx)
+}
+
+fn contains3<T, U>(items: &[(u32, T, U)], idx: u32) -> bool {
+	items.iter().any(|&(i, _, _)| i == idx)
+}
+
+struct Source<'a> {
+	module: &'a elements::Module,
+	imported_functions: Vec<&'a ImportEntry>,
+	imported_globals: Vec<&'a ImportEntry>,
+}
+
+impl<'a> Source<'a> {
+	fn new(module: &'a elements::Module) -> Self {
+		let imports = module.import_section().map_or(&[][..], |is| is.entries());
+		Source {
+			module,
+			imported_functions: imports.iter().filter(|e| matches!(*e.external(), External::Function(_))).collect(),
+			imported_globals: imports.iter().filter(|e| matches!(*e.external(), External::Global(_))).collect(),
+		}
+	}
+
+	fn function_type(&self, type_ref: u32) -> Result<FunctionType, CopyError> {
+		match self.module.type_section().and_then(|ts| ts.types().get(type_ref as usize)) {
+			Some(Type::Function(func_type)) => Ok(func_type.clone()),
+			None => Err(CopyError::NoType(type_ref)),
+		}
+	}
+
+	fn export_name(&self, internal: Internal) -> Option<&'a str> {
+		self.module.export_section()?.entries().iter()
+			.find(|entry| *entry.internal() == internal)
+			.map(|entry| entry.field())
+	}
+
+	/// Type and body of the defined function `idx`.
+	fn function(&self, idx: u32) -> Result<(FunctionType, &'a FuncBody), CopyError> {
+		let imported = self.imported_functions.len() as u32;
+		if idx < imported {
+			return Err(CopyError::Imported(idx));
+		}
+		let func = self.module.function_section()
+			.and_then(|fs| fs.entries().get((idx - imported) as usize))
+			.ok_or(CopyError::NoFunction(idx))?;
+		let body = self.module.code_section()
+			.and_then(|cs| cs.bodies().get((idx - imported) as usize))
+			.ok_or(CopyError::NoFunction(idx))?;
+		Ok((self.function_type(func.type_ref())?, body))
+	}
+
+	/// Import of the function `idx`, whose type is given as is in the
+	/// source module.
+	fn function_import(&self, idx: u32, policy: &DependencyPolicy) -> Result<(ImportEntry, FunctionType), CopyError> {
+		if let Some(entry) = self.imported_functions.get(idx as usize) {
+			let type_ref = match *entry.external() {
+				External::Function(type_ref) => type_ref,
+				_ => unreachable!("only function imports are collected; qed"),
+			};
+			return Ok(((*entry).clone(), self.function_type(type_ref)?));
+		}
+		let (func_type, _) = self.function(idx)?;
+		let module = match *policy {
+			DependencyPolicy::Import(ref module) => module.clone(),
+			DependencyPolicy::Copy => unreachable!("defined functions are only imported with the import policy; qed"),
+		};
+		let field = self.export_name(Internal::Function(idx)).ok_or(CopyError::FunctionNotExported(idx))?;
+		Ok((ImportEntry::new(module, field.into(), External::Function(0)), func_type))
+	}
+
+	/// The defined global `idx`.
+	fn global(&self, idx: u32) -> Result<&'a GlobalEntry, CopyError> {
+		let imported = self.imported_globals.len() as u32;
+		self.module.global_section()
+			.and_then(|gs| gs.entries().get(idx.checked_sub(imported)? as usize))
+			.ok_or(CopyError::NoGlobal(idx))
+	}
+
+	fn global_import(&self, idx: u32, policy: &DependencyPolicy) -> Result<ImportEntry, CopyError> {
+		if let Some(entry) = self.imported_globals.get(idx as usize) {
+			return Ok((*entry).clone());
+		}
+		let global_type: GlobalType = *self.global(idx)?.global_type();
+		let module = match *policy {
+			DependencyPolicy::Import(ref module) => module.clone(),
+			DependencyPolicy::Copy => unreachable!("defined globals are only imported with the import policy; qed"),
+		};
+		let field = self.export_name(Internal::Global(idx)).ok_or(CopyError::GlobalNotExported(idx))?;
+		Ok(ImportEntry::new(module, field.into(), External::Global(global_type)))
+	}
+}
+
+/// Collects the function `func_idx` of `source` and its dependencies.
+///
+/// The types of the imported functions are left to be resolved in the
+/// destination: the `External::Function` of function imports holds an
+/// index in the returned list of types.
+pub(super) fn collect(
+	source: &elements::Module,
+	func_idx: u32,
+	policy: &DependencyPolicy,
+) -> Result<(Dependencies, Vec<FunctionType>), CopyError> {
+	let source = Source::new(source);
+	let mut deps = Dependencies {
+		functions_imported: Vec::new(),
+		functions_copied: Vec::new(),
+		globals_imported: Vec::new(),
+		globals_copied: Vec::new(),
+	};
+	let mut import_types = Vec::new();
+	let copy = *policy == DependencyPolicy::Copy;
+
+	let (func_type, body) = source.function(func_idx)?;
+	deps.functions_copied.push((func_idx, func_type, body.clone()));
+
+	let mut global_refs = Vec::new();
+	let mut next = 0;
+	while next < deps.functions_copied.len() {
+		let idx = deps.functions_copied[next].0;
+		next += 1;
+		let (_, body) = source.function(idx)?;
+		for instruction in body.code().elements() {
+			match *instruction {
+				Instruction::Call(callee) | Instruction::RefFunc(callee) => {
+					if contains3(&deps.functions_copied, callee) || contains(&deps.functions_imported, callee) {
+						continue;
+					}
+					if copy && callee as usize >= source.imported_functions.len() {
+						let (func_type, body) = source.function(callee)?;
+						deps.functions_copied.push((callee, func_type, body.clone()));
+					} else {
+						let (mut entry, func_type) = source.function_import(callee, policy)?;
+						*entry.external_mut() = External::Function(import_types.len() as u32);
+						import_types.push(func_type);
+						deps.functions_imported.push((callee, entry));
+					}
+				},
+				Instruction::CallIndirect(type_ref, _)
+					| Instruction::Block(BlockType::TypeIndex(type_ref))
+					| Instruction::Loop(BlockType::TypeIndex(type_ref))
+					| Instruction::If(BlockType::TypeIndex(type_ref)) => { source.function_type(type_ref)?; },
+				Instruction::GetGlobal(global) | Instruction::SetGlobal(global) => global_refs.push(global),
+				Instruction::MemoryInit(_) | Instruction::MemoryDrop(_)
+					| Instruction::TableInit(..) | Instruction::TableDrop(_) => return Err(CopyError::Segments(idx)),
+				_ => {},
+			}
+		}
+	}
+
+	while let Some(global) = global_refs.pop() {
+		if contains(&deps.globals_copied, global) || contains(&deps.globals_imported, global) {
+			continue;
+		}
+		if copy && global as usize >= source.imported_globals.len() {
+			let entry = source.global(global)?;
+			for instruction in entry.init_expr().code() {
+				if let Instruction::GetGlobal(init_global) = *instruction {
+					global_refs.push(init_global);
+				}
+			}
+			deps.globals_copied.push((global, entry.clone()));
+		} else {
+			deps.globals_imported.push((global, source.global_import(global, policy)?));
+		}
+	}
+
+	Ok((deps, import_types))
+}
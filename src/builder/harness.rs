@@ -0,0 +1,219 @@
+use std::vec::Vec;
+use std::boxed::Box;
+use elements::{
+	self, Instruction, Instructions, External, Internal, ImportEntry, ExportEntry, FuncBody, Func,
+	GlobalEntry, GlobalType, InitExpr, MemoryType, TableType, Type, ValueType, Section, ImportCountType, TableElementType,
+};
+use super::copy::{DependencyPolicy, CopyError};
+use super::module::{module, from_module};
+
+/// Name of the module the harness built by `fuzz_harness` imports from.
+pub const FUZZ_HOST_MODULE: &str = "fuzz_host";
+
+/// Name under which the harness built by `fuzz_harness` exports the target.
+pub const FUZZ_TARGET_EXPORT: &str = "fuzz_target";
+
+/// Builds a harness to fuzz the function `func_idx` of `source` on its own,
+/// along with the host module satisfying its imports.
+///
+/// The harness holds a copy of the function and of the functions and globals
+/// it refers to (see `ModuleBuilder::import_function_body_from`), and exports
+/// the target as [`FUZZ_TARGET_EXPORT`]. Everything else comes from
+/// [`FUZZ_HOST_MODULE`]: the memories and tables of `source`, with the same
+/// types, and the functions and globals `source` imports, whose field is
+/// `module.field` after their original import. The host module defines
+/// these as stubs: zeroed memories, empty tables, globals set to zero and
+/// functions returning zero.
+///
+/// Instantiating the host module under [`FUZZ_HOST_MODULE`] and then the
+/// harness is enough to call the target with arbitrary arguments. Data and
+/// element segments are not copied, so fails with `CopyError::Segments` if
+/// the function refers to them.
+///
+/// [`FUZZ_HOST_MODULE`]: constant.FUZZ_HOST_MODULE.html
+/// [`FUZZ_TARGET_EXPORT`]: constant.FUZZ_TARGET_EXPORT.html
+pub fn fuzz_harness(source: &elements::Module, func_idx: u32) -> Result<(elements::Module, elements::Module), CopyError> {
+	let mut builder = module();
+	let location = builder.import_function_body_from(source, func_idx, &DependencyPolicy::Copy)?;
+	for (idx, memory_type) in memories(source).into_iter().enumerate() {
+		builder.push_import(ImportEntry::new(FUZZ_HOST_MODULE.into(), format!("memory{}", idx), External::Memory(memory_type)));
+	}
+	for (idx, table_type) in tables(source).into_iter().enumerate() {
+		builder.push_import(ImportEntry::new(FUZZ_HOST_MODULE.into(), format!("table{}", idx), External::Table(table_type)));
+	}
+	let harness = builder.build();
+	let target = harness.import_count(ImportCountType::Function) as u32 + location.signature;
+	let mut harness = from_module(harness)
+		.with_export(ExportEntry::new(FUZZ_TARGET_EXPORT.into(), Internal::Function(target)))
+		.build();
+
+	let mut types = Vec::new();
+	let mut functions = Vec::new();
+	let mut bodies = Vec::new();
+	let mut globals = Vec::new();
+	let mut memories = Vec::new();
+	let mut tables = Vec::new();
+	let mut exports = Vec::new();
+	let harness_types = harness.type_section().map(|ts| ts.types().to_vec()).unwrap_or_default();
+	if let Some(imports) = harness.import_section_mut() {
+		for entry in imports.entries_mut() {
+			if entry.module() != FUZZ_HOST_MODULE {
+				let field = format!("{}.{}", entry.module(), entry.field());
+				*entry = ImportEntry::new(FUZZ_HOST_MODULE.into(), field, *entry.external());
+			}
+			let internal = match *entry.external() {
+				External::Function(type_ref) => {
+					let Type::Function(ref func_type) = harness_types[type_ref as usize];
+					let type_ref = match types.iter().position(|t| *t == harness_types[type_ref as usize]) {
+						Some(position) => position,
+						None => {
+							types.push(harness_types[type_ref as usize].clone());
+							types.len() - 1
+						},
+					};
+					functions.push(Func::new(type_ref as u32));
+					let mut code: Vec<Instruction> = func_type.results().iter().cloned().map(zero).collect();
+					code.push(Instruction::End);
+					bodies.push(FuncBody::new(Vec::new(), Instructions::new(code)));
+					Internal::Function(functions.len() as u32 - 1)
+				},
+				External::Global(global_type) => {
+					let content_type = global_type.content_type();
+					globals.push(GlobalEntry::new(
+						GlobalType::new(content_type, global_type.is_mutable()),
+						InitExpr::new(vec![zero(content_type), Instruction::End]),
+					));
+					Internal::Global(globals.len() as u32 - 1)
+				},
+				External::Memory(memory_type) => {
+					memories.push(memory_type);
+					Internal::Memory(memories.len() as u32 - 1)
+				},
+				External::Table(table_type) => {
+					tables.push(table_type);
+					Internal::Table(tables.len() as u32 - 1)
+				},
+			};
+			exports.push(ExportEntry::new(entry.field().into(), internal));
+		}
+	}
+
+	let mut sections = Vec::new();
+	if !types.is_empty() {
+		sections.push(Section::Type(elements::TypeSection::with_types(types)));
+		sections.push(Section::Function(elements::FunctionSection::with_entries(functions)));
+	}
+	if !tables.is_empty() {
+		sections.push(Section::Table(elements::TableSection::with_entries(tables)));
+	}
+	if !memories.is_empty() {
+		sections.push(Section::Memory(elements::MemorySection::with_entries(memories)));
+	}
+	if !globals.is_empty() {
+		sections.push(Section::Global(elements::GlobalSection::with_entries(globals)));
+	}
+	if !exports.is_empty() {
+		sections.push(Section::Export(elements::ExportSection::with_entries(exports)));
+	}
+	if !bodies.is_empty() {
+		sections.push(Section::Code(elements::CodeSection::with_bodies(bodies)));
+	}
+	Ok((harness, elements::Module::new(sections)))
+}
+
+/// Types of the memories of `module`, imported ones first.
+fn memories(module: &elements::Module) -> Vec<MemoryType> {
+	let imported = module.import_section().map_or(&[][..], |is| is.entries()).iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Memory(memory_type) => Some(memory_type),
+			_ => None,
+		});
+	imported.chain(module.memory_section().map_or(&[][..], |ms| ms.entries()).iter().cloned()).collect()
+}
+
+/// Types of the tables of `module`, imported ones first.
+fn tables(module: &elements::Module) -> Vec<TableType> {
+	let imported = module.import_section().map_or(&[][..], |is| is.entries()).iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Table(table_type) => Some(table_type),
+			_ => None,
+		});
+	imported.chain(module.table_section().map_or(&[][..], |ts| ts.entries()).iter().cloned()).collect()
+}
+
+fn zero(value_type: ValueType) -> Instruction {
+	match value_type {
+		ValueType::I32 => Instruction::I32Const(0),
+		ValueType::I64 => Instruction::I64Const(0),
+		ValueType::F32 => Instruction::F32Const(0),
+		ValueType::F64 => Instruction::F64Const(0),
+		ValueType::V128 => Instruction::V128Const(Box::new([0; 16])),
+		ValueType::FuncRef => Instruction::RefNull(TableElementType::AnyFunc),
+		ValueType::ExternRef => Instruction::RefNull(TableElementType::ExternRef),
+	}
+}
+
+#[cfg(all(test, feature = "validation"))]
+mod tests {
+	use super::{fuzz_harness, FUZZ_HOST_MODULE, FUZZ_TARGET_EXPORT};
+	use builder::{module, CopyError};
+	use elements::{External, Internal, Instructions, ValueType};
+	use elements::Instruction::*;
+	use validation::{validate, Features};
+
+	#[test]
+	fn harness() {
+		let source = module()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("env").field("base").external().global(ValueType::I32, false).build()
+			.memory().with_min(2).build()
+			.table().with_min(1).build()
+			.function().signature().with_param(ValueType::I32).with_return_type(Some(ValueType::I64)).build()
+				.body().with_instructions(Instructions::new(vec![I64Const(0), End])).build()
+				.build()
+			.function().signature().with_param(ValueType::I32).build()
+				.body().with_instructions(Instructions::new(vec![
+					GetLocal(0), GetGlobal(0), I32Add, I32Load(2, 0), Call(0), Drop, End,
+				])).build()
+				.build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(2).build()
+			.build();
+
+		let (harness, host) = fuzz_harness(&source, 2).expect("harness to be built");
+		let imports: Vec<(&str, &str)> = harness.import_section().expect("import section").entries().iter()
+			.map(|entry| (entry.module(), entry.field()))
+			.collect();
+		assert_eq!(imports, vec![
+			(FUZZ_HOST_MODULE, "env.log"),
+			(FUZZ_HOST_MODULE, "env.base"),
+			(FUZZ_HOST_MODULE, "memory0"),
+			(FUZZ_HOST_MODULE, "table0"),
+		]);
+		let exports = harness.export_section().expect("export section").entries();
+		assert_eq!(exports.len(), 1);
+		assert_eq!(exports[0].field(), FUZZ_TARGET_EXPORT);
+		assert_eq!(*exports[0].internal(), Internal::Function(1));
+		assert_eq!(harness.code_section().expect("code section").bodies().len(), 1);
+
+		let host_exports: Vec<(&str, Internal)> = host.export_section().expect("export section").entries().iter()
+			.map(|entry| (entry.field(), *entry.internal()))
+			.collect();
+		assert_eq!(host_exports, vec![
+			("env.log", Internal::Function(0)),
+			("env.base", Internal::Global(0)),
+			("memory0", Internal::Memory(0)),
+			("table0", Internal::Table(0)),
+		]);
+		assert_eq!(host.code_section().expect("code section").bodies()[0].code().elements(), &[I64Const(0), End]);
+		match *harness.import_section().expect("import section").entries()[2].external() {
+			External::Memory(ref memory_type) => assert_eq!(memory_type.limits().initial(), 2),
+			_ => panic!("memory to be imported"),
+		}
+
+		validate(harness, &Features::default()).expect("harness to be valid");
+		validate(host, &Features::default()).expect("host to be valid");
+
+		assert_eq!(fuzz_harness(&source, 0).err(), Some(CopyError::Imported(0)));
+	}
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::vec::Vec;
+use std::string::String;
+use elements::{FunctionType, TableType, MemoryType, GlobalType, ResizableLimits, ImportEntry, External, Type};
+
+/// Item a host environment provides.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostItem {
+	/// Function with this signature.
+	Function(FunctionType),
+	/// Table.
+	Table(TableType),
+	/// Linear memory.
+	Memory(MemoryType),
+	/// Global.
+	Global(GlobalType),
+}
+
+/// Items a host environment provides under a module name, to check the
+/// imports of a module under construction against (see
+/// `ModuleBuilder::verify_against`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostModule {
+	name: String,
+	items: Vec<(String, HostItem)>,
+}
+
+impl HostModule {
+	/// New host module named `name`, providing nothing.
+	pub fn new(name: &str) -> Self {
+		HostModule { name: name.into(), items: Vec::new() }
+	}
+
+	/// Provides the function `field`.
+	pub fn with_function(self, field: &str, signature: FunctionType) -> Self {
+		self.with_item(field, HostItem::Function(signature))
+	}
+
+	/// Provides the table `field`.
+	pub fn with_table(self, field: &str, table_type: TableType) -> Self {
+		self.with_item(field, HostItem::Table(table_type))
+	}
+
+	/// Provides the memory `field`.
+	pub fn with_memory(self, field: &str, memory_type: MemoryType) -> Self {
+		self.with_item(field, HostItem::Memory(memory_type))
+	}
+
+	/// Provides the global `field`.
+	pub fn with_global(self, field: &str, global_type: GlobalType) -> Self {
+		self.with_item(field, HostItem::Global(global_type))
+	}
+
+	/// Provides `item` as `field`, replacing any item of the same name.
+	pub fn with_item(mut self, field: &str, item: HostItem) -> Self {
+		self.items.retain(|(name, _)| name != field);
+		self.items.push((field.into(), item));
+		self
+	}
+
+	/// Name of the module.
+	pub fn name(&self) -> &str { &self.name }
+
+	/// Item provided as `field`.
+	pub fn item(&self, field: &str) -> Option<&HostItem> {
+		self.items.iter().find(|(name, _)| name == field).map(|(_, item)| item)
+	}
+
+	/// Checks that the item `entry` imports from this module is provided
+	/// with a matching type, function imports referring to `types`.
+	pub(crate) fn check_import(&self, entry: &ImportEntry, types: &[Type]) -> Result<(), ImportError> {
+		let field = || String::from(entry.field());
+		let item = self.item(entry.field()).ok_or_else(|| ImportError::Missing(field()))?;
+		let (matches, mismatch): (bool, fn(String) -> ImportError) = match (entry.external(), item) {
+			(&External::Function(type_ref), HostItem::Function(provided)) => {
+				let Type::Function(imported) = types.get(type_ref as usize)
+					.ok_or_else(|| ImportError::NoType(field(), type_ref))?;
+				(imported == provided, ImportError::SignatureMismatch)
+			},
+			(External::Table(imported), HostItem::Table(provided)) => (
+				imported.elem_type() == provided.elem_type() && limits_match(provided.limits(), imported.limits()),
+				ImportError::LimitsMismatch,
+			),
+			(External::Memory(imported), HostItem::Memory(provided)) =>
+				(limits_match(provided.limits(), imported.limits()), ImportError::LimitsMismatch),
+			(External::Global(imported), HostItem::Global(provided)) =>
+				(imported == provided, ImportError::GlobalMismatch),
+			_ => (false, ImportError::KindMismatch),
+		};
+		if matches { Ok(()) } else { Err(mismatch(field())) }
+	}
+}
+
+/// Import of a module under construction which a host module does not
+/// satisfy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+	/// The host module does not provide the field.
+	Missing(String),
+	/// The host module provides the field as another kind of item.
+	KindMismatch(String),
+	/// The function imported refers to a missing type.
+	NoType(String, u32),
+	/// The function provided has another signature.
+	SignatureMismatch(String),
+	/// The table or memory provided does not fit in the limits imported,
+	/// or has another element type, sharing, index type or page size.
+	LimitsMismatch(String),
+	/// The global provided has another type or mutability.
+	GlobalMismatch(String),
+}
+
+impl fmt::Display for ImportError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ImportError::Missing(ref field) => write!(f, "Import `{}` is not provided", field),
+			ImportError::KindMismatch(ref field) => write!(f, "Import `{}` is provided as another kind of item", field),
+			ImportError::NoType(ref field, idx) => write!(f, "Import `{}` refers to missing type {}", field, idx),
+			ImportError::SignatureMismatch(ref field) => write!(f, "Import `{}` has another signature", field),
+			ImportError::LimitsMismatch(ref field) => write!(f, "Import `{}` does not match the limits provided", field),
+			ImportError::GlobalMismatch(ref field) => write!(f, "Import `{}` has another global type", field),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ImportError {
+	fn description(&self) -> &str {
+		match *self {
+			ImportError::Missing(_) => "Import is not provided",
+			ImportError::KindMismatch(_) => "Import is provided as another kind of item",
+			ImportError::NoType(..) => "Import refers to missing type",
+			ImportError::SignatureMismatch(_) => "Import has another signature",
+			ImportError::LimitsMismatch(_) => "Import does not match the limits provided",
+			ImportError::GlobalMismatch(_) => "Import has another global type",
+		}
+	}
+}
+
+/// Whether a table or memory with the `provided` limits can be imported with
+/// the `imported` ones.
+fn limits_match(provided: &ResizableLimits, imported: &ResizableLimits) -> bool {
+	let maximum = match (provided.maximum(), imported.maximum()) {
+		(_, None) => true,
+		(Some(provided), Some(imported)) => provided <= imported,
+		(None, Some(_)) => false,
+	};
+	maximum && provided.initial() >= imported.initial() &&
+		provided.shared() == imported.shared() &&
+		provided.is_64() == imported.is_64() &&
+		provided.page_size() == imported.page_size()
+}
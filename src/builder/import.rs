@@ -99,12 +99,29 @@ impl<F> ImportExternalBuilder<F> where F: Invoke<elements::External> {
 		self.callback.invoke(self.binding)
 	}
 
+	/// Shared memory mapping with specified limits
+	pub fn shared_memory(self, min: u32, max: u32) -> F::Result {
+		self.memory_type(elements::MemoryType::new(min, Some(max), true))
+	}
+
+	/// Memory mapping with specified type
+	pub fn memory_type(mut self, memory_type: elements::MemoryType) -> F::Result {
+		self.binding = elements::External::Memory(memory_type);
+		self.callback.invoke(self.binding)
+	}
+
 	/// Table mapping with specified limits
 	pub fn table(mut self, min: u32, max: Option<u32>) -> F::Result {
 		self.binding = elements::External::Table(elements::TableType::new(min, max));
 		self.callback.invoke(self.binding)
 	}
 
+	/// Table mapping with specified type
+	pub fn table_type(mut self, table_type: elements::TableType) -> F::Result {
+		self.binding = elements::External::Table(table_type);
+		self.callback.invoke(self.binding)
+	}
+
 	/// Global mapping with speciifed type and mutability
 	pub fn global(mut self, value_type: elements::ValueType, is_mut: bool) -> F::Result {
 		self.binding = elements::External::Global(elements::GlobalType::new(value_type, is_mut));
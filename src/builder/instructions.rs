@@ -0,0 +1,363 @@
+use std::fmt;
+use std::vec::Vec;
+use std::boxed::Box;
+use elements::{self, Instruction, BlockType, BrTableData, ValueType, Local, FuncBody};
+
+/// Handle to the label of a block, loop or `if` opened with an
+/// `InstructionsBuilder`, used as a branch target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// Handle to a parameter or a local of the function whose body is built by
+/// an `InstructionsBuilder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalId(u32);
+
+/// Error reported by `InstructionsBuilder::build`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelError {
+	/// A branch targets a label whose block is already closed at the branch
+	/// position.
+	Dangling(Label),
+	/// An `end` has no block to close.
+	UnmatchedEnd,
+	/// Some blocks are still open when building.
+	Unclosed(usize),
+}
+
+impl fmt::Display for LabelError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LabelError::Dangling(Label(id)) => write!(f, "Branch to label {} outside of its block", id),
+			LabelError::UnmatchedEnd => write!(f, "End without a block to close"),
+			LabelError::Unclosed(count) => write!(f, "{} block(s) not closed", count),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for LabelError {
+	fn description(&self) -> &str {
+		match *self {
+			LabelError::Dangling(_) => "Branch to label outside of its block",
+			LabelError::UnmatchedEnd => "End without a block to close",
+			LabelError::Unclosed(_) => "Blocks not closed",
+		}
+	}
+}
+
+/// Builder of instruction sequences computing branch depths from label
+/// handles.
+///
+/// ```
+/// use parity_wasm::builder::InstructionsBuilder;
+/// use parity_wasm::elements::{BlockType, Instruction};
+///
+/// let mut b = InstructionsBuilder::new();
+/// let outer = b.block(BlockType::NoResult);
+/// let top = b.loop_(BlockType::NoResult);
+/// b.instruction(Instruction::GetLocal(0)).br_if(outer).br(top);
+/// b.end().end();
+/// let instructions = b.build().expect("labels to be valid");
+/// assert_eq!(instructions.elements()[3], Instruction::BrIf(1));
+/// assert_eq!(instructions.elements()[4], Instruction::Br(0));
+/// ```
+///
+/// Locals declared with `local` are accessed through their handle, and
+/// `build_body` declares them grouped by type:
+///
+/// ```
+/// use parity_wasm::builder::InstructionsBuilder;
+/// use parity_wasm::elements::{Instruction, ValueType};
+///
+/// let mut b = InstructionsBuilder::with_params(1);
+/// let sum = b.local(ValueType::I64);
+/// let count = b.local(ValueType::I32);
+/// let copy = b.local(ValueType::I64);
+/// let param = b.param(0);
+/// b.get_local(sum).set_local(copy).get_local(param).set_local(count);
+/// let body = b.build_body().expect("labels to be valid");
+/// assert_eq!(body.locals().len(), 2);
+/// assert_eq!(body.code().elements()[1], Instruction::SetLocal(2));
+/// ```
+#[derive(Debug)]
+pub struct InstructionsBuilder {
+	instructions: Vec<Instruction>,
+	open: Vec<usize>,
+	next_label: usize,
+	error: Option<LabelError>,
+	params: u32,
+	/// Type of each declared local, in declaration order.
+	locals: Vec<ValueType>,
+	/// Positions of the instructions accessing a declared local through its
+	/// handle.
+	local_accesses: Vec<usize>,
+}
+
+impl Default for InstructionsBuilder {
+	fn default() -> Self {
+		InstructionsBuilder::new()
+	}
+}
+
+impl InstructionsBuilder {
+	/// New builder, positioned in the implicit block of the function body.
+	pub fn new() -> Self {
+		InstructionsBuilder::with_params(0)
+	}
+
+	/// New builder for the body of a function taking `params` parameters,
+	/// which come first in the local index space.
+	pub fn with_params(params: u32) -> Self {
+		InstructionsBuilder {
+			instructions: Vec::new(),
+			open: vec![0],
+			next_label: 1,
+			error: None,
+			params,
+			locals: Vec::new(),
+			local_accesses: Vec::new(),
+		}
+	}
+
+	/// Handle to the parameter `index`.
+	pub fn param(&self, index: u32) -> LocalId {
+		LocalId(index)
+	}
+
+	/// Declares a local of type `value_type`, returning its handle.
+	pub fn local(&mut self, value_type: ValueType) -> LocalId {
+		self.locals.push(value_type);
+		LocalId(self.params + self.locals.len() as u32 - 1)
+	}
+
+	/// Label of the function body itself: branching to it returns from the
+	/// function.
+	pub fn function_label(&self) -> Label {
+		Label(0)
+	}
+
+	fn fail(&mut self, error: LabelError) {
+		if self.error.is_none() {
+			self.error = Some(error);
+		}
+	}
+
+	fn open(&mut self, instruction: Instruction) -> Label {
+		let label = self.next_label;
+		self.next_label += 1;
+		self.open.push(label);
+		self.instructions.push(instruction);
+		Label(label)
+	}
+
+	fn depth(&mut self, label: Label) -> u32 {
+		match self.open.iter().rev().position(|&open| open == label.0) {
+			Some(depth) => depth as u32,
+			None => {
+				self.fail(LabelError::Dangling(label));
+				0
+			},
+		}
+	}
+
+	/// Appends an instruction as is.
+	///
+	/// Use the dedicated methods for instructions opening or closing blocks
+	/// and for branches, so that labels are tracked.
+	pub fn instruction(&mut self, instruction: Instruction) -> &mut Self {
+		self.instructions.push(instruction);
+		self
+	}
+
+	/// Opens a block, returning its label.
+	pub fn block(&mut self, block_type: BlockType) -> Label {
+		self.open(Instruction::Block(block_type))
+	}
+
+	/// Opens a loop, returning its label (branching to it restarts the loop).
+	pub fn loop_(&mut self, block_type: BlockType) -> Label {
+		self.open(Instruction::Loop(block_type))
+	}
+
+	/// Opens an `if`, returning its label.
+	pub fn if_(&mut self, block_type: BlockType) -> Label {
+		self.open(Instruction::If(block_type))
+	}
+
+	/// Starts the `else` branch of the innermost `if`.
+	pub fn else_(&mut self) -> &mut Self {
+		self.instruction(Instruction::Else)
+	}
+
+	/// Closes the innermost open block.
+	pub fn end(&mut self) -> &mut Self {
+		if self.open.len() > 1 {
+			self.open.pop();
+		} else {
+			self.fail(LabelError::UnmatchedEnd);
+		}
+		self.instruction(Instruction::End)
+	}
+
+	/// Unconditional branch to `label`.
+	pub fn br(&mut self, label: Label) -> &mut Self {
+		let depth = self.depth(label);
+		self.instruction(Instruction::Br(depth))
+	}
+
+	/// Conditional branch to `label`.
+	pub fn br_if(&mut self, label: Label) -> &mut Self {
+		let depth = self.depth(label);
+		self.instruction(Instruction::BrIf(depth))
+	}
+
+	/// Branch to `targets[operand]`, or to `default` if the operand is out of
+	/// range.
+	pub fn br_table(&mut self, targets: &[Label], default: Label) -> &mut Self {
+		let table = targets.iter().map(|&label| self.depth(label)).collect::<Vec<_>>();
+		let default = self.depth(default);
+		self.instruction(Instruction::BrTable(Box::new(BrTableData {
+			table: table.into_boxed_slice(),
+			default,
+		})))
+	}
+
+	fn access(&mut self, instruction: Instruction) -> &mut Self {
+		self.local_accesses.push(self.instructions.len());
+		self.instruction(instruction)
+	}
+
+	/// Pushes the value of `local`.
+	pub fn get_local(&mut self, local: LocalId) -> &mut Self {
+		self.access(Instruction::GetLocal(local.0))
+	}
+
+	/// Pops a value into `local`.
+	pub fn set_local(&mut self, local: LocalId) -> &mut Self {
+		self.access(Instruction::SetLocal(local.0))
+	}
+
+	/// Copies the value on top of the stack into `local`.
+	pub fn tee_local(&mut self, local: LocalId) -> &mut Self {
+		self.access(Instruction::TeeLocal(local.0))
+	}
+
+	/// Finishes the body, appending the final `end`.
+	///
+	/// Declared locals keep their declaration order, after the parameters.
+	///
+	/// Fails if a branch targeted a label outside of its block, or if blocks
+	/// are left open or closed too many times.
+	pub fn build(mut self) -> Result<elements::Instructions, LabelError> {
+		if let Some(error) = self.error {
+			return Err(error);
+		}
+		if self.open.len() > 1 {
+			return Err(LabelError::Unclosed(self.open.len() - 1));
+		}
+		self.instructions.push(Instruction::End);
+		Ok(elements::Instructions::new(self.instructions))
+	}
+
+	/// Finishes the function body, declaring its locals.
+	///
+	/// Locals are grouped by type, in the order the types were first
+	/// declared, so that each type takes a single declaration. Accesses
+	/// through handles are renumbered accordingly, while local instructions
+	/// appended with `instruction` are kept as is.
+	pub fn build_body(mut self) -> Result<FuncBody, LabelError> {
+		let mut types: Vec<ValueType> = Vec::new();
+		for &value_type in &self.locals {
+			if !types.contains(&value_type) {
+				types.push(value_type);
+			}
+		}
+		let mut indices = vec![0; self.locals.len()];
+		let mut declarations = Vec::new();
+		let mut next = self.params;
+		for &value_type in &types {
+			let mut count = 0;
+			for (idx, _) in self.locals.iter().enumerate().filter(|&(_, &local)| local == value_type) {
+				indices[idx] = next;
+				next += 1;
+				count += 1;
+			}
+			declarations.push(Local::new(count, value_type));
+		}
+
+		let params = self.params;
+		for &position in &self.local_accesses {
+			match self.instructions[position] {
+				Instruction::GetLocal(ref mut idx) | Instruction::SetLocal(ref mut idx) | Instruction::TeeLocal(ref mut idx) => {
+					if *idx >= params {
+						*idx = indices[(*idx - params) as usize];
+					}
+				},
+				_ => unreachable!("handles are only used by local instructions; qed"),
+			}
+		}
+		Ok(FuncBody::new(declarations, self.build()?))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{InstructionsBuilder, LabelError};
+	use elements::{BlockType, BrTableData, Instruction, Local, ValueType};
+	use elements::Instruction::*;
+
+	#[test]
+	fn depths() {
+		let mut b = InstructionsBuilder::new();
+		let function = b.function_label();
+		let a = b.block(BlockType::NoResult);
+		let c = b.if_(BlockType::NoResult);
+		b.br_table(&[a, c, function], c).else_().br(a).end();
+		b.instruction(Nop).end();
+
+		let expected: Vec<Instruction> = vec![
+			Block(BlockType::NoResult),
+			If(BlockType::NoResult),
+			BrTable(Box::new(BrTableData { table: Box::new([1, 0, 2]), default: 0 })),
+			Else,
+			Br(1),
+			End,
+			Nop,
+			End,
+			End,
+		];
+		assert_eq!(b.build().expect("labels to be valid").elements(), &expected[..]);
+	}
+
+	#[test]
+	fn locals() {
+		let mut b = InstructionsBuilder::with_params(2);
+		let a = b.local(ValueType::F64);
+		let c = b.local(ValueType::I32);
+		let d = b.local(ValueType::F64);
+		let x = b.param(1);
+		b.get_local(x).tee_local(c).set_local(a);
+		b.instruction(GetLocal(3)).set_local(d);
+		let body = b.build_body().expect("labels to be valid");
+		assert_eq!(body.locals(), &[Local::new(2, ValueType::F64), Local::new(1, ValueType::I32)][..]);
+		assert_eq!(body.code().elements(), &[GetLocal(1), TeeLocal(4), SetLocal(2), GetLocal(3), SetLocal(3), End][..]);
+	}
+
+	#[test]
+	fn errors() {
+		let mut b = InstructionsBuilder::new();
+		let closed = b.block(BlockType::NoResult);
+		b.end();
+		b.br(closed);
+		assert_eq!(b.build(), Err(LabelError::Dangling(super::Label(1))));
+
+		let mut b = InstructionsBuilder::new();
+		b.loop_(BlockType::NoResult);
+		assert_eq!(b.build(), Err(LabelError::Unclosed(1)));
+
+		let mut b = InstructionsBuilder::new();
+		b.end();
+		assert_eq!(b.build(), Err(LabelError::UnmatchedEnd));
+	}
+}
@@ -9,6 +9,8 @@ pub struct MemoryDefinition {
 	pub min: u32,
 	/// Maximum memory size
 	pub max: Option<u32>,
+	/// Whether the memory is indexed with 64-bit addresses
+	pub is_64: bool,
 	/// Memory data segments (static regions)
 	pub data: Vec<MemoryDataDefinition>,
 }
@@ -56,6 +58,12 @@ impl<F> MemoryBuilder<F> where F: Invoke<MemoryDefinition> {
 		self
 	}
 
+	/// Set/override whether the memory is indexed with 64-bit addresses
+	pub fn with_64(mut self, is_64: bool) -> Self {
+		self.memory.is_64 = is_64;
+		self
+	}
+
 	/// Push new static region with initialized offset expression and raw bytes
 	pub fn with_data(mut self, index: u32, values: Vec<u8>) -> Self {
 		self.memory.data.push(MemoryDataDefinition {
@@ -79,6 +87,7 @@ impl Default for MemoryDefinition {
 		MemoryDefinition {
 			min: 1,
 			max: None,
+			is_64: false,
 			data: Vec::new(),
 		}
 	}
@@ -10,16 +10,24 @@ mod table;
 mod export;
 mod global;
 mod data;
+mod instructions;
+mod copy;
+mod harness;
+mod host;
 
 pub use self::code::{
-	signatures, signature, function, SignatureBuilder, SignaturesBuilder,
+	signatures, signature, function, SignatureBuilder, SignatureResults, SignaturesBuilder,
 	FunctionBuilder, TypeRefBuilder, FuncBodyBuilder, FunctionDefinition,
 };
+pub use self::copy::{DependencyPolicy, CopyError};
+pub use self::harness::{fuzz_harness, FUZZ_HOST_MODULE, FUZZ_TARGET_EXPORT};
 pub use self::data::DataSegmentBuilder;
 pub use self::export::{export, ExportBuilder, ExportInternalBuilder};
 pub use self::global::{global, GlobalBuilder};
 pub use self::import::{import, ImportBuilder};
+pub use self::host::{HostModule, HostItem, ImportError};
+pub use self::instructions::{InstructionsBuilder, Label, LabelError, LocalId};
 pub use self::invoke::Identity;
 pub use self::memory::MemoryBuilder;
-pub use self::module::{module, from_module, ModuleBuilder};
+pub use self::module::{module, from_module, ModuleBuilder, VerifyError};
 pub use self::table::{TableBuilder, TableDefinition, TableEntryDefinition};
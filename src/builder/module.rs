@@ -1,15 +1,70 @@
+use std::fmt;
+use std::mem;
 use std::vec::Vec;
+use super::copy::{self, DependencyPolicy, CopyError};
 use super::invoke::{Invoke, Identity};
 use super::code::{self, SignaturesBuilder, FunctionBuilder};
 use super::memory::{self, MemoryBuilder};
 use super::table::{self, TableBuilder};
 use super::{import, export, global, data};
+use super::host::{HostModule, ImportError};
 use elements;
+#[cfg(feature = "analysis")]
+use analysis::{serialize_within, BudgetError};
 
 /// Module builder
+///
+/// The built module only depends on the sequence of builder calls: types
+/// are deduplicated in order of first use and every entry keeps the order in
+/// which it was pushed, so identical builder programs always serialize to
+/// identical binaries.
+///
+/// Indices are not checked while building unless the builder is `checked`;
+/// `verify` checks them all at once.
 pub struct ModuleBuilder<F=Identity> {
 	callback: F,
 	module: ModuleScaffold,
+	checked: bool,
+}
+
+/// Index of a module under construction referring to a missing item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyError {
+	/// The type does not exist.
+	NoType(u32),
+	/// The function does not exist.
+	NoFunction(u32),
+	/// The table does not exist.
+	NoTable(u32),
+	/// The memory does not exist.
+	NoMemory(u32),
+	/// The global does not exist.
+	NoGlobal(u32),
+}
+
+impl fmt::Display for VerifyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			VerifyError::NoType(idx) => write!(f, "Type {} does not exist", idx),
+			VerifyError::NoFunction(idx) => write!(f, "Function {} does not exist", idx),
+			VerifyError::NoTable(idx) => write!(f, "Table {} does not exist", idx),
+			VerifyError::NoMemory(idx) => write!(f, "Memory {} does not exist", idx),
+			VerifyError::NoGlobal(idx) => write!(f, "Global {} does not exist", idx),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for VerifyError {
+	fn description(&self) -> &str {
+		match *self {
+			VerifyError::NoType(_) => "Type does not exist",
+			VerifyError::NoFunction(_) => "Function does not exist",
+			VerifyError::NoTable(_) => "Table does not exist",
+			VerifyError::NoMemory(_) => "Memory does not exist",
+			VerifyError::NoGlobal(_) => "Global does not exist",
+		}
+	}
 }
 
 /// Location of the internal module function
@@ -33,7 +88,9 @@ struct ModuleScaffold {
 	pub element: elements::ElementSection,
 	pub code: elements::CodeSection,
 	pub data: elements::DataSection,
+	pub data_count: Option<Option<u32>>,
 	pub other: Vec<elements::Section>,
+	pub code_metadata: Vec<elements::CodeMetadataSection>,
 }
 
 impl From<elements::Module> for ModuleScaffold {
@@ -80,7 +137,9 @@ impl From<elements::Module> for ModuleScaffold {
 			element: element.unwrap_or_default(),
 			code: code.unwrap_or_default(),
 			data: data.unwrap_or_default(),
+			data_count: None,
 			other: sections,
+			code_metadata: Vec::new(),
 		}
 	}
 }
@@ -88,6 +147,13 @@ impl From<elements::Module> for ModuleScaffold {
 impl From<ModuleScaffold> for elements::Module {
 	fn from(module: ModuleScaffold) -> Self {
 		let mut sections = Vec::new();
+		let data_count = module.data_count.unwrap_or_else(|| {
+			if needs_data_count(&module.code, &module.data) {
+				Some(module.data.entries().len() as u32)
+			} else {
+				None
+			}
+		});
 
 		let types = module.types;
 		if types.types().len() > 0 {
@@ -124,6 +190,9 @@ impl From<ModuleScaffold> for elements::Module {
 		if element.entries().len() > 0 {
 			sections.push(elements::Section::Element(element));
 		}
+		if let Some(count) = data_count {
+			sections.push(elements::Section::DataCount(count));
+		}
 		let code = module.code;
 		if code.bodies().len() > 0 {
 			sections.push(elements::Section::Code(code));
@@ -133,10 +202,102 @@ impl From<ModuleScaffold> for elements::Module {
 			sections.push(elements::Section::Data(data));
 		}
 		sections.extend(module.other);
-		elements::Module::new(sections)
+		let mut built = elements::Module::new(sections);
+		for metadata in module.code_metadata {
+			built.set_code_metadata(metadata).expect("built bodies can be serialized; qed");
+		}
+		built
+	}
+}
+
+impl ModuleScaffold {
+	fn check_type(&self, idx: u32) -> Result<(), VerifyError> {
+		if (idx as usize) < self.types.types().len() { Ok(()) } else { Err(VerifyError::NoType(idx)) }
+	}
+
+	fn check_function(&self, idx: u32) -> Result<(), VerifyError> {
+		let count = self.import.functions() + self.functions.entries().len();
+		if (idx as usize) < count { Ok(()) } else { Err(VerifyError::NoFunction(idx)) }
+	}
+
+	fn check_table(&self, idx: u32) -> Result<(), VerifyError> {
+		let count = self.import.entries().iter()
+			.filter(|entry| matches!(*entry.external(), elements::External::Table(_)))
+			.count() + self.table.entries().len();
+		if (idx as usize) < count { Ok(()) } else { Err(VerifyError::NoTable(idx)) }
+	}
+
+	fn check_memory(&self, idx: u32) -> Result<(), VerifyError> {
+		let count = self.import.entries().iter()
+			.filter(|entry| matches!(*entry.external(), elements::External::Memory(_)))
+			.count() + self.memory.entries().len();
+		if (idx as usize) < count { Ok(()) } else { Err(VerifyError::NoMemory(idx)) }
+	}
+
+	fn check_global(&self, idx: u32) -> Result<(), VerifyError> {
+		let count = self.import.globals() + self.global.entries().len();
+		if (idx as usize) < count { Ok(()) } else { Err(VerifyError::NoGlobal(idx)) }
+	}
+
+	fn check_import(&self, entry: &elements::ImportEntry) -> Result<(), VerifyError> {
+		match *entry.external() {
+			elements::External::Function(type_ref) => self.check_type(type_ref),
+			_ => Ok(()),
+		}
+	}
+
+	fn check_export(&self, entry: &elements::ExportEntry) -> Result<(), VerifyError> {
+		match *entry.internal() {
+			elements::Internal::Function(idx) => self.check_function(idx),
+			elements::Internal::Table(idx) => self.check_table(idx),
+			elements::Internal::Memory(idx) => self.check_memory(idx),
+			elements::Internal::Global(idx) => self.check_global(idx),
+		}
+	}
+
+	fn check_data(&self, segment: &elements::DataSegment) -> Result<(), VerifyError> {
+		if segment.passive() { Ok(()) } else { self.check_memory(segment.index()) }
+	}
+
+	fn check_element(&self, segment: &elements::ElementSegment) -> Result<(), VerifyError> {
+		if !segment.passive() && !segment.declared() {
+			self.check_table(segment.index())?;
+		}
+		segment.members().iter().try_for_each(|&idx| self.check_function(idx))
+	}
+
+	fn verify(&self) -> Result<(), VerifyError> {
+		self.import.entries().iter().try_for_each(|entry| self.check_import(entry))?;
+		self.functions.entries().iter().try_for_each(|func| self.check_type(func.type_ref()))?;
+		self.export.entries().iter().try_for_each(|entry| self.check_export(entry))?;
+		if let Some(start) = self.start {
+			self.check_function(start)?;
+		}
+		self.element.entries().iter().try_for_each(|segment| self.check_element(segment))?;
+		self.data.entries().iter().try_for_each(|segment| self.check_data(segment))
 	}
 }
 
+/// Turns the `i32` constant `offset` into an `i64` one, for 64-bit memories
+/// and tables.
+fn offset_64(offset: elements::InitExpr) -> elements::InitExpr {
+	match offset.code() {
+		[elements::Instruction::I32Const(value), elements::Instruction::End] => elements::InitExpr::new(vec![
+			elements::Instruction::I64Const(*value as u32 as i64),
+			elements::Instruction::End,
+		]),
+		_ => offset,
+	}
+}
+
+/// Whether the data count section is required, by passive data segments or
+/// instructions referring to data segments.
+fn needs_data_count(code: &elements::CodeSection, data: &elements::DataSection) -> bool {
+	data.entries().iter().any(|segment| segment.passive()) ||
+		code.bodies().iter().any(|body| body.code().elements().iter().any(|instruction| matches!(*instruction,
+			elements::Instruction::MemoryInit(_) | elements::Instruction::MemoryDrop(_))))
+}
+
 impl ModuleBuilder {
 	/// New empty module builder
 	pub fn new() -> Self {
@@ -150,9 +311,37 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 		ModuleBuilder {
 			callback: callback,
 			module: Default::default(),
+			checked: false,
 		}
 	}
 
+	/// Checks the indices of every function, import, export and segment
+	/// when it is added, panicking with a description of the first invalid
+	/// one, and verifies the whole module when building.
+	///
+	/// Items must then be added before the entries referring to them
+	/// (e.g. a function before its export).
+	pub fn checked(mut self) -> Self {
+		self.checked = true;
+		self
+	}
+
+	/// Checks that every type, function, table, memory and global index of
+	/// the module built so far refers to an existing item.
+	pub fn verify(&self) -> Result<(), VerifyError> {
+		self.module.verify()
+	}
+
+	/// Checks that `host` provides every item the module built so far
+	/// imports from it, with a type it can be imported with: the same
+	/// signature or global type, and tables and memories within the limits
+	/// imported. Imports from other modules are not checked.
+	pub fn verify_against(&self, host: &HostModule) -> Result<(), ImportError> {
+		self.module.import.entries().iter()
+			.filter(|entry| entry.module() == host.name())
+			.try_for_each(|entry| host.check_import(entry, self.module.types.types()))
+	}
+
 	/// Builder from raw module
 	pub fn with_module(mut self, module: elements::Module) -> Self {
 		self.module = module.into();
@@ -187,6 +376,11 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 		let body = func.code;
 
 		let type_ref = self.resolve_type_ref(signature);
+		if self.checked {
+			if let Err(e) = self.module.check_type(type_ref) {
+				panic!("Invalid signature of function {}: {}", self.module.import.functions() + self.module.functions.entries().len(), e);
+			}
+		}
 
 		self.module.functions.entries_mut().push(elements::Func::new(type_ref));
 		let signature_index = self.module.functions.entries_mut().len() as u32 - 1;
@@ -204,25 +398,37 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 	}
 
 	/// Push linear memory region
+	///
+	/// The offsets of the static regions of 64-bit memories are turned into
+	/// `i64` constants.
 	pub fn push_memory(&mut self, mut memory: memory::MemoryDefinition) -> u32 {
 		let entries = self.module.memory.entries_mut();
-		entries.push(elements::MemoryType::new(memory.min, memory.max, false));
+		let mut memory_type = elements::MemoryType::new(memory.min, memory.max, false);
+		*memory_type.limits_mut().is_64_mut() = memory.is_64;
+		entries.push(memory_type);
 		let memory_index = (entries.len() - 1) as u32;
 		for data in memory.data.drain(..) {
+			let offset = if memory.is_64 { offset_64(data.offset) } else { data.offset };
 			self.module.data.entries_mut()
-				.push(elements::DataSegment::new(memory_index, Some(data.offset), data.values, false))
+				.push(elements::DataSegment::new(memory_index, Some(offset), data.values, false))
 		}
 		memory_index
 	}
 
 	/// Push table
+	///
+	/// The offsets of the element segments of 64-bit tables are turned into
+	/// `i64` constants.
 	pub fn push_table(&mut self, mut table: table::TableDefinition) -> u32 {
 		let entries = self.module.table.entries_mut();
-		entries.push(elements::TableType::new(table.min, table.max));
+		let mut table_type = elements::TableType::new(table.min, table.max);
+		*table_type.limits_mut().is_64_mut() = table.is_64;
+		entries.push(table_type);
 		let table_index = (entries.len() - 1) as u32;
 		for entry in table.elements.drain(..) {
+			let offset = if table.is_64 { offset_64(entry.offset) } else { entry.offset };
 			self.module.element.entries_mut()
-				.push(elements::ElementSegment::new(table_index, Some(entry.offset), entry.values, false))
+				.push(elements::ElementSegment::new(table_index, Some(offset), entry.values, false))
 		}
 		table_index
 	}
@@ -261,6 +467,7 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 	/// Push import entry to module. Not that it does not update calling indices in
 	/// function bodies.
 	pub fn push_import(&mut self, import: elements::ImportEntry) -> u32 {
+		self.check_import(&import);
 		self.module.import.entries_mut().push(import);
 		// todo: actually update calling addresses in function bodies
 		// todo: also batch push
@@ -270,10 +477,160 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 
 	/// Push export entry to module.
 	pub fn push_export(&mut self, export: elements::ExportEntry) -> u32 {
+		self.check_export(&export);
 		self.module.export.entries_mut().push(export);
 		self.module.export.entries_mut().len() as u32 - 1
 	}
 
+	/// Copies the function `func_idx` of `source` along with its type and the
+	/// functions and globals it refers to, which are imported or copied
+	/// according to `policy`, remapping the indices in the copied code.
+	/// Returns the location of the copy of `func_idx`.
+	///
+	/// Imports are only added if the module doesn't have identical ones yet.
+	/// References to the functions and globals defined so far are updated
+	/// for the added imports. Memory and table instructions are kept as they
+	/// are, so they refer to the memories and tables of the built module.
+	pub fn import_function_body_from(
+		&mut self,
+		source: &elements::Module,
+		func_idx: u32,
+		policy: &DependencyPolicy,
+	) -> Result<CodeLocation, CopyError> {
+		let (deps, import_types) = copy::collect(source, func_idx, policy)?;
+
+		let imported_functions = self.module.import.functions() as u32;
+		let imported_globals = self.module.import.globals() as u32;
+		let mut functions = Vec::new();
+		for (idx, mut entry) in deps.functions_imported {
+			if let elements::External::Function(ref mut type_ref) = *entry.external_mut() {
+				let func_type = import_types[*type_ref as usize].clone();
+				*type_ref = self.resolve_type_ref(code::Signature::Inline(func_type));
+			}
+			functions.push((idx, self.import_index(entry)));
+		}
+		let mut globals = Vec::new();
+		for (idx, entry) in deps.globals_imported {
+			globals.push((idx, self.import_index(entry)));
+		}
+		let added_functions = self.module.import.functions() as u32 - imported_functions;
+		let added_globals = self.module.import.globals() as u32 - imported_globals;
+		self.shift_defined(imported_functions, added_functions, imported_globals, added_globals);
+
+		let first_global = (self.module.import.globals() + self.module.global.entries().len()) as u32;
+		globals.extend(deps.globals_copied.iter().enumerate().map(|(n, &(idx, _))| (idx, first_global + n as u32)));
+		let first_function = (self.module.import.functions() + self.module.functions.entries().len()) as u32;
+		functions.extend(deps.functions_copied.iter().enumerate().map(|(n, &(idx, _, _))| (idx, first_function + n as u32)));
+
+		let lookup = |map: &[(u32, u32)], idx: u32| {
+			map.iter().find(|&&(i, _)| i == idx).map(|&(_, new_idx)| new_idx).expect("all references are collected; qed")
+		};
+		for (_, mut entry) in deps.globals_copied {
+			for instruction in entry.init_expr_mut().code_mut() {
+				if let elements::Instruction::GetGlobal(ref mut idx) = *instruction {
+					*idx = lookup(&globals, *idx);
+				}
+			}
+			self.module.global.entries_mut().push(entry);
+		}
+
+		let mut location = None;
+		for (_, func_type, mut body) in deps.functions_copied {
+			for instruction in body.code_mut().elements_mut() {
+				match *instruction {
+					elements::Instruction::Call(ref mut idx) | elements::Instruction::RefFunc(ref mut idx) => {
+						*idx = lookup(&functions, *idx);
+					},
+					elements::Instruction::GetGlobal(ref mut idx) | elements::Instruction::SetGlobal(ref mut idx) => {
+						*idx = lookup(&globals, *idx);
+					},
+					elements::Instruction::CallIndirect(ref mut type_ref, _)
+						| elements::Instruction::Block(elements::BlockType::TypeIndex(ref mut type_ref))
+						| elements::Instruction::Loop(elements::BlockType::TypeIndex(ref mut type_ref))
+						| elements::Instruction::If(elements::BlockType::TypeIndex(ref mut type_ref)) => {
+						let elements::Type::Function(ref func_type) = source.type_section()
+							.expect("types are checked when collecting; qed")
+							.types()[*type_ref as usize];
+						*type_ref = self.resolve_type_ref(code::Signature::Inline(func_type.clone()));
+					},
+					_ => {},
+				}
+			}
+			let copied = self.push_function(code::FunctionDefinition {
+				is_main: false,
+				signature: code::Signature::Inline(func_type),
+				code: body,
+			});
+			location.get_or_insert(copied);
+		}
+		Ok(location.expect("the function itself is always copied; qed"))
+	}
+
+	/// Index of `entry` among the imports of its kind, pushing it unless an
+	/// identical import exists.
+	fn import_index(&mut self, entry: elements::ImportEntry) -> u32 {
+		let kind = mem::discriminant(entry.external());
+		let entries = self.module.import.entries_mut();
+		let position = match entries.iter().position(|existing| *existing == entry) {
+			Some(position) => position,
+			None => {
+				entries.push(entry);
+				entries.len() - 1
+			},
+		};
+		entries[..position].iter().filter(|e| mem::discriminant(e.external()) == kind).count() as u32
+	}
+
+	/// Shifts references to defined functions and globals after `functions`
+	/// function and `globals` global imports are added.
+	fn shift_defined(&mut self, imported_functions: u32, functions: u32, imported_globals: u32, globals: u32) {
+		if functions == 0 && globals == 0 {
+			return;
+		}
+		let shift_function = |idx: &mut u32| if *idx >= imported_functions { *idx += functions };
+		let shift_global = |idx: &mut u32| if *idx >= imported_globals { *idx += globals };
+		let shift = |instruction: &mut elements::Instruction| match *instruction {
+			elements::Instruction::Call(ref mut idx) | elements::Instruction::RefFunc(ref mut idx) => shift_function(idx),
+			elements::Instruction::GetGlobal(ref mut idx) | elements::Instruction::SetGlobal(ref mut idx) => shift_global(idx),
+			_ => {},
+		};
+
+		for body in self.module.code.bodies_mut() {
+			body.code_mut().elements_mut().iter_mut().for_each(shift);
+		}
+		for entry in self.module.global.entries_mut() {
+			entry.init_expr_mut().code_mut().iter_mut().for_each(shift);
+		}
+		for segment in self.module.data.entries_mut() {
+			if let Some(ref mut offset) = *segment.offset_mut() {
+				offset.code_mut().iter_mut().for_each(shift);
+			}
+		}
+		for segment in self.module.element.entries_mut() {
+			if let Some(ref mut offset) = *segment.offset_mut() {
+				offset.code_mut().iter_mut().for_each(shift);
+			}
+			segment.members_mut().iter_mut().for_each(shift_function);
+		}
+		for entry in self.module.export.entries_mut() {
+			match *entry.internal_mut() {
+				elements::Internal::Function(ref mut idx) => shift_function(idx),
+				elements::Internal::Global(ref mut idx) => shift_global(idx),
+				_ => {},
+			}
+		}
+		if let Some(ref mut start) = self.module.start {
+			shift_function(start);
+		}
+	}
+
+	/// With code metadata (e.g. branch hints) for the functions of the module,
+	/// encoded for the final bodies when building.
+	pub fn with_code_metadata(mut self, metadata: elements::CodeMetadataSection) -> Self {
+		self.module.code_metadata.push(metadata);
+		self
+	}
+
 	/// Add new function using dedicated builder
 	pub fn function(self) -> FunctionBuilder<Self> {
 		FunctionBuilder::with_callback(self)
@@ -296,12 +653,14 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 
 	/// With inserted export entry
 	pub fn with_export(mut self, entry: elements::ExportEntry) -> Self {
+		self.check_export(&entry);
 		self.module.export.entries_mut().push(entry);
 		self
 	}
 
 	/// With inserted import entry
 	pub fn with_import(mut self, entry: elements::ImportEntry) -> Self {
+		self.check_import(&entry);
 		self.module.import.entries_mut().push(entry);
 		self
 	}
@@ -381,19 +740,75 @@ impl<F> ModuleBuilder<F> where F: Invoke<elements::Module> {
 
 	/// Add data segment to the builder
 	pub fn with_data_segment(mut self, segment: elements::DataSegment) -> Self {
+		if self.checked {
+			if let Err(e) = self.module.check_data(&segment) {
+				panic!("Invalid data segment {}: {}", self.module.data.entries().len(), e);
+			}
+		}
 		self.module.data.entries_mut().push(segment);
 		self
 	}
 
+	/// Overrides the data count section: `Some(count)` always emits it with
+	/// `count` and `None` never emits it.
+	///
+	/// By default, the section is emitted with the number of data segments
+	/// when there are passive segments or `memory.init`/`data.drop`
+	/// instructions, which require it.
+	pub fn with_data_count(mut self, count: Option<u32>) -> Self {
+		self.module.data_count = Some(count);
+		self
+	}
+
 	/// Data entry builder
 	pub fn data(self) -> data::DataSegmentBuilder<Self> {
 		data::DataSegmentBuilder::with_callback(self)
 	}
 
+	fn check_import(&self, entry: &elements::ImportEntry) {
+		if self.checked {
+			if let Err(e) = self.module.check_import(entry) {
+				panic!("Invalid import `{}.{}`: {}", entry.module(), entry.field(), e);
+			}
+		}
+	}
+
+	fn check_export(&self, entry: &elements::ExportEntry) {
+		if self.checked {
+			if let Err(e) = self.module.check_export(entry) {
+				panic!("Invalid export `{}`: {}", entry.field(), e);
+			}
+		}
+	}
+
+	fn check_module(&self) {
+		if self.checked {
+			if let Err(e) = self.module.verify() {
+				panic!("Invalid module: {}", e);
+			}
+		}
+	}
+
 	/// Build module (final step)
+	///
+	/// Panics if the builder is `checked` and the module does not verify.
 	pub fn build(self) -> F::Result {
+		self.check_module();
 		self.callback.invoke(self.module.into())
 	}
+
+	/// Build module (final step), failing if its binary is larger than
+	/// `max_size` bytes, with the largest sections and functions in the
+	/// error.
+	///
+	/// Panics if the builder is `checked` and the module does not verify.
+	#[cfg(feature = "analysis")]
+	pub fn build_within(self, max_size: usize) -> Result<F::Result, BudgetError> {
+		self.check_module();
+		let module: elements::Module = self.module.into();
+		serialize_within(module.clone(), max_size)?;
+		Ok(self.callback.invoke(module))
+	}
 }
 
 impl<F> Invoke<elements::FunctionSection> for ModuleBuilder<F>
@@ -589,4 +1004,224 @@ mod tests {
 
 		assert_eq!(module.type_section().expect("type section failed").types().len(), 1);
 	}
+ 
+	#[test]
+	fn deterministic() {
+		fn build() -> Vec<u8> {
+			let module = module()
+				.function()
+					.signature().param().i64().return_type().i32().build()
+					.body().build()
+					.build()
+				.function()
+					.signature().param().i32().build()
+					.body().build()
+					.build()
+				.function()
+					.signature().param().i64().return_type().i32().build()
+					.body().build()
+					.build()
+				.import().module("env").field("g").external().func(1).build()
+				.global().value_type().i32().mutable().init_expr(::elements::Instruction::I32Const(0)).build()
+				.export().field("f").internal().func(2).build()
+				.data().offset(::elements::Instruction::I32Const(0)).value(vec![1, 2, 3]).build()
+				.build();
+			::elements::serialize(module).expect("module to serialize")
+		}
+
+		let first = build();
+		for _ in 0..16 {
+			assert_eq!(build(), first);
+		}
+
+		let module: ::elements::Module = ::elements::deserialize_buffer(&first).expect("module to deserialize");
+		let types = module.type_section().expect("type section to exist").types();
+		assert_eq!(types.len(), 2);
+		assert_eq!(module.function_section().expect("function section to exist").entries()
+			.iter().map(|f| f.type_ref()).collect::<Vec<_>>(), vec![0, 1, 0]);
+	}
+
+	#[test]
+	fn code_metadata() {
+		use elements::{CodeMetadataSection, Instructions, BlockType};
+		use elements::Instruction::*;
+
+		let mut hints = CodeMetadataSection::branch_hints();
+		hints.set_branch_hint(0, 1, true);
+		let module = module()
+			.with_code_metadata(hints.clone())
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![I32Const(1), If(BlockType::NoResult), End, End])).build()
+				.build()
+			.build();
+		assert_eq!(module.code_metadata("branch_hint").expect("branch hints").expect("branch hints to parse"), hints);
+	}
+
+	#[test]
+	fn limits_64() {
+		use elements::Instruction::*;
+
+		let built = module()
+			.memory().with_64(true).with_data(8, vec![1]).build()
+			.table().with_64(true).build()
+			.build();
+		assert!(built.memory_section().expect("memory section").entries()[0].limits().is_64());
+		assert!(built.table_section().expect("table section").entries()[0].limits().is_64());
+		assert_eq!(built.data_section().expect("data section").entries()[0].offset().as_ref().expect("active segment").code(), &[I64Const(8), End]);
+	}
+
+	#[test]
+	fn data_count() {
+		use elements::{DataSegment, Instructions, Section};
+		use elements::Instruction::*;
+
+		let built = module()
+			.memory().build()
+			.data().offset(I32Const(0)).value(vec![1]).build()
+			.build();
+		assert_eq!(built.data_count_section(), None);
+
+		let built = module()
+			.memory().build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![MemoryDrop(1), End])).build()
+				.build()
+			.data().offset(I32Const(0)).value(vec![1]).build()
+			.with_data_segment(DataSegment::new(0, None, vec![2], true))
+			.build();
+		assert_eq!(built.data_count_section(), Some(2));
+		let ids: Vec<u8> = built.sections().iter().map(Section::id).collect();
+		assert_eq!(ids, vec![1, 3, 5, 12, 10, 11]);
+
+		let built = module()
+			.with_data_segment(DataSegment::new(0, None, vec![2], true))
+			.with_data_count(None)
+			.build();
+		assert_eq!(built.data_count_section(), None);
+	}
+
+	#[test]
+	fn import_function_body_from() {
+		use builder::{DependencyPolicy, CopyError};
+		use elements::{Instructions, ValueType, Internal};
+		use elements::Instruction::*;
+
+		let source = module()
+			.function().signature().with_param(ValueType::I32).build()
+				.body().with_instructions(Instructions::new(vec![GetLocal(0), Call(0), End])).build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					GetGlobal(0), Call(1), I32Const(0), CallIndirect(0, 0), End,
+				])).build()
+				.build()
+			.import().module("env").field("log").external().func(0).build()
+			.global().value_type().i32().mutable().init_expr(I32Const(0)).build()
+			.export().field("helper").internal().func(1).build()
+			.export().field("counter").internal().global(0).build()
+			.build();
+		let destination = || module()
+			.function().signature().build().body().build().build()
+			.export().field("own").internal().func(0).build();
+		let code = |module: &::elements::Module, body: usize| module.code_section().expect("code section").bodies()[body]
+			.code().elements().to_vec();
+		let imports = |module: &::elements::Module| module.import_section().expect("import section").entries().iter()
+			.map(|e| (e.module().to_owned(), e.field().to_owned())).collect::<Vec<_>>();
+
+		let mut builder = destination();
+		let location = builder.import_function_body_from(&source, 2, &DependencyPolicy::Copy).expect("copy to succeed");
+		assert_eq!(location.body, 1);
+		let copied = builder.build();
+		assert_eq!(imports(&copied), vec![("env".to_owned(), "log".to_owned())]);
+		assert_eq!(copied.global_section().expect("global section").entries().len(), 1);
+		assert_eq!(code(&copied, 1), vec![GetGlobal(0), Call(3), I32Const(0), CallIndirect(1, 0), End]);
+		assert_eq!(code(&copied, 2), vec![GetLocal(0), Call(0), End]);
+		assert_eq!(*copied.export_section().expect("export section").entries()[0].internal(), Internal::Function(1));
+
+		let mut builder = destination();
+		let policy = DependencyPolicy::Import("src".into());
+		builder.import_function_body_from(&source, 2, &policy).expect("copy to succeed");
+		builder.import_function_body_from(&source, 2, &policy).expect("copy to succeed");
+		let imported = builder.build();
+		assert_eq!(imports(&imported), vec![("src".to_owned(), "helper".to_owned()), ("src".to_owned(), "counter".to_owned())]);
+		assert_eq!(code(&imported, 1), vec![GetGlobal(0), Call(0), I32Const(0), CallIndirect(1, 0), End]);
+		assert_eq!(code(&imported, 1), code(&imported, 2));
+		assert_eq!(*imported.export_section().expect("export section").entries()[0].internal(), Internal::Function(1));
+
+		let mut builder = destination();
+		assert_eq!(builder.import_function_body_from(&source, 0, &policy).err(), Some(CopyError::Imported(0)));
+		assert_eq!(builder.import_function_body_from(&source, 5, &policy).err(), Some(CopyError::NoFunction(5)));
+		let unexported = module()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().with_instructions(Instructions::new(vec![Call(0), End])).build().build()
+			.build();
+		assert_eq!(builder.import_function_body_from(&unexported, 1, &policy).err(), Some(CopyError::FunctionNotExported(0)));
+	}
+
+	#[test]
+	fn verify() {
+		use super::VerifyError;
+
+		let deferred = module()
+			.export().field("main").internal().func(0).build()
+			.import().module("env").field("log").external().func(1).build();
+		assert_eq!(deferred.verify(), Err(VerifyError::NoType(1)));
+		let deferred = deferred
+			.function().signature().build().body().build().build()
+			.function().signature().param().i32().build().body().build().build();
+		assert_eq!(deferred.verify(), Ok(()));
+		deferred.build();
+
+		let checked = module().checked()
+			.memory().with_min(1).build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(0).build()
+			.export().field("memory").internal().memory(0).build();
+		assert_eq!(checked.verify(), Ok(()));
+		checked.build();
+	}
+
+	#[test]
+	fn verify_against() {
+		use super::super::{HostModule, ImportError};
+		use elements::{FunctionType, GlobalType, MemoryType, TableType, ValueType};
+
+		let builder = module()
+			.import().module("env").field("memory").external().shared_memory(1, 16).build()
+			.import().module("env").field("table").external().table_type(TableType::new(2, None)).build()
+			.import().module("env").field("log").external().func(0).build()
+			.import().module("wasi").field("exit").external().func(0).build()
+			.function().signature().param().i32().build().body().build().build();
+		let host = || HostModule::new("env")
+			.with_memory("memory", MemoryType::new(2, Some(8), true))
+			.with_table("table", TableType::new(4, Some(4)))
+			.with_function("log", FunctionType::with_results(vec![ValueType::I32], vec![]));
+		assert_eq!(builder.verify_against(&host()), Ok(()));
+
+		let check = |host: HostModule| builder.verify_against(&host).err();
+		assert_eq!(check(host().with_memory("memory", MemoryType::new(2, None, true))), Some(ImportError::LimitsMismatch("memory".into())));
+		assert_eq!(check(host().with_memory("memory", MemoryType::new(2, Some(8), false))), Some(ImportError::LimitsMismatch("memory".into())));
+		assert_eq!(check(host().with_table("table", TableType::new(1, None))), Some(ImportError::LimitsMismatch("table".into())));
+		assert_eq!(check(host().with_function("log", FunctionType::default())), Some(ImportError::SignatureMismatch("log".into())));
+		assert_eq!(check(host().with_global("log", GlobalType::new(ValueType::I32, false))), Some(ImportError::KindMismatch("log".into())));
+		assert_eq!(check(HostModule::new("env")), Some(ImportError::Missing("memory".into())));
+	}
+
+	#[test]
+	#[cfg(feature = "analysis")]
+	fn build_within() {
+		let builder = || module().function().signature().build().body().build().build();
+		let size = ::elements::serialize(builder().build()).expect("module to serialize").len();
+		assert_eq!(builder().build_within(size).expect("module to fit"), builder().build());
+		assert!(builder().build_within(size - 1).is_err());
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid export `main`: Function 0 does not exist")]
+	fn checked() {
+		module().checked()
+			.export().field("main").internal().func(0).build()
+			.function().signature().build().body().build().build()
+			.build();
+	}
  }
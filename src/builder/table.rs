@@ -9,6 +9,8 @@ pub struct TableDefinition {
 	pub min: u32,
 	/// Maximum length, if any
 	pub max: Option<u32>,
+	/// Whether the table is indexed with 64-bit indices
+	pub is_64: bool,
 	/// Element segments, if any
 	pub elements: Vec<TableEntryDefinition>,
 }
@@ -56,6 +58,12 @@ impl<F> TableBuilder<F> where F: Invoke<TableDefinition> {
 		self
 	}
 
+	/// Set/override whether the table is indexed with 64-bit indices
+	pub fn with_64(mut self, is_64: bool) -> Self {
+		self.table.is_64 = is_64;
+		self
+	}
+
 	/// Generate initialization expression and element values on specified index
 	pub fn with_element(mut self, index: u32, values: Vec<u32>) -> Self {
 		self.table.elements.push(TableEntryDefinition {
@@ -79,6 +87,7 @@ impl Default for TableDefinition {
 		TableDefinition {
 			min: 0,
 			max: None,
+			is_64: false,
 			elements: Vec::new(),
 		}
 	}
@@ -0,0 +1,260 @@
+//! Container bundling a main module with side modules and resources.
+//!
+//! Binary layout: the magic `\0wbn`, a little-endian `u32` version (`1`),
+//! then a `varuint32` count of entries, each made of a kind byte (`0` main
+//! module, `1` side module, `2` resource), a name (encoded like names in
+//! modules) and a `varuint32`-length-prefixed payload.
+
+use io;
+use std::vec::Vec;
+use std::string::String;
+use elements::{Deserialize, Serialize, Error, Uint8, Uint32, VarUint32, Module, deserialize_buffer};
+use loader::{self, ModuleLoader, ModuleGraph};
+
+const MAGIC: [u8; 4] = [0x00, b'w', b'b', b'n'];
+const VERSION: u32 = 1;
+
+/// Kind of a bundle entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+	/// The module to instantiate, there is at most one per bundle.
+	Main,
+	/// Module the main module (or other side modules) may import from.
+	SideModule,
+	/// Arbitrary data shipped along the modules.
+	Resource,
+}
+
+impl EntryKind {
+	fn is_module(&self) -> bool {
+		*self != EntryKind::Resource
+	}
+}
+
+/// Named entry of a bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleEntry {
+	kind: EntryKind,
+	name: String,
+	data: Vec<u8>,
+}
+
+impl BundleEntry {
+	/// Kind of the entry.
+	pub fn kind(&self) -> EntryKind { self.kind }
+
+	/// Name of the entry: the module name other modules import from, or the
+	/// name of the resource.
+	pub fn name(&self) -> &str { &self.name }
+
+	/// Content of the entry (module binary or resource data).
+	pub fn data(&self) -> &[u8] { &self.data }
+
+	/// Decodes the module held by this entry.
+	pub fn module(&self) -> Result<Module, Error> {
+		if !self.kind.is_module() {
+			return Err(Error::Other("bundle entry is not a module"));
+		}
+		deserialize_buffer(&self.data)
+	}
+}
+
+/// Bundle of a main module, side modules and resources.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bundle {
+	entries: Vec<BundleEntry>,
+}
+
+impl Bundle {
+	/// New empty bundle.
+	pub fn new() -> Bundle {
+		Bundle::default()
+	}
+
+	/// All entries, in insertion order.
+	pub fn entries(&self) -> &[BundleEntry] {
+		&self.entries
+	}
+
+	/// Main module entry, if any.
+	pub fn main(&self) -> Option<&BundleEntry> {
+		self.entries.iter().find(|entry| entry.kind == EntryKind::Main)
+	}
+
+	/// Module entry (main or side module) named `name`.
+	pub fn module(&self, name: &str) -> Option<&BundleEntry> {
+		self.entries.iter().find(|entry| entry.kind.is_module() && entry.name == name)
+	}
+
+	/// Resource entry named `name`.
+	pub fn resource(&self, name: &str) -> Option<&BundleEntry> {
+		self.entries.iter().find(|entry| entry.kind == EntryKind::Resource && entry.name == name)
+	}
+
+	/// Adds an entry.
+	///
+	/// Fails if the bundle already has a main module and `kind` is `Main`,
+	/// or if an entry of the same namespace (modules or resources) already
+	/// has this name.
+	pub fn add<N: Into<String>>(&mut self, kind: EntryKind, name: N, data: Vec<u8>) -> Result<(), Error> {
+		let name = name.into();
+		if kind == EntryKind::Main && self.main().is_some() {
+			return Err(Error::Other("bundle already has a main module"));
+		}
+		let taken = if kind.is_module() { self.module(&name).is_some() } else { self.resource(&name).is_some() };
+		if taken {
+			return Err(Error::HeapOther(format!("duplicate bundle entry {}", name)));
+		}
+		self.entries.push(BundleEntry { kind, name, data });
+		Ok(())
+	}
+
+	/// Adds the main module serialized.
+	pub fn add_main<N: Into<String>>(&mut self, name: N, module: Module) -> Result<(), Error> {
+		let data = ::elements::serialize(module)?;
+		self.add(EntryKind::Main, name, data)
+	}
+
+	/// Adds a side module serialized.
+	pub fn add_side_module<N: Into<String>>(&mut self, name: N, module: Module) -> Result<(), Error> {
+		let data = ::elements::serialize(module)?;
+		self.add(EntryKind::SideModule, name, data)
+	}
+
+	/// Adds a resource.
+	pub fn add_resource<N: Into<String>>(&mut self, name: N, data: Vec<u8>) -> Result<(), Error> {
+		self.add(EntryKind::Resource, name, data)
+	}
+
+	/// Loads the main module along with the side modules it depends on.
+	pub fn load_main(&mut self) -> Result<ModuleGraph, loader::Error> {
+		let main = match self.main() {
+			Some(main) => main.name.clone(),
+			None => return Err(loader::Error::NotFound(String::new())),
+		};
+		loader::resolve(self, &main)
+	}
+}
+
+/// Bundles serve their main and side modules.
+impl ModuleLoader for Bundle {
+	fn load(&mut self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+		Ok(self.module(name).map(|entry| entry.data.clone()))
+	}
+}
+
+fn read_bytes<R: io::Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+	let length: usize = VarUint32::deserialize(reader)?.into();
+	// Read in chunks so that a bogus length fails with `UnexpectedEof`
+	// instead of allocating a huge buffer upfront.
+	let mut data = Vec::new();
+	let mut chunk = [0u8; 4096];
+	while data.len() < length {
+		let size = ::std::cmp::min(chunk.len(), length - data.len());
+		reader.read(&mut chunk[..size])?;
+		data.extend_from_slice(&chunk[..size]);
+	}
+	Ok(data)
+}
+
+impl Deserialize for Bundle {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		let mut magic = [0u8; 4];
+		reader.read(&mut magic)?;
+		if magic != MAGIC {
+			return Err(Error::InvalidMagic);
+		}
+		let version: u32 = Uint32::deserialize(reader)?.into();
+		if version != VERSION {
+			return Err(Error::UnsupportedVersion(version));
+		}
+
+		let mut bundle = Bundle::new();
+		let count: u32 = VarUint32::deserialize(reader)?.into();
+		for _ in 0..count {
+			let kind = match u8::from(Uint8::deserialize(reader)?) {
+				0 => EntryKind::Main,
+				1 => EntryKind::SideModule,
+				2 => EntryKind::Resource,
+				_ => return Err(Error::Other("unknown bundle entry kind")),
+			};
+			let name = String::deserialize(reader)?;
+			let data = read_bytes(reader)?;
+			bundle.add(kind, name, data)?;
+		}
+		Ok(bundle)
+	}
+}
+
+impl Serialize for Bundle {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		writer.write(&MAGIC)?;
+		Uint32::from(VERSION).serialize(writer)?;
+		VarUint32::from(self.entries.len()).serialize(writer)?;
+		for entry in self.entries {
+			let kind = match entry.kind {
+				EntryKind::Main => 0,
+				EntryKind::SideModule => 1,
+				EntryKind::Resource => 2,
+			};
+			Uint8::from(kind).serialize(writer)?;
+			entry.name.serialize(writer)?;
+			VarUint32::from(entry.data.len()).serialize(writer)?;
+			writer.write(&entry.data)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{Bundle, EntryKind};
+	use builder::module;
+	use elements::{serialize, deserialize_buffer, Error};
+
+	fn bundle() -> Bundle {
+		let mut bundle = Bundle::new();
+		bundle.add_main("app", module()
+			.import().module("lib").field("f").external().func(0).build()
+			.import().module("env").field("g").external().func(0).build()
+			.build()).expect("main to be added");
+		bundle.add_side_module("lib", module()
+			.function().signature().build().body().build().build()
+			.export().field("f").internal().func(0).build()
+			.build()).expect("side module to be added");
+		bundle.add_resource("lib", b"config".to_vec()).expect("resource to be added");
+		bundle
+	}
+
+	#[test]
+	fn roundtrip() {
+		let original = bundle();
+		let bytes = serialize(original.clone()).expect("bundle to serialize");
+		let mut bundle: Bundle = deserialize_buffer(&bytes).expect("bundle to deserialize");
+		assert_eq!(bundle, original);
+
+		let kinds: Vec<EntryKind> = bundle.entries().iter().map(|e| e.kind()).collect();
+		assert_eq!(kinds, vec![EntryKind::Main, EntryKind::SideModule, EntryKind::Resource]);
+		assert_eq!(bundle.resource("lib").expect("resource").data(), b"config");
+
+		let graph = bundle.load_main().expect("bundle to load");
+		assert_eq!(graph.modules().len(), 2);
+		assert_eq!(graph.external(), &["env".to_owned()][..]);
+	}
+
+	#[test]
+	fn invalid() {
+		let mut bundle = bundle();
+		assert!(bundle.add(EntryKind::Main, "other", vec![]).is_err());
+		assert!(bundle.add(EntryKind::SideModule, "app", vec![]).is_err());
+
+		let mut bytes = serialize(bundle).expect("bundle to serialize");
+		bytes.truncate(bytes.len() - 1);
+		assert!(deserialize_buffer::<Bundle>(&bytes).is_err());
+		assert!(matches!(deserialize_buffer::<Bundle>(b"\0asm\x01\0\0\0"), Err(Error::InvalidMagic)));
+	}
+}
@@ -0,0 +1,204 @@
+//! C API over module decoding and encoding.
+//!
+//! Modules are exposed as opaque handles owned by the caller, which has to
+//! release them (and any buffer or error string returned) with the matching
+//! `pwasm_*_free` function. The declarations are in `include/parity_wasm.h`.
+//!
+//! Link the crate as a `staticlib` or `cdylib` from a wrapper crate to use
+//! these functions from C.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::{ptr, slice};
+use elements::{self, Module, Internal};
+
+/// Stores the message of `error` in `*error_out`, if not null.
+unsafe fn set_error(error_out: *mut *mut c_char, error: &elements::Error) {
+	if !error_out.is_null() {
+		let message = format!("{}", error).replace('\0', " ");
+		*error_out = CString::new(message).expect("nul bytes are replaced above; qed").into_raw();
+	}
+}
+
+/// Decodes the module in the `len` bytes at `bytes`.
+///
+/// Returns null on failure, in which case `*error_out` (if `error_out` is
+/// not null) is set to an error message to release with
+/// `pwasm_error_free`.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes, `error_out` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_module_deserialize(bytes: *const u8, len: usize, error_out: *mut *mut c_char) -> *mut Module {
+	let bytes = if len == 0 { &[][..] } else { slice::from_raw_parts(bytes, len) };
+	match elements::deserialize_buffer::<Module>(bytes) {
+		Ok(module) => Box::into_raw(Box::new(module)),
+		Err(error) => {
+			set_error(error_out, &error);
+			ptr::null_mut()
+		},
+	}
+}
+
+/// Encodes `module`, storing the size of the returned buffer in `*len_out`.
+///
+/// The buffer is to be released with `pwasm_bytes_free`. Returns null on
+/// failure, see `pwasm_module_deserialize` for `error_out`.
+///
+/// # Safety
+///
+/// `module` must be a live handle, `len_out` must be valid for writes and
+/// `error_out` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_module_serialize(module: *const Module, len_out: *mut usize, error_out: *mut *mut c_char) -> *mut u8 {
+	match elements::serialize((*module).clone()) {
+		Ok(bytes) => {
+			let mut bytes = bytes.into_boxed_slice();
+			*len_out = bytes.len();
+			let data = bytes.as_mut_ptr();
+			::std::mem::forget(bytes);
+			data
+		},
+		Err(error) => {
+			set_error(error_out, &error);
+			ptr::null_mut()
+		},
+	}
+}
+
+/// Releases a module handle. Does nothing if `module` is null.
+///
+/// # Safety
+///
+/// `module` must be null or a live handle, which is no longer usable.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_module_free(module: *mut Module) {
+	if !module.is_null() {
+		drop(Box::from_raw(module));
+	}
+}
+
+/// Number of functions of `module`, imported ones included.
+///
+/// # Safety
+///
+/// `module` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_module_function_count(module: *const Module) -> usize {
+	(*module).functions_space()
+}
+
+/// Number of exports of `module`.
+///
+/// # Safety
+///
+/// `module` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_module_export_count(module: *const Module) -> usize {
+	(*module).export_section().map_or(0, |es| es.entries().len())
+}
+
+/// Name of the export `index` of `module` (UTF-8, not nul-terminated), its
+/// length being stored in `*len_out`. Returns null if there is no such
+/// export.
+///
+/// The name is borrowed from the module and valid as long as the handle.
+///
+/// # Safety
+///
+/// `module` must be a live handle and `len_out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_module_export_name(module: *const Module, index: usize, len_out: *mut usize) -> *const u8 {
+	match (*module).export_section().and_then(|es| es.entries().get(index)) {
+		Some(entry) => {
+			*len_out = entry.field().len();
+			entry.field().as_ptr()
+		},
+		None => ptr::null(),
+	}
+}
+
+/// Kind of the export `index` of `module`: 0 function, 1 table, 2 memory,
+/// 3 global (as in the binary format), or -1 if there is no such export.
+///
+/// # Safety
+///
+/// `module` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_module_export_kind(module: *const Module, index: usize) -> i32 {
+	match (*module).export_section().and_then(|es| es.entries().get(index)) {
+		Some(entry) => match *entry.internal() {
+			Internal::Function(_) => 0,
+			Internal::Table(_) => 1,
+			Internal::Memory(_) => 2,
+			Internal::Global(_) => 3,
+		},
+		None => -1,
+	}
+}
+
+/// Releases a buffer returned by `pwasm_module_serialize`.
+///
+/// # Safety
+///
+/// `bytes` must be null or a buffer returned by `pwasm_module_serialize`
+/// along with its length `len`.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_bytes_free(bytes: *mut u8, len: usize) {
+	if !bytes.is_null() {
+		drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+	}
+}
+
+/// Releases an error message.
+///
+/// # Safety
+///
+/// `error` must be null or a message returned through an `error_out`
+/// parameter.
+#[no_mangle]
+pub unsafe extern "C" fn pwasm_error_free(error: *mut c_char) {
+	if !error.is_null() {
+		drop(CString::from_raw(error));
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::*;
+	use std::ffi::CStr;
+	use builder::module;
+
+	#[test]
+	fn roundtrip() {
+		let bytes = elements::serialize(module()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(0).build()
+			.build()).expect("module to serialize");
+
+		unsafe {
+			let module = pwasm_module_deserialize(bytes.as_ptr(), bytes.len(), ptr::null_mut());
+			assert!(!module.is_null());
+			assert_eq!(pwasm_module_function_count(module), 1);
+			assert_eq!(pwasm_module_export_count(module), 1);
+			let mut len = 0;
+			let name = pwasm_module_export_name(module, 0, &mut len);
+			assert_eq!(slice::from_raw_parts(name, len), b"main");
+			assert_eq!(pwasm_module_export_kind(module, 0), 0);
+			assert!(pwasm_module_export_name(module, 1, &mut len).is_null());
+
+			let mut error = ptr::null_mut();
+			let serialized = pwasm_module_serialize(module, &mut len, &mut error);
+			assert_eq!(slice::from_raw_parts(serialized, len), &bytes[..]);
+			pwasm_bytes_free(serialized, len);
+			pwasm_module_free(module);
+
+			let module = pwasm_module_deserialize(bytes.as_ptr(), 3, &mut error);
+			assert!(module.is_null());
+			assert!(!CStr::from_ptr(error).to_bytes().is_empty());
+			pwasm_error_free(error);
+		}
+	}
+}
@@ -0,0 +1,304 @@
+//! Compact encoding of modules for embedders controlling both ends of a
+//! constrained link.
+//!
+//! The encoding is not WebAssembly: it is a container starting with
+//! [`MAGIC`], which only [`decompress`] (or [`load`], which also accepts
+//! standard modules) can read. The function bodies are split into a stream
+//! of opcodes, renumbered by decreasing frequency, and a stream of
+//! immediates, so that similar bytes end up next to each other. The rest
+//! of the module and both streams are then compressed with a LZ77 variant.
+//!
+//! Decompressing gives back the same module, although its encoding may
+//! differ from the original binary (non-minimal LEB128 integers are not
+//! kept, for instance).
+//!
+//! [`MAGIC`]: constant.MAGIC.html
+//! [`decompress`]: fn.decompress.html
+//! [`load`]: fn.load.html
+
+use std::vec::Vec;
+use io;
+use elements::{
+	self, Module, Instruction, Instructions, Deserialize, Serialize, VarUint32, Error,
+};
+
+/// Magic number starting compressed modules.
+pub const MAGIC: [u8; 4] = *b"\0wzc";
+
+/// Version of the encoding written by `compress`.
+const VERSION: u8 = 1;
+
+/// Shortest repetition replaced by a back reference.
+const MIN_MATCH: usize = 4;
+
+/// Farthest back reference.
+const WINDOW: usize = 1 << 20;
+
+const HASH_BITS: u32 = 15;
+
+/// Encodes `module` in the compressed format.
+pub fn compress(module: &Module) -> Result<Vec<u8>, Error> {
+	let mut skeleton = module.clone();
+	let mut opcodes = Vec::new();
+	let mut immediates = Vec::new();
+	if let Some(code) = skeleton.code_section_mut() {
+		for body in code.bodies_mut() {
+			let instructions = body.code().elements();
+			VarUint32::from(instructions.len() as u32).serialize(&mut immediates)?;
+			for instruction in instructions {
+				let bytes = elements::serialize(instruction.clone())?;
+				opcodes.push(bytes[0]);
+				immediates.extend_from_slice(&bytes[1..]);
+			}
+			*body.code_mut() = Instructions::empty();
+		}
+	}
+	let skeleton = elements::serialize(skeleton)?;
+
+	// Opcodes sorted by decreasing frequency, each opcode being replaced by
+	// its rank.
+	let mut counts = [0usize; 256];
+	for &opcode in &opcodes {
+		counts[opcode as usize] += 1;
+	}
+	let mut ranked: Vec<u8> = (0..=255u8).filter(|&opcode| counts[opcode as usize] > 0).collect();
+	ranked.sort_by(|&a, &b| counts[b as usize].cmp(&counts[a as usize]).then(a.cmp(&b)));
+	let mut ranks = [0u8; 256];
+	for (rank, &opcode) in ranked.iter().enumerate() {
+		ranks[opcode as usize] = rank as u8;
+	}
+
+	let mut payload = Vec::new();
+	VarUint32::from(skeleton.len() as u32).serialize(&mut payload)?;
+	payload.extend_from_slice(&skeleton);
+	VarUint32::from(ranked.len() as u32).serialize(&mut payload)?;
+	payload.extend_from_slice(&ranked);
+	VarUint32::from(opcodes.len() as u32).serialize(&mut payload)?;
+	payload.extend(opcodes.iter().map(|&opcode| ranks[opcode as usize]));
+	payload.extend_from_slice(&immediates);
+
+	let mut out = MAGIC.to_vec();
+	out.push(VERSION);
+	VarUint32::from(payload.len() as u32).serialize(&mut out)?;
+	lz_compress(&payload, &mut out)?;
+	Ok(out)
+}
+
+/// Whether `bytes` starts like a compressed module.
+pub fn is_compressed(bytes: &[u8]) -> bool {
+	bytes.starts_with(&MAGIC)
+}
+
+/// Decodes a module encoded by `compress`.
+pub fn decompress(bytes: &[u8]) -> Result<Module, Error> {
+	if !is_compressed(bytes) {
+		return Err(Error::Other("not a compressed module"));
+	}
+	if bytes.get(MAGIC.len()) != Some(&VERSION) {
+		return Err(Error::Other("unsupported compressed module version"));
+	}
+	let mut pos = MAGIC.len() + 1;
+	let size = var_u32(bytes, &mut pos)?;
+	let payload = lz_decompress(&bytes[pos..], size)?;
+
+	let mut pos = 0;
+	let skeleton = take(&payload, &mut pos)?;
+	let ranked = take(&payload, &mut pos)?;
+	let opcodes = take(&payload, &mut pos)?;
+	let mut module: Module = elements::deserialize_buffer(skeleton)?;
+
+	let mut split = Split {
+		ranked,
+		opcodes: io::Cursor::new(opcodes),
+		immediates: io::Cursor::new(&payload[pos..]),
+		first: false,
+	};
+	if let Some(code) = module.code_section_mut() {
+		for body in code.bodies_mut() {
+			let count = u32::from(VarUint32::deserialize(&mut split.immediates)?);
+			let mut instructions = Vec::new();
+			for _ in 0..count {
+				split.first = true;
+				instructions.push(Instruction::deserialize(&mut split)?);
+			}
+			*body.code_mut() = Instructions::new(instructions);
+		}
+	}
+	if split.opcodes.position() != opcodes.len() || split.immediates.position() != payload.len() - pos {
+		return Err(Error::Other("trailing data in compressed module"));
+	}
+	Ok(module)
+}
+
+/// Decodes a module, whether it is compressed or a standard binary.
+pub fn load(bytes: &[u8]) -> Result<Module, Error> {
+	if is_compressed(bytes) {
+		decompress(bytes)
+	} else {
+		elements::deserialize_buffer(bytes)
+	}
+}
+
+/// Reads the length-prefixed part of `data` at `pos`, moving `pos` past it.
+fn take<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a [u8], Error> {
+	let mut reader = io::Cursor::new(&data[*pos..]);
+	let len = u32::from(VarUint32::deserialize(&mut reader)?) as usize;
+	let start = *pos + reader.position();
+	if data.len() - start < len {
+		return Err(Error::UnexpectedEof);
+	}
+	*pos = start + len;
+	Ok(&data[start..*pos])
+}
+
+fn var_u32(data: &[u8], pos: &mut usize) -> Result<usize, Error> {
+	let mut reader = io::Cursor::new(&data[*pos..]);
+	let value = u32::from(VarUint32::deserialize(&mut reader)?) as usize;
+	*pos += reader.position();
+	Ok(value)
+}
+
+/// Reader of instructions whose opcode comes from the opcode stream and
+/// whose immediates come from the immediate stream.
+struct Split<'a> {
+	ranked: &'a [u8],
+	opcodes: io::Cursor<&'a [u8]>,
+	immediates: io::Cursor<&'a [u8]>,
+	/// Whether the next read is the opcode of an instruction.
+	first: bool,
+}
+
+impl<'a> io::Read for Split<'a> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		if !self.first {
+			return self.immediates.read(buf);
+		}
+		self.first = false;
+		let (opcode, rest) = buf.split_first_mut().ok_or(io::Error::InvalidData)?;
+		let mut rank = [0u8; 1];
+		self.opcodes.read(&mut rank)?;
+		*opcode = *self.ranked.get(rank[0] as usize).ok_or(io::Error::InvalidData)?;
+		self.immediates.read(rest)
+	}
+}
+
+fn hash(bytes: &[u8]) -> usize {
+	let value = u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24;
+	(value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Appends `data` compressed as a sequence of literal runs, each followed
+/// by a back reference (length, then distance) unless it ends the data.
+fn lz_compress(data: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+	let mut table = vec![usize::MAX; 1 << HASH_BITS];
+	let mut literals = 0;
+	let mut pos = 0;
+	while pos + MIN_MATCH <= data.len() {
+		let slot = &mut table[hash(&data[pos..])];
+		let candidate = *slot;
+		*slot = pos;
+		if candidate == usize::MAX || pos - candidate > WINDOW || data[candidate..candidate + MIN_MATCH] != data[pos..pos + MIN_MATCH] {
+			pos += 1;
+			continue;
+		}
+		let len = data[pos..].iter().zip(&data[candidate..]).take_while(|&(a, b)| a == b).count();
+		VarUint32::from((pos - literals) as u32).serialize(out)?;
+		out.extend_from_slice(&data[literals..pos]);
+		VarUint32::from(len as u32).serialize(out)?;
+		VarUint32::from((pos - candidate) as u32).serialize(out)?;
+		pos += len;
+		literals = pos;
+	}
+	VarUint32::from((data.len() - literals) as u32).serialize(out)?;
+	out.extend_from_slice(&data[literals..]);
+	Ok(())
+}
+
+/// Decompresses `size` bytes compressed by `lz_compress`.
+fn lz_decompress(data: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+	let mut pos = 0;
+	let mut out = Vec::new();
+	loop {
+		let literals = take(data, &mut pos)?;
+		if size - out.len() < literals.len() {
+			return Err(Error::Other("corrupted compressed module"));
+		}
+		out.extend_from_slice(literals);
+		if out.len() == size {
+			break;
+		}
+		let len = var_u32(data, &mut pos)?;
+		let distance = var_u32(data, &mut pos)?;
+		if distance == 0 || distance > out.len() || len < MIN_MATCH || size - out.len() < len {
+			return Err(Error::Other("corrupted compressed module"));
+		}
+		// References may overlap the bytes they produce.
+		for _ in 0..len {
+			let byte = out[out.len() - distance];
+			out.push(byte);
+		}
+	}
+	if pos != data.len() {
+		return Err(Error::Other("trailing data in compressed module"));
+	}
+	Ok(out)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{compress, decompress, load, lz_compress, lz_decompress};
+	use elements::{self, Instruction, Instructions, BlockType};
+	use elements::Instruction::*;
+	use builder::module;
+
+	#[test]
+	fn lz() {
+		let data: Vec<u8> = b"abcabcabcabcabcabcxyz0123012301230123".iter().cloned().cycle().take(500).collect();
+		let mut compressed = Vec::new();
+		lz_compress(&data, &mut compressed).expect("data to be compressed");
+		assert!(compressed.len() < data.len() / 4);
+		assert_eq!(lz_decompress(&compressed, data.len()).expect("data to be decompressed"), data);
+		assert!(lz_decompress(&compressed, data.len() + 1).is_err());
+		assert_eq!(lz_decompress(&[0], 0).expect("empty data to be decompressed"), Vec::<u8>::new());
+	}
+
+	#[test]
+	fn round_trip() {
+		let code: Vec<Instruction> = (0..50)
+			.flat_map(|i| vec![GetLocal(0), I32Const(i), I32Add, SetLocal(0)])
+			.chain(vec![
+				Block(BlockType::NoResult),
+				GetLocal(0),
+				BrIf(0),
+				I32Const(0),
+				I32Load(2, 16),
+				Drop,
+				MemoryFill,
+				End,
+				GetLocal(0),
+				End,
+			])
+			.collect();
+		let source = module()
+			.memory().with_min(1).build()
+			.function().signature().with_param(::elements::ValueType::I32).with_result(::elements::ValueType::I32).build()
+				.body().with_instructions(Instructions::new(code)).build()
+				.build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(0).build()
+			.build();
+		let binary = elements::serialize(source.clone()).expect("module to be serialized");
+
+		let compressed = compress(&source).expect("module to be compressed");
+		assert!(compressed.len() < binary.len() * 2 / 3);
+		assert_eq!(decompress(&compressed).expect("module to be decompressed"), source);
+		assert_eq!(load(&compressed).expect("compressed module to be loaded"), source);
+		assert_eq!(load(&binary).expect("binary module to be loaded"), source);
+
+		assert!(decompress(&binary).is_err());
+		assert!(decompress(&compressed[..compressed.len() - 1]).is_err());
+		let mut trailing = compressed.clone();
+		trailing.push(0);
+		assert!(decompress(&trailing).is_err());
+	}
+}
@@ -0,0 +1,189 @@
+use io;
+use std::vec::Vec;
+
+use super::{Deserialize, Serialize, Error, FuncBody, VarUint32, ModuleReader};
+
+const START_SECTION_ID: u8 = 0x08;
+const CODE_SECTION_ID: u8 = 0x0a;
+const DATA_COUNT_SECTION_ID: u8 = 0x0c;
+
+/// Encoding quirk found by `audit_encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingQuirk {
+	/// LEB128 integer encoded with more bytes than needed.
+	OverlongLeb {
+		/// Number of bytes used.
+		len: usize,
+		/// Number of bytes needed.
+		minimal: usize,
+	},
+	/// Section whose payload differs from its canonical re-encoding.
+	NonCanonicalSection {
+		/// Id of the section.
+		id: u8,
+	},
+	/// Non-custom section without any entry.
+	EmptySection {
+		/// Id of the section.
+		id: u8,
+	},
+	/// Function body which differs from its canonical re-encoding.
+	NonCanonicalBody {
+		/// Index of the body in the code section.
+		index: usize,
+	},
+	/// Local declaration of the same type as the previous one, which could
+	/// have been merged with it.
+	UnmergedLocals {
+		/// Index of the body in the code section.
+		index: usize,
+	},
+}
+
+/// Encoding quirk found at some offset of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingFinding {
+	/// Offset from the beginning of the module.
+	pub offset: usize,
+	/// Quirk found.
+	pub quirk: EncodingQuirk,
+}
+
+/// Lists the non-canonical encodings of the serialized module `bytes`.
+///
+/// Encoders leave their fingerprint in valid modules: padded LEB128
+/// integers, empty sections, unmerged local declarations, etc. An empty
+/// list means that the module is encoded as this crate would serialize it.
+///
+/// Sections and function bodies are re-encoded to catch quirks in their
+/// entries (e.g. padded instruction immediates); such findings are reported
+/// at the start of the section or body, in addition to any more precise
+/// finding. Custom sections are only checked for their name length.
+pub fn audit_encoding(bytes: &[u8]) -> Result<Vec<EncodingFinding>, Error> {
+	let mut findings = Vec::new();
+	for header in ModuleReader::new(bytes)?.sections() {
+		let header = header?;
+		leb(bytes, header.offset() + 1, &mut findings)?;
+		let payload_offset = header.payload_offset();
+		let entries = match header.id() {
+			0 | START_SECTION_ID | DATA_COUNT_SECTION_ID => { leb(bytes, payload_offset, &mut findings)?; None },
+			CODE_SECTION_ID => Some(code(bytes, payload_offset, payload_offset + header.payload().len(), &mut findings)?),
+			_ => Some(leb(bytes, payload_offset, &mut findings)?.0),
+		};
+		if entries == Some(0) {
+			findings.push(EncodingFinding { offset: header.offset(), quirk: EncodingQuirk::EmptySection { id: header.id() } });
+		}
+		if header.id() != 0 && header.id() != CODE_SECTION_ID
+			&& reencode_payload(header.decode()?, 1)? != header.payload()
+		{
+			findings.push(EncodingFinding {
+				offset: header.offset(),
+				quirk: EncodingQuirk::NonCanonicalSection { id: header.id() },
+			});
+		}
+	}
+	Ok(findings)
+}
+
+/// Audits the code section payload between `start` and `end`, returning the
+/// number of bodies.
+fn code(bytes: &[u8], start: usize, end: usize, findings: &mut Vec<EncodingFinding>) -> Result<u32, Error> {
+	let (count, len) = leb(bytes, start, findings)?;
+	let mut position = start + len;
+	for index in 0..count as usize {
+		let body_offset = position;
+		let (size, len) = leb(bytes, position, findings)?;
+		let body_start = position + len;
+		let body_end = body_start.checked_add(size as usize)
+			.filter(|&body_end| body_end <= end)
+			.ok_or(Error::UnexpectedEof)?;
+
+		let (locals, len) = leb(bytes, body_start, findings)?;
+		position = body_start + len;
+		let mut previous_type = None;
+		for _ in 0..locals {
+			let (_, len) = leb(bytes, position, findings)?;
+			let value_type = *bytes.get(position + len).ok_or(Error::UnexpectedEof)?;
+			if previous_type == Some(value_type) {
+				findings.push(EncodingFinding { offset: position, quirk: EncodingQuirk::UnmergedLocals { index } });
+			}
+			previous_type = Some(value_type);
+			position += len + 1;
+		}
+
+		let body = FuncBody::deserialize(&mut io::Cursor::new(&bytes[body_offset..body_end]))?;
+		if reencode_payload(body, 0)?[..] != bytes[body_start..body_end] {
+			findings.push(EncodingFinding { offset: body_offset, quirk: EncodingQuirk::NonCanonicalBody { index } });
+		}
+		position = body_end;
+	}
+	Ok(count)
+}
+
+/// Reads the `VarUint32` at `offset`, returning it with its length.
+fn leb(bytes: &[u8], offset: usize, findings: &mut Vec<EncodingFinding>) -> Result<(u32, usize), Error> {
+	let mut rdr = io::Cursor::new(bytes.get(offset..).ok_or(Error::UnexpectedEof)?);
+	let value: u32 = VarUint32::deserialize(&mut rdr)?.into();
+	let len = rdr.position();
+	let mut minimal = 1;
+	while minimal < 5 && value >> (7 * minimal) != 0 {
+		minimal += 1;
+	}
+	if len != minimal {
+		findings.push(EncodingFinding { offset, quirk: EncodingQuirk::OverlongLeb { len, minimal } });
+	}
+	Ok((value, len))
+}
+
+/// Serializes `item`, whose size follows `prefix` bytes, and strips both.
+fn reencode_payload<T: Serialize<Error = Error>>(item: T, prefix: usize) -> Result<Vec<u8>, Error> {
+	let mut buf = Vec::new();
+	item.serialize(&mut buf)?;
+	let mut rdr = io::Cursor::new(&buf[prefix..]);
+	let _ = VarUint32::deserialize(&mut rdr)?;
+	let start = prefix + rdr.position();
+	Ok(buf[start..].to_vec())
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{audit_encoding, EncodingFinding, EncodingQuirk};
+	use builder::module;
+	use elements::{serialize, Instructions, Local, ValueType};
+	use elements::Instruction::*;
+
+	#[test]
+	fn canonical() {
+		let bytes = serialize(module()
+			.function().signature().build()
+				.body()
+					.with_locals(vec![Local::new(2, ValueType::I32)])
+					.with_instructions(Instructions::new(vec![I32Const(1000), Drop, End]))
+					.build()
+				.build()
+			.export().field("main").internal().func(0).build()
+			.build()).expect("module to serialize");
+		assert_eq!(audit_encoding(&bytes).expect("module to be valid"), vec![]);
+	}
+
+	#[test]
+	fn quirks() {
+		let bytes = [
+			0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+			// Type section, with a padded size.
+			0x01, 0x84, 0x80, 0x00, 0x01, 0x60, 0x00, 0x00,
+			// Function section.
+			0x03, 0x02, 0x01, 0x00,
+			// Empty export section.
+			0x07, 0x01, 0x00,
+			// Code section: unmerged i32 locals, padded `i32.const 0`.
+			0x0a, 0x0c, 0x01, 0x0a, 0x02, 0x01, 0x7f, 0x01, 0x7f, 0x41, 0x80, 0x00, 0x1a, 0x0b,
+		];
+		assert_eq!(audit_encoding(&bytes).expect("module to be valid"), vec![
+			EncodingFinding { offset: 9, quirk: EncodingQuirk::OverlongLeb { len: 3, minimal: 1 } },
+			EncodingFinding { offset: 20, quirk: EncodingQuirk::EmptySection { id: 7 } },
+			EncodingFinding { offset: 30, quirk: EncodingQuirk::UnmergedLocals { index: 0 } },
+			EncodingFinding { offset: 26, quirk: EncodingQuirk::NonCanonicalBody { index: 0 } },
+		]);
+	}
+}
@@ -0,0 +1,352 @@
+use io;
+use std::ops::Range;
+use std::vec::Vec;
+use std::string::String;
+
+use super::{
+	CountedList, CustomSection, Deserialize, Error, FuncBody, ImportCountType, IndexMap, Instruction,
+	Local, Module, Section, Serialize, VarUint32,
+};
+
+/// Prefix of the names of the custom sections holding code metadata.
+pub const CODE_METADATA_PREFIX: &str = "metadata.code.";
+
+/// Kind of the code metadata holding branch hints.
+pub const BRANCH_HINT_KIND: &str = "branch_hint";
+
+/// Metadata attached to an instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeAnnotation {
+	instruction: u32,
+	data: Vec<u8>,
+}
+
+impl CodeAnnotation {
+	/// Index of the annotated instruction in the body of its function.
+	pub fn instruction(&self) -> u32 { self.instruction }
+
+	/// Payload of the annotation, whose meaning depends on the kind of
+	/// metadata.
+	pub fn data(&self) -> &[u8] { &self.data }
+}
+
+/// Per-instruction annotations stored in a `metadata.code.<kind>` custom
+/// section, following the code metadata convention (branch hints being the
+/// `branch_hint` kind).
+///
+/// The binary format locates instructions by their byte offset from the
+/// start of the function body (its locals declarations); here annotations
+/// refer to instructions by index in the body, and offsets are computed from
+/// the current bodies when serializing, so they stay right when the
+/// encoding of the bodies changes. Use `splice` to keep indices in sync when
+/// instructions are inserted or removed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CodeMetadataSection {
+	kind: String,
+	functions: IndexMap<Vec<CodeAnnotation>>,
+}
+
+/// Byte offsets of the instructions of `body` from the start of the body.
+fn instruction_offsets(body: &FuncBody) -> Result<Vec<u32>, Error> {
+	let bytes = match body.raw_bytes() {
+		Some(raw) => raw.to_vec(),
+		None => {
+			let mut bytes = Vec::new();
+			body.clone().serialize(&mut bytes)?;
+			let mut rdr = io::Cursor::new(&bytes[..]);
+			VarUint32::deserialize(&mut rdr)?;
+			let prefix = rdr.position();
+			bytes.split_off(prefix)
+		},
+	};
+	let mut rdr = io::Cursor::new(&bytes[..]);
+	CountedList::<Local>::deserialize(&mut rdr)?;
+	let mut offsets = Vec::with_capacity(body.code().elements().len());
+	for _ in body.code().elements() {
+		offsets.push(rdr.position() as u32);
+		Instruction::deserialize(&mut rdr)?;
+	}
+	Ok(offsets)
+}
+
+/// Body of the function `func_idx` of `module`, if it is defined.
+fn function_body(module: &Module, func_idx: u32) -> Option<&FuncBody> {
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	module.code_section()?.bodies().get(func_idx.checked_sub(imported)? as usize)
+}
+
+impl CodeMetadataSection {
+	/// New empty metadata of the given kind (the section name without the
+	/// `metadata.code.` prefix).
+	pub fn new<S: Into<String>>(kind: S) -> CodeMetadataSection {
+		CodeMetadataSection { kind: kind.into(), functions: IndexMap::default() }
+	}
+
+	/// New empty branch hints.
+	pub fn branch_hints() -> CodeMetadataSection {
+		CodeMetadataSection::new(BRANCH_HINT_KIND)
+	}
+
+	/// Kind of the metadata.
+	pub fn kind(&self) -> &str {
+		&self.kind
+	}
+
+	/// Annotations by function index (imports included), sorted by
+	/// instruction.
+	pub fn functions(&self) -> &IndexMap<Vec<CodeAnnotation>> {
+		&self.functions
+	}
+
+	/// Annotations of the function `func_idx`, sorted by instruction.
+	pub fn annotations(&self, func_idx: u32) -> &[CodeAnnotation] {
+		self.functions.get(func_idx).map_or(&[][..], |annotations| &annotations[..])
+	}
+
+	/// Annotation of the instruction `instruction` of the function `func_idx`.
+	pub fn annotation(&self, func_idx: u32, instruction: u32) -> Option<&[u8]> {
+		self.annotations(func_idx).iter()
+			.find(|annotation| annotation.instruction == instruction)
+			.map(|annotation| &annotation.data[..])
+	}
+
+	/// Annotates the instruction `instruction` of the function `func_idx`,
+	/// replacing its existing annotation.
+	pub fn annotate(&mut self, func_idx: u32, instruction: u32, data: Vec<u8>) {
+		let mut annotations = self.functions.remove(func_idx).unwrap_or_default();
+		match annotations.binary_search_by_key(&instruction, |annotation| annotation.instruction) {
+			Ok(position) => annotations[position].data = data,
+			Err(position) => annotations.insert(position, CodeAnnotation { instruction, data }),
+		}
+		self.functions.insert(func_idx, annotations);
+	}
+
+	/// Removes the annotation of the instruction `instruction` of the
+	/// function `func_idx`, returning its data.
+	pub fn remove(&mut self, func_idx: u32, instruction: u32) -> Option<Vec<u8>> {
+		let mut annotations = self.functions.remove(func_idx)?;
+		let removed = annotations.iter()
+			.position(|annotation| annotation.instruction == instruction)
+			.map(|position| annotations.remove(position).data);
+		if !annotations.is_empty() {
+			self.functions.insert(func_idx, annotations);
+		}
+		removed
+	}
+
+	/// Updates the annotations of the function `func_idx` after the
+	/// instructions at `range` were replaced by `inserted` instructions:
+	/// annotations in `range` are dropped, and the following ones are moved.
+	pub fn splice(&mut self, func_idx: u32, range: Range<u32>, inserted: u32) {
+		let annotations = match self.functions.remove(func_idx) {
+			Some(annotations) => annotations,
+			None => return,
+		};
+		let annotations: Vec<CodeAnnotation> = annotations.into_iter()
+			.filter(|annotation| !range.contains(&annotation.instruction))
+			.map(|mut annotation| {
+				if annotation.instruction >= range.end {
+					annotation.instruction = annotation.instruction - (range.end - range.start) + inserted;
+				}
+				annotation
+			})
+			.collect();
+		if !annotations.is_empty() {
+			self.functions.insert(func_idx, annotations);
+		}
+	}
+
+	/// Branch hint of the instruction `instruction` of the function
+	/// `func_idx`: `Some(true)` if the branch is likely taken, `Some(false)`
+	/// if it is unlikely.
+	pub fn branch_hint(&self, func_idx: u32, instruction: u32) -> Option<bool> {
+		match self.annotation(func_idx, instruction) {
+			Some(&[hint]) => Some(hint == 1),
+			_ => None,
+		}
+	}
+
+	/// Hints whether the branch (`br_if` or `if`) at `instruction` in the
+	/// function `func_idx` is likely taken.
+	pub fn set_branch_hint(&mut self, func_idx: u32, instruction: u32, likely: bool) {
+		self.annotate(func_idx, instruction, vec![likely as u8]);
+	}
+
+	/// Parses the payload of a code metadata section of the given `kind`,
+	/// mapping offsets to the instructions of the bodies of `module`.
+	pub fn deserialize<R: io::Read>(module: &Module, kind: &str, rdr: &mut R) -> Result<CodeMetadataSection, Error> {
+		let deserialize_annotations = |func_idx: u32, rdr: &mut R| -> Result<Vec<CodeAnnotation>, Error> {
+			let body = function_body(module, func_idx)
+				.ok_or(Error::Other("code metadata refers to a function without a body"))?;
+			let offsets = instruction_offsets(body)?;
+			let count: u32 = VarUint32::deserialize(rdr)?.into();
+			let mut annotations: Vec<CodeAnnotation> = Vec::new();
+			for _ in 0..count {
+				let offset: u32 = VarUint32::deserialize(rdr)?.into();
+				let size: u32 = VarUint32::deserialize(rdr)?.into();
+				let data = buffered_read!(1024, size as usize, rdr);
+				let instruction = offsets.binary_search(&offset)
+					.map_err(|_| Error::Other("code metadata offset is not at an instruction"))? as u32;
+				if annotations.last().map_or(false, |last| last.instruction >= instruction) {
+					return Err(Error::Other("code metadata offsets are out of order"));
+				}
+				annotations.push(CodeAnnotation { instruction, data });
+			}
+			Ok(annotations)
+		};
+		let functions = IndexMap::deserialize_with(module.functions_space(), &deserialize_annotations, rdr)?;
+		Ok(CodeMetadataSection { kind: kind.into(), functions })
+	}
+
+	/// Parses code metadata out of the custom section `section` of `module`.
+	pub fn from_custom_section(module: &Module, section: &CustomSection) -> Result<CodeMetadataSection, Error> {
+		if !section.name().starts_with(CODE_METADATA_PREFIX) {
+			return Err(Error::Other("custom section is not a code metadata section"));
+		}
+		let kind = &section.name()[CODE_METADATA_PREFIX.len()..];
+		let mut rdr = io::Cursor::new(section.payload());
+		let metadata = CodeMetadataSection::deserialize(module, kind, &mut rdr)?;
+		if rdr.position() != section.payload().len() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(metadata)
+	}
+
+	/// Encodes the metadata for the bodies of `module` into a custom section.
+	///
+	/// Annotations of functions without a body or of instructions past the
+	/// end of their body are dropped.
+	pub fn into_custom_section(self, module: &Module) -> Result<CustomSection, Error> {
+		let mut functions = Vec::new();
+		for (func_idx, annotations) in self.functions {
+			let offsets = match function_body(module, func_idx) {
+				Some(body) => instruction_offsets(body)?,
+				None => continue,
+			};
+			let annotations: Vec<(u32, Vec<u8>)> = annotations.into_iter()
+				.filter_map(|annotation| Some((*offsets.get(annotation.instruction as usize)?, annotation.data)))
+				.collect();
+			if !annotations.is_empty() {
+				functions.push((func_idx, annotations));
+			}
+		}
+
+		let mut payload = Vec::new();
+		VarUint32::from(functions.len()).serialize(&mut payload)?;
+		for (func_idx, annotations) in functions {
+			VarUint32::from(func_idx).serialize(&mut payload)?;
+			VarUint32::from(annotations.len()).serialize(&mut payload)?;
+			for (offset, data) in annotations {
+				VarUint32::from(offset).serialize(&mut payload)?;
+				VarUint32::from(data.len()).serialize(&mut payload)?;
+				payload.extend(data);
+			}
+		}
+		Ok(CustomSection::new(format!("{}{}", CODE_METADATA_PREFIX, self.kind), payload))
+	}
+}
+
+impl Module {
+	/// Code metadata of the given `kind` (e.g. `BRANCH_HINT_KIND`), stored in
+	/// the `metadata.code.<kind>` custom section, if any.
+	pub fn code_metadata(&self, kind: &str) -> Option<Result<CodeMetadataSection, Error>> {
+		self.sections().iter()
+			.filter_map(|section| match *section {
+				Section::Custom(ref custom) if custom.name().starts_with(CODE_METADATA_PREFIX)
+					&& &custom.name()[CODE_METADATA_PREFIX.len()..] == kind => Some(custom),
+				_ => None,
+			})
+			.next()
+			.map(|custom| CodeMetadataSection::from_custom_section(self, custom))
+	}
+
+	/// Stores `metadata` in its custom section, replacing the existing one or
+	/// inserting a new section before the code section (or at the end of the
+	/// module if there is none).
+	///
+	/// Offsets are computed from the current bodies, so this has to be called
+	/// again after the bodies change.
+	pub fn set_code_metadata(&mut self, metadata: CodeMetadataSection) -> Result<(), Error> {
+		let custom = metadata.into_custom_section(self)?;
+		let existing = self.sections().iter().position(|section| match *section {
+			Section::Custom(ref existing) => existing.name() == custom.name(),
+			_ => false,
+		});
+		match existing {
+			Some(position) => self.sections_mut()[position] = Section::Custom(custom),
+			None => {
+				let position = self.sections().iter()
+					.position(|section| matches!(*section, Section::Code(_)))
+					.unwrap_or_else(|| self.sections().len());
+				self.sections_mut().insert(position, Section::Custom(custom));
+			},
+		}
+		Ok(())
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::CodeMetadataSection;
+	use builder::module;
+	use elements::{serialize, deserialize_buffer, Module, Section, Instructions, Local, ValueType, BlockType};
+	use elements::Instruction::*;
+
+	fn sample() -> Module {
+		module()
+			.import().module("env").field("f").external().func(0).build()
+			.function().signature().build()
+				.body()
+					.with_locals(vec![Local::new(1, ValueType::I32)])
+					.with_instructions(Instructions::new(vec![
+						I32Const(1000), If(BlockType::NoResult), Nop, End, End,
+					]))
+					.build()
+				.build()
+			.build()
+	}
+
+	#[test]
+	fn branch_hints() {
+		let mut module = sample();
+		let mut hints = CodeMetadataSection::branch_hints();
+		hints.set_branch_hint(1, 1, true);
+		module.set_code_metadata(hints.clone()).expect("metadata to be stored");
+
+		let position = module.sections().iter().position(|s| matches!(*s, Section::Code(_))).expect("code section");
+		match module.sections()[position - 1] {
+			Section::Custom(ref custom) => {
+				assert_eq!(custom.name(), "metadata.code.branch_hint");
+				// Function 1, one hint at offset 6 (locals: 3 bytes, i32.const 1000: 3 bytes).
+				assert_eq!(custom.payload(), &[1, 1, 1, 6, 1, 1][..]);
+			},
+			ref other => panic!("unexpected section {:?}", other),
+		}
+
+		let module: Module = deserialize_buffer(&serialize(module).expect("module to serialize"))
+			.expect("module to deserialize");
+		let parsed = module.code_metadata("branch_hint").expect("branch hints").expect("branch hints to parse");
+		assert_eq!(parsed, hints);
+		assert_eq!(parsed.branch_hint(1, 1), Some(true));
+		assert_eq!(parsed.branch_hint(1, 0), None);
+	}
+
+	#[test]
+	fn offsets_follow_bodies() {
+		let mut module = sample();
+		let mut hints = CodeMetadataSection::branch_hints();
+		hints.set_branch_hint(1, 1, false);
+
+		// Insert a `nop` in front: the hint moves along with the `if`.
+		module.code_section_mut().expect("code section").bodies_mut()[0].code_mut().elements_mut().insert(0, Nop);
+		hints.splice(1, 0..0, 1);
+		assert_eq!(hints.branch_hint(1, 2), Some(false));
+		module.set_code_metadata(hints).expect("metadata to be stored");
+		let parsed = module.code_metadata("branch_hint").expect("branch hints").expect("branch hints to parse");
+		assert_eq!(parsed.branch_hint(1, 2), Some(false));
+
+		let mut removed = parsed.clone();
+		removed.splice(1, 1..3, 0);
+		assert!(removed.annotations(1).is_empty());
+	}
+}
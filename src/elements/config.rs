@@ -0,0 +1,183 @@
+use io::{self, Read};
+use super::{deserialize_buffer, Deserialize, Error, Module, ModuleReader, VarUint32};
+
+const START_SECTION_ID: u8 = 0x08;
+const CODE_SECTION_ID: u8 = 0x0a;
+const DATA_COUNT_SECTION_ID: u8 = 0x0c;
+
+/// Limits checked by `deserialize_with_config` before decoding a module
+/// from untrusted input.
+///
+/// The default limits are the implementation limits of the web engines,
+/// with non-minimal LEB128 integers allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializeConfig {
+	/// Maximum size of the binary, in bytes.
+	pub max_module_size: usize,
+	/// Maximum size of the payload of a section, in bytes.
+	pub max_section_size: usize,
+	/// Maximum number of entries of a section (types, imports, function
+	/// bodies, data segments, ...).
+	pub max_section_entries: u32,
+	/// Maximum size of a function body, in bytes.
+	pub max_function_size: usize,
+	/// Maximum number of locals declared by a function body, parameters
+	/// excluded.
+	pub max_locals: u32,
+	/// Whether to reject the LEB128 integers of section sizes, entry counts,
+	/// body sizes and local counts which are longer than their shortest
+	/// encoding. Linkers pad some of them, which the specification allows.
+	pub strict_leb128: bool,
+}
+
+impl Default for DeserializeConfig {
+	fn default() -> Self {
+		DeserializeConfig {
+			max_module_size: 1_073_741_824,
+			max_section_size: 1_073_741_824,
+			max_section_entries: 1_000_000,
+			max_function_size: 7_654_321,
+			max_locals: 50_000,
+			strict_leb128: false,
+		}
+	}
+}
+
+/// Deserializes the module `contents` after checking it against the limits
+/// of `config`.
+///
+/// The sizes and counts declared by the sections and function bodies are
+/// checked before anything is decoded, so that a small binary declaring
+/// huge sections is rejected without allocating for them. A count can't be
+/// larger than the size of its section either, every entry taking at least
+/// a byte.
+pub fn deserialize_with_config(contents: &[u8], config: &DeserializeConfig) -> Result<Module, Error> {
+	if contents.len() > config.max_module_size {
+		return Err(Error::LimitExceeded("module size"));
+	}
+	for header in ModuleReader::new(contents)?.sections() {
+		let header = header?;
+		let payload = header.payload();
+		if config.strict_leb128 && header.payload_offset() - header.offset() - 1 != leb128_len(payload.len() as u32) {
+			return Err(Error::OverlongLeb128);
+		}
+		if payload.len() > config.max_section_size {
+			return Err(Error::LimitExceeded("section size"));
+		}
+		match header.id() {
+			0 => {
+				let mut rdr = io::Cursor::new(payload);
+				read_u32(&mut rdr, config)?;
+			},
+			START_SECTION_ID | DATA_COUNT_SECTION_ID => {},
+			_ => {
+				let mut rdr = io::Cursor::new(payload);
+				let count = read_u32(&mut rdr, config)?;
+				if count > config.max_section_entries {
+					return Err(Error::LimitExceeded("section entries"));
+				}
+				if count as usize > payload.len() {
+					return Err(Error::UnexpectedEof);
+				}
+				if header.id() == CODE_SECTION_ID {
+					let code = header.code()?;
+					for body_idx in 0..code.len() {
+						check_body(code.raw_body(body_idx).ok_or(Error::UnexpectedEof)?, config)?;
+					}
+				}
+			},
+		}
+	}
+	deserialize_buffer(contents)
+}
+
+/// Checks the size and the locals of the function body `raw`, size prefix
+/// included.
+fn check_body(raw: &[u8], config: &DeserializeConfig) -> Result<(), Error> {
+	let mut rdr = io::Cursor::new(raw);
+	if read_u32(&mut rdr, config)? as usize > config.max_function_size {
+		return Err(Error::LimitExceeded("function size"));
+	}
+	let groups = read_u32(&mut rdr, config)?;
+	if groups as usize > raw.len() {
+		return Err(Error::UnexpectedEof);
+	}
+	let mut locals = 0u32;
+	for _ in 0..groups {
+		locals = locals.saturating_add(read_u32(&mut rdr, config)?);
+		if locals > config.max_locals {
+			return Err(Error::LimitExceeded("locals"));
+		}
+		// Value type.
+		rdr.read(&mut [0u8; 1])?;
+	}
+	Ok(())
+}
+
+/// Reads a LEB128 integer, checking its encoding is minimal if required.
+fn read_u32(rdr: &mut io::Cursor<&[u8]>, config: &DeserializeConfig) -> Result<u32, Error> {
+	let start = rdr.position();
+	let value: u32 = VarUint32::deserialize(rdr)?.into();
+	if config.strict_leb128 && rdr.position() - start != leb128_len(value) {
+		return Err(Error::OverlongLeb128);
+	}
+	Ok(value)
+}
+
+/// Length of the shortest LEB128 encoding of `value`.
+fn leb128_len(value: u32) -> usize {
+	let bits = 32 - value.leading_zeros() as usize;
+	if bits == 0 { 1 } else { (bits + 6) / 7 }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{deserialize_with_config, DeserializeConfig};
+	use elements::Error;
+
+	fn config() -> DeserializeConfig {
+		DeserializeConfig { strict_leb128: true, ..DeserializeConfig::default() }
+	}
+
+	#[test]
+	#[cfg(feature = "builder")]
+	fn limits() {
+		use builder::module;
+		use elements::{self, Local, ValueType};
+
+		let mut built = module()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(0).build()
+			.build();
+		built.code_section_mut().expect("code section").bodies_mut()[0].locals_mut()
+			.extend(vec![Local::new(10, ValueType::I32), Local::new(20, ValueType::I64)]);
+		let binary = elements::serialize(built.clone()).expect("module to serialize");
+
+		assert_eq!(deserialize_with_config(&binary, &config()).expect("module to be within limits"), built);
+		let check = |config: DeserializeConfig| match deserialize_with_config(&binary, &config) {
+			Err(Error::LimitExceeded(limit)) => limit,
+			other => panic!("unexpected {:?}", other),
+		};
+		assert_eq!(check(DeserializeConfig { max_module_size: binary.len() - 1, ..config() }), "module size");
+		assert_eq!(check(DeserializeConfig { max_section_size: 5, ..config() }), "section size");
+		assert_eq!(check(DeserializeConfig { max_section_entries: 0, ..config() }), "section entries");
+		assert_eq!(check(DeserializeConfig { max_function_size: 5, ..config() }), "function size");
+		assert_eq!(check(DeserializeConfig { max_locals: 29, ..config() }), "locals");
+	}
+
+	#[test]
+	fn untrusted() {
+		// A type section declaring a million types.
+		let bogus = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0xc0, 0x84, 0x3d, 0x60];
+		assert!(matches!(deserialize_with_config(&bogus, &config()), Err(Error::UnexpectedEof)));
+
+		// An empty type section whose size is padded.
+		let padded = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x81, 0x00, 0x00];
+		assert!(matches!(deserialize_with_config(&padded, &config()), Err(Error::OverlongLeb128)));
+		assert!(deserialize_with_config(&padded, &DeserializeConfig::default()).is_ok());
+
+		// An empty type section whose count is padded.
+		let padded = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x02, 0x80, 0x00];
+		assert!(matches!(deserialize_with_config(&padded, &config()), Err(Error::OverlongLeb128)));
+	}
+}
@@ -82,23 +82,44 @@ impl Serialize for Local {
 }
 
 /// Function body definition.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct FuncBody {
 	locals: Vec<Local>,
 	instructions: Instructions,
+	raw: Option<Vec<u8>>,
+}
+
+/// Bodies are compared by their locals and instructions, whether or not
+/// their raw bytes are kept.
+impl PartialEq for FuncBody {
+	fn eq(&self, other: &FuncBody) -> bool {
+		self.locals == other.locals && self.instructions == other.instructions
+	}
 }
 
 impl FuncBody {
 	/// New function body with given `locals` and `instructions`.
 	pub fn new(locals: Vec<Local>, instructions: Instructions) -> Self {
-		FuncBody { locals: locals, instructions: instructions }
+		FuncBody { locals: locals, instructions: instructions, raw: None }
 	}
 
 	/// List of individual instructions.
 	pub fn empty() -> Self {
-		FuncBody { locals: Vec::new(), instructions: Instructions::empty() }
+		FuncBody { locals: Vec::new(), instructions: Instructions::empty(), raw: None }
 	}
 
+	/// Encoding of the body (locals and instructions, without the size
+	/// prefix) as found in the binary it was decoded from.
+	///
+	/// Only kept when the module is decoded with
+	/// `deserialize_buffer_with_raw_bodies`, and dropped as soon as the
+	/// locals or instructions are borrowed mutably. While available, these
+	/// bytes are written verbatim when the body is serialized.
+	pub fn raw_bytes(&self) -> Option<&[u8]> { self.raw.as_ref().map(|raw| &raw[..]) }
+
+	/// Keeps `raw` as the encoding of this body, see `raw_bytes`.
+	pub(crate) fn set_raw_bytes(&mut self, raw: Vec<u8>) { self.raw = Some(raw); }
+
 	/// Locals declared in function body.
 	pub fn locals(&self) -> &[Local] { &self.locals }
 
@@ -108,10 +129,16 @@ impl FuncBody {
 	pub fn code(&self) -> &Instructions { &self.instructions }
 
 	/// Locals declared in function body (mutable).
-	pub fn locals_mut(&mut self) -> &mut Vec<Local> { &mut self.locals }
+	pub fn locals_mut(&mut self) -> &mut Vec<Local> {
+		self.raw = None;
+		&mut self.locals
+	}
 
 	/// Instruction list of the function body (mutable).
-	pub fn code_mut(&mut self) -> &mut Instructions { &mut self.instructions }
+	pub fn code_mut(&mut self) -> &mut Instructions {
+		self.raw = None;
+		&mut self.instructions
+	}
 }
 
 impl Deserialize for FuncBody {
@@ -130,7 +157,7 @@ impl Deserialize for FuncBody {
 
 		let instructions = Instructions::deserialize(&mut body_reader)?;
 		body_reader.close()?;
-		Ok(FuncBody { locals: locals, instructions: instructions })
+		Ok(FuncBody { locals: locals, instructions: instructions, raw: None })
 	}
 }
 
@@ -138,6 +165,12 @@ impl Serialize for FuncBody {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		if let Some(raw) = self.raw {
+			VarUint32::from(raw.len()).serialize(writer)?;
+			writer.write(&raw)?;
+			return Ok(());
+		}
+
 		let mut counted_writer = CountedWriter::new(writer);
 
 		let data = self.locals;
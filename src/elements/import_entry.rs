@@ -1,12 +1,15 @@
 use io;
 use std::string::String;
 use super::{
-	Deserialize, Serialize, Error, VarUint7, VarInt7, VarUint32, VarUint1, Uint8,
+	Deserialize, Serialize, Error, VarUint7, VarInt7, VarUint32, VarUint64, VarUint1, Uint8,
 	ValueType, TableElementType
 };
 
 const FLAG_HAS_MAX: u8 = 0x01;
 const FLAG_SHARED: u8 = 0x02;
+const FLAG_64: u8 = 0x04;
+const FLAG_PAGE_SIZE: u8 = 0x08;
+const KNOWN_FLAGS: u8 = FLAG_HAS_MAX | FLAG_SHARED | FLAG_64 | FLAG_PAGE_SIZE;
 
 /// Global definition struct
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -73,8 +76,14 @@ impl TableType {
 	/// Table memory specification
 	pub fn limits(&self) -> &ResizableLimits { &self.limits }
 
+	/// Table memory specification (mutable)
+	pub fn limits_mut(&mut self) -> &mut ResizableLimits { &mut self.limits }
+
 	/// Table element type
 	pub fn elem_type(&self) -> TableElementType { self.elem_type }
+
+	/// Table element type (mutable)
+	pub fn elem_type_mut(&mut self) -> &mut TableElementType { &mut self.elem_type }
 }
 
 impl Deserialize for TableType {
@@ -83,6 +92,9 @@ impl Deserialize for TableType {
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let elem_type = TableElementType::deserialize(reader)?;
 		let limits = ResizableLimits::deserialize(reader)?;
+		if limits.shared() || limits.page_size_log2().is_some() {
+			return Err(Error::InvalidLimitsFlags(limits.flags()));
+		}
 		Ok(TableType {
 			elem_type: elem_type,
 			limits: limits,
@@ -100,11 +112,19 @@ impl Serialize for TableType {
 }
 
 /// Memory and table limits.
+///
+/// Sizes are encoded as 64-bit integers for 64-bit memories and tables, but
+/// sizes which don't fit in 32 bits are not supported.
+///
+/// Memories may declare a custom page size (custom-page-sizes proposal),
+/// encoded as its base 2 logarithm after the sizes.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct ResizableLimits {
 	initial: u32,
 	maximum: Option<u32>,
 	shared: bool,
+	is_64: bool,
+	page_size_log2: Option<u32>,
 }
 
 impl ResizableLimits {
@@ -114,6 +134,8 @@ impl ResizableLimits {
 			initial: min,
 			maximum: max,
 			shared: false,
+			is_64: false,
+			page_size_log2: None,
 		}
 	}
 	/// Initial size.
@@ -122,6 +144,53 @@ impl ResizableLimits {
 	pub fn maximum(&self) -> Option<u32> { self.maximum }
 	/// Whether or not this is a shared array buffer.
 	pub fn shared(&self) -> bool { self.shared }
+	/// Whether or not the memory or table is indexed with 64-bit addresses.
+	pub fn is_64(&self) -> bool { self.is_64 }
+	/// Whether or not the memory or table is indexed with 64-bit addresses (mutable).
+	pub fn is_64_mut(&mut self) -> &mut bool { &mut self.is_64 }
+	/// Base 2 logarithm of the page size, if it is not the default 64KiB.
+	pub fn page_size_log2(&self) -> Option<u32> { self.page_size_log2 }
+	/// Base 2 logarithm of the page size, if it is not the default 64KiB (mutable).
+	pub fn page_size_log2_mut(&mut self) -> &mut Option<u32> { &mut self.page_size_log2 }
+	/// Size of a page in bytes (at most 2^63, which no valid memory exceeds).
+	pub fn page_size(&self) -> u64 { 1 << self.page_size_log2.unwrap_or(16).min(63) }
+
+	/// Flags byte of the encoding.
+	pub fn flags(&self) -> u8 {
+		let mut flags = 0;
+		if self.maximum.is_some() {
+			flags |= FLAG_HAS_MAX;
+		}
+		if self.shared {
+			flags |= FLAG_SHARED;
+		}
+		if self.is_64 {
+			flags |= FLAG_64;
+		}
+		if self.page_size_log2.is_some() {
+			flags |= FLAG_PAGE_SIZE;
+		}
+		flags
+	}
+}
+
+fn deserialize_size<R: io::Read>(reader: &mut R, is_64: bool) -> Result<u32, Error> {
+	if !is_64 {
+		return Ok(VarUint32::deserialize(reader)?.into());
+	}
+	let size: u64 = VarUint64::deserialize(reader)?.into();
+	if size > ::std::u32::MAX as u64 {
+		return Err(Error::Other("64-bit limits larger than 2^32 are not supported"));
+	}
+	Ok(size as u32)
+}
+
+fn serialize_size<W: io::Write>(writer: &mut W, size: u32, is_64: bool) -> Result<(), Error> {
+	if is_64 {
+		VarUint64::from(size as u64).serialize(writer)
+	} else {
+		VarUint32::from(size).serialize(writer)
+	}
 }
 
 impl Deserialize for ResizableLimits {
@@ -129,23 +198,34 @@ impl Deserialize for ResizableLimits {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let flags: u8 = Uint8::deserialize(reader)?.into();
-		match flags {
-			0x00 | 0x01 | 0x03 => {},
-			_ => return Err(Error::InvalidLimitsFlags(flags)),
+		if flags & !KNOWN_FLAGS != 0 {
+			return Err(Error::UnknownLimitsFlags(flags & !KNOWN_FLAGS));
+		}
+		let shared = flags & FLAG_SHARED != 0;
+		let is_64 = flags & FLAG_64 != 0;
+		// Shared memories must declare their maximum size.
+		if shared && flags & FLAG_HAS_MAX == 0 {
+			return Err(Error::InvalidLimitsFlags(flags));
 		}
 
-		let initial = VarUint32::deserialize(reader)?;
+		let initial = deserialize_size(reader, is_64)?;
 		let maximum = if flags & FLAG_HAS_MAX != 0 {
+			Some(deserialize_size(reader, is_64)?)
+		} else {
+			None
+		};
+		let page_size_log2 = if flags & FLAG_PAGE_SIZE != 0 {
 			Some(VarUint32::deserialize(reader)?.into())
 		} else {
 			None
 		};
-		let shared = flags & FLAG_SHARED != 0;
 
 		Ok(ResizableLimits {
-			initial: initial.into(),
+			initial: initial,
 			maximum: maximum,
 			shared,
+			is_64,
+			page_size_log2,
 		})
 	}
 }
@@ -154,17 +234,13 @@ impl Serialize for ResizableLimits {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
-		let mut flags: u8 = 0;
-		if self.maximum.is_some() {
-			flags |= FLAG_HAS_MAX;
-		}
-		if self.shared {
-			flags |= FLAG_SHARED;
-		}
-		Uint8::from(flags).serialize(writer)?;
-		VarUint32::from(self.initial).serialize(writer)?;
+		Uint8::from(self.flags()).serialize(writer)?;
+		serialize_size(writer, self.initial, self.is_64)?;
 		if let Some(max) = self.maximum {
-			VarUint32::from(max).serialize(writer)?;
+			serialize_size(writer, max, self.is_64)?;
+		}
+		if let Some(page_size_log2) = self.page_size_log2 {
+			VarUint32::from(page_size_log2).serialize(writer)?;
 		}
 		Ok(())
 	}
@@ -186,6 +262,11 @@ impl MemoryType {
 	pub fn limits(&self) -> &ResizableLimits {
 		&self.0
 	}
+
+	/// Limits of the memory entry (mutable).
+	pub fn limits_mut(&mut self) -> &mut ResizableLimits {
+		&mut self.0
+	}
 }
 
 impl Deserialize for MemoryType {
@@ -327,3 +408,36 @@ impl Serialize for ImportEntry {
 		self.external.serialize(writer)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{ResizableLimits, TableType, MemoryType};
+	use elements::{deserialize_buffer, serialize, Error};
+
+	#[test]
+	fn limits_flags() {
+		let limits: ResizableLimits = deserialize_buffer(&[0x05, 0x01, 0x80, 0x01]).expect("64-bit limits to deserialize");
+		assert_eq!((limits.initial(), limits.maximum(), limits.is_64(), limits.shared()), (1, Some(128), true, false));
+		assert_eq!(limits.flags(), 0x05);
+		assert_eq!(serialize(limits).expect("limits to serialize"), vec![0x05, 0x01, 0x80, 0x01]);
+
+		let memory: MemoryType = deserialize_buffer(&[0x03, 0x01, 0x02]).expect("shared memory to deserialize");
+		assert!(memory.limits().shared());
+
+		assert!(match deserialize_buffer::<ResizableLimits>(&[0x11, 0x01]) { Err(Error::UnknownLimitsFlags(0x10)) => true, _ => false });
+		assert!(match deserialize_buffer::<ResizableLimits>(&[0x02, 0x01]) { Err(Error::InvalidLimitsFlags(0x02)) => true, _ => false });
+		assert!(match deserialize_buffer::<TableType>(&[0x70, 0x03, 0x01, 0x02]) { Err(Error::InvalidLimitsFlags(0x03)) => true, _ => false });
+		assert!(deserialize_buffer::<ResizableLimits>(&[0x04, 0x80, 0x80, 0x80, 0x80, 0x10]).is_err());
+	}
+
+	#[test]
+	fn page_size() {
+		let memory: MemoryType = deserialize_buffer(&[0x09, 0x02, 0x80, 0x02, 0x00]).expect("memory with custom page size to deserialize");
+		assert_eq!((memory.limits().initial(), memory.limits().maximum()), (2, Some(256)));
+		assert_eq!((memory.limits().page_size_log2(), memory.limits().page_size()), (Some(0), 1));
+		assert_eq!(serialize(memory).expect("memory to serialize"), vec![0x09, 0x02, 0x80, 0x02, 0x00]);
+		assert_eq!(MemoryType::new(1, None, false).limits().page_size(), 65536);
+
+		assert!(matches!(deserialize_buffer::<TableType>(&[0x70, 0x08, 0x01, 0x00]), Err(Error::InvalidLimitsFlags(0x08))));
+	}
+}
@@ -0,0 +1,484 @@
+use io;
+use std::vec::Vec;
+use std::string::String;
+
+use super::{CustomSection, Deserialize, Error, Module, Section, Serialize, Uint8, VarUint32, VarUint7};
+
+/// Name of the custom section holding the linking metadata.
+pub const LINKING_SECTION_NAME: &str = "linking";
+
+/// Version of the linking metadata supported by `LinkingSection`.
+pub const LINKING_VERSION: u32 = 2;
+
+const SEGMENT_INFO: u8 = 5;
+const INIT_FUNCS: u8 = 6;
+const SYMBOL_TABLE: u8 = 8;
+
+const SYMBOL_FUNCTION: u8 = 0;
+const SYMBOL_DATA: u8 = 1;
+const SYMBOL_GLOBAL: u8 = 2;
+const SYMBOL_SECTION: u8 = 3;
+const SYMBOL_EVENT: u8 = 4;
+const SYMBOL_TABLE_KIND: u8 = 5;
+
+/// Flags of symbols.
+pub mod symbol_flags {
+	/// Symbol with weak binding.
+	pub const BINDING_WEAK: u32 = 0x01;
+	/// Symbol with local binding, not visible outside of the object.
+	pub const BINDING_LOCAL: u32 = 0x02;
+	/// Symbol with hidden visibility.
+	pub const VISIBILITY_HIDDEN: u32 = 0x04;
+	/// Symbol referring to an import.
+	pub const UNDEFINED: u32 = 0x10;
+	/// Symbol exported from the linked module.
+	pub const EXPORTED: u32 = 0x20;
+	/// Undefined symbol whose name differs from the field of its import.
+	pub const EXPLICIT_NAME: u32 = 0x40;
+	/// Symbol which must not be stripped by the linker.
+	pub const NO_STRIP: u32 = 0x80;
+}
+
+/// Linking metadata of a relocatable object, as emitted by LLVM.
+///
+/// Stored in a custom section named `linking`, whose payload is the version
+/// (`2`) followed by subsections, each a `uint8` type, a `varuint32` size and
+/// the payload. Subsections other than segment info, init functions and the
+/// symbol table are kept unparsed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkingSection {
+	subsections: Vec<LinkingSubsection>,
+}
+
+/// Subsection of the linking metadata.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkingSubsection {
+	/// Information about the data segments, in the order of the data section.
+	SegmentInfo(Vec<SegmentInfo>),
+	/// Functions to call at startup.
+	InitFuncs(Vec<InitFunc>),
+	/// Symbol table, referenced by index from relocation entries.
+	SymbolTable(Vec<Symbol>),
+	/// Subsection is unparsed.
+	Unparsed {
+		/// The numeric identifier for this subsection type.
+		subsection_type: u8,
+		/// The contents of this subsection, unparsed.
+		payload: Vec<u8>,
+	},
+}
+
+/// Information about a data segment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentInfo {
+	/// Name of the segment.
+	pub name: String,
+	/// Alignment of the segment, as a power of two.
+	pub alignment: u32,
+	/// Flags of the segment.
+	pub flags: u32,
+}
+
+/// Function called at startup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InitFunc {
+	/// Priority of the call, lower first.
+	pub priority: u32,
+	/// Index of the function symbol in the symbol table.
+	pub symbol: u32,
+}
+
+/// Location of a defined data symbol.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DataDefinition {
+	/// Index of the data segment.
+	pub segment: u32,
+	/// Offset in the data segment.
+	pub offset: u32,
+	/// Size of the data.
+	pub size: u32,
+}
+
+/// Entry of the symbol table.
+///
+/// Names of function, global, event and table symbols are only present when
+/// the symbol is defined or has the `EXPLICIT_NAME` flag, otherwise it has
+/// the name of its import.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Symbol {
+	/// Function symbol.
+	Function {
+		/// Flags of the symbol (see `symbol_flags`).
+		flags: u32,
+		/// Index of the function.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+	/// Data symbol.
+	Data {
+		/// Flags of the symbol (see `symbol_flags`).
+		flags: u32,
+		/// Name of the symbol.
+		name: String,
+		/// Location of the data, `None` for undefined symbols.
+		definition: Option<DataDefinition>,
+	},
+	/// Global symbol.
+	Global {
+		/// Flags of the symbol (see `symbol_flags`).
+		flags: u32,
+		/// Index of the global.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+	/// Section symbol.
+	Section {
+		/// Flags of the symbol (see `symbol_flags`).
+		flags: u32,
+		/// Index of the section.
+		section: u32,
+	},
+	/// Event symbol.
+	Event {
+		/// Flags of the symbol (see `symbol_flags`).
+		flags: u32,
+		/// Index of the event.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+	/// Table symbol.
+	Table {
+		/// Flags of the symbol (see `symbol_flags`).
+		flags: u32,
+		/// Index of the table.
+		index: u32,
+		/// Name of the symbol.
+		name: Option<String>,
+	},
+}
+
+impl Symbol {
+	/// Flags of the symbol (see `symbol_flags`).
+	pub fn flags(&self) -> u32 {
+		match *self {
+			Symbol::Function { flags, .. } | Symbol::Data { flags, .. } | Symbol::Global { flags, .. }
+				| Symbol::Section { flags, .. } | Symbol::Event { flags, .. } | Symbol::Table { flags, .. } => flags,
+		}
+	}
+
+	/// Name of the symbol, if it has one of its own.
+	pub fn name(&self) -> Option<&str> {
+		match *self {
+			Symbol::Data { ref name, .. } => Some(name),
+			Symbol::Function { ref name, .. } | Symbol::Global { ref name, .. }
+				| Symbol::Event { ref name, .. } | Symbol::Table { ref name, .. } => name.as_ref().map(String::as_str),
+			Symbol::Section { .. } => None,
+		}
+	}
+
+	/// Whether the symbol refers to an import.
+	pub fn is_undefined(&self) -> bool {
+		self.flags() & symbol_flags::UNDEFINED != 0
+	}
+}
+
+fn has_name(flags: u32) -> bool {
+	flags & symbol_flags::UNDEFINED == 0 || flags & symbol_flags::EXPLICIT_NAME != 0
+}
+
+fn read_list<R: io::Read, T, F: Fn(&mut R) -> Result<T, Error>>(rdr: &mut R, read: F) -> Result<Vec<T>, Error> {
+	let count: u32 = VarUint32::deserialize(rdr)?.into();
+	let mut entries = Vec::new();
+	for _ in 0..count {
+		entries.push(read(rdr)?);
+	}
+	Ok(entries)
+}
+
+impl Deserialize for Symbol {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(rdr: &mut R) -> Result<Self, Self::Error> {
+		let kind: u8 = Uint8::deserialize(rdr)?.into();
+		let flags: u32 = VarUint32::deserialize(rdr)?.into();
+		match kind {
+			SYMBOL_DATA => {
+				let name = String::deserialize(rdr)?;
+				let definition = if flags & symbol_flags::UNDEFINED == 0 {
+					Some(DataDefinition {
+						segment: VarUint32::deserialize(rdr)?.into(),
+						offset: VarUint32::deserialize(rdr)?.into(),
+						size: VarUint32::deserialize(rdr)?.into(),
+					})
+				} else {
+					None
+				};
+				Ok(Symbol::Data { flags, name, definition })
+			},
+			SYMBOL_SECTION => Ok(Symbol::Section { flags, section: VarUint32::deserialize(rdr)?.into() }),
+			SYMBOL_FUNCTION | SYMBOL_GLOBAL | SYMBOL_EVENT | SYMBOL_TABLE_KIND => {
+				let index = VarUint32::deserialize(rdr)?.into();
+				let name = if has_name(flags) { Some(String::deserialize(rdr)?) } else { None };
+				Ok(match kind {
+					SYMBOL_FUNCTION => Symbol::Function { flags, index, name },
+					SYMBOL_GLOBAL => Symbol::Global { flags, index, name },
+					SYMBOL_EVENT => Symbol::Event { flags, index, name },
+					_ => Symbol::Table { flags, index, name },
+				})
+			},
+			_ => Err(Error::Other("unknown symbol kind")),
+		}
+	}
+}
+
+impl Serialize for Symbol {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		let (kind, flags, index, name) = match self {
+			Symbol::Data { flags, name, definition } => {
+				if definition.is_none() != (flags & symbol_flags::UNDEFINED != 0) {
+					return Err(Error::Other("data symbol must be defined unless it is undefined"));
+				}
+				Uint8::from(SYMBOL_DATA).serialize(wtr)?;
+				VarUint32::from(flags).serialize(wtr)?;
+				name.serialize(wtr)?;
+				if let Some(definition) = definition {
+					VarUint32::from(definition.segment).serialize(wtr)?;
+					VarUint32::from(definition.offset).serialize(wtr)?;
+					VarUint32::from(definition.size).serialize(wtr)?;
+				}
+				return Ok(());
+			},
+			Symbol::Section { flags, section } => {
+				Uint8::from(SYMBOL_SECTION).serialize(wtr)?;
+				VarUint32::from(flags).serialize(wtr)?;
+				VarUint32::from(section).serialize(wtr)?;
+				return Ok(());
+			},
+			Symbol::Function { flags, index, name } => (SYMBOL_FUNCTION, flags, index, name),
+			Symbol::Global { flags, index, name } => (SYMBOL_GLOBAL, flags, index, name),
+			Symbol::Event { flags, index, name } => (SYMBOL_EVENT, flags, index, name),
+			Symbol::Table { flags, index, name } => (SYMBOL_TABLE_KIND, flags, index, name),
+		};
+		if name.is_some() != has_name(flags) {
+			return Err(Error::Other("symbol must be named unless it is undefined without an explicit name"));
+		}
+		Uint8::from(kind).serialize(wtr)?;
+		VarUint32::from(flags).serialize(wtr)?;
+		VarUint32::from(index).serialize(wtr)?;
+		if let Some(name) = name {
+			name.serialize(wtr)?;
+		}
+		Ok(())
+	}
+}
+
+impl LinkingSection {
+	/// New linking metadata with `subsections`.
+	pub fn new(subsections: Vec<LinkingSubsection>) -> LinkingSection {
+		LinkingSection { subsections }
+	}
+
+	/// Subsections, in order.
+	pub fn subsections(&self) -> &[LinkingSubsection] {
+		&self.subsections
+	}
+
+	/// Subsections, in order (mutable).
+	pub fn subsections_mut(&mut self) -> &mut Vec<LinkingSubsection> {
+		&mut self.subsections
+	}
+
+	/// Symbol table, if any.
+	pub fn symbols(&self) -> Option<&[Symbol]> {
+		self.subsections.iter()
+			.filter_map(|subsection| match *subsection {
+				LinkingSubsection::SymbolTable(ref symbols) => Some(&symbols[..]),
+				_ => None,
+			})
+			.next()
+	}
+
+	/// Information about the data segments, if any.
+	pub fn segments(&self) -> Option<&[SegmentInfo]> {
+		self.subsections.iter()
+			.filter_map(|subsection| match *subsection {
+				LinkingSubsection::SegmentInfo(ref segments) => Some(&segments[..]),
+				_ => None,
+			})
+			.next()
+	}
+
+	/// Parses linking metadata out of the custom section `section`.
+	pub fn from_custom_section(section: &CustomSection) -> Result<LinkingSection, Error> {
+		if section.name() != LINKING_SECTION_NAME {
+			return Err(Error::Other("custom section is not a linking section"));
+		}
+		let payload = section.payload();
+		let mut rdr = io::Cursor::new(payload);
+		let version: u32 = VarUint32::deserialize(&mut rdr)?.into();
+		if version != LINKING_VERSION {
+			return Err(Error::Other("unsupported linking section version"));
+		}
+		let mut subsections = Vec::new();
+		let mut position = rdr.position();
+		while position < payload.len() {
+			let mut rdr = io::Cursor::new(&payload[position..]);
+			let subsection_type: u8 = VarUint7::deserialize(&mut rdr)?.into();
+			let size: u32 = VarUint32::deserialize(&mut rdr)?.into();
+			let start = position + rdr.position();
+			let end = start.checked_add(size as usize)
+				.filter(|&end| end <= payload.len())
+				.ok_or(Error::UnexpectedEof)?;
+			let mut sub_rdr = io::Cursor::new(&payload[start..end]);
+			subsections.push(match subsection_type {
+				SEGMENT_INFO => LinkingSubsection::SegmentInfo(read_list(&mut sub_rdr, |rdr| Ok(SegmentInfo {
+					name: String::deserialize(rdr)?,
+					alignment: VarUint32::deserialize(rdr)?.into(),
+					flags: VarUint32::deserialize(rdr)?.into(),
+				}))?),
+				INIT_FUNCS => LinkingSubsection::InitFuncs(read_list(&mut sub_rdr, |rdr| Ok(InitFunc {
+					priority: VarUint32::deserialize(rdr)?.into(),
+					symbol: VarUint32::deserialize(rdr)?.into(),
+				}))?),
+				SYMBOL_TABLE => LinkingSubsection::SymbolTable(read_list(&mut sub_rdr, Symbol::deserialize)?),
+				_ => LinkingSubsection::Unparsed { subsection_type, payload: payload[start..end].to_vec() },
+			});
+			let parsed = match subsections.last() {
+				Some(&LinkingSubsection::Unparsed { .. }) => end - start,
+				_ => sub_rdr.position(),
+			};
+			if start + parsed != end {
+				return Err(io::Error::InvalidData.into());
+			}
+			position = end;
+		}
+		Ok(LinkingSection { subsections })
+	}
+
+	/// Converts the linking metadata into a custom section.
+	pub fn into_custom_section(self) -> Result<CustomSection, Error> {
+		let mut payload = Vec::new();
+		self.serialize(&mut payload)?;
+		Ok(CustomSection::new(String::from(LINKING_SECTION_NAME), payload))
+	}
+}
+
+impl Serialize for LinkingSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		VarUint32::from(LINKING_VERSION).serialize(wtr)?;
+		for subsection in self.subsections {
+			let mut buffer = Vec::new();
+			let subsection_type = match subsection {
+				LinkingSubsection::SegmentInfo(segments) => {
+					VarUint32::from(segments.len()).serialize(&mut buffer)?;
+					for segment in segments {
+						segment.name.serialize(&mut buffer)?;
+						VarUint32::from(segment.alignment).serialize(&mut buffer)?;
+						VarUint32::from(segment.flags).serialize(&mut buffer)?;
+					}
+					SEGMENT_INFO
+				},
+				LinkingSubsection::InitFuncs(funcs) => {
+					VarUint32::from(funcs.len()).serialize(&mut buffer)?;
+					for func in funcs {
+						VarUint32::from(func.priority).serialize(&mut buffer)?;
+						VarUint32::from(func.symbol).serialize(&mut buffer)?;
+					}
+					INIT_FUNCS
+				},
+				LinkingSubsection::SymbolTable(symbols) => {
+					VarUint32::from(symbols.len()).serialize(&mut buffer)?;
+					for symbol in symbols {
+						symbol.serialize(&mut buffer)?;
+					}
+					SYMBOL_TABLE
+				},
+				LinkingSubsection::Unparsed { subsection_type, payload } => {
+					buffer = payload;
+					subsection_type
+				},
+			};
+			VarUint7::from(subsection_type).serialize(wtr)?;
+			VarUint32::from(buffer.len()).serialize(wtr)?;
+			wtr.write(&buffer)?;
+		}
+		Ok(())
+	}
+}
+
+impl Module {
+	/// Linking metadata stored in the `linking` custom section, if any.
+	pub fn linking(&self) -> Option<Result<LinkingSection, Error>> {
+		self.sections().iter()
+			.filter_map(|section| match *section {
+				Section::Custom(ref custom) if custom.name() == LINKING_SECTION_NAME => Some(custom),
+				_ => None,
+			})
+			.next()
+			.map(LinkingSection::from_custom_section)
+	}
+
+	/// Stores `linking` in the `linking` custom section, replacing the
+	/// existing one or appending a new section at the end of the module.
+	pub fn set_linking(&mut self, linking: LinkingSection) -> Result<(), Error> {
+		let custom = linking.into_custom_section()?;
+		let existing = self.sections_mut().iter_mut().find(|section| match **section {
+			Section::Custom(ref custom) => custom.name() == LINKING_SECTION_NAME,
+			_ => false,
+		});
+		match existing {
+			Some(section) => *section = Section::Custom(custom),
+			None => self.sections_mut().push(Section::Custom(custom)),
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{symbol_flags, LinkingSection, LinkingSubsection, SegmentInfo, InitFunc, Symbol, DataDefinition};
+	use elements::{serialize, deserialize_buffer, Module};
+
+	#[test]
+	fn roundtrip() {
+		let linking = LinkingSection::new(vec![
+			LinkingSubsection::SymbolTable(vec![
+				Symbol::Function { flags: 0, index: 1, name: Some("main".into()) },
+				Symbol::Function { flags: symbol_flags::UNDEFINED, index: 0, name: None },
+				Symbol::Data { flags: 0, name: ".L.str".into(), definition: Some(DataDefinition { segment: 0, offset: 0, size: 6 }) },
+				Symbol::Data { flags: symbol_flags::UNDEFINED, name: "errno".into(), definition: None },
+				Symbol::Global { flags: symbol_flags::UNDEFINED | symbol_flags::EXPLICIT_NAME, index: 0, name: Some("__stack_pointer".into()) },
+				Symbol::Section { flags: symbol_flags::BINDING_LOCAL, section: 3 },
+			]),
+			LinkingSubsection::SegmentInfo(vec![SegmentInfo { name: ".rodata.str".into(), alignment: 0, flags: 0 }]),
+			LinkingSubsection::InitFuncs(vec![InitFunc { priority: 65535, symbol: 0 }]),
+			LinkingSubsection::Unparsed { subsection_type: 7, payload: vec![0] },
+		]);
+
+		let mut module = Module::default();
+		assert!(module.linking().is_none());
+		module.set_linking(linking.clone()).expect("linking section to serialize");
+		let module: Module = deserialize_buffer(&serialize(module).expect("module to serialize"))
+			.expect("module to deserialize");
+		let read = module.linking().expect("linking section to exist").expect("linking section to parse");
+		assert_eq!(read, linking);
+		assert_eq!(read.symbols().expect("symbol table")[0].name(), Some("main"));
+		assert!(read.symbols().expect("symbol table")[3].is_undefined());
+		assert_eq!(read.segments().expect("segment info")[0].name, ".rodata.str");
+	}
+
+	#[test]
+	fn unnamed_defined_symbol() {
+		let linking = LinkingSection::new(vec![
+			LinkingSubsection::SymbolTable(vec![Symbol::Function { flags: 0, index: 0, name: None }]),
+		]);
+		assert!(linking.into_custom_section().is_err());
+	}
+}
@@ -35,8 +35,20 @@ mod segment;
 mod index_map;
 mod name_section;
 mod reloc_section;
+mod linking_section;
+mod profile_section;
+mod toc_section;
+mod code_metadata;
+mod reader;
+mod audit;
+mod stats;
+mod config;
+mod stream;
 
-pub use self::module::{Module, peek_size, ImportCountType};
+pub use self::module::{
+	Module, peek_size, ImportCountType, deserialize_buffer_with_raw_bodies, deserialize_buffer_with_raw_sections,
+	deserialize_buffer_with_location, DeserializeError,
+};
 pub use self::section::{
 	Section, FunctionSection, CodeSection, MemorySection, DataSection,
 	ImportSection, ExportSection, GlobalSection, TypeSection, ElementSection,
@@ -49,18 +61,30 @@ pub use self::primitives::{
 	VarUint32, VarUint7, Uint8, VarUint1, VarInt7, Uint32, VarInt32, VarInt64,
 	Uint64, VarUint64, CountedList, CountedWriter, CountedListWriter,
 };
-pub use self::types::{Type, ValueType, BlockType, FunctionType, TableElementType};
-pub use self::ops::{Instruction, Instructions, InitExpr, opcodes, MemArg, BrTableData};
+pub use self::types::{Type, ValueType, BlockType, FunctionType, ReturnTypeMut, TableElementType};
+pub use self::ops::{Instruction, Instructions, InitExpr, opcodes, MemArg, BrTableData, Opcode};
+pub use self::stats::{Stats, SectionSize, FunctionSize, DataSegmentSize, opcode_counts};
+pub use self::config::{DeserializeConfig, deserialize_with_config};
+pub use self::stream::{DataSource, serialize_with_data};
 pub use self::func::{Func, FuncBody, Local};
 pub use self::segment::{ElementSegment, DataSegment};
 pub use self::index_map::IndexMap;
 pub use self::name_section::{
 	NameMap, NameSection, ModuleNameSection, FunctionNameSection,
-	LocalNameSection,
+	LocalNameSection, NameDecoding,
 };
 pub use self::reloc_section::{
 	RelocSection, RelocationEntry,
 };
+pub use self::linking_section::{
+	LinkingSection, LinkingSubsection, SegmentInfo, InitFunc, Symbol, DataDefinition,
+	symbol_flags, LINKING_SECTION_NAME, LINKING_VERSION,
+};
+pub use self::profile_section::{ProfileSection, PROFILE_SECTION_NAME};
+pub use self::toc_section::{TocSection, TOC_SECTION_NAME, serialize_with_toc};
+pub use self::reader::{ModuleReader, SectionHeader, SectionHeaders, LazyCodeSection};
+pub use self::audit::{audit_encoding, EncodingFinding, EncodingQuirk};
+pub use self::code_metadata::{CodeMetadataSection, CodeAnnotation, CODE_METADATA_PREFIX, BRANCH_HINT_KIND};
 
 /// Deserialization from serial i/o.
 pub trait Deserialize : Sized {
@@ -133,10 +157,10 @@ pub enum Error {
 	DuplicatedSections(u8),
 	/// Invalid memory reference (should be 0).
 	InvalidMemoryReference(u8),
-	/// Invalid table reference (should be 0).
-	InvalidTableReference(u8),
-	/// Invalid value used for flags in limits type.
+	/// Invalid combination of flags in limits type.
 	InvalidLimitsFlags(u8),
+	/// Unknown bits set in the flags of limits type.
+	UnknownLimitsFlags(u8),
 	/// Unknown function form (should be 0x60).
 	UnknownFunctionForm(u8),
 	/// Invalid varint7 (should be in -64..63 range).
@@ -147,6 +171,10 @@ pub enum Error {
 	InvalidSegmentFlags(u32),
 	/// Sum of counts of locals is greater than 2^32.
 	TooManyLocals,
+	/// A limit of `DeserializeConfig` is exceeded.
+	LimitExceeded(&'static str),
+	/// LEB128 integer longer than its shortest encoding.
+	OverlongLeb128,
 }
 
 impl fmt::Display for Error {
@@ -178,12 +206,14 @@ impl fmt::Display for Error {
 			Error::SectionsOutOfOrder =>  write!(f, "Sections out of order"),
 			Error::DuplicatedSections(ref id) =>  write!(f, "Dupliated sections ({})", id),
 			Error::InvalidMemoryReference(ref mem_ref) =>  write!(f, "Invalid memory reference ({})", mem_ref),
-			Error::InvalidTableReference(ref table_ref) =>  write!(f, "Invalid table reference ({})", table_ref),
 			Error::InvalidLimitsFlags(ref flags) =>  write!(f, "Invalid limits flags ({})", flags),
+			Error::UnknownLimitsFlags(ref flags) =>  write!(f, "Unknown limits flags ({:#x})", flags),
 			Error::UnknownFunctionForm(ref form) =>  write!(f, "Unknown function form ({})", form),
 			Error::InconsistentCode =>  write!(f, "Number of function body entries and signatures does not match"),
 			Error::InvalidSegmentFlags(n) =>  write!(f, "Invalid segment flags: {}", n),
 			Error::TooManyLocals => write!(f, "Too many locals"),
+			Error::LimitExceeded(limit) => write!(f, "Limit exceeded: {}", limit),
+			Error::OverlongLeb128 => write!(f, "Overlong LEB128 integer"),
 		}
 	}
 }
@@ -216,12 +246,14 @@ impl ::std::error::Error for Error {
 			Error::SectionsOutOfOrder =>  "Sections out of order",
 			Error::DuplicatedSections(_) =>  "Duplicated section",
 			Error::InvalidMemoryReference(_) =>  "Invalid memory reference",
-			Error::InvalidTableReference(_) =>  "Invalid table reference",
 			Error::InvalidLimitsFlags(_) => "Invalid limits flags",
+			Error::UnknownLimitsFlags(_) => "Unknown limits flags",
 			Error::UnknownFunctionForm(_) =>  "Unknown function form",
 			Error::InconsistentCode =>  "Number of function body entries and signatures does not match",
 			Error::InvalidSegmentFlags(_) =>  "Invalid segment flags",
 			Error::TooManyLocals => "Too many locals",
+			Error::LimitExceeded(_) => "Limit exceeded",
+			Error::OverlongLeb128 => "Overlong LEB128 integer",
 		}
 	}
 }
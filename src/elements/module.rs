@@ -1,24 +1,45 @@
 use io;
+use std::cell::Cell;
+use std::fmt;
 use std::vec::Vec;
+use std::string::String;
 use std::borrow::ToOwned;
 use byteorder::{LittleEndian, ByteOrder};
 
 use super::{Deserialize, Serialize, Error, Uint32, External};
 use super::section::{
 	Section, CodeSection, TypeSection, ImportSection, ExportSection, FunctionSection,
-	GlobalSection, TableSection, ElementSection, DataSection, MemorySection
+	GlobalSection, TableSection, ElementSection, DataSection, MemorySection, CustomSection
 };
-use super::name_section::NameSection;
+use super::name_section::{NameSection, NameDecoding};
 use super::reloc_section::RelocSection;
 
-const WASM_MAGIC_NUMBER: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
+pub(crate) const WASM_MAGIC_NUMBER: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
 
 /// WebAssembly module
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Module {
 	magic: u32,
 	version: u32,
 	sections: Vec<Section>,
+	/// Original encoding of the sections which were not borrowed mutably
+	/// since deserialization, in parallel to `sections` (empty unless
+	/// requested, see `deserialize_buffer_with_raw_sections`).
+	raw_sections: Vec<Option<Vec<u8>>>,
+}
+
+/// Modules are compared by their content, whether or not the original
+/// encoding of their sections is kept.
+impl PartialEq for Module {
+	fn eq(&self, other: &Module) -> bool {
+		self.magic == other.magic && self.version == other.version && self.sections == other.sections
+	}
+}
+
+fn mark_dirty(raw_sections: &mut [Option<Vec<u8>>], idx: usize) {
+	if let Some(raw) = raw_sections.get_mut(idx) {
+		*raw = None;
+	}
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -40,6 +61,7 @@ impl Default for Module {
 			magic: LittleEndian::read_u32(&WASM_MAGIC_NUMBER),
 			version: 1,
 			sections: Vec::with_capacity(16),
+			raw_sections: Vec::new(),
 		}
 	}
 }
@@ -70,7 +92,11 @@ impl Module {
 	/// Sections list (mutable).
 	///
 	/// Each known section is optional and may appear at most once.
+	///
+	/// This drops the original encoding of all sections, prefer the accessors
+	/// of specific sections (like `export_section_mut`) to only modify one.
 	pub fn sections_mut(&mut self) -> &mut Vec<Section> {
+		self.raw_sections.clear();
 		&mut self.sections
 	}
 
@@ -84,8 +110,11 @@ impl Module {
 
 	/// Code section mutable reference, if any.
 	pub fn code_section_mut(&mut self) -> Option<&mut CodeSection> {
-		for section in self.sections_mut() {
-			if let Section::Code(ref mut code_section) = *section { return Some(code_section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Code(ref mut code_section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(code_section);
+			}
 		}
 		None
 	}
@@ -100,8 +129,11 @@ impl Module {
 
 	/// Types section mutable reference, if any.
 	pub fn type_section_mut(&mut self) -> Option<&mut TypeSection> {
-		for section in self.sections_mut() {
-			if let Section::Type(ref mut type_section) = *section { return Some(type_section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Type(ref mut type_section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(type_section);
+			}
 		}
 		None
 	}
@@ -116,8 +148,11 @@ impl Module {
 
 	/// Imports section mutable reference, if any.
 	pub fn import_section_mut(&mut self) -> Option<&mut ImportSection> {
-		for section in self.sections_mut() {
-			if let Section::Import(ref mut import_section) = *section { return Some(import_section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Import(ref mut import_section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(import_section);
+			}
 		}
 		None
 	}
@@ -132,8 +167,11 @@ impl Module {
 
 		/// Globals section mutable reference, if any.
 	pub fn global_section_mut(&mut self) -> Option<&mut GlobalSection> {
-		for section in self.sections_mut() {
-			if let Section::Global(ref mut section) = *section { return Some(section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Global(ref mut section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(section);
+			}
 		}
 		None
 	}
@@ -149,8 +187,11 @@ impl Module {
 
 	/// Exports section mutable reference, if any.
 	pub fn export_section_mut(&mut self) -> Option<&mut ExportSection> {
-		for section in self.sections_mut() {
-			if let Section::Export(ref mut export_section) = *section { return Some(export_section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Export(ref mut export_section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(export_section);
+			}
 		}
 		None
 	}
@@ -165,8 +206,11 @@ impl Module {
 
 	/// Table section mutable reference, if any.
 	pub fn table_section_mut(&mut self) -> Option<&mut TableSection> {
-		for section in self.sections_mut() {
-			if let Section::Table(ref mut section) = *section { return Some(section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Table(ref mut section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(section);
+			}
 		}
 		None
 	}
@@ -181,8 +225,11 @@ impl Module {
 
 	/// Data section mutable reference, if any.
 	pub fn data_section_mut(&mut self) -> Option<&mut DataSection> {
-		for section in self.sections_mut() {
-			if let Section::Data(ref mut section) = *section { return Some(section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Data(ref mut section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(section);
+			}
 		}
 		None
 	}
@@ -197,8 +244,11 @@ impl Module {
 
 	/// Element section mutable reference, if any.
 	pub fn elements_section_mut(&mut self) -> Option<&mut ElementSection> {
-		for section in self.sections_mut() {
-			if let Section::Element(ref mut section) = *section { return Some(section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Element(ref mut section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(section);
+			}
 		}
 		None
 	}
@@ -213,8 +263,11 @@ impl Module {
 
 	/// Memory section mutable reference, if any.
 	pub fn memory_section_mut(&mut self) -> Option<&mut MemorySection> {
-		for section in self.sections_mut() {
-			if let Section::Memory(ref mut section) = *section { return Some(section); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Memory(ref mut section) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(section);
+			}
 		}
 		None
 	}
@@ -229,8 +282,11 @@ impl Module {
 
 	/// Functions signatures section mutable reference, if any.
 	pub fn function_section_mut(&mut self) -> Option<&mut FunctionSection> {
-		for section in self.sections_mut() {
-			if let Section::Function(ref mut sect) = *section { return Some(sect); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Function(ref mut sect) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(sect);
+			}
 		}
 		None
 	}
@@ -243,15 +299,23 @@ impl Module {
 		None
 	}
 
-	/// Changes the module's start section.
+	/// Changes the module's start section, inserting it after the export
+	/// section if there is none.
 	pub fn set_start_section(&mut self, new_start : u32) {
-		for section in self.sections_mut() {
+		for (idx, section) in self.sections.iter_mut().enumerate() {
 			if let &mut Section::Start(_sect) = section {
 				*section = Section::Start(new_start);
+				mark_dirty(&mut self.raw_sections, idx);
 				return
 			}
 		}
-		self.sections_mut().push(Section::Start(new_start));
+		let position = self.sections.iter()
+			.position(|s| s.order() > Section::Start(new_start).order())
+			.unwrap_or(self.sections.len());
+		self.sections.insert(position, Section::Start(new_start));
+		if position < self.raw_sections.len() {
+			self.raw_sections.insert(position, None);
+		}
 	}
 
 	/// Removes the module's start section.
@@ -269,6 +333,43 @@ impl Module {
 		}
 	}
 
+	/// Data count section, if any.
+	pub fn data_count_section(&self) -> Option<u32> {
+		for section in self.sections() {
+			if let &Section::DataCount(count) = section { return Some(count); }
+		}
+		None
+	}
+
+	/// Changes the module's data count section, inserting it before the code
+	/// and data sections if there is none.
+	pub fn set_data_count_section(&mut self, count: u32) {
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let &mut Section::DataCount(_) = section {
+				*section = Section::DataCount(count);
+				mark_dirty(&mut self.raw_sections, idx);
+				return
+			}
+		}
+		let position = self.sections.iter()
+			.position(|s| s.order() > Section::DataCount(count).order())
+			.unwrap_or(self.sections.len());
+		self.sections.insert(position, Section::DataCount(count));
+		if position < self.raw_sections.len() {
+			self.raw_sections.insert(position, None);
+		}
+	}
+
+	/// Removes the module's data count section.
+	pub fn clear_data_count_section(&mut self) {
+		if let Some(idx) = self.sections.iter().position(|section| matches!(*section, Section::DataCount(_))) {
+			self.sections.remove(idx);
+			if idx < self.raw_sections.len() {
+				self.raw_sections.remove(idx);
+			}
+		}
+	}
+
 	/// Functions signatures section reference, if any.
 	///
 	/// NOTE: name section is not parsed by default so `names_section` could return None even if name section exists.
@@ -285,18 +386,107 @@ impl Module {
 	/// NOTE: name section is not parsed by default so `names_section` could return None even if name section exists.
 	/// Call `parse_names` to parse name section
 	pub fn names_section_mut(&mut self) -> Option<&mut NameSection> {
-		for section in self.sections_mut() {
-			if let Section::Name(ref mut sect) = *section { return Some(sect); }
+		for (idx, section) in self.sections.iter_mut().enumerate() {
+			if let Section::Name(ref mut sect) = *section {
+				mark_dirty(&mut self.raw_sections, idx);
+				return Some(sect);
+			}
 		}
 		None
 	}
 
+	/// Subsections of the name section, in order, whether it was parsed or
+	/// not.
+	///
+	/// Unlike `names_section`, all the subsections of a `name` custom section
+	/// are returned, not only the first one.
+	pub fn names(&self) -> Result<Vec<NameSection>, Error> {
+		let mut names = Vec::new();
+		for section in self.sections() {
+			match *section {
+				Section::Name(ref name_section) => names.push(name_section.clone()),
+				Section::Custom(ref custom) if custom.name() == "name" =>
+					names.extend(NameSection::deserialize_all(self, custom.payload(), NameDecoding::Strict)?),
+				_ => {},
+			}
+		}
+		Ok(names)
+	}
+
+	/// Replaces the name section by a single `name` custom section holding
+	/// `names`, appended after the other sections, or removes it if `names`
+	/// is empty.
+	pub fn set_names(&mut self, names: Vec<NameSection>) -> Result<(), Error> {
+		let mut payload = Vec::new();
+		for name_section in names {
+			name_section.serialize(&mut payload)?;
+		}
+		let sections = self.sections_mut();
+		sections.retain(|section| match *section {
+			Section::Name(_) => false,
+			Section::Custom(ref custom) => custom.name() != "name",
+			_ => true,
+		});
+		if !payload.is_empty() {
+			sections.push(Section::Custom(CustomSection::new("name".to_owned(), payload)));
+		}
+		Ok(())
+	}
+
+	/// Custom sections, in order.
+	///
+	/// Parsed name and reloc sections are not custom sections anymore and
+	/// are left out.
+	pub fn custom_sections(&self) -> impl Iterator<Item = &CustomSection> + '_ {
+		self.sections.iter().filter_map(|section| match *section {
+			Section::Custom(ref custom) => Some(custom),
+			_ => None,
+		})
+	}
+
+	/// Appends a custom section named `name` after the other sections.
+	///
+	/// The original encoding of the other sections is kept.
+	pub fn add_custom_section(&mut self, name: String, payload: Vec<u8>) {
+		self.sections.push(Section::Custom(CustomSection::new(name, payload)));
+	}
+
+	/// Removes the custom sections named `name`, returning them in order.
+	///
+	/// The other sections keep their relative order and original encoding.
+	pub fn remove_custom_section(&mut self, name: &str) -> Vec<CustomSection> {
+		let mut removed = Vec::new();
+		let mut idx = 0;
+		while idx < self.sections.len() {
+			match self.sections[idx] {
+				Section::Custom(ref custom) if custom.name() == name => {},
+				_ => {
+					idx += 1;
+					continue;
+				},
+			}
+			if idx < self.raw_sections.len() {
+				self.raw_sections.remove(idx);
+			}
+			if let Section::Custom(custom) = self.sections.remove(idx) {
+				removed.push(custom);
+			}
+		}
+		removed
+	}
+
 	/// Try to parse name section in place/
 	///
 	/// Corresponding custom section with proper header will convert to name sections
 	/// If some of them will fail to be decoded, Err variant is returned with the list of
 	/// (index, Error) tuples of failed sections.
-	pub fn parse_names(mut self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+	pub fn parse_names(self) -> Result<Self, (Vec<(usize, Error)>, Self)> {
+		self.parse_names_with(NameDecoding::Strict)
+	}
+
+	/// Same as `parse_names`, decoding names which are not valid UTF-8
+	/// according to `decoding`.
+	pub fn parse_names_with(mut self, decoding: NameDecoding) -> Result<Self, (Vec<(usize, Error)>, Self)> {
 		let mut parse_errors = Vec::new();
 
 		for i in 0..self.sections.len() {
@@ -305,7 +495,7 @@ impl Module {
 				if let Section::Custom(ref custom) = *section {
 					if custom.name() == "name" {
 						let mut rdr = io::Cursor::new(custom.payload());
-						let name_section = match NameSection::deserialize(&self, &mut rdr) {
+						let name_section = match NameSection::deserialize_with_decoding(&self, &mut rdr, decoding) {
 							Ok(ns) => ns,
 							Err(e) => { parse_errors.push((i, e)); continue; }
 						};
@@ -411,6 +601,14 @@ impl Deserialize for Module {
 	type Error = super::Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		Module::deserialize_observed(reader, |_| {})
+	}
+}
+
+impl Module {
+	/// Deserializes a module, calling `observe` with every section once it is
+	/// read.
+	pub(crate) fn deserialize_observed<R: io::Read, F: FnMut(&Section)>(reader: &mut R, mut observe: F) -> Result<Module, Error> {
 		let mut sections = Vec::new();
 
 		let mut magic = [0u8; 4];
@@ -425,7 +623,7 @@ impl Deserialize for Module {
 			return Err(Error::UnsupportedVersion(version));
 		}
 
-		let mut last_section_id = 0;
+		let mut last_section_order = 0;
 
 		loop {
 			match Section::deserialize(reader) {
@@ -433,13 +631,14 @@ impl Deserialize for Module {
 				Err(e) => { return Err(e) },
 				Ok(section) => {
 					if section.id() != 0 {
-						if last_section_id > section.id() {
+						if last_section_order > section.order() {
 							return Err(Error::SectionsOutOfOrder);
-						} else if last_section_id == section.id() {
-							return Err(Error::DuplicatedSections(last_section_id));
+						} else if last_section_order == section.order() {
+							return Err(Error::DuplicatedSections(section.id()));
 						}
-						last_section_id = section.id();
+						last_section_order = section.order();
 					}
+					observe(&section);
 					sections.push(section);
 				}
 			}
@@ -449,6 +648,7 @@ impl Deserialize for Module {
 			magic: LittleEndian::read_u32(&magic),
 			version: version,
 			sections: sections,
+			raw_sections: Vec::new(),
 		};
 
 		if module.code_section().map(|cs| cs.bodies().len()).unwrap_or(0) !=
@@ -467,8 +667,12 @@ impl Serialize for Module {
 	fn serialize<W: io::Write>(self, w: &mut W) -> Result<(), Self::Error> {
 		Uint32::from(self.magic).serialize(w)?;
 		Uint32::from(self.version).serialize(w)?;
+		let mut raw_sections = self.raw_sections.into_iter();
 		for section in self.sections.into_iter() {
-			section.serialize(w)?;
+			match raw_sections.next() {
+				Some(Some(raw)) => w.write(&raw)?,
+				_ => section.serialize(w)?,
+			}
 		}
 		Ok(())
 	}
@@ -534,10 +738,150 @@ pub fn peek_size(source: &[u8]) -> usize {
 	cursor
 }
 
+/// Deserializes a module, keeping the encoding of every function body (see
+/// `FuncBody::raw_bytes`) so that bodies left untouched are written back
+/// byte-for-byte.
+pub fn deserialize_buffer_with_raw_bodies(contents: &[u8]) -> Result<Module, Error> {
+	let mut module: Module = super::deserialize_buffer(contents)?;
+	let toc = super::TocSection::compute(contents)?;
+	if let Some(code) = module.code_section_mut() {
+		for (body, &offset) in code.bodies_mut().iter_mut().zip(toc.function_bodies()) {
+			let mut rdr = io::Cursor::new(&contents[offset as usize..]);
+			let size: u32 = super::VarUint32::deserialize(&mut rdr)?.into();
+			let start = offset as usize + rdr.position();
+			body.set_raw_bytes(contents[start..start + size as usize].to_vec());
+		}
+	}
+	Ok(module)
+}
+
+/// Deserializes a module, keeping the encoding of every section so that
+/// sections which are not borrowed mutably before serialization are written
+/// back byte-for-byte instead of being encoded again.
+///
+/// Only the accessors of specific sections (`code_section_mut`,
+/// `export_section_mut`, ...) keep the encoding of the other sections,
+/// `Module::sections_mut` drops all of it.
+pub fn deserialize_buffer_with_raw_sections(contents: &[u8]) -> Result<Module, Error> {
+	let mut module: Module = super::deserialize_buffer(contents)?;
+	let mut raw_sections = Vec::with_capacity(module.sections.len());
+	// Skip magic and version.
+	let mut offset = 8;
+	while offset < contents.len() {
+		let mut rdr = io::Cursor::new(&contents[offset..]);
+		super::Uint8::deserialize(&mut rdr)?;
+		let size: u32 = super::VarUint32::deserialize(&mut rdr)?.into();
+		let end = offset + rdr.position() + size as usize;
+		raw_sections.push(Some(contents[offset..end].to_vec()));
+		offset = end;
+	}
+	if raw_sections.len() != module.sections.len() {
+		return Err(Error::Other("sections count mismatch"));
+	}
+	module.raw_sections = raw_sections;
+	Ok(module)
+}
+
+/// Deserialization error, with the location of the failure.
+#[derive(Debug, Clone)]
+pub struct DeserializeError {
+	offset: usize,
+	section_id: Option<u8>,
+	error: Error,
+}
+
+impl DeserializeError {
+	/// Offset of the part of the binary which failed to decode: the magic
+	/// number or the version of the header, or the start of the section.
+	pub fn offset(&self) -> usize { self.offset }
+
+	/// Id of the section which failed to decode, `None` if it is the header.
+	pub fn section_id(&self) -> Option<u8> { self.section_id }
+
+	/// The error.
+	pub fn error(&self) -> &Error { &self.error }
+
+	/// The error, without its location.
+	pub fn into_error(self) -> Error { self.error }
+}
+
+impl fmt::Display for DeserializeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.section_id {
+			Some(id) => write!(f, "{} (section {} at offset {:#x})", self.error, id, self.offset),
+			None => write!(f, "{} (header at offset {:#x})", self.error, self.offset),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for DeserializeError {
+	fn description(&self) -> &str {
+		"Failed to decode module"
+	}
+}
+
+impl From<DeserializeError> for Error {
+	fn from(err: DeserializeError) -> Self {
+		err.error
+	}
+}
+
+/// Reader of a buffer exposing its position while it is borrowed.
+struct Tracked<'a, 'b> {
+	cursor: io::Cursor<&'a [u8]>,
+	position: &'b Cell<usize>,
+}
+
+impl<'a, 'b> io::Read for Tracked<'a, 'b> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		self.cursor.read(buf)?;
+		self.position.set(self.cursor.position());
+		Ok(())
+	}
+}
+
+/// Deserializes a module like `deserialize_buffer`, locating errors at the
+/// section which failed to decode.
+pub fn deserialize_buffer_with_location(contents: &[u8]) -> Result<Module, DeserializeError> {
+	let position = Cell::new(0);
+	// Start and id of every section read.
+	let mut sections = Vec::new();
+	let mut next = 8;
+	let result = {
+		let mut reader = Tracked { cursor: io::Cursor::new(contents), position: &position };
+		Module::deserialize_observed(&mut reader, |section| {
+			sections.push((next, section.id()));
+			next = position.get();
+		})
+	};
+	let error = match result {
+		Ok(module) if next == contents.len() => return Ok(module),
+		Ok(_) => io::Error::TrailingData.into(),
+		Err(error) => error,
+	};
+
+	let offset = match error {
+		Error::InvalidMagic => 0,
+		Error::UnsupportedVersion(_) => 4,
+		_ if position.get() < 8 => position.get(),
+		Error::InconsistentCode => sections.iter()
+			.find(|&&(_, id)| id == 10)
+			.or_else(|| sections.iter().find(|&&(_, id)| id == 3))
+			.map_or(next, |&(start, _)| start),
+		// Truncated sections end the module too, so that they are trailing data.
+		_ => next,
+	};
+	let section_id = if offset < 8 { None } else { contents.get(offset).cloned() };
+	Err(DeserializeError { offset, section_id, error })
+}
+
 #[cfg(test)]
 mod integration_tests {
 
 	use super::super::{deserialize_file, serialize, deserialize_buffer, Section};
+	#[cfg(feature = "builder")]
+	use super::{deserialize_buffer_with_raw_bodies, deserialize_buffer_with_raw_sections};
 	use super::Module;
 
 	#[test]
@@ -733,6 +1077,66 @@ mod integration_tests {
 		assert!(found_section, "Name section should be present in dedicated example");
 	}
 
+	#[test]
+	fn names_roundtrip() {
+		use super::super::name_section::{NameSection, ModuleNameSection, FunctionNameSection, LocalNameSection, NameMap};
+
+		let mut module = deserialize_file("./res/cases/v1/with_names.wasm").expect("Should be deserialized");
+		let names = module.names().expect("Names to be parsed");
+		match names.iter().find(|name_section| matches!(**name_section, NameSection::Function(_))) {
+			Some(&NameSection::Function(ref function_names)) =>
+				assert_eq!(function_names.names().get(0).expect("Should be entry #0"), "elog"),
+			_ => panic!("function names should be present in dedicated example"),
+		}
+
+		let mut function_names = FunctionNameSection::default();
+		function_names.names_mut().insert(0, "main".to_string());
+		let mut local_names = LocalNameSection::default();
+		let mut locals = NameMap::default();
+		locals.insert(0, "x".to_string());
+		local_names.local_names_mut().insert(0, locals);
+		let names = vec![
+			NameSection::Module(ModuleNameSection::new("test")),
+			NameSection::Function(function_names),
+			NameSection::Local(local_names),
+		];
+		module.set_names(names.clone()).expect("Names to be serialized");
+		let mut module: Module = deserialize_buffer(&serialize(module).expect("serialize to succeed"))
+			.expect("Deserialization should succeed");
+		assert_eq!(module.names().expect("Names to be parsed"), names);
+		module.set_names(Vec::new()).expect("Names to be removed");
+		assert_eq!(module.names().expect("Names to be parsed"), vec![]);
+	}
+
+	#[test]
+	fn non_utf8_names() {
+		use super::super::{CustomSection, NameSection, NameDecoding, TypeSection, FunctionSection, Type, FunctionType, Func};
+
+		// Function names subsection naming function 0 "a\xff".
+		let payload = vec![0x01, 0x05, 0x01, 0x00, 0x02, b'a', 0xff];
+		let module = || Module::new(vec![
+			Section::Type(TypeSection::with_types(vec![Type::Function(FunctionType::default())])),
+			Section::Function(FunctionSection::with_entries(vec![Func::new(0)])),
+			Section::Custom(CustomSection::new("name".to_owned(), payload.clone())),
+		]);
+
+		assert!(module().parse_names().is_err());
+
+		let lossy = module().parse_names_with(NameDecoding::Lossy).expect("names to be parsed");
+		match lossy.names_section() {
+			Some(&NameSection::Function(ref names)) => assert_eq!(names.names().get(0).map(|s| s.as_str()), Some("a\u{fffd}")),
+			other => panic!("unexpected name section {:?}", other),
+		}
+
+		let lenient = module().parse_names_with(NameDecoding::Lenient).expect("names to be parsed");
+		assert_eq!(
+			lenient.names_section(),
+			Some(&NameSection::Unparsed { name_type: 0x01, name_payload: payload[2..].to_vec() }),
+		);
+		let bytes = serialize(lenient).expect("module to serialize");
+		assert!(bytes.ends_with(&payload));
+	}
+
 	#[test]
 	fn varuint1_case() {
 		let _module = deserialize_file("./res/cases/v1/varuint1_1.wasm")
@@ -746,6 +1150,39 @@ mod integration_tests {
 		assert_eq!(module.memory_space(), 2);
 	}
 
+	#[test]
+	#[cfg(feature = "builder")]
+	fn custom_sections() {
+		use super::super::CustomSection;
+
+		let mut module = ::builder::module()
+			.function().signature().build().body().build().build()
+			.build();
+		module.sections_mut().insert(1, Section::Custom(CustomSection::new("build-id".into(), vec![1, 2])));
+		module.add_custom_section("sourceMappingURL".into(), b"a.map".to_vec());
+		let bytes = serialize(module).expect("module to serialize");
+
+		let mut module = deserialize_buffer_with_raw_sections(&bytes).expect("module to deserialize");
+		assert_eq!(module.custom_sections().map(|custom| custom.name()).collect::<Vec<_>>(), vec!["build-id", "sourceMappingURL"]);
+
+		// Known sections are inserted at their place, around the custom ones.
+		module.set_start_section(0);
+		let ids = |module: &Module| module.sections().iter().map(|section| section.id()).collect::<Vec<_>>();
+		assert_eq!(ids(&module), vec![1, 0, 3, 8, 10, 0]);
+		let decoded: Module = deserialize_buffer(&serialize(module.clone()).expect("module to serialize"))
+			.expect("module to deserialize");
+		assert_eq!(decoded, module);
+
+		let removed = module.remove_custom_section("build-id");
+		assert_eq!(removed, vec![CustomSection::new("build-id".into(), vec![1, 2])]);
+		assert!(module.remove_custom_section("build-id").is_empty());
+		assert_eq!(ids(&module), vec![1, 3, 8, 10, 0]);
+		module.clear_start_section();
+		let bytes = serialize(module).expect("module to serialize");
+		let module: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		assert_eq!(module.custom_sections().map(|custom| custom.payload()).collect::<Vec<_>>(), vec![&b"a.map"[..]]);
+	}
+
     #[test]
     fn mut_start() {
         let mut module = deserialize_file("./res/cases/v1/start_mut.wasm").expect("failed to deserialize");
@@ -755,4 +1192,145 @@ mod integration_tests {
         module.clear_start_section();
         assert_eq!(None, module.start_section());
     }
+
+	#[test]
+	#[cfg(feature = "builder")]
+	fn data_count() {
+		let mut module = ::builder::module()
+			.function().signature().build().body().build().build()
+			.data().offset(::elements::Instruction::I32Const(0)).value(vec![1]).build()
+			.build();
+		module.set_data_count_section(1);
+		let bytes = serialize(module.clone()).expect("module to serialize");
+		let deserialized = deserialize_buffer::<Module>(&bytes).expect("module to deserialize");
+		assert_eq!(deserialized, module);
+		assert_eq!(deserialized.sections()[2], Section::DataCount(1));
+		module.clear_data_count_section();
+		assert_eq!(module.data_count_section(), None);
+
+		// The data count section comes before the code section.
+		let mut sections = deserialized.into_sections();
+		sections.swap(2, 3);
+		let bytes = serialize(Module::new(sections)).expect("module to serialize");
+		assert!(deserialize_buffer::<Module>(&bytes).is_err());
+	}
+
+	#[test]
+	#[cfg(feature = "builder")]
+	fn raw_bodies() {
+		use super::super::Instruction;
+
+		// Non-minimal LEB128 immediates are not preserved by re-encoding
+		// decoded instructions, but are by raw bodies.
+		let mut bytes = serialize(::builder::module()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.build()).expect("module to serialize");
+		let end = bytes.len();
+		// The code section comes last, with both bodies being
+		// `[0x02, 0x00, 0x0b]`: make the first one use a padded local count.
+		let first = end - 6;
+		assert_eq!(&bytes[first - 3..], &[0x0a, 0x07, 0x02, 0x02, 0x00, 0x0b, 0x02, 0x00, 0x0b]);
+		bytes.splice(first..first + 2, vec![0x03, 0x80, 0x00]);
+		bytes[first - 2] = 0x08;
+
+		let module = deserialize_buffer_with_raw_bodies(&bytes).expect("module to deserialize");
+		let bodies = module.code_section().expect("code section").bodies();
+		assert_eq!(bodies[0].raw_bytes(), Some(&[0x80, 0x00, 0x0b][..]));
+		assert_eq!(serialize(module.clone()).expect("module to serialize"), bytes);
+
+		let mut module = module;
+		module.code_section_mut().expect("code section").bodies_mut()[0].code_mut()
+			.elements_mut().insert(0, Instruction::Nop);
+		let bodies = module.code_section().expect("code section").bodies();
+		assert!(bodies[0].raw_bytes().is_none());
+		assert!(bodies[1].raw_bytes().is_some());
+		let reencoded = serialize(module).expect("module to serialize");
+		assert_eq!(&reencoded[reencoded.len() - 7..], &[0x03, 0x00, 0x01, 0x0b, 0x02, 0x00, 0x0b]);
+	}
+
+	#[test]
+	#[cfg(feature = "builder")]
+	fn raw_sections() {
+		use super::super::{ExportEntry, Internal};
+
+		let mut bytes = serialize(::builder::module()
+			.function().signature().build().body().build().build()
+			.export().field("f").internal().func(0).build()
+			.build()).expect("module to serialize");
+		// Pad the type count of the type section (right after the header)
+		// with a non-minimal LEB128 encoding, which re-encoding would lose.
+		assert_eq!(&bytes[8..11], &[0x01, 0x04, 0x01]);
+		bytes.splice(10..11, vec![0x81, 0x00]);
+		bytes[9] = 0x05;
+
+		let mut module = deserialize_buffer_with_raw_sections(&bytes).expect("module to deserialize");
+		assert_eq!(serialize(module.clone()).expect("module to serialize"), bytes);
+
+		module.export_section_mut().expect("export section").entries_mut()
+			.push(ExportEntry::new("g".into(), Internal::Function(0)));
+		let reencoded = serialize(module.clone()).expect("module to serialize");
+		assert_eq!(&reencoded[..12], &bytes[..12]);
+		let decoded: super::Module = deserialize_buffer(&reencoded).expect("module to deserialize");
+		assert_eq!(decoded, module);
+
+		module.sections_mut();
+		assert_eq!(serialize(module).expect("module to serialize")[8..11], [0x01, 0x04, 0x01]);
+	}
+
+	#[test]
+	#[cfg(feature = "builder")]
+	fn raw_sections_data_count() {
+		let mut bytes = serialize(::builder::module()
+			.function().signature().build().body().build().build()
+			.data().offset(::elements::Instruction::I32Const(0)).value(vec![1]).build()
+			.build()).expect("module to serialize");
+		// Same padding of the type count as in `raw_sections`.
+		assert_eq!(&bytes[8..11], &[0x01, 0x04, 0x01]);
+		bytes.splice(10..11, vec![0x81, 0x00]);
+		bytes[9] = 0x05;
+		// Type and function sections, then the code section.
+		let code = 19;
+		assert_eq!(bytes[code], 0x0a);
+
+		let mut module = deserialize_buffer_with_raw_sections(&bytes).expect("module to deserialize");
+		module.set_data_count_section(1);
+		module.set_data_count_section(1);
+		let mut expected = bytes.clone();
+		expected.splice(code..code, vec![0x0c, 0x01, 0x01]);
+		assert_eq!(serialize(module.clone()).expect("module to serialize"), expected);
+
+		module.clear_data_count_section();
+		assert_eq!(serialize(module).expect("module to serialize"), bytes);
+	}
+
+	#[test]
+	#[cfg(feature = "builder")]
+	fn location() {
+		use super::super::Error;
+		use super::deserialize_buffer_with_location;
+
+		let bytes = serialize(::builder::module()
+			.function().signature().build().body().build().build()
+			.export().field("f").internal().func(0).build()
+			.build()).expect("module to serialize");
+		// Type, function, export and code sections.
+		let (function, export, code) = (14, 18, 25);
+		assert_eq!((bytes[function], bytes[export], bytes[code]), (0x03, 0x07, 0x0a));
+		assert!(deserialize_buffer_with_location(&bytes).is_ok());
+
+		let locate = |bytes: &[u8]| {
+			let e = deserialize_buffer_with_location(bytes).expect_err("module to fail");
+			(e.offset(), e.section_id(), e.into_error())
+		};
+		let mut invalid = bytes.clone();
+		invalid[4] = 2;
+		assert!(matches!(locate(&invalid), (4, None, Error::UnsupportedVersion(2))));
+		let mut invalid = bytes.clone();
+		invalid[export + 5] = 9;
+		assert!(matches!(locate(&invalid), (18, Some(0x07), Error::UnknownInternalKind(9))));
+		assert!(matches!(locate(&bytes[..bytes.len() - 1]), (25, Some(0x0a), _)));
+		assert!(matches!(locate(&bytes[..code]), (14, Some(0x03), Error::InconsistentCode)));
+		assert!(matches!(locate(&bytes[..3]), (0, None, _)));
+	}
 }
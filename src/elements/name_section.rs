@@ -30,28 +30,106 @@ pub enum NameSection {
 	},
 }
 
+/// How names which are not valid UTF-8 are decoded from the name section.
+///
+/// The specification requires valid UTF-8, but some producers emit invalid
+/// bytes in the name section, which only holds debug information.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameDecoding {
+	/// Fail with `Error::NonUtf8String`.
+	#[default]
+	Strict,
+	/// Replace invalid sequences by `U+FFFD REPLACEMENT CHARACTER`.
+	Lossy,
+	/// Keep subsections with invalid names as `NameSection::Unparsed`, so
+	/// that their bytes are preserved as they are.
+	Lenient,
+}
+
+fn deserialize_name<R: io::Read>(rdr: &mut R, decoding: NameDecoding) -> Result<String, Error> {
+	match decoding {
+		NameDecoding::Lossy => {
+			let length = u32::from(VarUint32::deserialize(rdr)?) as usize;
+			let bytes = buffered_read!(1024, length, rdr);
+			Ok(String::from_utf8_lossy(&bytes).into_owned())
+		},
+		NameDecoding::Strict | NameDecoding::Lenient => String::deserialize(rdr),
+	}
+}
+
+pub(super) fn deserialize_name_map<R: io::Read>(max_entry_space: usize, rdr: &mut R, decoding: NameDecoding) -> Result<NameMap, Error> {
+	IndexMap::deserialize_with(max_entry_space, &|_, rdr: &mut R| deserialize_name(rdr, decoding), rdr)
+}
+
 impl NameSection {
 	/// Deserialize a name section.
 	pub fn deserialize<R: io::Read>(
 		module: &Module,
 		rdr: &mut R,
+	) -> Result<NameSection, Error> {
+		NameSection::deserialize_with_decoding(module, rdr, NameDecoding::Strict)
+	}
+
+	/// Deserialize a name section, decoding names according to `decoding`.
+	pub fn deserialize_with_decoding<R: io::Read>(
+		module: &Module,
+		rdr: &mut R,
+		decoding: NameDecoding,
 	) -> Result<NameSection, Error> {
 		let name_type: u8 = VarUint7::deserialize(rdr)?.into();
 		let name_payload_len: u32 = VarUint32::deserialize(rdr)?.into();
-		let name_section = match name_type {
-			NAME_TYPE_MODULE => NameSection::Module(ModuleNameSection::deserialize(rdr)?),
-			NAME_TYPE_FUNCTION => NameSection::Function(FunctionNameSection::deserialize(module, rdr)?),
-			NAME_TYPE_LOCAL => NameSection::Local(LocalNameSection::deserialize(module, rdr)?),
-			_ => {
+		if decoding == NameDecoding::Lenient {
+			let name_payload = buffered_read!(1024, name_payload_len as usize, rdr);
+			let mut payload_rdr = io::Cursor::new(&name_payload[..]);
+			return match NameSection::deserialize_payload(module, name_type, &mut payload_rdr, decoding) {
+				Ok(Some(name_section)) => Ok(name_section),
+				Ok(None) | Err(Error::NonUtf8String) => Ok(NameSection::Unparsed { name_type, name_payload }),
+				Err(e) => Err(e),
+			};
+		}
+		match NameSection::deserialize_payload(module, name_type, rdr, decoding)? {
+			Some(name_section) => Ok(name_section),
+			None => {
 				let mut name_payload = vec![0u8; name_payload_len as usize];
 				rdr.read(&mut name_payload)?;
-				NameSection::Unparsed {
+				Ok(NameSection::Unparsed {
 					name_type,
 					name_payload,
-				}
+				})
 			}
-		};
-		Ok(name_section)
+		}
+	}
+
+	/// Deserialize all the subsections of the payload of a `name` custom
+	/// section, in order.
+	pub fn deserialize_all(
+		module: &Module,
+		payload: &[u8],
+		decoding: NameDecoding,
+	) -> Result<Vec<NameSection>, Error> {
+		let mut rdr = io::Cursor::new(payload);
+		let mut subsections = Vec::new();
+		while rdr.position() < payload.len() {
+			subsections.push(NameSection::deserialize_with_decoding(module, &mut rdr, decoding)?);
+		}
+		Ok(subsections)
+	}
+
+	/// Known subsection of type `name_type`, `None` for unknown types.
+	fn deserialize_payload<R: io::Read>(
+		module: &Module,
+		name_type: u8,
+		rdr: &mut R,
+		decoding: NameDecoding,
+	) -> Result<Option<NameSection>, Error> {
+		Ok(Some(match name_type {
+			NAME_TYPE_MODULE => NameSection::Module(ModuleNameSection {
+				name: deserialize_name(rdr, decoding)?,
+			}),
+			NAME_TYPE_FUNCTION => NameSection::Function(FunctionNameSection::deserialize_with_decoding(module, rdr, decoding)?),
+			NAME_TYPE_LOCAL => NameSection::Local(LocalNameSection::deserialize_with_decoding(module, rdr, decoding)?),
+			_ => return Ok(None),
+		}))
 	}
 }
 
@@ -149,7 +227,15 @@ impl FunctionNameSection {
 		module: &Module,
 		rdr: &mut R,
 	) -> Result<FunctionNameSection, Error> {
-		let names = IndexMap::deserialize(module.functions_space(), rdr)?;
+		FunctionNameSection::deserialize_with_decoding(module, rdr, NameDecoding::Strict)
+	}
+
+	fn deserialize_with_decoding<R: io::Read>(
+		module: &Module,
+		rdr: &mut R,
+		decoding: NameDecoding,
+	) -> Result<FunctionNameSection, Error> {
+		let names = deserialize_name_map(module.functions_space(), rdr, decoding)?;
 		Ok(FunctionNameSection { names })
 	}
 }
@@ -186,10 +272,19 @@ impl LocalNameSection {
 		module: &Module,
 		rdr: &mut R,
 	) -> Result<LocalNameSection, Error> {
-		let funcs = module.function_section().ok_or_else(|| {
-			Error::Other("cannot deserialize local names without a function section")
-		})?;
-		let max_entry_space = funcs.entries().len();
+		LocalNameSection::deserialize_with_decoding(module, rdr, NameDecoding::Strict)
+	}
+
+	fn deserialize_with_decoding<R: io::Read>(
+		module: &Module,
+		rdr: &mut R,
+		decoding: NameDecoding,
+	) -> Result<LocalNameSection, Error> {
+		if module.function_section().is_none() {
+			return Err(Error::Other("cannot deserialize local names without a function section"));
+		}
+		// Local names are keyed by function index, imported functions included.
+		let max_entry_space = module.functions_space();
 
 		let max_signature_args = module
 			.type_section()
@@ -203,12 +298,12 @@ impl LocalNameSection {
 
 		let max_locals = module
 			.code_section()
-			.map(|cs| cs.bodies().iter().map(|f| f.locals().len()).max().unwrap_or(0))
+			.map(|cs| cs.bodies().iter().map(|f| f.locals().iter().map(|l| l.count() as usize).sum()).max().unwrap_or(0))
 			.unwrap_or(0);
 
 		let max_space = max_signature_args + max_locals;
 
-		let deserialize_locals = |_: u32, rdr: &mut R| IndexMap::deserialize(max_space, rdr);
+		let deserialize_locals = |_: u32, rdr: &mut R| deserialize_name_map(max_space, rdr, decoding);
 
 		let local_names = IndexMap::deserialize_with(
 			max_entry_space,
@@ -1,10 +1,11 @@
 use std::fmt;
 use std::vec::Vec;
 use std::boxed::Box;
+use std::convert::TryFrom;
 use io;
 use super::{
 	Serialize, Deserialize, Error,
-	Uint8, VarUint32, CountedList, BlockType,
+	Uint8, VarUint32, CountedList, BlockType, TableElementType, ValueType,
 	Uint32, Uint64, CountedListWriter,
 	VarInt32, VarInt64,
 };
@@ -120,10 +121,11 @@ pub enum Instruction {
 	Return,
 
 	Call(u32),
-	CallIndirect(u32, u8),
+	CallIndirect(u32, u32),
 
 	Drop,
 	Select,
+	TypedSelect(ValueType),
 
 	GetLocal(u32),
 	SetLocal(u32),
@@ -534,9 +536,19 @@ pub enum Instruction {
 	MemoryDrop(u32),
 	MemoryCopy,
 	MemoryFill,
-	TableInit(u32),
+	TableInit(u32, u32),
 	TableDrop(u32),
-	TableCopy,
+	TableCopy(u32, u32),
+
+	// https://github.com/WebAssembly/reference-types
+	RefNull(TableElementType),
+	RefIsNull,
+	RefFunc(u32),
+	TableGet(u32),
+	TableSet(u32),
+	TableGrow(u32),
+	TableSize(u32),
+	TableFill(u32),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -571,6 +583,569 @@ impl Instruction {
 			_ => false,
 		}
 	}
+
+	/// Opcode of this instruction, without its immediates.
+	pub fn opcode(&self) -> Opcode {
+		use self::Instruction::*;
+		use self::opcodes::*;
+
+		match *self {
+			Unreachable => Opcode::Plain(UNREACHABLE),
+			Nop => Opcode::Plain(NOP),
+			Block(..) => Opcode::Plain(BLOCK),
+			Loop(..) => Opcode::Plain(LOOP),
+			If(..) => Opcode::Plain(IF),
+			Else => Opcode::Plain(ELSE),
+			End => Opcode::Plain(END),
+			Br(..) => Opcode::Plain(BR),
+			BrIf(..) => Opcode::Plain(BRIF),
+			BrTable(..) => Opcode::Plain(BRTABLE),
+			Return => Opcode::Plain(RETURN),
+			Call(..) => Opcode::Plain(CALL),
+			CallIndirect(..) => Opcode::Plain(CALLINDIRECT),
+			Drop => Opcode::Plain(DROP),
+			Select => Opcode::Plain(SELECT),
+			TypedSelect(..) => Opcode::Plain(SELECT_T),
+			GetLocal(..) => Opcode::Plain(GETLOCAL),
+			SetLocal(..) => Opcode::Plain(SETLOCAL),
+			TeeLocal(..) => Opcode::Plain(TEELOCAL),
+			GetGlobal(..) => Opcode::Plain(GETGLOBAL),
+			SetGlobal(..) => Opcode::Plain(SETGLOBAL),
+			I32Load(..) => Opcode::Plain(I32LOAD),
+			I64Load(..) => Opcode::Plain(I64LOAD),
+			F32Load(..) => Opcode::Plain(F32LOAD),
+			F64Load(..) => Opcode::Plain(F64LOAD),
+			I32Load8S(..) => Opcode::Plain(I32LOAD8S),
+			I32Load8U(..) => Opcode::Plain(I32LOAD8U),
+			I32Load16S(..) => Opcode::Plain(I32LOAD16S),
+			I32Load16U(..) => Opcode::Plain(I32LOAD16U),
+			I64Load8S(..) => Opcode::Plain(I64LOAD8S),
+			I64Load8U(..) => Opcode::Plain(I64LOAD8U),
+			I64Load16S(..) => Opcode::Plain(I64LOAD16S),
+			I64Load16U(..) => Opcode::Plain(I64LOAD16U),
+			I64Load32S(..) => Opcode::Plain(I64LOAD32S),
+			I64Load32U(..) => Opcode::Plain(I64LOAD32U),
+			I32Store(..) => Opcode::Plain(I32STORE),
+			I64Store(..) => Opcode::Plain(I64STORE),
+			F32Store(..) => Opcode::Plain(F32STORE),
+			F64Store(..) => Opcode::Plain(F64STORE),
+			I32Store8(..) => Opcode::Plain(I32STORE8),
+			I32Store16(..) => Opcode::Plain(I32STORE16),
+			I64Store8(..) => Opcode::Plain(I64STORE8),
+			I64Store16(..) => Opcode::Plain(I64STORE16),
+			I64Store32(..) => Opcode::Plain(I64STORE32),
+			CurrentMemory(..) => Opcode::Plain(CURRENTMEMORY),
+			GrowMemory(..) => Opcode::Plain(GROWMEMORY),
+			I32Const(..) => Opcode::Plain(I32CONST),
+			I64Const(..) => Opcode::Plain(I64CONST),
+			F32Const(..) => Opcode::Plain(F32CONST),
+			F64Const(..) => Opcode::Plain(F64CONST),
+			I32Eqz => Opcode::Plain(I32EQZ),
+			I32Eq => Opcode::Plain(I32EQ),
+			I32Ne => Opcode::Plain(I32NE),
+			I32LtS => Opcode::Plain(I32LTS),
+			I32LtU => Opcode::Plain(I32LTU),
+			I32GtS => Opcode::Plain(I32GTS),
+			I32GtU => Opcode::Plain(I32GTU),
+			I32LeS => Opcode::Plain(I32LES),
+			I32LeU => Opcode::Plain(I32LEU),
+			I32GeS => Opcode::Plain(I32GES),
+			I32GeU => Opcode::Plain(I32GEU),
+			I64Eqz => Opcode::Plain(I64EQZ),
+			I64Eq => Opcode::Plain(I64EQ),
+			I64Ne => Opcode::Plain(I64NE),
+			I64LtS => Opcode::Plain(I64LTS),
+			I64LtU => Opcode::Plain(I64LTU),
+			I64GtS => Opcode::Plain(I64GTS),
+			I64GtU => Opcode::Plain(I64GTU),
+			I64LeS => Opcode::Plain(I64LES),
+			I64LeU => Opcode::Plain(I64LEU),
+			I64GeS => Opcode::Plain(I64GES),
+			I64GeU => Opcode::Plain(I64GEU),
+			F32Eq => Opcode::Plain(F32EQ),
+			F32Ne => Opcode::Plain(F32NE),
+			F32Lt => Opcode::Plain(F32LT),
+			F32Gt => Opcode::Plain(F32GT),
+			F32Le => Opcode::Plain(F32LE),
+			F32Ge => Opcode::Plain(F32GE),
+			F64Eq => Opcode::Plain(F64EQ),
+			F64Ne => Opcode::Plain(F64NE),
+			F64Lt => Opcode::Plain(F64LT),
+			F64Gt => Opcode::Plain(F64GT),
+			F64Le => Opcode::Plain(F64LE),
+			F64Ge => Opcode::Plain(F64GE),
+			I32Clz => Opcode::Plain(I32CLZ),
+			I32Ctz => Opcode::Plain(I32CTZ),
+			I32Popcnt => Opcode::Plain(I32POPCNT),
+			I32Add => Opcode::Plain(I32ADD),
+			I32Sub => Opcode::Plain(I32SUB),
+			I32Mul => Opcode::Plain(I32MUL),
+			I32DivS => Opcode::Plain(I32DIVS),
+			I32DivU => Opcode::Plain(I32DIVU),
+			I32RemS => Opcode::Plain(I32REMS),
+			I32RemU => Opcode::Plain(I32REMU),
+			I32And => Opcode::Plain(I32AND),
+			I32Or => Opcode::Plain(I32OR),
+			I32Xor => Opcode::Plain(I32XOR),
+			I32Shl => Opcode::Plain(I32SHL),
+			I32ShrS => Opcode::Plain(I32SHRS),
+			I32ShrU => Opcode::Plain(I32SHRU),
+			I32Rotl => Opcode::Plain(I32ROTL),
+			I32Rotr => Opcode::Plain(I32ROTR),
+			I64Clz => Opcode::Plain(I64CLZ),
+			I64Ctz => Opcode::Plain(I64CTZ),
+			I64Popcnt => Opcode::Plain(I64POPCNT),
+			I64Add => Opcode::Plain(I64ADD),
+			I64Sub => Opcode::Plain(I64SUB),
+			I64Mul => Opcode::Plain(I64MUL),
+			I64DivS => Opcode::Plain(I64DIVS),
+			I64DivU => Opcode::Plain(I64DIVU),
+			I64RemS => Opcode::Plain(I64REMS),
+			I64RemU => Opcode::Plain(I64REMU),
+			I64And => Opcode::Plain(I64AND),
+			I64Or => Opcode::Plain(I64OR),
+			I64Xor => Opcode::Plain(I64XOR),
+			I64Shl => Opcode::Plain(I64SHL),
+			I64ShrS => Opcode::Plain(I64SHRS),
+			I64ShrU => Opcode::Plain(I64SHRU),
+			I64Rotl => Opcode::Plain(I64ROTL),
+			I64Rotr => Opcode::Plain(I64ROTR),
+			F32Abs => Opcode::Plain(F32ABS),
+			F32Neg => Opcode::Plain(F32NEG),
+			F32Ceil => Opcode::Plain(F32CEIL),
+			F32Floor => Opcode::Plain(F32FLOOR),
+			F32Trunc => Opcode::Plain(F32TRUNC),
+			F32Nearest => Opcode::Plain(F32NEAREST),
+			F32Sqrt => Opcode::Plain(F32SQRT),
+			F32Add => Opcode::Plain(F32ADD),
+			F32Sub => Opcode::Plain(F32SUB),
+			F32Mul => Opcode::Plain(F32MUL),
+			F32Div => Opcode::Plain(F32DIV),
+			F32Min => Opcode::Plain(F32MIN),
+			F32Max => Opcode::Plain(F32MAX),
+			F32Copysign => Opcode::Plain(F32COPYSIGN),
+			F64Abs => Opcode::Plain(F64ABS),
+			F64Neg => Opcode::Plain(F64NEG),
+			F64Ceil => Opcode::Plain(F64CEIL),
+			F64Floor => Opcode::Plain(F64FLOOR),
+			F64Trunc => Opcode::Plain(F64TRUNC),
+			F64Nearest => Opcode::Plain(F64NEAREST),
+			F64Sqrt => Opcode::Plain(F64SQRT),
+			F64Add => Opcode::Plain(F64ADD),
+			F64Sub => Opcode::Plain(F64SUB),
+			F64Mul => Opcode::Plain(F64MUL),
+			F64Div => Opcode::Plain(F64DIV),
+			F64Min => Opcode::Plain(F64MIN),
+			F64Max => Opcode::Plain(F64MAX),
+			F64Copysign => Opcode::Plain(F64COPYSIGN),
+			I32WrapI64 => Opcode::Plain(I32WRAPI64),
+			I32TruncSF32 => Opcode::Plain(I32TRUNCSF32),
+			I32TruncUF32 => Opcode::Plain(I32TRUNCUF32),
+			I32TruncSF64 => Opcode::Plain(I32TRUNCSF64),
+			I32TruncUF64 => Opcode::Plain(I32TRUNCUF64),
+			I64ExtendSI32 => Opcode::Plain(I64EXTENDSI32),
+			I64ExtendUI32 => Opcode::Plain(I64EXTENDUI32),
+			I64TruncSF32 => Opcode::Plain(I64TRUNCSF32),
+			I64TruncUF32 => Opcode::Plain(I64TRUNCUF32),
+			I64TruncSF64 => Opcode::Plain(I64TRUNCSF64),
+			I64TruncUF64 => Opcode::Plain(I64TRUNCUF64),
+			F32ConvertSI32 => Opcode::Plain(F32CONVERTSI32),
+			F32ConvertUI32 => Opcode::Plain(F32CONVERTUI32),
+			F32ConvertSI64 => Opcode::Plain(F32CONVERTSI64),
+			F32ConvertUI64 => Opcode::Plain(F32CONVERTUI64),
+			F32DemoteF64 => Opcode::Plain(F32DEMOTEF64),
+			F64ConvertSI32 => Opcode::Plain(F64CONVERTSI32),
+			F64ConvertUI32 => Opcode::Plain(F64CONVERTUI32),
+			F64ConvertSI64 => Opcode::Plain(F64CONVERTSI64),
+			F64ConvertUI64 => Opcode::Plain(F64CONVERTUI64),
+			F64PromoteF32 => Opcode::Plain(F64PROMOTEF32),
+			I32ReinterpretF32 => Opcode::Plain(I32REINTERPRETF32),
+			I64ReinterpretF64 => Opcode::Plain(I64REINTERPRETF64),
+			F32ReinterpretI32 => Opcode::Plain(F32REINTERPRETI32),
+			F64ReinterpretI64 => Opcode::Plain(F64REINTERPRETI64),
+			I32Extend8S => Opcode::Plain(I32_EXTEND8_S),
+			I32Extend16S => Opcode::Plain(I32_EXTEND16_S),
+			I64Extend8S => Opcode::Plain(I64_EXTEND8_S),
+			I64Extend16S => Opcode::Plain(I64_EXTEND16_S),
+			I64Extend32S => Opcode::Plain(I64_EXTEND32_S),
+			AtomicWake(..) => Opcode::Atomic(ATOMIC_WAKE),
+			I32AtomicWait(..) => Opcode::Atomic(I32_ATOMIC_WAIT),
+			I64AtomicWait(..) => Opcode::Atomic(I64_ATOMIC_WAIT),
+			I32AtomicLoad(..) => Opcode::Atomic(I32_ATOMIC_LOAD),
+			I64AtomicLoad(..) => Opcode::Atomic(I64_ATOMIC_LOAD),
+			I32AtomicLoad8u(..) => Opcode::Atomic(I32_ATOMIC_LOAD8U),
+			I32AtomicLoad16u(..) => Opcode::Atomic(I32_ATOMIC_LOAD16U),
+			I64AtomicLoad8u(..) => Opcode::Atomic(I64_ATOMIC_LOAD8U),
+			I64AtomicLoad16u(..) => Opcode::Atomic(I64_ATOMIC_LOAD16U),
+			I64AtomicLoad32u(..) => Opcode::Atomic(I64_ATOMIC_LOAD32U),
+			I32AtomicStore(..) => Opcode::Atomic(I32_ATOMIC_STORE),
+			I64AtomicStore(..) => Opcode::Atomic(I64_ATOMIC_STORE),
+			I32AtomicStore8u(..) => Opcode::Atomic(I32_ATOMIC_STORE8U),
+			I32AtomicStore16u(..) => Opcode::Atomic(I32_ATOMIC_STORE16U),
+			I64AtomicStore8u(..) => Opcode::Atomic(I64_ATOMIC_STORE8U),
+			I64AtomicStore16u(..) => Opcode::Atomic(I64_ATOMIC_STORE16U),
+			I64AtomicStore32u(..) => Opcode::Atomic(I64_ATOMIC_STORE32U),
+			I32AtomicRmwAdd(..) => Opcode::Atomic(I32_ATOMIC_RMW_ADD),
+			I64AtomicRmwAdd(..) => Opcode::Atomic(I64_ATOMIC_RMW_ADD),
+			I32AtomicRmwAdd8u(..) => Opcode::Atomic(I32_ATOMIC_RMW_ADD8U),
+			I32AtomicRmwAdd16u(..) => Opcode::Atomic(I32_ATOMIC_RMW_ADD16U),
+			I64AtomicRmwAdd8u(..) => Opcode::Atomic(I64_ATOMIC_RMW_ADD8U),
+			I64AtomicRmwAdd16u(..) => Opcode::Atomic(I64_ATOMIC_RMW_ADD16U),
+			I64AtomicRmwAdd32u(..) => Opcode::Atomic(I64_ATOMIC_RMW_ADD32U),
+			I32AtomicRmwSub(..) => Opcode::Atomic(I32_ATOMIC_RMW_SUB),
+			I64AtomicRmwSub(..) => Opcode::Atomic(I64_ATOMIC_RMW_SUB),
+			I32AtomicRmwSub8u(..) => Opcode::Atomic(I32_ATOMIC_RMW_SUB8U),
+			I32AtomicRmwSub16u(..) => Opcode::Atomic(I32_ATOMIC_RMW_SUB16U),
+			I64AtomicRmwSub8u(..) => Opcode::Atomic(I64_ATOMIC_RMW_SUB8U),
+			I64AtomicRmwSub16u(..) => Opcode::Atomic(I64_ATOMIC_RMW_SUB16U),
+			I64AtomicRmwSub32u(..) => Opcode::Atomic(I64_ATOMIC_RMW_SUB32U),
+			I32AtomicRmwAnd(..) => Opcode::Atomic(I32_ATOMIC_RMW_AND),
+			I64AtomicRmwAnd(..) => Opcode::Atomic(I64_ATOMIC_RMW_AND),
+			I32AtomicRmwAnd8u(..) => Opcode::Atomic(I32_ATOMIC_RMW_AND8U),
+			I32AtomicRmwAnd16u(..) => Opcode::Atomic(I32_ATOMIC_RMW_AND16U),
+			I64AtomicRmwAnd8u(..) => Opcode::Atomic(I64_ATOMIC_RMW_AND8U),
+			I64AtomicRmwAnd16u(..) => Opcode::Atomic(I64_ATOMIC_RMW_AND16U),
+			I64AtomicRmwAnd32u(..) => Opcode::Atomic(I64_ATOMIC_RMW_AND32U),
+			I32AtomicRmwOr(..) => Opcode::Atomic(I32_ATOMIC_RMW_OR),
+			I64AtomicRmwOr(..) => Opcode::Atomic(I64_ATOMIC_RMW_OR),
+			I32AtomicRmwOr8u(..) => Opcode::Atomic(I32_ATOMIC_RMW_OR8U),
+			I32AtomicRmwOr16u(..) => Opcode::Atomic(I32_ATOMIC_RMW_OR16U),
+			I64AtomicRmwOr8u(..) => Opcode::Atomic(I64_ATOMIC_RMW_OR8U),
+			I64AtomicRmwOr16u(..) => Opcode::Atomic(I64_ATOMIC_RMW_OR16U),
+			I64AtomicRmwOr32u(..) => Opcode::Atomic(I64_ATOMIC_RMW_OR32U),
+			I32AtomicRmwXor(..) => Opcode::Atomic(I32_ATOMIC_RMW_XOR),
+			I64AtomicRmwXor(..) => Opcode::Atomic(I64_ATOMIC_RMW_XOR),
+			I32AtomicRmwXor8u(..) => Opcode::Atomic(I32_ATOMIC_RMW_XOR8U),
+			I32AtomicRmwXor16u(..) => Opcode::Atomic(I32_ATOMIC_RMW_XOR16U),
+			I64AtomicRmwXor8u(..) => Opcode::Atomic(I64_ATOMIC_RMW_XOR8U),
+			I64AtomicRmwXor16u(..) => Opcode::Atomic(I64_ATOMIC_RMW_XOR16U),
+			I64AtomicRmwXor32u(..) => Opcode::Atomic(I64_ATOMIC_RMW_XOR32U),
+			I32AtomicRmwXchg(..) => Opcode::Atomic(I32_ATOMIC_RMW_XCHG),
+			I64AtomicRmwXchg(..) => Opcode::Atomic(I64_ATOMIC_RMW_XCHG),
+			I32AtomicRmwXchg8u(..) => Opcode::Atomic(I32_ATOMIC_RMW_XCHG8U),
+			I32AtomicRmwXchg16u(..) => Opcode::Atomic(I32_ATOMIC_RMW_XCHG16U),
+			I64AtomicRmwXchg8u(..) => Opcode::Atomic(I64_ATOMIC_RMW_XCHG8U),
+			I64AtomicRmwXchg16u(..) => Opcode::Atomic(I64_ATOMIC_RMW_XCHG16U),
+			I64AtomicRmwXchg32u(..) => Opcode::Atomic(I64_ATOMIC_RMW_XCHG32U),
+			I32AtomicRmwCmpxchg(..) => Opcode::Atomic(I32_ATOMIC_RMW_CMPXCHG),
+			I64AtomicRmwCmpxchg(..) => Opcode::Atomic(I64_ATOMIC_RMW_CMPXCHG),
+			I32AtomicRmwCmpxchg8u(..) => Opcode::Atomic(I32_ATOMIC_RMW_CMPXCHG8U),
+			I32AtomicRmwCmpxchg16u(..) => Opcode::Atomic(I32_ATOMIC_RMW_CMPXCHG16U),
+			I64AtomicRmwCmpxchg8u(..) => Opcode::Atomic(I64_ATOMIC_RMW_CMPXCHG8U),
+			I64AtomicRmwCmpxchg16u(..) => Opcode::Atomic(I64_ATOMIC_RMW_CMPXCHG16U),
+			I64AtomicRmwCmpxchg32u(..) => Opcode::Atomic(I64_ATOMIC_RMW_CMPXCHG32U),
+			V128Const(..) => Opcode::Simd(V128_CONST),
+			V128Load(..) => Opcode::Simd(V128_LOAD),
+			V128Store(..) => Opcode::Simd(V128_STORE),
+			I8x16Splat => Opcode::Simd(I8X16_SPLAT),
+			I16x8Splat => Opcode::Simd(I16X8_SPLAT),
+			I32x4Splat => Opcode::Simd(I32X4_SPLAT),
+			I64x2Splat => Opcode::Simd(I64X2_SPLAT),
+			F32x4Splat => Opcode::Simd(F32X4_SPLAT),
+			F64x2Splat => Opcode::Simd(F64X2_SPLAT),
+			I8x16ExtractLaneS(..) => Opcode::Simd(I8X16_EXTRACT_LANE_S),
+			I8x16ExtractLaneU(..) => Opcode::Simd(I8X16_EXTRACT_LANE_U),
+			I16x8ExtractLaneS(..) => Opcode::Simd(I16X8_EXTRACT_LANE_S),
+			I16x8ExtractLaneU(..) => Opcode::Simd(I16X8_EXTRACT_LANE_U),
+			I32x4ExtractLane(..) => Opcode::Simd(I32X4_EXTRACT_LANE),
+			I64x2ExtractLane(..) => Opcode::Simd(I64X2_EXTRACT_LANE),
+			F32x4ExtractLane(..) => Opcode::Simd(F32X4_EXTRACT_LANE),
+			F64x2ExtractLane(..) => Opcode::Simd(F64X2_EXTRACT_LANE),
+			I8x16ReplaceLane(..) => Opcode::Simd(I8X16_REPLACE_LANE),
+			I16x8ReplaceLane(..) => Opcode::Simd(I16X8_REPLACE_LANE),
+			I32x4ReplaceLane(..) => Opcode::Simd(I32X4_REPLACE_LANE),
+			I64x2ReplaceLane(..) => Opcode::Simd(I64X2_REPLACE_LANE),
+			F32x4ReplaceLane(..) => Opcode::Simd(F32X4_REPLACE_LANE),
+			F64x2ReplaceLane(..) => Opcode::Simd(F64X2_REPLACE_LANE),
+			V8x16Shuffle(..) => Opcode::Simd(V8X16_SHUFFLE),
+			I8x16Add => Opcode::Simd(I8X16_ADD),
+			I16x8Add => Opcode::Simd(I16X8_ADD),
+			I32x4Add => Opcode::Simd(I32X4_ADD),
+			I64x2Add => Opcode::Simd(I64X2_ADD),
+			I8x16Sub => Opcode::Simd(I8X16_SUB),
+			I16x8Sub => Opcode::Simd(I16X8_SUB),
+			I32x4Sub => Opcode::Simd(I32X4_SUB),
+			I64x2Sub => Opcode::Simd(I64X2_SUB),
+			I8x16Mul => Opcode::Simd(I8X16_MUL),
+			I16x8Mul => Opcode::Simd(I16X8_MUL),
+			I32x4Mul => Opcode::Simd(I32X4_MUL),
+			I8x16Neg => Opcode::Simd(I8X16_NEG),
+			I16x8Neg => Opcode::Simd(I16X8_NEG),
+			I32x4Neg => Opcode::Simd(I32X4_NEG),
+			I64x2Neg => Opcode::Simd(I64X2_NEG),
+			I8x16AddSaturateS => Opcode::Simd(I8X16_ADD_SATURATE_S),
+			I8x16AddSaturateU => Opcode::Simd(I8X16_ADD_SATURATE_U),
+			I16x8AddSaturateS => Opcode::Simd(I16X8_ADD_SATURATE_S),
+			I16x8AddSaturateU => Opcode::Simd(I16X8_ADD_SATURATE_U),
+			I8x16SubSaturateS => Opcode::Simd(I8X16_SUB_SATURATE_S),
+			I8x16SubSaturateU => Opcode::Simd(I8X16_SUB_SATURATE_U),
+			I16x8SubSaturateS => Opcode::Simd(I16X8_SUB_SATURATE_S),
+			I16x8SubSaturateU => Opcode::Simd(I16X8_SUB_SATURATE_U),
+			I8x16Shl => Opcode::Simd(I8X16_SHL),
+			I16x8Shl => Opcode::Simd(I16X8_SHL),
+			I32x4Shl => Opcode::Simd(I32X4_SHL),
+			I64x2Shl => Opcode::Simd(I64X2_SHL),
+			I8x16ShrS => Opcode::Simd(I8X16_SHR_S),
+			I8x16ShrU => Opcode::Simd(I8X16_SHR_U),
+			I16x8ShrS => Opcode::Simd(I16X8_SHR_S),
+			I16x8ShrU => Opcode::Simd(I16X8_SHR_U),
+			I32x4ShrU => Opcode::Simd(I32X4_SHR_U),
+			I32x4ShrS => Opcode::Simd(I32X4_SHR_S),
+			I64x2ShrU => Opcode::Simd(I64X2_SHR_U),
+			I64x2ShrS => Opcode::Simd(I64X2_SHR_S),
+			V128And => Opcode::Simd(V128_AND),
+			V128Or => Opcode::Simd(V128_OR),
+			V128Xor => Opcode::Simd(V128_XOR),
+			V128Not => Opcode::Simd(V128_NOT),
+			V128Bitselect => Opcode::Simd(V128_BITSELECT),
+			I8x16AnyTrue => Opcode::Simd(I8X16_ANY_TRUE),
+			I16x8AnyTrue => Opcode::Simd(I16X8_ANY_TRUE),
+			I32x4AnyTrue => Opcode::Simd(I32X4_ANY_TRUE),
+			I64x2AnyTrue => Opcode::Simd(I64X2_ANY_TRUE),
+			I8x16AllTrue => Opcode::Simd(I8X16_ALL_TRUE),
+			I16x8AllTrue => Opcode::Simd(I16X8_ALL_TRUE),
+			I32x4AllTrue => Opcode::Simd(I32X4_ALL_TRUE),
+			I64x2AllTrue => Opcode::Simd(I64X2_ALL_TRUE),
+			I8x16Eq => Opcode::Simd(I8X16_EQ),
+			I16x8Eq => Opcode::Simd(I16X8_EQ),
+			I32x4Eq => Opcode::Simd(I32X4_EQ),
+			F32x4Eq => Opcode::Simd(F32X4_EQ),
+			F64x2Eq => Opcode::Simd(F64X2_EQ),
+			I8x16Ne => Opcode::Simd(I8X16_NE),
+			I16x8Ne => Opcode::Simd(I16X8_NE),
+			I32x4Ne => Opcode::Simd(I32X4_NE),
+			F32x4Ne => Opcode::Simd(F32X4_NE),
+			F64x2Ne => Opcode::Simd(F64X2_NE),
+			I8x16LtS => Opcode::Simd(I8X16_LT_S),
+			I8x16LtU => Opcode::Simd(I8X16_LT_U),
+			I16x8LtS => Opcode::Simd(I16X8_LT_S),
+			I16x8LtU => Opcode::Simd(I16X8_LT_U),
+			I32x4LtS => Opcode::Simd(I32X4_LT_S),
+			I32x4LtU => Opcode::Simd(I32X4_LT_U),
+			F32x4Lt => Opcode::Simd(F32X4_LT),
+			F64x2Lt => Opcode::Simd(F64X2_LT),
+			I8x16LeS => Opcode::Simd(I8X16_LE_S),
+			I8x16LeU => Opcode::Simd(I8X16_LE_U),
+			I16x8LeS => Opcode::Simd(I16X8_LE_S),
+			I16x8LeU => Opcode::Simd(I16X8_LE_U),
+			I32x4LeS => Opcode::Simd(I32X4_LE_S),
+			I32x4LeU => Opcode::Simd(I32X4_LE_U),
+			F32x4Le => Opcode::Simd(F32X4_LE),
+			F64x2Le => Opcode::Simd(F64X2_LE),
+			I8x16GtS => Opcode::Simd(I8X16_GT_S),
+			I8x16GtU => Opcode::Simd(I8X16_GT_U),
+			I16x8GtS => Opcode::Simd(I16X8_GT_S),
+			I16x8GtU => Opcode::Simd(I16X8_GT_U),
+			I32x4GtS => Opcode::Simd(I32X4_GT_S),
+			I32x4GtU => Opcode::Simd(I32X4_GT_U),
+			F32x4Gt => Opcode::Simd(F32X4_GT),
+			F64x2Gt => Opcode::Simd(F64X2_GT),
+			I8x16GeS => Opcode::Simd(I8X16_GE_S),
+			I8x16GeU => Opcode::Simd(I8X16_GE_U),
+			I16x8GeS => Opcode::Simd(I16X8_GE_S),
+			I16x8GeU => Opcode::Simd(I16X8_GE_U),
+			I32x4GeS => Opcode::Simd(I32X4_GE_S),
+			I32x4GeU => Opcode::Simd(I32X4_GE_U),
+			F32x4Ge => Opcode::Simd(F32X4_GE),
+			F64x2Ge => Opcode::Simd(F64X2_GE),
+			F32x4Neg => Opcode::Simd(F32X4_NEG),
+			F64x2Neg => Opcode::Simd(F64X2_NEG),
+			F32x4Abs => Opcode::Simd(F32X4_ABS),
+			F64x2Abs => Opcode::Simd(F64X2_ABS),
+			F32x4Min => Opcode::Simd(F32X4_MIN),
+			F64x2Min => Opcode::Simd(F64X2_MIN),
+			F32x4Max => Opcode::Simd(F32X4_MAX),
+			F64x2Max => Opcode::Simd(F64X2_MAX),
+			F32x4Add => Opcode::Simd(F32X4_ADD),
+			F64x2Add => Opcode::Simd(F64X2_ADD),
+			F32x4Sub => Opcode::Simd(F32X4_SUB),
+			F64x2Sub => Opcode::Simd(F64X2_SUB),
+			F32x4Div => Opcode::Simd(F32X4_DIV),
+			F64x2Div => Opcode::Simd(F64X2_DIV),
+			F32x4Mul => Opcode::Simd(F32X4_MUL),
+			F64x2Mul => Opcode::Simd(F64X2_MUL),
+			F32x4Sqrt => Opcode::Simd(F32X4_SQRT),
+			F64x2Sqrt => Opcode::Simd(F64X2_SQRT),
+			F32x4ConvertSI32x4 => Opcode::Simd(F32X4_CONVERT_S_I32X4),
+			F32x4ConvertUI32x4 => Opcode::Simd(F32X4_CONVERT_U_I32X4),
+			F64x2ConvertSI64x2 => Opcode::Simd(F64X2_CONVERT_S_I64X2),
+			F64x2ConvertUI64x2 => Opcode::Simd(F64X2_CONVERT_U_I64X2),
+			I32x4TruncSF32x4Sat => Opcode::Simd(I32X4_TRUNC_S_F32X4_SAT),
+			I32x4TruncUF32x4Sat => Opcode::Simd(I32X4_TRUNC_U_F32X4_SAT),
+			I64x2TruncSF64x2Sat => Opcode::Simd(I64X2_TRUNC_S_F64X2_SAT),
+			I64x2TruncUF64x2Sat => Opcode::Simd(I64X2_TRUNC_U_F64X2_SAT),
+			MemoryInit(..) => Opcode::Bulk(MEMORY_INIT),
+			MemoryDrop(..) => Opcode::Bulk(MEMORY_DROP),
+			MemoryFill => Opcode::Bulk(MEMORY_FILL),
+			MemoryCopy => Opcode::Bulk(MEMORY_COPY),
+			TableInit(..) => Opcode::Bulk(TABLE_INIT),
+			TableDrop(..) => Opcode::Bulk(TABLE_DROP),
+			TableCopy(..) => Opcode::Bulk(TABLE_COPY),
+
+			RefNull(..) => Opcode::Plain(REF_NULL),
+			RefIsNull => Opcode::Plain(REF_IS_NULL),
+			RefFunc(..) => Opcode::Plain(REF_FUNC),
+			TableGet(..) => Opcode::Plain(TABLE_GET),
+			TableSet(..) => Opcode::Plain(TABLE_SET),
+			TableGrow(..) => Opcode::Bulk(TABLE_GROW),
+			TableSize(..) => Opcode::Bulk(TABLE_SIZE),
+			TableFill(..) => Opcode::Bulk(TABLE_FILL),
+		}
+	}
+}
+
+/// Opcode of an instruction, without its immediates.
+///
+/// Instructions using a prefix byte are identified by the prefix and the
+/// opcode following it, so that opcodes of different proposals never
+/// collide. Opcodes are ordered by encoding, prefixed ones last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Opcode {
+	/// Single byte opcode.
+	Plain(u8),
+	/// Opcode following `opcodes::ATOMIC_PREFIX`.
+	Atomic(u8),
+	/// Opcode following `opcodes::SIMD_PREFIX`.
+	Simd(u32),
+	/// Opcode following `opcodes::BULK_PREFIX`.
+	Bulk(u8),
+}
+
+impl Opcode {
+	/// Is this the opcode of some instruction?
+	pub fn is_known(&self) -> bool {
+		use self::opcodes::*;
+
+		match *self {
+			Opcode::Plain(opcode) => matches!(opcode,
+				UNREACHABLE | NOP | BLOCK | LOOP | IF | ELSE | END | BR | BRIF | BRTABLE | RETURN |
+				CALL | CALLINDIRECT | DROP | SELECT | SELECT_T | GETLOCAL | SETLOCAL | TEELOCAL | GETGLOBAL |
+				SETGLOBAL | I32LOAD | I64LOAD | F32LOAD | F64LOAD | I32LOAD8S | I32LOAD8U |
+				I32LOAD16S | I32LOAD16U | I64LOAD8S | I64LOAD8U | I64LOAD16S | I64LOAD16U |
+				I64LOAD32S | I64LOAD32U | I32STORE | I64STORE | F32STORE | F64STORE | I32STORE8 |
+				I32STORE16 | I64STORE8 | I64STORE16 | I64STORE32 | CURRENTMEMORY | GROWMEMORY |
+				I32CONST | I64CONST | F32CONST | F64CONST | I32EQZ | I32EQ | I32NE | I32LTS | I32LTU |
+				I32GTS | I32GTU | I32LES | I32LEU | I32GES | I32GEU | I64EQZ | I64EQ | I64NE |
+				I64LTS | I64LTU | I64GTS | I64GTU | I64LES | I64LEU | I64GES | I64GEU | F32EQ |
+				F32NE | F32LT | F32GT | F32LE | F32GE | F64EQ | F64NE | F64LT | F64GT | F64LE |
+				F64GE | I32CLZ | I32CTZ | I32POPCNT | I32ADD | I32SUB | I32MUL | I32DIVS | I32DIVU |
+				I32REMS | I32REMU | I32AND | I32OR | I32XOR | I32SHL | I32SHRS | I32SHRU | I32ROTL |
+				I32ROTR | I64CLZ | I64CTZ | I64POPCNT | I64ADD | I64SUB | I64MUL | I64DIVS | I64DIVU |
+				I64REMS | I64REMU | I64AND | I64OR | I64XOR | I64SHL | I64SHRS | I64SHRU | I64ROTL |
+				I64ROTR | F32ABS | F32NEG | F32CEIL | F32FLOOR | F32TRUNC | F32NEAREST | F32SQRT |
+				F32ADD | F32SUB | F32MUL | F32DIV | F32MIN | F32MAX | F32COPYSIGN | F64ABS | F64NEG |
+				F64CEIL | F64FLOOR | F64TRUNC | F64NEAREST | F64SQRT | F64ADD | F64SUB | F64MUL |
+				F64DIV | F64MIN | F64MAX | F64COPYSIGN | I32WRAPI64 | I32TRUNCSF32 | I32TRUNCUF32 |
+				I32TRUNCSF64 | I32TRUNCUF64 | I64EXTENDSI32 | I64EXTENDUI32 | I64TRUNCSF32 |
+				I64TRUNCUF32 | I64TRUNCSF64 | I64TRUNCUF64 | F32CONVERTSI32 | F32CONVERTUI32 |
+				F32CONVERTSI64 | F32CONVERTUI64 | F32DEMOTEF64 | F64CONVERTSI32 | F64CONVERTUI32 |
+				F64CONVERTSI64 | F64CONVERTUI64 | F64PROMOTEF32 | I32REINTERPRETF32 |
+				I64REINTERPRETF64 | F32REINTERPRETI32 | F64REINTERPRETI64 | I32_EXTEND8_S |
+				I32_EXTEND16_S | I64_EXTEND8_S | I64_EXTEND16_S | I64_EXTEND32_S | REF_NULL |
+				REF_IS_NULL | REF_FUNC | TABLE_GET | TABLE_SET),
+			Opcode::Atomic(opcode) => matches!(opcode,
+				ATOMIC_WAKE | I32_ATOMIC_WAIT | I64_ATOMIC_WAIT | I32_ATOMIC_LOAD | I64_ATOMIC_LOAD |
+				I32_ATOMIC_LOAD8U | I32_ATOMIC_LOAD16U | I64_ATOMIC_LOAD8U | I64_ATOMIC_LOAD16U |
+				I64_ATOMIC_LOAD32U | I32_ATOMIC_STORE | I64_ATOMIC_STORE | I32_ATOMIC_STORE8U |
+				I32_ATOMIC_STORE16U | I64_ATOMIC_STORE8U | I64_ATOMIC_STORE16U | I64_ATOMIC_STORE32U |
+				I32_ATOMIC_RMW_ADD | I64_ATOMIC_RMW_ADD | I32_ATOMIC_RMW_ADD8U |
+				I32_ATOMIC_RMW_ADD16U | I64_ATOMIC_RMW_ADD8U | I64_ATOMIC_RMW_ADD16U |
+				I64_ATOMIC_RMW_ADD32U | I32_ATOMIC_RMW_SUB | I64_ATOMIC_RMW_SUB |
+				I32_ATOMIC_RMW_SUB8U | I32_ATOMIC_RMW_SUB16U | I64_ATOMIC_RMW_SUB8U |
+				I64_ATOMIC_RMW_SUB16U | I64_ATOMIC_RMW_SUB32U | I32_ATOMIC_RMW_AND |
+				I64_ATOMIC_RMW_AND | I32_ATOMIC_RMW_AND8U | I32_ATOMIC_RMW_AND16U |
+				I64_ATOMIC_RMW_AND8U | I64_ATOMIC_RMW_AND16U | I64_ATOMIC_RMW_AND32U |
+				I32_ATOMIC_RMW_OR | I64_ATOMIC_RMW_OR | I32_ATOMIC_RMW_OR8U | I32_ATOMIC_RMW_OR16U |
+				I64_ATOMIC_RMW_OR8U | I64_ATOMIC_RMW_OR16U | I64_ATOMIC_RMW_OR32U |
+				I32_ATOMIC_RMW_XOR | I64_ATOMIC_RMW_XOR | I32_ATOMIC_RMW_XOR8U |
+				I32_ATOMIC_RMW_XOR16U | I64_ATOMIC_RMW_XOR8U | I64_ATOMIC_RMW_XOR16U |
+				I64_ATOMIC_RMW_XOR32U | I32_ATOMIC_RMW_XCHG | I64_ATOMIC_RMW_XCHG |
+				I32_ATOMIC_RMW_XCHG8U | I32_ATOMIC_RMW_XCHG16U | I64_ATOMIC_RMW_XCHG8U |
+				I64_ATOMIC_RMW_XCHG16U | I64_ATOMIC_RMW_XCHG32U | I32_ATOMIC_RMW_CMPXCHG |
+				I64_ATOMIC_RMW_CMPXCHG | I32_ATOMIC_RMW_CMPXCHG8U | I32_ATOMIC_RMW_CMPXCHG16U |
+				I64_ATOMIC_RMW_CMPXCHG8U | I64_ATOMIC_RMW_CMPXCHG16U | I64_ATOMIC_RMW_CMPXCHG32U),
+			Opcode::Simd(opcode) => matches!(opcode,
+				V128_CONST | V128_LOAD | V128_STORE | I8X16_SPLAT | I16X8_SPLAT | I32X4_SPLAT |
+				I64X2_SPLAT | F32X4_SPLAT | F64X2_SPLAT | I8X16_EXTRACT_LANE_S |
+				I8X16_EXTRACT_LANE_U | I16X8_EXTRACT_LANE_S | I16X8_EXTRACT_LANE_U |
+				I32X4_EXTRACT_LANE | I64X2_EXTRACT_LANE | F32X4_EXTRACT_LANE | F64X2_EXTRACT_LANE |
+				I8X16_REPLACE_LANE | I16X8_REPLACE_LANE | I32X4_REPLACE_LANE | I64X2_REPLACE_LANE |
+				F32X4_REPLACE_LANE | F64X2_REPLACE_LANE | V8X16_SHUFFLE | I8X16_ADD | I16X8_ADD |
+				I32X4_ADD | I64X2_ADD | I8X16_SUB | I16X8_SUB | I32X4_SUB | I64X2_SUB | I8X16_MUL |
+				I16X8_MUL | I32X4_MUL | I8X16_NEG | I16X8_NEG | I32X4_NEG | I64X2_NEG |
+				I8X16_ADD_SATURATE_S | I8X16_ADD_SATURATE_U | I16X8_ADD_SATURATE_S |
+				I16X8_ADD_SATURATE_U | I8X16_SUB_SATURATE_S | I8X16_SUB_SATURATE_U |
+				I16X8_SUB_SATURATE_S | I16X8_SUB_SATURATE_U | I8X16_SHL | I16X8_SHL | I32X4_SHL |
+				I64X2_SHL | I8X16_SHR_S | I8X16_SHR_U | I16X8_SHR_S | I16X8_SHR_U | I32X4_SHR_U |
+				I32X4_SHR_S | I64X2_SHR_U | I64X2_SHR_S | V128_AND | V128_OR | V128_XOR | V128_NOT |
+				V128_BITSELECT | I8X16_ANY_TRUE | I16X8_ANY_TRUE | I32X4_ANY_TRUE | I64X2_ANY_TRUE |
+				I8X16_ALL_TRUE | I16X8_ALL_TRUE | I32X4_ALL_TRUE | I64X2_ALL_TRUE | I8X16_EQ |
+				I16X8_EQ | I32X4_EQ | F32X4_EQ | F64X2_EQ | I8X16_NE | I16X8_NE | I32X4_NE |
+				F32X4_NE | F64X2_NE | I8X16_LT_S | I8X16_LT_U | I16X8_LT_S | I16X8_LT_U | I32X4_LT_S |
+				I32X4_LT_U | F32X4_LT | F64X2_LT | I8X16_LE_S | I8X16_LE_U | I16X8_LE_S | I16X8_LE_U |
+				I32X4_LE_S | I32X4_LE_U | F32X4_LE | F64X2_LE | I8X16_GT_S | I8X16_GT_U | I16X8_GT_S |
+				I16X8_GT_U | I32X4_GT_S | I32X4_GT_U | F32X4_GT | F64X2_GT | I8X16_GE_S | I8X16_GE_U |
+				I16X8_GE_S | I16X8_GE_U | I32X4_GE_S | I32X4_GE_U | F32X4_GE | F64X2_GE | F32X4_NEG |
+				F64X2_NEG | F32X4_ABS | F64X2_ABS | F32X4_MIN | F64X2_MIN | F32X4_MAX | F64X2_MAX |
+				F32X4_ADD | F64X2_ADD | F32X4_SUB | F64X2_SUB | F32X4_DIV | F64X2_DIV | F32X4_MUL |
+				F64X2_MUL | F32X4_SQRT | F64X2_SQRT | F32X4_CONVERT_S_I32X4 | F32X4_CONVERT_U_I32X4 |
+				F64X2_CONVERT_S_I64X2 | F64X2_CONVERT_U_I64X2 | I32X4_TRUNC_S_F32X4_SAT |
+				I32X4_TRUNC_U_F32X4_SAT | I64X2_TRUNC_S_F64X2_SAT | I64X2_TRUNC_U_F64X2_SAT),
+			Opcode::Bulk(opcode) => matches!(opcode,
+				MEMORY_INIT | MEMORY_DROP | MEMORY_FILL | MEMORY_COPY | TABLE_INIT | TABLE_DROP |
+				TABLE_COPY | TABLE_GROW | TABLE_SIZE | TABLE_FILL),
+		}
+	}
+}
+
+impl Deserialize for Opcode {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		use self::opcodes::*;
+
+		let val: u8 = Uint8::deserialize(reader)?.into();
+		let opcode = match val {
+			ATOMIC_PREFIX => Opcode::Atomic(Uint8::deserialize(reader)?.into()),
+			SIMD_PREFIX => Opcode::Simd(VarUint32::deserialize(reader)?.into()),
+			BULK_PREFIX => Opcode::Bulk(Uint8::deserialize(reader)?.into()),
+			_ => Opcode::Plain(val),
+		};
+		match opcode {
+			_ if opcode.is_known() => Ok(opcode),
+			Opcode::Simd(val) => Err(Error::UnknownSimdOpcode(val)),
+			Opcode::Plain(val) | Opcode::Atomic(val) | Opcode::Bulk(val) => Err(Error::UnknownOpcode(val)),
+		}
+	}
+}
+
+impl Serialize for Opcode {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
+		use self::opcodes::*;
+
+		match self {
+			Opcode::Plain(val) => writer.write(&[val])?,
+			Opcode::Atomic(val) => writer.write(&[ATOMIC_PREFIX, val])?,
+			Opcode::Simd(val) => {
+				writer.write(&[SIMD_PREFIX])?;
+				VarUint32::from(val).serialize(writer)?;
+			},
+			Opcode::Bulk(val) => writer.write(&[BULK_PREFIX, val])?,
+		}
+		Ok(())
+	}
+}
+
+impl<'a> TryFrom<&'a [u8]> for Opcode {
+	type Error = Error;
+
+	/// Reads the opcode at the start of `bytes`, ignoring the immediates
+	/// following it.
+	fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+		Opcode::deserialize(&mut io::Cursor::new(bytes))
+	}
 }
 
 #[allow(missing_docs)]
@@ -590,6 +1165,7 @@ pub mod opcodes {
 	pub const CALLINDIRECT: u8 = 0x11;
 	pub const DROP: u8 = 0x1a;
 	pub const SELECT: u8 = 0x1b;
+	pub const SELECT_T: u8 = 0x1c;
 	pub const GETLOCAL: u8 = 0x20;
 	pub const SETLOCAL: u8 = 0x21;
 	pub const TEELOCAL: u8 = 0x22;
@@ -1007,6 +1583,15 @@ pub mod opcodes {
 	pub const TABLE_INIT: u8 = 0x0c;
 	pub const TABLE_DROP: u8 = 0x0d;
 	pub const TABLE_COPY: u8 = 0x0e;
+	pub const TABLE_GROW: u8 = 0x0f;
+	pub const TABLE_SIZE: u8 = 0x10;
+	pub const TABLE_FILL: u8 = 0x11;
+
+	pub const TABLE_GET: u8 = 0x25;
+	pub const TABLE_SET: u8 = 0x26;
+	pub const REF_NULL: u8 = 0xd0;
+	pub const REF_IS_NULL: u8 = 0xd1;
+	pub const REF_FUNC: u8 = 0xd2;
 }
 
 impl Deserialize for Instruction {
@@ -1046,8 +1631,9 @@ impl Deserialize for Instruction {
 				CALL => Call(VarUint32::deserialize(reader)?.into()),
 				CALLINDIRECT => {
 					let signature: u32 = VarUint32::deserialize(reader)?.into();
-					let table_ref: u8 = Uint8::deserialize(reader)?.into();
-					if table_ref != 0 { return Err(Error::InvalidTableReference(table_ref)); }
+					// A single 0 byte before reference types, which allow
+					// other tables.
+					let table_ref: u32 = VarUint32::deserialize(reader)?.into();
 
 					CallIndirect(
 						signature,
@@ -1056,6 +1642,13 @@ impl Deserialize for Instruction {
 				},
 				DROP => Drop,
 				SELECT => Select,
+				SELECT_T => {
+					let value_types: Vec<ValueType> = CountedList::deserialize(reader)?.into_inner();
+					match value_types[..] {
+						[value_type] => TypedSelect(value_type),
+						_ => return Err(Error::Other("typed select must have a single result type")),
+					}
+				},
 
 				GETLOCAL => GetLocal(VarUint32::deserialize(reader)?.into()),
 				SETLOCAL => SetLocal(VarUint32::deserialize(reader)?.into()),
@@ -1307,6 +1900,12 @@ impl Deserialize for Instruction {
 				I64_EXTEND16_S => I64Extend16S,
 				I64_EXTEND32_S => I64Extend32S,
 
+				REF_NULL => RefNull(TableElementType::deserialize(reader)?),
+				REF_IS_NULL => RefIsNull,
+				REF_FUNC => RefFunc(VarUint32::deserialize(reader)?.into()),
+				TABLE_GET => TableGet(VarUint32::deserialize(reader)?.into()),
+				TABLE_SET => TableSet(VarUint32::deserialize(reader)?.into()),
+
 				ATOMIC_PREFIX => return deserialize_atomic(reader),
 				SIMD_PREFIX => return deserialize_simd(reader),
 
@@ -1573,44 +2172,51 @@ fn deserialize_bulk<R: io::Read>(reader: &mut R) -> Result<Instruction, Error> {
 
 	let val: u8 = Uint8::deserialize(reader)?.into();
 	Ok(match val {
+		// Memory immediates are only allowed to be 0 (the segment index
+		// coming first).
 		MEMORY_INIT => {
-			if u8::from(Uint8::deserialize(reader)?) != 0 {
-				return Err(Error::UnknownOpcode(val))
-			}
-			MemoryInit(VarUint32::deserialize(reader)?.into())
+			let segment = VarUint32::deserialize(reader)?.into();
+			reserved_zero(reader, val, 1)?;
+			MemoryInit(segment)
 		}
 		MEMORY_DROP => MemoryDrop(VarUint32::deserialize(reader)?.into()),
 		MEMORY_FILL => {
-			if u8::from(Uint8::deserialize(reader)?) != 0 {
-				return Err(Error::UnknownOpcode(val))
-			}
+			reserved_zero(reader, val, 1)?;
 			MemoryFill
 		}
 		MEMORY_COPY => {
-			if u8::from(Uint8::deserialize(reader)?) != 0 {
-				return Err(Error::UnknownOpcode(val))
-			}
+			reserved_zero(reader, val, 2)?;
 			MemoryCopy
 		}
 
 		TABLE_INIT => {
-			if u8::from(Uint8::deserialize(reader)?) != 0 {
-				return Err(Error::UnknownOpcode(val))
-			}
-			TableInit(VarUint32::deserialize(reader)?.into())
+			let segment = VarUint32::deserialize(reader)?.into();
+			TableInit(segment, VarUint32::deserialize(reader)?.into())
 		}
 		TABLE_DROP => TableDrop(VarUint32::deserialize(reader)?.into()),
 		TABLE_COPY => {
-			if u8::from(Uint8::deserialize(reader)?) != 0 {
-				return Err(Error::UnknownOpcode(val))
-			}
-			TableCopy
+			let destination = VarUint32::deserialize(reader)?.into();
+			TableCopy(destination, VarUint32::deserialize(reader)?.into())
 		}
+		TABLE_GROW => TableGrow(VarUint32::deserialize(reader)?.into()),
+		TABLE_SIZE => TableSize(VarUint32::deserialize(reader)?.into()),
+		TABLE_FILL => TableFill(VarUint32::deserialize(reader)?.into()),
 
 		_ => return Err(Error::UnknownOpcode(val)),
 	})
 }
 
+/// Reads `count` memory indices of the bulk instruction `val`, which have
+/// to be 0.
+fn reserved_zero<R: io::Read>(reader: &mut R, val: u8, count: usize) -> Result<(), Error> {
+	for _ in 0..count {
+		if u8::from(Uint8::deserialize(reader)?) != 0 {
+			return Err(Error::UnknownOpcode(val))
+		}
+	}
+	Ok(())
+}
+
 impl Deserialize for MemArg {
 	type Error = Error;
 
@@ -1696,12 +2302,15 @@ impl Serialize for Instruction {
 			Call(index) => op!(writer, CALL, {
 				VarUint32::from(index).serialize(writer)?;
 			}),
-			CallIndirect(index, reserved) => op!(writer, CALLINDIRECT, {
+			CallIndirect(index, table_ref) => op!(writer, CALLINDIRECT, {
 				VarUint32::from(index).serialize(writer)?;
-				Uint8::from(reserved).serialize(writer)?;
+				VarUint32::from(table_ref).serialize(writer)?;
 			}),
 			Drop => op!(writer, DROP),
 			Select => op!(writer, SELECT),
+			TypedSelect(value_type) => op!(writer, SELECT_T, {
+				CountedListWriter::<ValueType, _>(1, Some(value_type).into_iter()).serialize(writer)?;
+			}),
 			GetLocal(index) => op!(writer, GETLOCAL, {
 				VarUint32::from(index).serialize(writer)?;
 			}),
@@ -2192,18 +2801,38 @@ impl Serialize for Instruction {
 			I64x2TruncUF64x2Sat => simd!(writer, opcodes::I64X2_TRUNC_U_F64X2_SAT, ()),
 
 			MemoryInit(seg) => bulk!(writer, MEMORY_INIT, {
-				Uint8::from(0).serialize(writer)?;
 				VarUint32::from(seg).serialize(writer)?;
+				Uint8::from(0).serialize(writer)?;
 			}),
 			MemoryDrop(seg) => bulk!(writer, MEMORY_DROP, VarUint32::from(seg).serialize(writer)?),
 			MemoryFill => bulk!(writer, MEMORY_FILL, Uint8::from(0).serialize(writer)?),
-			MemoryCopy => bulk!(writer, MEMORY_COPY, Uint8::from(0).serialize(writer)?),
-			TableInit(seg) => bulk!(writer, TABLE_INIT, {
-				Uint8::from(0).serialize(writer)?;
+			MemoryCopy => bulk!(writer, MEMORY_COPY, writer.write(&[0, 0])?),
+			TableInit(seg, table) => bulk!(writer, TABLE_INIT, {
 				VarUint32::from(seg).serialize(writer)?;
+				VarUint32::from(table).serialize(writer)?;
 			}),
 			TableDrop(seg) => bulk!(writer, TABLE_DROP, VarUint32::from(seg).serialize(writer)?),
-			TableCopy => bulk!(writer, TABLE_COPY, Uint8::from(0).serialize(writer)?),
+			TableCopy(destination, source) => bulk!(writer, TABLE_COPY, {
+				VarUint32::from(destination).serialize(writer)?;
+				VarUint32::from(source).serialize(writer)?;
+			}),
+			TableGrow(table) => bulk!(writer, TABLE_GROW, VarUint32::from(table).serialize(writer)?),
+			TableSize(table) => bulk!(writer, TABLE_SIZE, VarUint32::from(table).serialize(writer)?),
+			TableFill(table) => bulk!(writer, TABLE_FILL, VarUint32::from(table).serialize(writer)?),
+
+			RefNull(elem_type) => op!(writer, REF_NULL, {
+				elem_type.serialize(writer)?;
+			}),
+			RefIsNull => op!(writer, REF_IS_NULL),
+			RefFunc(index) => op!(writer, REF_FUNC, {
+				VarUint32::from(index).serialize(writer)?;
+			}),
+			TableGet(table) => op!(writer, TABLE_GET, {
+				VarUint32::from(table).serialize(writer)?;
+			}),
+			TableSet(table) => op!(writer, TABLE_SET, {
+				VarUint32::from(table).serialize(writer)?;
+			}),
 		}
 
 		Ok(())
@@ -2246,6 +2875,9 @@ impl fmt::Display for Instruction {
 			Loop(BlockType::Value(value_type)) => fmt_op!(f, "loop", value_type),
 			If(BlockType::NoResult) => fmt_op!(f, "if"),
 			If(BlockType::Value(value_type)) => fmt_op!(f, "if", value_type),
+			Block(BlockType::TypeIndex(idx)) => write!(f, "block (type {})", idx),
+			Loop(BlockType::TypeIndex(idx)) => write!(f, "loop (type {})", idx),
+			If(BlockType::TypeIndex(idx)) => write!(f, "if (type {})", idx),
 			Else => fmt_op!(f, "else"),
 			End => fmt_op!(f, "end"),
 			Br(idx) => fmt_op!(f, "br",  idx),
@@ -2256,6 +2888,7 @@ impl fmt::Display for Instruction {
 			CallIndirect(index, _) =>  fmt_op!(f, "call_indirect", index),
 			Drop => fmt_op!(f, "drop"),
 			Select => fmt_op!(f, "select"),
+			TypedSelect(value_type) => fmt_op!(f, "select", value_type),
 			GetLocal(index) => fmt_op!(f, "get_local", index),
 			SetLocal(index) => fmt_op!(f, "set_local", index),
 			TeeLocal(index) => fmt_op!(f, "tee_local", index),
@@ -2713,9 +3346,18 @@ impl fmt::Display for Instruction {
 			MemoryDrop(_) => write!(f, "memory.drop"),
 			MemoryFill => write!(f, "memory.fill"),
 			MemoryCopy => write!(f, "memory.copy"),
-			TableInit(_) => write!(f, "table.init"),
+			TableInit(_, _) => write!(f, "table.init"),
 			TableDrop(_) => write!(f, "table.drop"),
-			TableCopy => write!(f, "table.copy"),
+			TableCopy(_, _) => write!(f, "table.copy"),
+
+			RefNull(elem_type) => fmt_op!(f, "ref.null", elem_type),
+			RefIsNull => fmt_op!(f, "ref.is_null"),
+			RefFunc(index) => fmt_op!(f, "ref.func", index),
+			TableGet(table) => fmt_op!(f, "table.get", table),
+			TableSet(table) => fmt_op!(f, "table.set", table),
+			TableGrow(table) => fmt_op!(f, "table.grow", table),
+			TableSize(table) => fmt_op!(f, "table.size", table),
+			TableFill(table) => fmt_op!(f, "table.fill", table),
 		}
 	}
 }
@@ -2776,7 +3418,157 @@ fn display() {
 	assert_eq!("i64.store", format!("{}", instruction));
 }
 
+#[test]
+fn bulk() {
+	let code = [
+		0xfc, 0x08, 0x01, 0x00, // memory.init 1
+		0xfc, 0x09, 0x01, // data.drop 1
+		0xfc, 0x0a, 0x00, 0x00, // memory.copy
+		0xfc, 0x0b, 0x00, // memory.fill
+		0xfc, 0x0c, 0x02, 0x00, // table.init 2
+		0xfc, 0x0d, 0x02, // elem.drop 2
+		0xfc, 0x0e, 0x00, 0x00, // table.copy
+		0xfc, 0x0c, 0x02, 0x01, // table.init 1 2
+		0xfc, 0x0e, 0x01, 0x02, // table.copy 1 2
+		0x0b,
+	];
+	let instructions = super::deserialize_buffer::<Instructions>(&code).expect("bulk instructions to be deserialized");
+	assert_eq!(instructions.elements(), &[
+		Instruction::MemoryInit(1),
+		Instruction::MemoryDrop(1),
+		Instruction::MemoryCopy,
+		Instruction::MemoryFill,
+		Instruction::TableInit(2, 0),
+		Instruction::TableDrop(2),
+		Instruction::TableCopy(0, 0),
+		Instruction::TableInit(2, 1),
+		Instruction::TableCopy(1, 2),
+		Instruction::End,
+	]);
+	assert_eq!(super::serialize(instructions).expect("bulk instructions to be serialized"), &code[..]);
+	assert!(super::deserialize_buffer::<Instructions>(&[0xfc, 0x0a, 0x00, 0x01, 0x0b]).is_err());
+}
+
+#[test]
+fn reference_types() {
+	let code = [
+		0xd0, 0x6f, // ref.null extern
+		0xd1, // ref.is_null
+		0xd2, 0x03, // ref.func 3
+		0x25, 0x01, // table.get 1
+		0x26, 0x01, // table.set 1
+		0xfc, 0x0f, 0x00, // table.grow 0
+		0xfc, 0x10, 0x02, // table.size 2
+		0xfc, 0x11, 0x00, // table.fill 0
+		0x1c, 0x01, 0x70, // select (result funcref)
+		0x11, 0x00, 0x01, // call_indirect 1 (type 0)
+		0x11, 0x00, 0xac, 0x02, // call_indirect 300 (type 0)
+		0x0b,
+	];
+	let instructions = super::deserialize_buffer::<Instructions>(&code).expect("reference instructions to be deserialized");
+	assert_eq!(instructions.elements(), &[
+		Instruction::RefNull(TableElementType::ExternRef),
+		Instruction::RefIsNull,
+		Instruction::RefFunc(3),
+		Instruction::TableGet(1),
+		Instruction::TableSet(1),
+		Instruction::TableGrow(0),
+		Instruction::TableSize(2),
+		Instruction::TableFill(0),
+		Instruction::TypedSelect(super::ValueType::FuncRef),
+		Instruction::CallIndirect(0, 1),
+		Instruction::CallIndirect(0, 300),
+		Instruction::End,
+	]);
+	assert_eq!(instructions.elements()[0].to_string(), "ref.null externref");
+	assert_eq!(super::serialize(instructions).expect("reference instructions to be serialized"), &code[..]);
+	assert!(super::deserialize_buffer::<Instructions>(&[0x1c, 0x02, 0x7f, 0x7f, 0x0b]).is_err());
+}
+
+#[test]
+fn simd() {
+	let code = [
+		0xfd, 0x02, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+		0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, // v128.const
+		0xfd, 0x03, 0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08,
+		0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x00, // v8x16.shuffle
+		0xfd, 0x05, 0x0f, // i8x16.extract_lane_s 15
+		0xfd, 0x0e, 0x03, // i32x4.replace_lane 3
+		0xfd, 0x84, 0x01, // i64x2.neg
+		0xfd, 0x00, 0x04, 0x10, // v128.load align=16 offset=16
+		0x0b,
+	];
+	let instructions = super::deserialize_buffer::<Instructions>(&code).expect("simd instructions to be deserialized");
+	assert_eq!(instructions.elements(), &[
+		Instruction::V128Const(Box::new([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15])),
+		Instruction::V8x16Shuffle(Box::new([15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0])),
+		Instruction::I8x16ExtractLaneS(15),
+		Instruction::I32x4ReplaceLane(3),
+		Instruction::I64x2Neg,
+		Instruction::V128Load(MemArg { align: 4, offset: 16 }),
+		Instruction::End,
+	]);
+	assert_eq!(super::serialize(instructions).expect("simd instructions to be serialized"), &code[..]);
+	assert!(super::deserialize_buffer::<Instructions>(&[0xfd, 0x80, 0x10, 0x0b]).is_err());
+}
+
+#[test]
+fn block_types() {
+	use super::BlockType;
+
+	let code = [
+		0x02, 0x40, // block
+		0x03, 0x7b, // loop (result v128)
+		0x04, 0x85, 0x01, // if (type 133)
+		0x0b, 0x0b, 0x0b, 0x0b,
+	];
+	let instructions = super::deserialize_buffer::<Instructions>(&code).expect("blocks to be deserialized");
+	assert_eq!(&instructions.elements()[..3], &[
+		Instruction::Block(BlockType::NoResult),
+		Instruction::Loop(BlockType::Value(super::ValueType::V128)),
+		Instruction::If(BlockType::TypeIndex(133)),
+	]);
+	assert_eq!(super::serialize(instructions).expect("blocks to be serialized"), &code[..]);
+}
+
 #[test]
 fn size_off() {
 	assert!(::std::mem::size_of::<Instruction>() <= 24);
 }
+
+#[test]
+fn opcodes() {
+	use std::convert::TryFrom;
+	use super::{BlockType, Opcode};
+
+	let instructions = [
+		Instruction::Block(BlockType::NoResult),
+		Instruction::BrTable(Box::new(BrTableData { table: Box::new([0, 1]), default: 2 })),
+		Instruction::I32Load(2, 8),
+		Instruction::TypedSelect(ValueType::ExternRef),
+		Instruction::I32AtomicRmwAdd(MemArg { align: 2, offset: 0 }),
+		Instruction::I32x4Add,
+		Instruction::V128Load(MemArg { align: 4, offset: 16 }),
+		Instruction::MemoryInit(1),
+		Instruction::End,
+	];
+	for instruction in instructions.iter() {
+		let bytes = super::serialize(instruction.clone()).expect("instruction to be serialized");
+		let opcode = Opcode::try_from(&bytes[..]).expect("opcode to be read");
+		assert_eq!(opcode, instruction.opcode());
+		let encoded = super::serialize(opcode).expect("opcode to be serialized");
+		assert_eq!(&bytes[..encoded.len()], &encoded[..]);
+		assert!(opcode.is_known());
+	}
+	assert_eq!(Instruction::I32x4Add.opcode(), Opcode::Simd(opcodes::I32X4_ADD));
+
+	match Opcode::try_from(&[0xfd, 0x80, 0x10][..]) {
+		Err(Error::UnknownSimdOpcode(0x800)) => {},
+		other => panic!("unexpected {:?}", other),
+	}
+	match Opcode::try_from(&[0x06][..]) {
+		Err(Error::UnknownOpcode(0x06)) => {},
+		other => panic!("unexpected {:?}", other),
+	}
+	assert!(Opcode::try_from(&[0xfc][..]).is_err());
+}
@@ -0,0 +1,174 @@
+use io;
+use std::vec::Vec;
+use std::string::String;
+
+use super::{CustomSection, Deserialize, Error, IndexMap, Module, Section, Serialize, VarUint32, VarUint64};
+
+/// Name of the custom section holding the profile.
+pub const PROFILE_SECTION_NAME: &str = "profile";
+
+/// Per-function execution counters collected by a profiling run.
+///
+/// Stored in a custom section named `profile`, whose payload is a map from
+/// function index (in the function index space, imports included) to a
+/// counter, encoded like the function names map of the name section:
+/// a `varuint32` count followed by `(varuint32 index, varuint64 counter)`
+/// pairs sorted by index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProfileSection {
+	counters: IndexMap<u64>,
+}
+
+impl ProfileSection {
+	/// New empty profile.
+	pub fn new() -> ProfileSection {
+		ProfileSection::default()
+	}
+
+	/// Counters by function index.
+	pub fn counters(&self) -> &IndexMap<u64> {
+		&self.counters
+	}
+
+	/// Counters by function index (mutable).
+	pub fn counters_mut(&mut self) -> &mut IndexMap<u64> {
+		&mut self.counters
+	}
+
+	/// Counter of the function `func_idx`, `0` if it is not recorded.
+	pub fn counter(&self, func_idx: u32) -> u64 {
+		self.counters.get(func_idx).cloned().unwrap_or(0)
+	}
+
+	/// Adds `amount` to the counter of the function `func_idx`.
+	pub fn record(&mut self, func_idx: u32, amount: u64) {
+		let counter = self.counter(func_idx).saturating_add(amount);
+		self.counters.insert(func_idx, counter);
+	}
+
+	/// Function indices sorted from the hottest to the coldest function.
+	///
+	/// Functions with equal counters are ordered by index.
+	pub fn hottest(&self) -> Vec<u32> {
+		let mut entries: Vec<(u32, u64)> = self.counters.iter().map(|(idx, &count)| (idx, count)).collect();
+		entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+		entries.into_iter().map(|(idx, _)| idx).collect()
+	}
+
+	/// Deserialize the payload of a profile section of `module`.
+	///
+	/// Indices must be below the size of the function index space of `module`.
+	pub fn deserialize<R: io::Read>(module: &Module, rdr: &mut R) -> Result<ProfileSection, Error> {
+		let deserialize_value = |_idx, rdr: &mut R| -> Result<u64, Error> {
+			Ok(VarUint64::deserialize(rdr)?.into())
+		};
+		let counters = IndexMap::deserialize_with(module.functions_space(), &deserialize_value, rdr)?;
+		Ok(ProfileSection { counters })
+	}
+
+	/// Parses a profile out of the custom section `section` of `module`.
+	pub fn from_custom_section(module: &Module, section: &CustomSection) -> Result<ProfileSection, Error> {
+		if section.name() != PROFILE_SECTION_NAME {
+			return Err(Error::Other("custom section is not a profile section"));
+		}
+		let mut rdr = io::Cursor::new(section.payload());
+		let profile = ProfileSection::deserialize(module, &mut rdr)?;
+		if rdr.position() != section.payload().len() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(profile)
+	}
+
+	/// Converts the profile into a custom section.
+	pub fn into_custom_section(self) -> Result<CustomSection, Error> {
+		let mut payload = Vec::new();
+		self.serialize(&mut payload)?;
+		Ok(CustomSection::new(String::from(PROFILE_SECTION_NAME), payload))
+	}
+}
+
+impl Serialize for ProfileSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		VarUint32::from(self.counters.len()).serialize(wtr)?;
+		for (idx, counter) in self.counters {
+			VarUint32::from(idx).serialize(wtr)?;
+			VarUint64::from(counter).serialize(wtr)?;
+		}
+		Ok(())
+	}
+}
+
+impl Module {
+	/// Profile stored in the `profile` custom section, if any.
+	pub fn profile(&self) -> Option<Result<ProfileSection, Error>> {
+		self.sections().iter()
+			.filter_map(|section| match *section {
+				Section::Custom(ref custom) if custom.name() == PROFILE_SECTION_NAME => Some(custom),
+				_ => None,
+			})
+			.next()
+			.map(|custom| ProfileSection::from_custom_section(self, custom))
+	}
+
+	/// Stores `profile` in the `profile` custom section, replacing the
+	/// existing one or appending a new section at the end of the module.
+	pub fn set_profile(&mut self, profile: ProfileSection) -> Result<(), Error> {
+		let custom = profile.into_custom_section()?;
+		let existing = self.sections_mut().iter_mut().find(|section| match **section {
+			Section::Custom(ref custom) => custom.name() == PROFILE_SECTION_NAME,
+			_ => false,
+		});
+		match existing {
+			Some(section) => *section = Section::Custom(custom),
+			None => self.sections_mut().push(Section::Custom(custom)),
+		}
+		Ok(())
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::ProfileSection;
+	use builder::module;
+	use elements::{serialize, deserialize_buffer, Module};
+
+	fn sample() -> Module {
+		module()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.build()
+	}
+
+	#[test]
+	fn roundtrip() {
+		let mut profile = ProfileSection::new();
+		profile.record(0, 10);
+		profile.record(2, 300);
+		profile.record(0, 5);
+
+		let mut module = sample();
+		assert!(module.profile().is_none());
+		module.set_profile(profile.clone()).expect("profile to serialize");
+
+		let module: Module = deserialize_buffer(&serialize(module).expect("module to serialize"))
+			.expect("module to deserialize");
+		let read = module.profile().expect("profile to exist").expect("profile to parse");
+		assert_eq!(read, profile);
+		assert_eq!(read.counter(0), 15);
+		assert_eq!(read.counter(1), 0);
+		assert_eq!(read.hottest(), vec![2, 0]);
+	}
+
+	#[test]
+	fn out_of_range() {
+		let mut profile = ProfileSection::new();
+		profile.record(3, 1);
+
+		let mut module = sample();
+		module.set_profile(profile).expect("profile to serialize");
+		assert!(module.profile().expect("profile to exist").is_err());
+	}
+}
@@ -0,0 +1,263 @@
+use io::{self, Read};
+use std::vec::Vec;
+use std::string::String;
+
+use super::{Deserialize, Error, FuncBody, Section, Uint8, Uint32, VarUint32};
+use super::module::WASM_MAGIC_NUMBER;
+
+const CODE_SECTION_ID: u8 = 0x0a;
+
+/// Reader of a serialized module which only decodes what is asked for.
+///
+/// Creating the reader checks the header; iterating over the sections only
+/// reads their headers, the payload of each section being decoded on demand
+/// with `SectionHeader::decode` (or `SectionHeader::code` for the code
+/// section, whose function bodies are then decoded one at a time).
+///
+/// ```
+/// use parity_wasm::builder::module;
+/// use parity_wasm::elements::{serialize, ModuleReader, Section};
+///
+/// let bytes = serialize(module()
+///     .function().signature().build().body().build().build()
+///     .export().field("main").internal().func(0).build()
+///     .build()).unwrap();
+///
+/// let reader = ModuleReader::new(&bytes).unwrap();
+/// let exports = reader.section(7).unwrap().expect("export section");
+/// match exports.decode().unwrap() {
+///     Section::Export(exports) => assert_eq!(exports.entries()[0].field(), "main"),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ModuleReader<'a> {
+	bytes: &'a [u8],
+}
+
+/// Header of a section, giving access to its payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SectionHeader<'a> {
+	id: u8,
+	offset: usize,
+	section: &'a [u8],
+	payload_start: usize,
+}
+
+/// Iterator over the section headers of a module.
+pub struct SectionHeaders<'a> {
+	bytes: &'a [u8],
+	offset: usize,
+}
+
+impl<'a> ModuleReader<'a> {
+	/// Reader of the module `bytes`, checking the magic number and version.
+	pub fn new(bytes: &'a [u8]) -> Result<ModuleReader<'a>, Error> {
+		let mut rdr = io::Cursor::new(bytes);
+		let mut magic = [0u8; 4];
+		rdr.read(&mut magic)?;
+		if magic != WASM_MAGIC_NUMBER {
+			return Err(Error::InvalidMagic);
+		}
+		let version: u32 = Uint32::deserialize(&mut rdr)?.into();
+		if version != 1 {
+			return Err(Error::UnsupportedVersion(version));
+		}
+		Ok(ModuleReader { bytes })
+	}
+
+	/// Headers of the sections, in order.
+	pub fn sections(&self) -> SectionHeaders<'a> {
+		SectionHeaders { bytes: self.bytes, offset: 8 }
+	}
+
+	/// First section with the given id (`0` being custom sections).
+	pub fn section(&self, id: u8) -> Result<Option<SectionHeader<'a>>, Error> {
+		for header in self.sections() {
+			let header = header?;
+			if header.id() == id {
+				return Ok(Some(header));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Custom section with the given name.
+	pub fn custom_section(&self, name: &str) -> Result<Option<SectionHeader<'a>>, Error> {
+		for header in self.sections() {
+			let header = header?;
+			if header.id() == 0 && header.custom_name()? == name {
+				return Ok(Some(header));
+			}
+		}
+		Ok(None)
+	}
+}
+
+impl<'a> Iterator for SectionHeaders<'a> {
+	type Item = Result<SectionHeader<'a>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.offset >= self.bytes.len() {
+			return None;
+		}
+		let offset = self.offset;
+		let header = (|| {
+			let mut rdr = io::Cursor::new(&self.bytes[offset..]);
+			let id: u8 = Uint8::deserialize(&mut rdr)?.into();
+			let size: u32 = VarUint32::deserialize(&mut rdr)?.into();
+			let payload_start = rdr.position();
+			let end = (offset + payload_start).checked_add(size as usize)
+				.filter(|&end| end <= self.bytes.len())
+				.ok_or(Error::UnexpectedEof)?;
+			Ok(SectionHeader { id, offset, section: &self.bytes[offset..end], payload_start })
+		})();
+		match header {
+			Ok(ref header) => self.offset = header.offset + header.section.len(),
+			// Stop after an error, the following sections can't be located.
+			Err(_) => self.offset = self.bytes.len(),
+		}
+		Some(header)
+	}
+}
+
+impl<'a> SectionHeader<'a> {
+	/// Id of the section (`0` for custom sections).
+	pub fn id(&self) -> u8 { self.id }
+
+	/// Offset of the section from the beginning of the module.
+	pub fn offset(&self) -> usize { self.offset }
+
+	/// Offset of the payload from the beginning of the module.
+	pub fn payload_offset(&self) -> usize { self.offset + self.payload_start }
+
+	/// Payload of the section, without the id and size.
+	pub fn payload(&self) -> &'a [u8] { &self.section[self.payload_start..] }
+
+	/// Name of a custom section.
+	pub fn custom_name(&self) -> Result<String, Error> {
+		if self.id != 0 {
+			return Err(Error::Other("not a custom section"));
+		}
+		String::deserialize(&mut io::Cursor::new(self.payload()))
+	}
+
+	/// Decodes the section.
+	pub fn decode(&self) -> Result<Section, Error> {
+		let mut rdr = io::Cursor::new(self.section);
+		let section = Section::deserialize(&mut rdr)?;
+		if rdr.position() != self.section.len() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(section)
+	}
+
+	/// Code section whose function bodies are decoded on demand.
+	pub fn code(&self) -> Result<LazyCodeSection<'a>, Error> {
+		if self.id != CODE_SECTION_ID {
+			return Err(Error::Other("not a code section"));
+		}
+		LazyCodeSection::new(self.payload())
+	}
+}
+
+/// Code section whose function bodies are only decoded when accessed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LazyCodeSection<'a> {
+	/// Function bodies, with their size prefix.
+	bodies: Vec<&'a [u8]>,
+}
+
+impl<'a> LazyCodeSection<'a> {
+	/// Locates the function bodies in the code section `payload`.
+	pub fn new(payload: &'a [u8]) -> Result<LazyCodeSection<'a>, Error> {
+		let mut rdr = io::Cursor::new(payload);
+		let count: u32 = VarUint32::deserialize(&mut rdr)?.into();
+		let mut position = rdr.position();
+		let mut bodies = Vec::new();
+		for _ in 0..count {
+			let mut rdr = io::Cursor::new(&payload[position..]);
+			let size: u32 = VarUint32::deserialize(&mut rdr)?.into();
+			let end = (position + rdr.position()).checked_add(size as usize)
+				.filter(|&end| end <= payload.len())
+				.ok_or(Error::UnexpectedEof)?;
+			bodies.push(&payload[position..end]);
+			position = end;
+		}
+		if position != payload.len() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(LazyCodeSection { bodies })
+	}
+
+	/// Number of function bodies.
+	pub fn len(&self) -> usize {
+		self.bodies.len()
+	}
+
+	/// Whether there are no function bodies.
+	pub fn is_empty(&self) -> bool {
+		self.bodies.is_empty()
+	}
+
+	/// Encoded body `index`, with its size prefix.
+	pub fn raw_body(&self, index: usize) -> Option<&'a [u8]> {
+		self.bodies.get(index).cloned()
+	}
+
+	/// Decodes the body `index`.
+	pub fn body(&self, index: usize) -> Result<FuncBody, Error> {
+		let raw = self.raw_body(index).ok_or(Error::Other("function body index out of range"))?;
+		FuncBody::deserialize(&mut io::Cursor::new(raw))
+	}
+
+	/// Decodes the bodies one at a time.
+	pub fn bodies(&self) -> impl Iterator<Item = Result<FuncBody, Error>> + '_ {
+		(0..self.bodies.len()).map(move |index| self.body(index))
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::ModuleReader;
+	use builder::module;
+	use elements::{serialize, Instructions, Section, CustomSection, Error};
+	use elements::Instruction::*;
+
+	#[test]
+	fn lazy() {
+		let module = module()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![I32Const(1), Drop, End])).build()
+				.build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(1).build()
+			.with_section(Section::Custom(CustomSection::new("meta".into(), vec![1, 2])))
+			.build();
+		let bytes = serialize(module.clone()).expect("module to serialize");
+
+		let reader = ModuleReader::new(&bytes).expect("header to be valid");
+		let ids: Vec<u8> = reader.sections().map(|h| h.expect("header to be valid").id()).collect();
+		assert_eq!(ids, vec![1, 3, 7, 10, 0]);
+
+		let code = reader.section(10).expect("sections to be valid").expect("code section").code()
+			.expect("code section to be valid");
+		assert_eq!(code.len(), 2);
+		assert_eq!(code.body(0).expect("body to decode"), module.code_section().expect("code").bodies()[0]);
+		assert_eq!(code.bodies().count(), 2);
+
+		let meta = reader.custom_section("meta").expect("sections to be valid").expect("custom section");
+		match meta.decode().expect("section to decode") {
+			Section::Custom(custom) => assert_eq!(custom.payload(), &[1, 2]),
+			other => panic!("unexpected section {:?}", other),
+		}
+
+		// Sections before a truncated one are still readable.
+		let truncated = &bytes[..bytes.len() - 1];
+		let headers: Vec<_> = ModuleReader::new(truncated).expect("header to be valid").sections().collect();
+		assert_eq!(headers.len(), 5);
+		assert!(headers[3].is_ok());
+		assert!(match headers[4] { Err(Error::UnexpectedEof) => true, _ => false });
+		assert!(ModuleReader::new(&bytes[..6]).is_err());
+	}
+}
@@ -2,7 +2,7 @@ use io;
 use std::vec::Vec;
 use std::string::String;
 
-use super::{CountedList, CountedListWriter, CountedWriter, Deserialize, Error, Serialize, VarInt32, VarUint32, VarUint7};
+use super::{Module, Section, CountedList, CountedListWriter, CountedWriter, Deserialize, Error, Serialize, VarInt32, VarUint32, VarUint7};
 
 const FUNCTION_INDEX_LEB: u8 = 0;
 const TABLE_INDEX_SLEB: u8 = 1;
@@ -12,6 +12,12 @@ const MEMORY_ADDR_SLEB: u8 = 4;
 const MEMORY_ADDR_I32: u8 = 5;
 const TYPE_INDEX_LEB: u8 = 6;
 const GLOBAL_INDEX_LEB: u8 = 7;
+const FUNCTION_OFFSET_I32: u8 = 8;
+const SECTION_OFFSET_I32: u8 = 9;
+const EVENT_INDEX_LEB: u8 = 10;
+const MEMORY_ADDR_REL_SLEB: u8 = 11;
+const TABLE_INDEX_REL_SLEB: u8 = 12;
+const GLOBAL_INDEX_I32: u8 = 13;
 
 /// Relocation information.
 #[derive(Clone, Debug, PartialEq)]
@@ -98,6 +104,30 @@ impl RelocSection {
 	}
 }
 
+impl Module {
+	/// Relocation sections, whether they were parsed or not, in order.
+	///
+	/// Custom sections whose name starts with `reloc.` are decoded; see
+	/// `parse_reloc` to replace them by `Section::Reloc` in place.
+	pub fn relocations(&self) -> Result<Vec<RelocSection>, Error> {
+		let mut relocations = Vec::new();
+		for section in self.sections() {
+			match *section {
+				Section::Reloc(ref reloc_section) => relocations.push(reloc_section.clone()),
+				Section::Custom(ref custom) if custom.name().starts_with("reloc.") => {
+					let mut rdr = io::Cursor::new(custom.payload());
+					relocations.push(RelocSection::deserialize(String::from(custom.name()), &mut rdr)?);
+					if rdr.position() != custom.payload().len() {
+						return Err(io::Error::InvalidData.into());
+					}
+				},
+				_ => {},
+			}
+		}
+		Ok(relocations)
+	}
+}
+
 impl Serialize for RelocSection {
 	type Error = Error;
 
@@ -204,6 +234,69 @@ pub enum RelocationEntry {
 		/// Index of the global symbol in the symbol table.
 		index: u32,
 	},
+
+	/// Offset of a function body in the code section.
+	FunctionOffsetI32 {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the function symbol in the symbol table.
+		index: u32,
+
+		/// Addend to add to the value.
+		addend: i32,
+	},
+
+	/// Offset in a section.
+	SectionOffsetI32 {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the section symbol in the symbol table.
+		index: u32,
+
+		/// Addend to add to the value.
+		addend: i32,
+	},
+
+	/// Event index.
+	EventIndexLeb {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the event symbol in the symbol table.
+		index: u32,
+	},
+
+	/// Linear memory index, relative to `__memory_base`.
+	MemoryAddressRelSleb {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the data symbol in the symbol table.
+		index: u32,
+
+		/// Addend to add to the value.
+		addend: i32,
+	},
+
+	/// Function table index, relative to `__table_base`.
+	TableIndexRelSleb {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the function symbol in the symbol table.
+		index: u32,
+	},
+
+	/// Global index.
+	GlobalIndexI32 {
+		/// Offset of the value to rewrite.
+		offset: u32,
+
+		/// Index of the global symbol in the symbol table.
+		index: u32,
+	},
 }
 
 impl Deserialize for RelocationEntry {
@@ -254,6 +347,39 @@ impl Deserialize for RelocationEntry {
 				index: VarUint32::deserialize(rdr)?.into(),
 			}),
 
+			FUNCTION_OFFSET_I32 => Ok(RelocationEntry::FunctionOffsetI32 {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+				addend: VarInt32::deserialize(rdr)?.into(),
+			}),
+
+			SECTION_OFFSET_I32 => Ok(RelocationEntry::SectionOffsetI32 {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+				addend: VarInt32::deserialize(rdr)?.into(),
+			}),
+
+			EVENT_INDEX_LEB => Ok(RelocationEntry::EventIndexLeb {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+			}),
+
+			MEMORY_ADDR_REL_SLEB => Ok(RelocationEntry::MemoryAddressRelSleb {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+				addend: VarInt32::deserialize(rdr)?.into(),
+			}),
+
+			TABLE_INDEX_REL_SLEB => Ok(RelocationEntry::TableIndexRelSleb {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+			}),
+
+			GLOBAL_INDEX_I32 => Ok(RelocationEntry::GlobalIndexI32 {
+				offset: VarUint32::deserialize(rdr)?.into(),
+				index: VarUint32::deserialize(rdr)?.into(),
+			}),
+
 			entry_type => Err(Error::UnknownValueType(entry_type as i8)),
 		}
 	}
@@ -314,6 +440,45 @@ impl Serialize for RelocationEntry {
 				VarUint32::from(offset).serialize(wtr)?;
 				VarUint32::from(index).serialize(wtr)?;
 			},
+
+			RelocationEntry::FunctionOffsetI32 { offset, index, addend } => {
+				VarUint7::from(FUNCTION_OFFSET_I32).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+				VarInt32::from(addend).serialize(wtr)?;
+			},
+
+			RelocationEntry::SectionOffsetI32 { offset, index, addend } => {
+				VarUint7::from(SECTION_OFFSET_I32).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+				VarInt32::from(addend).serialize(wtr)?;
+			},
+
+			RelocationEntry::EventIndexLeb { offset, index } => {
+				VarUint7::from(EVENT_INDEX_LEB).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+			},
+
+			RelocationEntry::MemoryAddressRelSleb { offset, index, addend } => {
+				VarUint7::from(MEMORY_ADDR_REL_SLEB).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+				VarInt32::from(addend).serialize(wtr)?;
+			},
+
+			RelocationEntry::TableIndexRelSleb { offset, index } => {
+				VarUint7::from(TABLE_INDEX_REL_SLEB).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+			},
+
+			RelocationEntry::GlobalIndexI32 { offset, index } => {
+				VarUint7::from(GLOBAL_INDEX_I32).serialize(wtr)?;
+				VarUint32::from(offset).serialize(wtr)?;
+				VarUint32::from(index).serialize(wtr)?;
+			},
 		}
 
 		Ok(())
@@ -322,7 +487,7 @@ impl Serialize for RelocationEntry {
 
 #[cfg(test)]
 mod tests {
-	use super::super::{Section, deserialize_file};
+	use super::super::{Section, deserialize_file, deserialize_buffer, serialize};
 	use super::RelocationEntry;
 
 	#[test]
@@ -345,4 +510,29 @@ mod tests {
 		}
 		assert!(found, "There should be a reloc section in relocatable.wasm");
 	}
+
+	#[test]
+	fn relocations() {
+		let module = deserialize_file("./res/cases/v1/relocatable.wasm").expect("Module should be deserialized");
+		let relocations = module.relocations().expect("Reloc sections should be deserialized");
+		assert_eq!(relocations.len(), 1);
+		assert_eq!(relocations[0].name(), "reloc.CODE");
+		assert_eq!(relocations, module.parse_reloc().expect("Reloc section should be deserialized").relocations().expect("Reloc sections should be parsed"));
+	}
+
+	#[test]
+	fn roundtrip_entries() {
+		let entries = vec![
+			RelocationEntry::FunctionOffsetI32 { offset: 1, index: 2, addend: -3 },
+			RelocationEntry::SectionOffsetI32 { offset: 4, index: 5, addend: 6 },
+			RelocationEntry::EventIndexLeb { offset: 7, index: 8 },
+			RelocationEntry::MemoryAddressRelSleb { offset: 9, index: 10, addend: 11 },
+			RelocationEntry::TableIndexRelSleb { offset: 12, index: 13 },
+			RelocationEntry::GlobalIndexI32 { offset: 14, index: 15 },
+		];
+		for entry in entries {
+			let bytes = serialize(entry).expect("Entry should serialize");
+			assert_eq!(deserialize_buffer::<RelocationEntry>(&bytes).expect("Entry should deserialize"), entry);
+		}
+	}
 }
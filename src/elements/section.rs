@@ -64,6 +64,9 @@ pub enum Section {
 	Code(CodeSection),
 	/// Data definition section.
 	Data(DataSection),
+	/// Number of data segments, needed to validate `memory.init` and
+	/// `data.drop` before the data section is read.
+	DataCount(u32),
 	/// Name section.
 	///
 	/// Note that initially it is not parsed until `parse_names` is called explicitly.
@@ -127,6 +130,12 @@ impl Deserialize for Section {
 				11 => {
 					Section::Data(DataSection::deserialize(reader)?)
 				},
+				12 => {
+					let mut section_reader = SectionReader::new(reader)?;
+					let count = VarUint32::deserialize(&mut section_reader)?;
+					section_reader.close()?;
+					Section::DataCount(count.into())
+				},
 				invalid_id => {
 					return Err(Error::InvalidSectionId(invalid_id))
 				},
@@ -194,6 +203,12 @@ impl Serialize for Section {
 				VarUint7::from(0x0b).serialize(writer)?;
 				data_section.serialize(writer)?;
 			},
+			Section::DataCount(count) => {
+				VarUint7::from(0x0c).serialize(writer)?;
+				let mut counted_writer = CountedWriter::new(writer);
+				VarUint32::from(count).serialize(&mut counted_writer)?;
+				counted_writer.done()?;
+			},
 			Section::Name(name_section) => {
 				VarUint7::from(0x00).serialize(writer)?;
 				let custom = CustomSection {
@@ -227,10 +242,22 @@ impl Section {
 			Section::Element(_) => 0x9,
 			Section::Code(_) => 0x0a,
 			Section::Data(_) => 0x0b,
+			Section::DataCount(_) => 0x0c,
 			Section::Name(_) => 0x00,
 			Section::Reloc(_) => 0x00,
 		}
 	}
+
+	/// Rank of the section in the order mandated by the binary format, which
+	/// differs from the id since the data count section precedes the code
+	/// section (`0` for custom sections, which can appear anywhere).
+	pub(crate) fn order(&self) -> u8 {
+		match self.id() {
+			0x0a | 0x0b => self.id() + 1,
+			0x0c => 0x0a,
+			id => id,
+		}
+	}
 }
 
 pub(crate) struct SectionReader {
@@ -630,6 +657,9 @@ pub struct ExportSection(Vec<ExportEntry>);
 
 impl ExportSection {
 	/// List of all export entries in the section.
+	///
+	/// Entries are kept in the order they appear in the binary (or were
+	/// added in), and are serialized back in that same order.
 	pub fn entries(&self) -> &[ExportEntry] {
 		&self.0
 	}
@@ -937,7 +967,7 @@ mod tests {
 			&Type::Function(ref func_type) => func_type
 		};
 
-		assert_eq!(Some(ValueType::I64), t1.return_type());
+		assert_eq!(&[ValueType::I64][..], t1.results());
 		assert_eq!(2, t1.params().len());
 	}
 
@@ -1091,6 +1121,28 @@ mod tests {
 		]);
 	}
 
+	#[test]
+	fn declared_element_section_roundtrip() {
+		let element_section = ElementSection::with_entries(
+			vec![ElementSegment::new_declared(vec![1, 2])]
+		);
+
+		let buf = serialize(element_section.clone()).expect("Element section to be serialized");
+
+		assert_eq!(buf, vec![
+			0x06, // 6 bytes overall
+			0x01, // number of segments
+			0x03, // declared
+			0x00, // funcref
+			0x02, // 2 elements
+			0x01, 0x02,
+		]);
+
+		let decoded: ElementSection = deserialize_buffer(&buf).expect("Element section to be deserialized");
+		assert!(decoded.entries()[0].declared());
+		assert_eq!(decoded, element_section);
+	}
+
 	#[test]
 	fn code_section_ser() {
 		use super::super::Instruction::*;
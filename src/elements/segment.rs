@@ -1,10 +1,22 @@
 use io;
 use std::vec::Vec;
-use super::{Deserialize, Serialize, Error, VarUint32, CountedList, InitExpr, CountedListWriter};
+use super::{Deserialize, Serialize, Error, VarUint32, Uint8, CountedList, InitExpr, CountedListWriter};
 
 const FLAG_MEMZERO: u32 = 0;
 const FLAG_PASSIVE: u32 = 1;
 const FLAG_MEM_NONZERO: u32 = 2;
+const FLAG_DECLARED: u32 = 3;
+const FLAG_EXPRESSIONS: u32 = 4;
+
+/// Element kind of function references, the only kind of the `elemkind`
+/// encoding.
+const ELEM_KIND_FUNCREF: u8 = 0x00;
+
+/// `funcref` reference type, the type of segments with expressions.
+const REF_TYPE_FUNCREF: u8 = 0x70;
+
+const REF_FUNC: u8 = 0xd2;
+const END: u8 = 0x0b;
 
 /// Entry in the element section.
 #[derive(Debug, Clone, PartialEq)]
@@ -13,12 +25,22 @@ pub struct ElementSegment {
 	offset: Option<InitExpr>,
 	members: Vec<u32>,
 	passive: bool,
+	declared: bool,
 }
 
 impl ElementSegment {
 	/// New element segment.
 	pub fn new(index: u32, offset: Option<InitExpr>, members: Vec<u32>, passive: bool) -> Self {
-		ElementSegment { index: index, offset: offset, members: members, passive: passive }
+		ElementSegment { index: index, offset: offset, members: members, passive: passive, declared: false }
+	}
+
+	/// New declared element segment.
+	///
+	/// Declared segments are neither copied into a table nor available to
+	/// `table.init`: they only declare the functions which `ref.func` may
+	/// refer to.
+	pub fn new_declared(members: Vec<u32>) -> Self {
+		ElementSegment { index: 0, offset: None, members, passive: false, declared: true }
 	}
 
 	/// Sequence of function indices.
@@ -45,36 +67,63 @@ impl ElementSegment {
 
 	/// Whether or not this table element is "passive"
 	pub fn passive_mut(&mut self) -> &mut bool { &mut self.passive }
+
+	/// Whether or not this table element is "declared"
+	///
+	/// Declared segments have no offset and are not passive.
+	pub fn declared(&self) -> bool { self.declared }
 }
 
 impl Deserialize for ElementSegment {
 	 type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		// Bit 0: passive or declared, bit 1: explicit table index (if active)
+		// or declared (otherwise) and element kind, bit 2: members given as
+		// expressions.
 		let flags: u32 = VarUint32::deserialize(reader)?.into();
-		let index = if flags == FLAG_MEMZERO || flags == FLAG_PASSIVE {
-			0u32
-		} else if flags == FLAG_MEM_NONZERO {
+		if flags > FLAG_EXPRESSIONS | FLAG_DECLARED {
+			return Err(Error::InvalidSegmentFlags(flags));
+		}
+		let kind = flags & FLAG_DECLARED;
+		let index = if kind == FLAG_MEM_NONZERO {
 			VarUint32::deserialize(reader)?.into()
 		} else {
-			return Err(Error::InvalidSegmentFlags(flags))
+			0u32
 		};
-		let offset = if flags == FLAG_PASSIVE {
+		let offset = if kind == FLAG_PASSIVE || kind == FLAG_DECLARED {
 			None
 		} else {
 			Some(InitExpr::deserialize(reader)?)
 		};
-		let funcs: Vec<u32> = CountedList::<VarUint32>::deserialize(reader)?
-			.into_inner()
-			.into_iter()
-			.map(Into::into)
-			.collect();
+		let expressions = flags & FLAG_EXPRESSIONS != 0;
+		if kind != FLAG_MEMZERO {
+			let elem_kind: u8 = Uint8::deserialize(reader)?.into();
+			let expected = if expressions { REF_TYPE_FUNCREF } else { ELEM_KIND_FUNCREF };
+			if elem_kind != expected {
+				return Err(Error::UnknownTableElementType(elem_kind as i8));
+			}
+		}
+		let funcs: Vec<u32> = if expressions {
+			CountedList::<FuncRef>::deserialize(reader)?
+				.into_inner()
+				.into_iter()
+				.map(|func_ref| func_ref.0)
+				.collect()
+		} else {
+			CountedList::<VarUint32>::deserialize(reader)?
+				.into_inner()
+				.into_iter()
+				.map(Into::into)
+				.collect()
+		};
 
 		Ok(ElementSegment {
 			index: index,
 			offset: offset,
 			members: funcs,
-			passive: flags == FLAG_PASSIVE,
+			passive: kind == FLAG_PASSIVE,
+			declared: kind == FLAG_DECLARED,
 		})
 	}
 }
@@ -83,16 +132,23 @@ impl Serialize for ElementSegment {
 	type Error = Error;
 
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
-		if self.passive {
+		if self.declared {
+			VarUint32::from(FLAG_DECLARED).serialize(writer)?;
+			Uint8::from(ELEM_KIND_FUNCREF).serialize(writer)?;
+		} else if self.passive {
 			VarUint32::from(FLAG_PASSIVE).serialize(writer)?;
+			Uint8::from(ELEM_KIND_FUNCREF).serialize(writer)?;
 		} else if self.index != 0 {
 			VarUint32::from(FLAG_MEM_NONZERO).serialize(writer)?;
 			VarUint32::from(self.index).serialize(writer)?;
 		} else {
 			VarUint32::from(FLAG_MEMZERO).serialize(writer)?;
 		}
-		if let Some(offset) = self.offset {
+		if let (false, Some(offset)) = (self.declared, self.offset) {
 			offset.serialize(writer)?;
+			if self.index != 0 {
+				Uint8::from(ELEM_KIND_FUNCREF).serialize(writer)?;
+			}
 		}
 		let data = self.members;
 		let counted_list = CountedListWriter::<VarUint32, _>(
@@ -104,6 +160,25 @@ impl Serialize for ElementSegment {
 	}
 }
 
+/// Member of an element segment given as an expression, which has to be
+/// `ref.func`.
+struct FuncRef(u32);
+
+impl Deserialize for FuncRef {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		if u8::from(Uint8::deserialize(reader)?) != REF_FUNC {
+			return Err(Error::Other("only ref.func is supported in element segment expressions"));
+		}
+		let func_idx = VarUint32::deserialize(reader)?.into();
+		if u8::from(Uint8::deserialize(reader)?) != END {
+			return Err(Error::Other("element segment expressions are a single ref.func"));
+		}
+		Ok(FuncRef(func_idx))
+	}
+}
+
 /// Data segment definition.
 #[derive(Clone, Debug, PartialEq)]
 pub struct DataSegment {
@@ -201,3 +276,32 @@ impl Serialize for DataSegment {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::ElementSegment;
+	use elements::{deserialize_buffer, serialize, InitExpr, Instruction};
+
+	#[test]
+	fn element_segment_encodings() {
+		let offset = || Some(InitExpr::new(vec![Instruction::I32Const(1), Instruction::End]));
+		let cases: Vec<(&[u8], ElementSegment)> = vec![
+			(&[0x00, 0x41, 0x01, 0x0b, 0x01, 0x02], ElementSegment::new(0, offset(), vec![2], false)),
+			(&[0x01, 0x00, 0x01, 0x02], ElementSegment::new(0, None, vec![2], true)),
+			(&[0x02, 0x01, 0x41, 0x01, 0x0b, 0x00, 0x01, 0x02], ElementSegment::new(1, offset(), vec![2], false)),
+			(&[0x03, 0x00, 0x01, 0x02], ElementSegment::new_declared(vec![2])),
+		];
+		for (bytes, segment) in cases {
+			assert_eq!(deserialize_buffer::<ElementSegment>(bytes).expect("segment to be deserialized"), segment);
+			assert_eq!(serialize(segment).expect("segment to be serialized"), bytes);
+		}
+
+		let expressions: &[u8] = &[0x05, 0x70, 0x02, 0xd2, 0x02, 0x0b, 0xd2, 0x00, 0x0b];
+		assert_eq!(
+			deserialize_buffer::<ElementSegment>(expressions).expect("segment to be deserialized"),
+			ElementSegment::new(0, None, vec![2, 0], true)
+		);
+		assert!(deserialize_buffer::<ElementSegment>(&[0x05, 0x70, 0x01, 0xd0, 0x70, 0x0b]).is_err());
+		assert!(deserialize_buffer::<ElementSegment>(&[0x08, 0x00]).is_err());
+	}
+}
@@ -0,0 +1,240 @@
+use io;
+use std::cmp::Reverse;
+use std::vec::Vec;
+use std::string::String;
+
+use super::{
+	Deserialize, Error, Module, Opcode, Section, ModuleReader, DataSegment, VarUint32,
+	VarUint7, NameDecoding,
+};
+use super::name_section::deserialize_name_map;
+
+const IMPORT_SECTION_ID: u8 = 0x02;
+const CODE_SECTION_ID: u8 = 0x0a;
+const DATA_SECTION_ID: u8 = 0x0b;
+const NAME_TYPE_FUNCTION: u8 = 1;
+
+/// Size of a section of a module binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionSize {
+	/// Id of the section, `0` for custom sections.
+	pub id: u8,
+	/// Name of the section, if it is a custom section.
+	pub name: Option<String>,
+	/// Size of the section in bytes, header included.
+	pub size: usize,
+}
+
+/// Size of a function body of a module binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionSize {
+	/// Index of the function, imported functions included.
+	pub index: u32,
+	/// Size of the body in bytes, without its length prefix.
+	pub size: usize,
+}
+
+/// Size of a data segment of a module binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataSegmentSize {
+	/// Index of the segment.
+	pub index: u32,
+	/// Size of the data in bytes.
+	pub size: usize,
+}
+
+/// Size statistics of a module binary, computed without decoding the
+/// function bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+	size: usize,
+	sections: Vec<SectionSize>,
+	functions: Vec<FunctionSize>,
+	data_segments: Vec<DataSegmentSize>,
+	/// Function names of the name section, by index.
+	function_names: Vec<(u32, String)>,
+}
+
+impl Stats {
+	/// Statistics of the serialized module `bytes`.
+	///
+	/// Function names come from the function names of the `name` custom
+	/// section, which is skipped if it is invalid.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Stats, Error> {
+		let mut stats = Stats {
+			size: bytes.len(),
+			sections: Vec::new(),
+			functions: Vec::new(),
+			data_segments: Vec::new(),
+			function_names: Vec::new(),
+		};
+		let mut imported_functions = 0;
+		for header in ModuleReader::new(bytes)?.sections() {
+			let header = header?;
+			let mut name = None;
+			match header.id() {
+				0 => {
+					let custom_name = header.custom_name()?;
+					if custom_name == "name" {
+						let functions = imported_functions + stats.functions.len();
+						stats.function_names = function_names(header.payload(), functions).unwrap_or_default();
+					}
+					name = Some(custom_name);
+				},
+				IMPORT_SECTION_ID => {
+					if let Section::Import(ref imports) = header.decode()? {
+						imported_functions = imports.functions();
+					}
+				},
+				CODE_SECTION_ID => {
+					let code = header.code()?;
+					for body_idx in 0..code.len() {
+						let raw = code.raw_body(body_idx).ok_or(Error::UnexpectedEof)?;
+						let size: u32 = VarUint32::deserialize(&mut io::Cursor::new(raw))?.into();
+						let index = (imported_functions + body_idx) as u32;
+						stats.functions.push(FunctionSize { index, size: size as usize });
+					}
+				},
+				DATA_SECTION_ID => {
+					let mut rdr = io::Cursor::new(header.payload());
+					let count: u32 = VarUint32::deserialize(&mut rdr)?.into();
+					for index in 0..count {
+						let size = DataSegment::deserialize(&mut rdr)?.value().len();
+						stats.data_segments.push(DataSegmentSize { index, size });
+					}
+				},
+				_ => {},
+			}
+			let size = header.payload_offset() + header.payload().len() - header.offset();
+			stats.sections.push(SectionSize { id: header.id(), name, size });
+		}
+		Ok(stats)
+	}
+
+	/// Statistics of the binary of `module`.
+	pub fn from_module(module: &Module) -> Result<Stats, Error> {
+		Stats::from_bytes(&super::serialize(module.clone())?)
+	}
+
+	/// Size of the binary.
+	pub fn size(&self) -> usize { self.size }
+
+	/// Sizes of the sections, in order.
+	pub fn sections(&self) -> &[SectionSize] { &self.sections }
+
+	/// Sizes of the function bodies, in the order of the code section.
+	pub fn functions(&self) -> &[FunctionSize] { &self.functions }
+
+	/// Sizes of the data segments, in order.
+	pub fn data_segments(&self) -> &[DataSegmentSize] { &self.data_segments }
+
+	/// Name of the function `index` in the name section.
+	pub fn function_name(&self, index: u32) -> Option<&str> {
+		self.function_names.binary_search_by_key(&index, |&(idx, _)| idx).ok()
+			.map(|position| self.function_names[position].1.as_str())
+	}
+
+	/// The `count` largest sections, by decreasing size.
+	pub fn largest_sections(&self, count: usize) -> Vec<SectionSize> {
+		let mut sections = self.sections.clone();
+		sections.sort_by_key(|section| Reverse(section.size));
+		sections.truncate(count);
+		sections
+	}
+
+	/// The `count` largest function bodies, by decreasing size.
+	pub fn largest_functions(&self, count: usize) -> Vec<FunctionSize> {
+		let mut functions = self.functions.clone();
+		functions.sort_by_key(|function| Reverse(function.size));
+		functions.truncate(count);
+		functions
+	}
+
+	/// The `count` largest data segments, by decreasing size.
+	pub fn largest_data_segments(&self, count: usize) -> Vec<DataSegmentSize> {
+		let mut segments = self.data_segments.clone();
+		segments.sort_by_key(|segment| Reverse(segment.size));
+		segments.truncate(count);
+		segments
+	}
+}
+
+/// Function names of the payload of a name section, for a module with
+/// `functions` functions.
+fn function_names(payload: &[u8], functions: usize) -> Result<Vec<(u32, String)>, Error> {
+	let mut rdr = io::Cursor::new(payload);
+	// Skip the name of the section.
+	String::deserialize(&mut rdr)?;
+	let mut position = rdr.position();
+	while position < payload.len() {
+		let mut rdr = io::Cursor::new(&payload[position..]);
+		let name_type: u8 = VarUint7::deserialize(&mut rdr)?.into();
+		let size: u32 = VarUint32::deserialize(&mut rdr)?.into();
+		let start = position + rdr.position();
+		let end = start.checked_add(size as usize)
+			.filter(|&end| end <= payload.len())
+			.ok_or(Error::UnexpectedEof)?;
+		if name_type == NAME_TYPE_FUNCTION {
+			let mut subsection = io::Cursor::new(&payload[start..end]);
+			let names = deserialize_name_map(functions, &mut subsection, NameDecoding::Lossy)?;
+			return Ok(names.iter().map(|(idx, name)| (idx, name.clone())).collect());
+		}
+		position = end;
+	}
+	Ok(Vec::new())
+}
+
+/// Number of instructions of the function bodies of `module` for every
+/// opcode used, by decreasing count.
+pub fn opcode_counts(module: &Module) -> Vec<(Opcode, usize)> {
+	let mut opcodes: Vec<Opcode> = module.code_section().map_or(&[][..], |cs| cs.bodies()).iter()
+		.flat_map(|body| body.code().elements().iter().map(|instruction| instruction.opcode()))
+		.collect();
+	opcodes.sort_unstable();
+	let mut counts: Vec<(Opcode, usize)> = Vec::new();
+	for opcode in opcodes {
+		match counts.last_mut() {
+			Some(&mut (last, ref mut count)) if last == opcode => *count += 1,
+			_ => counts.push((opcode, 1)),
+		}
+	}
+	// The sort is stable, so equal counts stay ordered by opcode.
+	counts.sort_by_key(|&(_, count)| Reverse(count));
+	counts
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{Stats, SectionSize, FunctionSize, DataSegmentSize, opcode_counts};
+	use builder::module;
+	use elements::{opcodes, Instructions, Instruction, Opcode, NameSection, FunctionNameSection};
+
+	#[test]
+	fn stats() {
+		let code = vec![Instruction::Nop, Instruction::Nop, Instruction::I32Const(1), Instruction::Drop, Instruction::End];
+		let mut built = module()
+			.import().module("env").field("f").external().func(0).build()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().with_instructions(Instructions::new(code)).build().build()
+			.memory().with_min(1).with_data(0, vec![0; 10]).with_data(16, vec![0; 30]).build()
+			.build();
+		let mut functions = FunctionNameSection::default();
+		functions.names_mut().insert(2, "work".into());
+		built.set_names(vec![NameSection::Function(functions)]).expect("names to be set");
+
+		let stats = Stats::from_module(&built).expect("stats to be computed");
+		assert_eq!(stats.sections().iter().map(|section| section.size).sum::<usize>() + 8, stats.size());
+		assert_eq!(stats.sections()[0], SectionSize { id: 1, name: None, size: 6 });
+		assert_eq!(stats.sections().last().map(|section| section.name.as_deref()), Some(Some("name")));
+		assert_eq!(stats.functions(), &[FunctionSize { index: 1, size: 2 }, FunctionSize { index: 2, size: 7 }][..]);
+		assert_eq!(stats.largest_functions(1), vec![FunctionSize { index: 2, size: 7 }]);
+		assert_eq!(stats.function_name(2), Some("work"));
+		assert_eq!(stats.function_name(1), None);
+		assert_eq!(stats.largest_data_segments(1), vec![DataSegmentSize { index: 1, size: 30 }]);
+
+		assert_eq!(opcode_counts(&built), vec![
+			(Opcode::Plain(opcodes::NOP), 2), (Opcode::Plain(opcodes::END), 2),
+			(Opcode::Plain(opcodes::DROP), 1), (Opcode::Plain(opcodes::I32CONST), 1),
+		]);
+	}
+}
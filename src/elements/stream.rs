@@ -0,0 +1,154 @@
+use io;
+use std::boxed::Box;
+use std::mem;
+use std::vec::Vec;
+use super::{serialize, DataSegment, Error, Module, Section, Serialize, Uint32, VarUint32, VarUint7};
+use super::module::WASM_MAGIC_NUMBER;
+
+const DATA_SECTION_ID: u8 = 0x0b;
+
+/// Size of the chunks copied from the sources to the writer.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Payload of a data segment, read while the module is serialized instead
+/// of being held by the segment.
+pub struct DataSource<'a> {
+	segment: u32,
+	len: u32,
+	reader: Box<dyn io::Read + 'a>,
+}
+
+impl<'a> DataSource<'a> {
+	/// Payload of the data segment `segment`, made of the next `len` bytes
+	/// of `reader`.
+	pub fn new<R: io::Read + 'a>(segment: u32, len: u32, reader: R) -> Self {
+		DataSource { segment, len, reader: Box::new(reader) }
+	}
+
+	/// Index of the data segment.
+	pub fn segment(&self) -> u32 { self.segment }
+
+	/// Size of the payload, in bytes.
+	pub fn len(&self) -> u32 { self.len }
+
+	/// Whether the payload is empty.
+	pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+/// Serializes `module` to `writer`, the payload of the data segments of
+/// `sources` being copied from their reader in chunks rather than taken
+/// from the segments, whose value should be left empty.
+///
+/// Only the data segments without a source and the rest of the module are
+/// kept in memory, so that large assets can be embedded without buffering
+/// them. Fails if a source refers to a missing segment or two sources to
+/// the same segment, or if a reader ends before the size of its payload.
+pub fn serialize_with_data<W: io::Write>(module: Module, writer: &mut W, mut sources: Vec<DataSource>) -> Result<(), Error> {
+	sources.sort_by_key(|source| source.segment);
+	if sources.windows(2).any(|pair| pair[0].segment == pair[1].segment) {
+		return Err(Error::Other("several sources for a data segment"));
+	}
+
+	writer.write(&WASM_MAGIC_NUMBER)?;
+	Uint32::from(module.version()).serialize(writer)?;
+	let mut sources = Some(sources);
+	for section in module.into_sections() {
+		match section {
+			Section::Data(mut data) => {
+				let sources = sources.take().unwrap_or_default();
+				serialize_data(mem::take(data.entries_mut()), writer, sources)?;
+			},
+			section => section.serialize(writer)?,
+		}
+	}
+	match sources {
+		Some(ref sources) if !sources.is_empty() => Err(Error::Other("data source for a missing segment")),
+		_ => Ok(()),
+	}
+}
+
+/// Writes the data section of `segments`, with the payloads of `sources`,
+/// which are sorted by segment.
+fn serialize_data<W: io::Write>(segments: Vec<DataSegment>, writer: &mut W, sources: Vec<DataSource>) -> Result<(), Error> {
+	if sources.last().map_or(false, |source| source.segment as usize >= segments.len()) {
+		return Err(Error::Other("data source for a missing segment"));
+	}
+	let mut sources = sources.into_iter().peekable();
+	// Every segment, encoded up to its payload, with its source if any.
+	let mut parts = Vec::with_capacity(segments.len());
+	let mut size = serialize(VarUint32::from(segments.len()))?.len() as u64;
+	for (idx, segment) in segments.into_iter().enumerate() {
+		let source = match sources.peek() {
+			Some(source) if source.segment as usize == idx => sources.next(),
+			_ => None,
+		};
+		let encoded = match source {
+			Some(ref source) => {
+				let mut segment = segment;
+				segment.value_mut().clear();
+				let mut encoded = serialize(segment)?;
+				// The length of the payload, which is 0.
+				encoded.pop();
+				encoded.extend(serialize(VarUint32::from(source.len))?);
+				size += source.len as u64;
+				encoded
+			},
+			None => serialize(segment)?,
+		};
+		size += encoded.len() as u64;
+		parts.push((encoded, source));
+	}
+	if size > u32::MAX as u64 {
+		return Err(Error::Other("data section larger than 4 GiB"));
+	}
+
+	VarUint7::from(DATA_SECTION_ID).serialize(writer)?;
+	VarUint32::from(size as u32).serialize(writer)?;
+	VarUint32::from(parts.len()).serialize(writer)?;
+	let mut chunk = vec![0u8; CHUNK_SIZE];
+	for (encoded, source) in parts {
+		writer.write(&encoded)?;
+		if let Some(mut source) = source {
+			let mut remaining = source.len as usize;
+			while remaining > 0 {
+				let len = remaining.min(CHUNK_SIZE);
+				source.reader.read(&mut chunk[..len])?;
+				writer.write(&chunk[..len])?;
+				remaining -= len;
+			}
+		}
+	}
+	Ok(())
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use io;
+	use super::{serialize_with_data, DataSource};
+	use builder::module;
+	use elements::{serialize, Module};
+
+	fn with_data(first: Vec<u8>, second: Vec<u8>) -> Module {
+		module()
+			.memory().with_min(4).with_data(0, first).with_data(100_000, second).build()
+			.build()
+	}
+
+	#[test]
+	fn stream() {
+		let asset: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+		let expected = serialize(with_data(vec![1, 2, 3], asset.clone())).expect("module to serialize");
+
+		let mut streamed = Vec::new();
+		let sources = vec![DataSource::new(1, asset.len() as u32, io::Cursor::new(&asset))];
+		serialize_with_data(with_data(vec![1, 2, 3], Vec::new()), &mut streamed, sources).expect("module to be streamed");
+		assert_eq!(streamed, expected);
+
+		let sources = vec![DataSource::new(2, 0, io::Cursor::new(&asset))];
+		assert!(serialize_with_data(with_data(Vec::new(), Vec::new()), &mut Vec::new(), sources).is_err());
+		let sources = vec![DataSource::new(0, 4, io::Cursor::new(&[0u8; 3]))];
+		assert!(serialize_with_data(with_data(Vec::new(), Vec::new()), &mut Vec::new(), sources).is_err());
+		let sources = vec![DataSource::new(0, 0, io::Cursor::new(&asset))];
+		assert!(serialize_with_data(module().build(), &mut Vec::new(), sources).is_err());
+	}
+}
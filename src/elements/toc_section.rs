@@ -0,0 +1,231 @@
+use io;
+use std::vec::Vec;
+use std::string::String;
+
+use super::{
+	CustomSection, DataSegment, Deserialize, Error, FuncBody, Module, Section, Serialize,
+	Uint8, VarUint32,
+};
+
+/// Name of the custom section holding the table of contents.
+pub const TOC_SECTION_NAME: &str = "toc";
+
+const CODE_SECTION_ID: u8 = 0x0a;
+const DATA_SECTION_ID: u8 = 0x0b;
+
+/// Table of contents of a serialized module.
+///
+/// Records the byte offset (from the beginning of the module binary) of every
+/// function body and data segment, so that tooling can decode item N without
+/// parsing everything before it. Stored in a custom section named `toc`,
+/// which has to be the last section of the module so that it does not shift
+/// the offsets it records; its payload is two lists (function bodies, then
+/// data segments), each a `varuint32` count followed by `varuint32` offsets.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TocSection {
+	function_bodies: Vec<u32>,
+	data_segments: Vec<u32>,
+}
+
+fn read_offsets<R: io::Read>(rdr: &mut R) -> Result<Vec<u32>, Error> {
+	let count: u32 = VarUint32::deserialize(rdr)?.into();
+	let mut offsets = Vec::new();
+	for _ in 0..count {
+		offsets.push(VarUint32::deserialize(rdr)?.into());
+	}
+	Ok(offsets)
+}
+
+fn write_offsets<W: io::Write>(offsets: Vec<u32>, wtr: &mut W) -> Result<(), Error> {
+	VarUint32::from(offsets.len()).serialize(wtr)?;
+	for offset in offsets {
+		VarUint32::from(offset).serialize(wtr)?;
+	}
+	Ok(())
+}
+
+impl TocSection {
+	/// New table of contents from the offsets of function bodies and data segments.
+	pub fn new(function_bodies: Vec<u32>, data_segments: Vec<u32>) -> TocSection {
+		TocSection { function_bodies, data_segments }
+	}
+
+	/// Offsets of function bodies (in the order of the code section).
+	pub fn function_bodies(&self) -> &[u32] {
+		&self.function_bodies
+	}
+
+	/// Offsets of data segments (in the order of the data section).
+	pub fn data_segments(&self) -> &[u32] {
+		&self.data_segments
+	}
+
+	/// Computes the table of contents of the serialized module `bytes`.
+	pub fn compute(bytes: &[u8]) -> Result<TocSection, Error> {
+		let mut toc = TocSection::default();
+		// Skip magic and version.
+		let mut offset = 8;
+		while offset < bytes.len() {
+			let mut rdr = io::Cursor::new(&bytes[offset..]);
+			let id: u8 = Uint8::deserialize(&mut rdr)?.into();
+			let size: u32 = VarUint32::deserialize(&mut rdr)?.into();
+			let payload_start = offset + rdr.position();
+			let payload_end = payload_start.checked_add(size as usize)
+				.filter(|&end| end <= bytes.len())
+				.ok_or(Error::UnexpectedEof)?;
+
+			match id {
+				CODE_SECTION_ID => {
+					let payload = &bytes[payload_start..payload_end];
+					let mut rdr = io::Cursor::new(payload);
+					let count: u32 = VarUint32::deserialize(&mut rdr)?.into();
+					let mut position = rdr.position();
+					for _ in 0..count {
+						toc.function_bodies.push((payload_start + position) as u32);
+						let mut rdr = io::Cursor::new(&payload[position..]);
+						let body_size: u32 = VarUint32::deserialize(&mut rdr)?.into();
+						position += rdr.position() + body_size as usize;
+						if position > payload.len() {
+							return Err(Error::UnexpectedEof);
+						}
+					}
+				},
+				DATA_SECTION_ID => {
+					let mut rdr = io::Cursor::new(&bytes[payload_start..payload_end]);
+					let count: u32 = VarUint32::deserialize(&mut rdr)?.into();
+					for _ in 0..count {
+						toc.data_segments.push((payload_start + rdr.position()) as u32);
+						DataSegment::deserialize(&mut rdr)?;
+					}
+				},
+				_ => {},
+			}
+			offset = payload_end;
+		}
+		Ok(toc)
+	}
+
+	/// Decodes the body of the function `index` (counting from the first
+	/// function of the code section) out of the serialized module `bytes`.
+	pub fn function_body(&self, bytes: &[u8], index: usize) -> Result<FuncBody, Error> {
+		let offset = *self.function_bodies.get(index).ok_or(Error::Other("function body index out of range"))?;
+		let slice = bytes.get(offset as usize..).ok_or(Error::UnexpectedEof)?;
+		FuncBody::deserialize(&mut io::Cursor::new(slice))
+	}
+
+	/// Decodes the data segment `index` out of the serialized module `bytes`.
+	pub fn data_segment(&self, bytes: &[u8], index: usize) -> Result<DataSegment, Error> {
+		let offset = *self.data_segments.get(index).ok_or(Error::Other("data segment index out of range"))?;
+		let slice = bytes.get(offset as usize..).ok_or(Error::UnexpectedEof)?;
+		DataSegment::deserialize(&mut io::Cursor::new(slice))
+	}
+
+	/// Parses a table of contents out of the custom section `section`.
+	pub fn from_custom_section(section: &CustomSection) -> Result<TocSection, Error> {
+		if section.name() != TOC_SECTION_NAME {
+			return Err(Error::Other("custom section is not a table of contents"));
+		}
+		let mut rdr = io::Cursor::new(section.payload());
+		let function_bodies = read_offsets(&mut rdr)?;
+		let data_segments = read_offsets(&mut rdr)?;
+		if rdr.position() != section.payload().len() {
+			return Err(io::Error::InvalidData.into());
+		}
+		Ok(TocSection { function_bodies, data_segments })
+	}
+
+	/// Converts the table of contents into a custom section.
+	pub fn into_custom_section(self) -> Result<CustomSection, Error> {
+		let mut payload = Vec::new();
+		self.serialize(&mut payload)?;
+		Ok(CustomSection::new(String::from(TOC_SECTION_NAME), payload))
+	}
+}
+
+impl Serialize for TocSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(self, wtr: &mut W) -> Result<(), Error> {
+		write_offsets(self.function_bodies, wtr)?;
+		write_offsets(self.data_segments, wtr)
+	}
+}
+
+impl Module {
+	/// Table of contents stored in the `toc` custom section, if any.
+	///
+	/// Offsets are only meaningful for the binary the module was read from.
+	pub fn toc(&self) -> Option<Result<TocSection, Error>> {
+		self.sections().iter()
+			.filter_map(|section| match *section {
+				Section::Custom(ref custom) if custom.name() == TOC_SECTION_NAME => Some(custom),
+				_ => None,
+			})
+			.next()
+			.map(TocSection::from_custom_section)
+	}
+}
+
+/// Serializes `module` and appends a `toc` section describing the result.
+///
+/// A table of contents already present in `module` is dropped, as it would
+/// be stale.
+pub fn serialize_with_toc(mut module: Module) -> Result<Vec<u8>, Error> {
+	module.sections_mut().retain(|section| match *section {
+		Section::Custom(ref custom) => custom.name() != TOC_SECTION_NAME,
+		_ => true,
+	});
+
+	let mut bytes = Vec::new();
+	module.serialize(&mut bytes)?;
+	let toc = TocSection::compute(&bytes)?;
+	Section::Custom(toc.into_custom_section()?).serialize(&mut bytes)?;
+	Ok(bytes)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{serialize_with_toc, TocSection};
+	use builder::module;
+	use elements::{deserialize_buffer, Instructions, Module};
+	use elements::Instruction::*;
+
+	#[test]
+	fn random_access() {
+		let module = module()
+			.memory().with_min(1)
+				.with_data(0, vec![1, 2, 3])
+				.with_data(16, vec![4, 5])
+				.build()
+			.function()
+				.signature().build()
+				.body().with_instructions(Instructions::new(vec![Nop, End])).build()
+				.build()
+			.function()
+				.signature().build()
+				.body().with_instructions(Instructions::new(vec![I32Const(42), Drop, End])).build()
+				.build()
+			.build();
+
+		let bytes = serialize_with_toc(module.clone()).expect("module to serialize");
+		let parsed: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		let toc = parsed.toc().expect("toc to exist").expect("toc to parse");
+		assert_eq!(toc.function_bodies().len(), 2);
+		assert_eq!(toc.data_segments().len(), 2);
+		assert_eq!(toc, TocSection::compute(&bytes).expect("toc to compute"));
+
+		assert_eq!(
+			toc.function_body(&bytes, 1).expect("body to decode"),
+			module.code_section().expect("code section").bodies()[1]
+		);
+		assert_eq!(
+			toc.data_segment(&bytes, 1).expect("segment to decode"),
+			module.data_section().expect("data section").entries()[1]
+		);
+		assert!(toc.function_body(&bytes, 2).is_err());
+
+		// Serializing again replaces the stale table of contents.
+		let again = serialize_with_toc(parsed).expect("module to serialize");
+		assert_eq!(again, bytes);
+	}
+}
@@ -1,9 +1,10 @@
 use io;
 use std::fmt;
+use std::ops::{Deref, DerefMut};
 use std::vec::Vec;
 use super::{
-	Deserialize, Serialize, Error, VarUint7, VarInt7, VarUint1, CountedList,
-	CountedListWriter, VarUint32,
+	Deserialize, Serialize, Error, VarUint7, VarInt7, VarInt64, CountedList,
+	CountedListWriter,
 };
 
 /// Type definition in types section. Currently can be only of the function type.
@@ -44,6 +45,10 @@ pub enum ValueType {
 	F64,
 	/// 128-bit SIMD register
 	V128,
+	/// Reference to a function, possibly null
+	FuncRef,
+	/// Opaque reference to a host value, possibly null
+	ExternRef,
 }
 
 impl Deserialize for ValueType {
@@ -58,6 +63,8 @@ impl Deserialize for ValueType {
 			-0x03 => Ok(ValueType::F32),
 			-0x04 => Ok(ValueType::F64),
 			-0x05 => Ok(ValueType::V128),
+			-0x10 => Ok(ValueType::FuncRef),
+			-0x11 => Ok(ValueType::ExternRef),
 			_ => Err(Error::UnknownValueType(val.into())),
 		}
 	}
@@ -73,6 +80,8 @@ impl Serialize for ValueType {
 			ValueType::F32 => -0x03,
 			ValueType::F64 => -0x04,
 			ValueType::V128 => -0x05,
+			ValueType::FuncRef => -0x10,
+			ValueType::ExternRef => -0x11,
 		}.into();
 		val.serialize(writer)?;
 		Ok(())
@@ -87,33 +96,45 @@ impl fmt::Display for ValueType {
 			ValueType::F32 => write!(f, "f32"),
 			ValueType::F64 => write!(f, "f64"),
 			ValueType::V128 => write!(f, "v128"),
+			ValueType::FuncRef => write!(f, "funcref"),
+			ValueType::ExternRef => write!(f, "externref"),
 		}
 	}
 }
 
 /// Block type which is basically `ValueType` + NoResult (to define blocks that have no return type)
+///
+/// Blocks taking parameters or returning several values refer to a function
+/// type of the type section instead.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BlockType {
 	/// Value-type specified block type
 	Value(ValueType),
 	/// No specified block type
 	NoResult,
+	/// Block type given by the function type with this index.
+	TypeIndex(u32),
 }
 
 impl Deserialize for BlockType {
 	type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		let val = VarInt7::deserialize(reader)?;
+		// Type indices are encoded as positive signed integers, so that they
+		// can't be confused with the (negative) single byte types.
+		let val: i64 = VarInt64::deserialize(reader)?.into();
 
-		match val.into() {
+		match val {
 			-0x01 => Ok(BlockType::Value(ValueType::I32)),
 			-0x02 => Ok(BlockType::Value(ValueType::I64)),
 			-0x03 => Ok(BlockType::Value(ValueType::F32)),
 			-0x04 => Ok(BlockType::Value(ValueType::F64)),
-			0x7b => Ok(BlockType::Value(ValueType::V128)),
+			-0x05 => Ok(BlockType::Value(ValueType::V128)),
+			-0x10 => Ok(BlockType::Value(ValueType::FuncRef)),
+			-0x11 => Ok(BlockType::Value(ValueType::ExternRef)),
 			-0x40 => Ok(BlockType::NoResult),
-			_ => Err(Error::UnknownValueType(val.into())),
+			0..=0xffff_ffff => Ok(BlockType::TypeIndex(val as u32)),
+			_ => Err(Error::UnknownValueType(val as i8)),
 		}
 	}
 }
@@ -128,7 +149,10 @@ impl Serialize for BlockType {
 			BlockType::Value(ValueType::I64) => -0x02,
 			BlockType::Value(ValueType::F32) => -0x03,
 			BlockType::Value(ValueType::F64) => -0x04,
-			BlockType::Value(ValueType::V128) => 0x7b,
+			BlockType::Value(ValueType::V128) => -0x05,
+			BlockType::Value(ValueType::FuncRef) => -0x10,
+			BlockType::Value(ValueType::ExternRef) => -0x11,
+			BlockType::TypeIndex(idx) => return VarInt64::from(idx as i64).serialize(writer),
 		}.into();
 		val.serialize(writer)?;
 		Ok(())
@@ -140,7 +164,7 @@ impl Serialize for BlockType {
 pub struct FunctionType {
 	form: u8,
 	params: Vec<ValueType>,
-	return_type: Option<ValueType>,
+	results: Vec<ValueType>,
 }
 
 impl Default for FunctionType {
@@ -148,17 +172,22 @@ impl Default for FunctionType {
 		FunctionType {
 			form: 0x60,
 			params: Vec::new(),
-			return_type: None,
+			results: Vec::new(),
 		}
 	}
 }
 
 impl FunctionType {
 	/// New function type given the signature in-params(`params`) and return type (`return_type`)
+	#[deprecated(note = "functions can return several values, use `with_results`")]
 	pub fn new(params: Vec<ValueType>, return_type: Option<ValueType>) -> Self {
+		FunctionType::with_results(params, return_type.into_iter().collect())
+	}
+	/// New function type given the signature in-params(`params`) and results (`results`)
+	pub fn with_results(params: Vec<ValueType>, results: Vec<ValueType>) -> Self {
 		FunctionType {
 			params: params,
-			return_type: return_type,
+			results: results,
 			..Default::default()
 		}
 	}
@@ -168,10 +197,47 @@ impl FunctionType {
 	pub fn params(&self) -> &[ValueType] { &self.params }
 	/// Mutable parameters in the function signature.
 	pub fn params_mut(&mut self) -> &mut Vec<ValueType> { &mut self.params }
-	/// Return type in the function signature, if any.
-	pub fn return_type(&self) -> Option<ValueType> { self.return_type }
-	/// Mutable type in the function signature, if any.
-	pub fn return_type_mut(&mut self) -> &mut Option<ValueType> { &mut self.return_type }
+	/// Results in the function signature.
+	pub fn results(&self) -> &[ValueType] { &self.results }
+	/// Mutable results in the function signature.
+	pub fn results_mut(&mut self) -> &mut Vec<ValueType> { &mut self.results }
+	/// First result in the function signature, if any.
+	#[deprecated(note = "functions can return several values, use `results`")]
+	pub fn return_type(&self) -> Option<ValueType> { self.results.first().cloned() }
+	/// Mutable first result in the function signature, if any. Setting it
+	/// replaces all the results.
+	#[deprecated(note = "functions can return several values, use `results_mut`")]
+	pub fn return_type_mut(&mut self) -> ReturnTypeMut<'_> {
+		let return_type = self.results.first().cloned();
+		ReturnTypeMut { results: &mut self.results, original: return_type, return_type }
+	}
+}
+
+/// First result of a function type, written back to its results when
+/// dropped if it was changed (see `FunctionType::return_type_mut`).
+pub struct ReturnTypeMut<'a> {
+	results: &'a mut Vec<ValueType>,
+	original: Option<ValueType>,
+	return_type: Option<ValueType>,
+}
+
+impl<'a> Deref for ReturnTypeMut<'a> {
+	type Target = Option<ValueType>;
+
+	fn deref(&self) -> &Option<ValueType> { &self.return_type }
+}
+
+impl<'a> DerefMut for ReturnTypeMut<'a> {
+	fn deref_mut(&mut self) -> &mut Option<ValueType> { &mut self.return_type }
+}
+
+impl<'a> Drop for ReturnTypeMut<'a> {
+	fn drop(&mut self) {
+		if self.return_type != self.original {
+			self.results.clear();
+			self.results.extend(self.return_type);
+		}
+	}
 }
 
 impl Deserialize for FunctionType {
@@ -185,21 +251,12 @@ impl Deserialize for FunctionType {
 		}
 
 		let params: Vec<ValueType> = CountedList::deserialize(reader)?.into_inner();
-
-		let return_types: u32 = VarUint32::deserialize(reader)?.into();
-
-		let return_type = if return_types == 1 {
-			Some(ValueType::deserialize(reader)?)
-		} else if return_types == 0 {
-			None
-		} else {
-			return Err(Error::Other("Return types length should be 0 or 1"));
-		};
+		let results: Vec<ValueType> = CountedList::deserialize(reader)?.into_inner();
 
 		Ok(FunctionType {
 			form: form,
 			params: params,
-			return_type: return_type,
+			results: results,
 		})
 	}
 }
@@ -217,12 +274,12 @@ impl Serialize for FunctionType {
 		);
 		counted_list.serialize(writer)?;
 
-		if let Some(return_type) = self.return_type {
-			VarUint1::from(true).serialize(writer)?;
-			return_type.serialize(writer)?;
-		} else {
-			VarUint1::from(false).serialize(writer)?;
-		}
+		let data = self.results;
+		let counted_list = CountedListWriter::<ValueType, _>(
+			data.len(),
+			data.into_iter(),
+		);
+		counted_list.serialize(writer)?;
 
 		Ok(())
 	}
@@ -233,6 +290,8 @@ impl Serialize for FunctionType {
 pub enum TableElementType {
 	/// A reference to a function with any signature.
 	AnyFunc,
+	/// An opaque reference to a host value.
+	ExternRef,
 }
 
 impl Deserialize for TableElementType {
@@ -243,6 +302,7 @@ impl Deserialize for TableElementType {
 
 		match val.into() {
 			-0x10 => Ok(TableElementType::AnyFunc),
+			-0x11 => Ok(TableElementType::ExternRef),
 			_ => Err(Error::UnknownTableElementType(val.into())),
 		}
 	}
@@ -254,8 +314,49 @@ impl Serialize for TableElementType {
 	fn serialize<W: io::Write>(self, writer: &mut W) -> Result<(), Self::Error> {
 		let val: VarInt7 = match self {
 			TableElementType::AnyFunc => -0x10,
+			TableElementType::ExternRef => -0x11,
 		}.into();
 		val.serialize(writer)?;
 		Ok(())
 	}
 }
+
+impl fmt::Display for TableElementType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			TableElementType::AnyFunc => write!(f, "funcref"),
+			TableElementType::ExternRef => write!(f, "externref"),
+		}
+	}
+}
+
+impl From<TableElementType> for ValueType {
+	fn from(elem_type: TableElementType) -> ValueType {
+		match elem_type {
+			TableElementType::AnyFunc => ValueType::FuncRef,
+			TableElementType::ExternRef => ValueType::ExternRef,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{FunctionType, ValueType};
+
+	#[test]
+	#[allow(deprecated)]
+	fn single_result_shims() {
+		let mut signature = FunctionType::new(vec![ValueType::I32], Some(ValueType::I64));
+		assert_eq!(signature, FunctionType::with_results(vec![ValueType::I32], vec![ValueType::I64]));
+		assert_eq!(*signature.return_type_mut(), Some(ValueType::I64));
+		*signature.return_type_mut() = None;
+		assert!(signature.results().is_empty());
+
+		// Results are left alone unless the first one is changed.
+		let mut signature = FunctionType::with_results(Vec::new(), vec![ValueType::I32, ValueType::F32]);
+		assert_eq!(signature.return_type_mut().unwrap(), ValueType::I32);
+		assert_eq!(signature.results(), &[ValueType::I32, ValueType::F32][..]);
+		*signature.return_type_mut() = Some(ValueType::F64);
+		assert_eq!(signature.results(), &[ValueType::F64][..]);
+	}
+}
@@ -1,7 +1,27 @@
 //! WebAssembly format library
+//!
+//! Only the `elements` module (parsing and serialization) is always
+//! available, the other modules are behind cargo features, all enabled by
+//! default:
+//!
+//! | Feature      | Module        | Requires   |
+//! |--------------|---------------|------------|
+//! | `std`        |               |            |
+//! | `builder`    | `builder`     |            |
+//! | `analysis`   | `analysis`    |            |
+//! | `transform`  | `transform`   | `analysis` |
+//! | `validation` | `validation`  |            |
+//! | `loader`     | `loader`      |            |
+//! | `bundle`     | `bundle`      | `loader`   |
+//! | `text`       | `text`        |            |
+//! | `reduce`     | `reduce`      | `transform` |
+//! | `compress`   | `compress`    |            |
+//! | `numeric`    | `numeric`     |            |
+//!
+//! The `capi` feature (requiring `std`, not enabled by default) adds the C
+//! API of the `capi` module.
 
 #![cfg_attr(not(feature = "std"), no_std)]
-#![cfg_attr(not(feature = "std"), feature(alloc))]
 
 #![warn(missing_docs)]
 
@@ -12,7 +32,28 @@ extern crate byteorder;
 extern crate alloc;
 
 pub mod elements;
+#[cfg(feature = "builder")]
 pub mod builder;
+#[cfg(feature = "transform")]
+pub mod transform;
+#[cfg(feature = "analysis")]
+pub mod analysis;
+#[cfg(feature = "validation")]
+pub mod validation;
+#[cfg(feature = "loader")]
+pub mod loader;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "text")]
+pub mod text;
+#[cfg(feature = "reduce")]
+pub mod reduce;
+#[cfg(feature = "compress")]
+pub mod compress;
+#[cfg(feature = "numeric")]
+pub mod numeric;
 mod io;
 
 pub use elements::{
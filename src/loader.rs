@@ -0,0 +1,243 @@
+//! Resolution of graphs of modules linked through their imports.
+
+use std::fmt;
+use std::vec::Vec;
+use std::string::String;
+use elements::{self, Module, deserialize_buffer};
+
+/// Source of module binaries by module name.
+pub trait ModuleLoader {
+	/// Returns the binary of the module `name`, or `None` if this loader
+	/// doesn't provide such a module (for instance when `name` refers to
+	/// host functions provided by the embedder).
+	fn load(&mut self, name: &str) -> Result<Option<Vec<u8>>, elements::Error>;
+}
+
+/// Loader serving modules from `(name, binary)` pairs held in memory.
+impl ModuleLoader for Vec<(String, Vec<u8>)> {
+	fn load(&mut self, name: &str) -> Result<Option<Vec<u8>>, elements::Error> {
+		Ok(self.iter().find(|(n, _)| n == name).map(|(_, bytes)| bytes.clone()))
+	}
+}
+
+/// Loader reading `<name>.wasm` files from a directory.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct DirectoryLoader {
+	root: ::std::path::PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl DirectoryLoader {
+	/// New loader for the directory `root`.
+	pub fn new<P: Into<::std::path::PathBuf>>(root: P) -> DirectoryLoader {
+		DirectoryLoader { root: root.into() }
+	}
+}
+
+#[cfg(feature = "std")]
+impl ModuleLoader for DirectoryLoader {
+	fn load(&mut self, name: &str) -> Result<Option<Vec<u8>>, elements::Error> {
+		// Refuse names escaping the directory.
+		if name.is_empty() || name.contains('/') || name.contains('\\') || name == "." || name == ".." {
+			return Ok(None);
+		}
+		let path = self.root.join(format!("{}.wasm", name));
+		match ::std::fs::read(&path) {
+			Ok(bytes) => Ok(Some(bytes)),
+			Err(ref e) if e.kind() == ::std::io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(elements::Error::HeapOther(format!("Can't read {}: {:?}", path.display(), e))),
+		}
+	}
+}
+
+/// Error while resolving a module graph.
+#[derive(Debug, Clone)]
+pub enum Error {
+	/// The root module is not provided by the loader.
+	NotFound(String),
+	/// The loader failed to load the module.
+	Load(String, elements::Error),
+	/// The module could not be decoded.
+	Deserialize(String, elements::Error),
+	/// The modules import each other in a cycle, which can not be
+	/// instantiated. Lists the modules forming the cycle.
+	Cycle(Vec<String>),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::NotFound(ref name) => write!(f, "Module {} not found", name),
+			Error::Load(ref name, ref e) => write!(f, "Failed to load module {}: {}", name, e),
+			Error::Deserialize(ref name, ref e) => write!(f, "Failed to decode module {}: {}", name, e),
+			Error::Cycle(ref names) => write!(f, "Import cycle between modules: {}", names.join(" -> ")),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {
+	fn description(&self) -> &str {
+		match *self {
+			Error::NotFound(_) => "Module not found",
+			Error::Load(..) => "Failed to load module",
+			Error::Deserialize(..) => "Failed to decode module",
+			Error::Cycle(_) => "Import cycle between modules",
+		}
+	}
+}
+
+/// Modules reachable from a root module through their imports.
+#[derive(Debug, Clone)]
+pub struct ModuleGraph {
+	modules: Vec<(String, Module)>,
+	external: Vec<String>,
+}
+
+impl ModuleGraph {
+	/// Modules in instantiation order: every module comes after the modules
+	/// it imports from, the root module comes last.
+	pub fn modules(&self) -> &[(String, Module)] {
+		&self.modules
+	}
+
+	/// Module named `name`, if it is part of the graph.
+	pub fn get(&self, name: &str) -> Option<&Module> {
+		self.modules.iter().find(|(n, _)| n == name).map(|(_, module)| module)
+	}
+
+	/// Root module of the graph.
+	pub fn root(&self) -> &Module {
+		&self.modules.last().expect("graph contains at least the root; qed").1
+	}
+
+	/// Names of imported modules which the loader didn't provide and which
+	/// the embedder has to supply (host modules), sorted.
+	pub fn external(&self) -> &[String] {
+		&self.external
+	}
+
+	/// Consumes the graph, returning the modules in instantiation order.
+	pub fn into_modules(self) -> Vec<(String, Module)> {
+		self.modules
+	}
+}
+
+fn imported_modules(module: &Module) -> Vec<String> {
+	let mut names: Vec<String> = Vec::new();
+	for entry in module.import_section().map(|is| is.entries()).unwrap_or(&[]) {
+		if !names.iter().any(|n| n == entry.module()) {
+			names.push(entry.module().into());
+		}
+	}
+	names
+}
+
+struct Resolver<'a, L: 'a + ModuleLoader> {
+	loader: &'a mut L,
+	done: Vec<(String, Module)>,
+	external: Vec<String>,
+	in_progress: Vec<String>,
+}
+
+impl<'a, L: ModuleLoader> Resolver<'a, L> {
+	/// Loads `name` and its dependencies. Returns `false` if the loader
+	/// doesn't know about `name`.
+	fn visit(&mut self, name: &str) -> Result<bool, Error> {
+		if self.done.iter().any(|(n, _)| n == name) {
+			return Ok(true);
+		}
+		if let Some(start) = self.in_progress.iter().position(|n| n == name) {
+			let mut cycle = self.in_progress[start..].to_vec();
+			cycle.push(name.into());
+			return Err(Error::Cycle(cycle));
+		}
+
+		let bytes = match self.loader.load(name) {
+			Ok(Some(bytes)) => bytes,
+			Ok(None) => return Ok(false),
+			Err(e) => return Err(Error::Load(name.into(), e)),
+		};
+		let module: Module = deserialize_buffer(&bytes).map_err(|e| Error::Deserialize(name.into(), e))?;
+
+		self.in_progress.push(name.into());
+		for dependency in imported_modules(&module) {
+			if !self.visit(&dependency)? {
+				if let Err(position) = self.external.binary_search(&dependency) {
+					self.external.insert(position, dependency);
+				}
+			}
+		}
+		self.in_progress.pop();
+
+		self.done.push((name.into(), module));
+		Ok(true)
+	}
+}
+
+/// Loads the module `root` and, recursively, every module it imports from
+/// which `loader` provides.
+///
+/// Each module is loaded once, however many modules import it. Fails if
+/// the modules import each other in a cycle.
+pub fn resolve<L: ModuleLoader>(loader: &mut L, root: &str) -> Result<ModuleGraph, Error> {
+	let mut resolver = Resolver {
+		loader,
+		done: Vec::new(),
+		external: Vec::new(),
+		in_progress: Vec::new(),
+	};
+	if !resolver.visit(root)? {
+		return Err(Error::NotFound(root.into()));
+	}
+	Ok(ModuleGraph {
+		modules: resolver.done,
+		external: resolver.external,
+	})
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{resolve, Error};
+	use builder::module;
+	use elements::serialize;
+
+	fn bytes(imports: &[&str]) -> Vec<u8> {
+		let mut builder = module();
+		for name in imports {
+			builder = builder.import().module(name).field("f").external().func(0).build();
+		}
+		serialize(builder.function().signature().build().body().build().build().build())
+			.expect("module to serialize")
+	}
+
+	#[test]
+	fn diamond() {
+		let mut loader = vec![
+			("main".to_owned(), bytes(&["a", "b", "env"])),
+			("a".to_owned(), bytes(&["c"])),
+			("b".to_owned(), bytes(&["c", "wasi"])),
+			("c".to_owned(), bytes(&["env"])),
+		];
+		let graph = resolve(&mut loader, "main").expect("graph to resolve");
+		let names: Vec<&str> = graph.modules().iter().map(|(n, _)| n.as_str()).collect();
+		assert_eq!(names, vec!["c", "a", "b", "main"]);
+		assert_eq!(graph.external(), &["env".to_owned(), "wasi".to_owned()][..]);
+		assert!(graph.get("a").is_some());
+	}
+
+	#[test]
+	fn cycle() {
+		let mut loader = vec![
+			("main".to_owned(), bytes(&["a"])),
+			("a".to_owned(), bytes(&["b"])),
+			("b".to_owned(), bytes(&["a"])),
+		];
+		match resolve(&mut loader, "main") {
+			Err(Error::Cycle(names)) => assert_eq!(names, vec!["a", "b", "a"]),
+			other => panic!("cycle expected, got {:?}", other),
+		}
+		assert!(matches!(resolve(&mut loader, "missing"), Err(Error::NotFound(_))));
+	}
+}
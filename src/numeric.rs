@@ -0,0 +1,143 @@
+//! Conversions between floats and integers with the exact semantics of the
+//! WebAssembly instructions, for host code emulating or checking them.
+//!
+//! Functions are named after the instruction they implement: the trapping
+//! truncations (`i32.trunc_f32_s`, ...) return a `Trap` where the
+//! instruction traps, while the saturating ones (`i32.trunc_sat_f32_s`,
+//! ...), the conversions from integers and the demotion and promotion
+//! always succeed.
+
+use std::fmt;
+
+/// Trap of a truncation from a float to an integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+	/// The float is NaN.
+	InvalidConversion,
+	/// The float, truncated, is out of the range of the integer type.
+	IntegerOverflow,
+}
+
+impl fmt::Display for Trap {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Trap::InvalidConversion => write!(f, "invalid conversion to integer"),
+			Trap::IntegerOverflow => write!(f, "integer overflow"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Trap {
+	fn description(&self) -> &str {
+		match *self {
+			Trap::InvalidConversion => "invalid conversion to integer",
+			Trap::IntegerOverflow => "integer overflow",
+		}
+	}
+}
+
+/// Defines the trapping truncation `$name` and the saturating truncation
+/// `$sat` from `$from` to `$to`, the truncation of the floats strictly
+/// between `$min` and `$max` being in the range of `$to`.
+macro_rules! trunc {
+	($name:ident, $sat:ident, $from:ty, $to:ty, $min:expr, $max:expr, $instruction:expr) => {
+		#[doc = "`"]
+		#[doc = $instruction]
+		#[doc = "`: truncation toward zero, trapping on NaN and out of range values."]
+		pub fn $name(value: $from) -> Result<$to, Trap> {
+			if value.is_nan() {
+				return Err(Trap::InvalidConversion);
+			}
+			// The bounds are exact in `f64`, as are `f32` values.
+			let wide = value as f64;
+			if wide <= $min || wide >= $max {
+				return Err(Trap::IntegerOverflow);
+			}
+			Ok(value as $to)
+		}
+
+		#[doc = "Saturating version of `"]
+		#[doc = $instruction]
+		#[doc = "`: NaN gives 0 and out of range values the closest bound."]
+		pub fn $sat(value: $from) -> $to {
+			// Casts saturate the same way.
+			value as $to
+		}
+	};
+}
+
+trunc!(i32_trunc_f32_s, i32_trunc_sat_f32_s, f32, i32, -2147483649.0, 2147483648.0, "i32.trunc_f32_s");
+trunc!(i32_trunc_f32_u, i32_trunc_sat_f32_u, f32, u32, -1.0, 4294967296.0, "i32.trunc_f32_u");
+trunc!(i32_trunc_f64_s, i32_trunc_sat_f64_s, f64, i32, -2147483649.0, 2147483648.0, "i32.trunc_f64_s");
+trunc!(i32_trunc_f64_u, i32_trunc_sat_f64_u, f64, u32, -1.0, 4294967296.0, "i32.trunc_f64_u");
+// -2^63 - 2^11 is the largest `f64` below -2^63.
+trunc!(i64_trunc_f32_s, i64_trunc_sat_f32_s, f32, i64, -9223372036854777856.0, 9223372036854775808.0, "i64.trunc_f32_s");
+trunc!(i64_trunc_f32_u, i64_trunc_sat_f32_u, f32, u64, -1.0, 18446744073709551616.0, "i64.trunc_f32_u");
+trunc!(i64_trunc_f64_s, i64_trunc_sat_f64_s, f64, i64, -9223372036854777856.0, 9223372036854775808.0, "i64.trunc_f64_s");
+trunc!(i64_trunc_f64_u, i64_trunc_sat_f64_u, f64, u64, -1.0, 18446744073709551616.0, "i64.trunc_f64_u");
+
+/// Defines the conversion `$name` from `$from` to `$to`, rounding to the
+/// nearest float, ties to even.
+macro_rules! convert {
+	($name:ident, $from:ty, $to:ty, $instruction:expr) => {
+		#[doc = "`"]
+		#[doc = $instruction]
+		#[doc = "`: rounding to the nearest float, ties to even."]
+		pub fn $name(value: $from) -> $to {
+			value as $to
+		}
+	};
+}
+
+convert!(f32_convert_i32_s, i32, f32, "f32.convert_i32_s");
+convert!(f32_convert_i32_u, u32, f32, "f32.convert_i32_u");
+convert!(f32_convert_i64_s, i64, f32, "f32.convert_i64_s");
+convert!(f32_convert_i64_u, u64, f32, "f32.convert_i64_u");
+convert!(f64_convert_i32_s, i32, f64, "f64.convert_i32_s");
+convert!(f64_convert_i32_u, u32, f64, "f64.convert_i32_u");
+convert!(f64_convert_i64_s, i64, f64, "f64.convert_i64_s");
+convert!(f64_convert_i64_u, u64, f64, "f64.convert_i64_u");
+convert!(f32_demote_f64, f64, f32, "f32.demote_f64");
+
+/// `f64.promote_f32`: exact, NaN staying NaN.
+pub fn f64_promote_f32(value: f32) -> f64 {
+	value as f64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn trunc() {
+		assert_eq!(i32_trunc_f32_s(-2147483648.0), Ok(i32::MIN));
+		assert_eq!(i32_trunc_f32_s(2147483648.0), Err(Trap::IntegerOverflow));
+		assert_eq!(i32_trunc_f32_s(-1.9), Ok(-1));
+		assert_eq!(i32_trunc_f32_u(-0.9), Ok(0));
+		assert_eq!(i32_trunc_f32_u(-1.0), Err(Trap::IntegerOverflow));
+		assert_eq!(i32_trunc_f64_s(-2147483648.9), Ok(i32::MIN));
+		assert_eq!(i32_trunc_f64_s(-2147483649.0), Err(Trap::IntegerOverflow));
+		assert_eq!(i32_trunc_f64_s(2147483647.9), Ok(i32::MAX));
+		assert_eq!(i32_trunc_f64_u(4294967295.9), Ok(u32::MAX));
+		assert_eq!(i32_trunc_f64_u(f64::NAN), Err(Trap::InvalidConversion));
+		assert_eq!(i64_trunc_f64_s(-9223372036854775808.0), Ok(i64::MIN));
+		assert_eq!(i64_trunc_f64_s(9223372036854775808.0), Err(Trap::IntegerOverflow));
+		assert_eq!(i64_trunc_f32_u(f32::INFINITY), Err(Trap::IntegerOverflow));
+		assert_eq!(i64_trunc_f64_u(18446744073709549568.0), Ok(18446744073709549568));
+
+		assert_eq!(i32_trunc_sat_f32_s(f32::NAN), 0);
+		assert_eq!(i32_trunc_sat_f64_s(-1e10), i32::MIN);
+		assert_eq!(i32_trunc_sat_f64_u(-1.0), 0);
+		assert_eq!(i64_trunc_sat_f32_u(f32::INFINITY), u64::MAX);
+	}
+
+	#[test]
+	fn convert() {
+		assert_eq!(f32_convert_i32_s(16777217), 16777216.0);
+		assert_eq!(f32_convert_i32_u(u32::MAX), 4294967296.0);
+		assert_eq!(f64_convert_i64_u(u64::MAX), 18446744073709551616.0);
+		assert_eq!(f32_demote_f64(1e300), f32::INFINITY);
+		assert!(f64_promote_f32(f32::NAN).is_nan());
+	}
+}
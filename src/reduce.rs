@@ -0,0 +1,329 @@
+//! Reduction of modules to a minimal test case.
+//!
+//! Given a module reproducing some behaviour (typically an engine bug) and
+//! a predicate telling whether a module still reproduces it, [`reduce`]
+//! removes as much of the module as it can while the predicate holds:
+//! exports, the start function, custom sections, data and element segments,
+//! instructions, locals and whole functions. Each part is reduced with delta
+//! debugging, trying to remove halves, then quarters and so on, and the
+//! passes are repeated until none removes anything.
+//!
+//! Candidates are not validated, so a predicate looking for a bug in valid
+//! modules has to check validity itself.
+//!
+//! [`reduce`]: fn.reduce.html
+
+use std::vec::Vec;
+use elements::{
+	Module, Section, Instruction, Instructions, ExportEntry, DataSegment, ElementSegment,
+};
+use transform::remove_function;
+
+/// Reduces `module` while `interesting` holds.
+///
+/// Returns `module` unchanged if it is not interesting in the first place.
+/// `interesting` is called once per candidate, so the number of calls grows
+/// with the size of the module, and quickly with the size of the function
+/// bodies.
+pub fn reduce<F: FnMut(&Module) -> bool>(module: Module, mut interesting: F) -> Module {
+	if !interesting(&module) {
+		return module;
+	}
+	let mut reducer = Reducer { module, interesting, changed: true };
+	while reducer.changed {
+		reducer.changed = false;
+		reducer.sections();
+		reducer.entries(exports);
+		reducer.entries(data_segments);
+		reducer.entries(element_segments);
+		reducer.bodies();
+		reducer.functions();
+	}
+	reducer.module
+}
+
+struct Reducer<F> {
+	module: Module,
+	interesting: F,
+	/// Whether a candidate was kept since the last pass.
+	changed: bool,
+}
+
+impl<F: FnMut(&Module) -> bool> Reducer<F> {
+	/// Keeps `candidate` if it is interesting.
+	fn try_candidate(&mut self, mut candidate: Module) -> bool {
+		if candidate.data_count_section().is_some() {
+			let segments = candidate.data_section().map_or(0, |ds| ds.entries().len());
+			candidate.set_data_count_section(segments as u32);
+		}
+		if (self.interesting)(&candidate) {
+			self.module = candidate;
+			self.changed = true;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Removes the start function and the custom sections.
+	fn sections(&mut self) {
+		let removable: Vec<usize> = self.module.sections().iter().enumerate()
+			.filter(|&(_, section)| matches!(*section, Section::Start(_) | Section::Custom(_) | Section::Name(_) | Section::Reloc(_)))
+			.map(|(idx, _)| idx)
+			.collect();
+		let sections = self.module.sections().to_vec();
+		minimize(removable.clone(), |kept| {
+			let candidate = sections.iter().enumerate()
+				.filter(|&(idx, _)| !removable.contains(&idx) || kept.contains(&idx))
+				.map(|(_, section)| section.clone())
+				.collect();
+			self.try_candidate(Module::new(candidate))
+		});
+	}
+
+	/// Removes entries of the list `entries` gives access to.
+	fn entries<T: Clone>(&mut self, entries: fn(&mut Module) -> Option<&mut Vec<T>>) {
+		let mut module = self.module.clone();
+		let items = match entries(&mut module) {
+			Some(items) => items.clone(),
+			None => return,
+		};
+		minimize(items, |kept| {
+			let mut candidate = self.module.clone();
+			*entries(&mut candidate).expect("candidates have the section of the module; qed") = kept.to_vec();
+			self.try_candidate(candidate)
+		});
+	}
+
+	/// Stubs, then shrinks, every function body.
+	fn bodies(&mut self) {
+		let count = self.module.code_section().map_or(0, |cs| cs.bodies().len());
+		for body_idx in 0..count {
+			let stub = vec![Instruction::Unreachable, Instruction::End];
+			if self.module.code_section().expect("there are bodies; qed").bodies()[body_idx].code().elements() != &stub[..] {
+				self.try_code(body_idx, stub);
+			}
+			let mut candidate = self.module.clone();
+			let body = &mut candidate.code_section_mut().expect("there are bodies; qed").bodies_mut()[body_idx];
+			if !body.locals().is_empty() {
+				body.locals_mut().clear();
+				self.try_candidate(candidate);
+			}
+
+			let code = self.module.code_section().expect("there are bodies; qed").bodies()[body_idx].code().elements().to_vec();
+			let sequence = code[..code.len().saturating_sub(1)].to_vec();
+			self.sequence(body_idx, sequence, &|sequence| {
+				let mut code = sequence.to_vec();
+				code.push(Instruction::End);
+				code
+			});
+		}
+	}
+
+	/// Removes whole functions, last ones first.
+	fn functions(&mut self) {
+		for func_idx in (0..self.module.functions_space() as u32).rev() {
+			let mut candidate = self.module.clone();
+			if remove_function(&mut candidate, func_idx).is_ok() {
+				self.try_candidate(candidate);
+			}
+		}
+	}
+
+	fn try_code(&mut self, body_idx: usize, code: Vec<Instruction>) -> bool {
+		let mut candidate = self.module.clone();
+		*candidate.code_section_mut().expect("there are bodies; qed").bodies_mut()[body_idx].code_mut() = Instructions::new(code);
+		self.try_candidate(candidate)
+	}
+
+	/// Shrinks `sequence`, a sequence of structured instructions of the body
+	/// `body_idx` whose whole code is given by `wrap`, then the sequences
+	/// nested in the remaining blocks, unwrapping these if possible. Returns
+	/// the shrunk sequence.
+	fn sequence(&mut self, body_idx: usize, sequence: Vec<Instruction>, wrap: &dyn Fn(&[Instruction]) -> Vec<Instruction>) -> Vec<Instruction> {
+		let mut units = minimize(units(&sequence), |kept| self.try_code(body_idx, wrap(&kept.concat())));
+		let mut idx = 0;
+		while idx < units.len() {
+			let unit = units[idx].clone();
+			if unit.len() < 2 {
+				idx += 1;
+				continue;
+			}
+			let interior = &unit[1..unit.len() - 1];
+			if let Instruction::Block(_) | Instruction::Loop(_) = unit[0] {
+				let unwrapped: Vec<Vec<Instruction>> = units[..idx].iter().cloned()
+					.chain(self::units(interior))
+					.chain(units[idx + 1..].iter().cloned())
+					.collect();
+				if self.try_code(body_idx, wrap(&unwrapped.concat())) {
+					units = unwrapped;
+					continue;
+				}
+			}
+			let (then, otherwise) = match else_position(interior) {
+				Some(position) => (interior[..position].to_vec(), Some(interior[position + 1..].to_vec())),
+				None => (interior.to_vec(), None),
+			};
+			let before = units[..idx].concat();
+			let after = units[idx + 1..].concat();
+			let rebuild = |then: &[Instruction], otherwise: Option<&[Instruction]>| {
+				let mut sequence = before.clone();
+				sequence.push(unit[0].clone());
+				sequence.extend_from_slice(then);
+				if let Some(otherwise) = otherwise {
+					sequence.push(Instruction::Else);
+					sequence.extend_from_slice(otherwise);
+				}
+				sequence.push(Instruction::End);
+				sequence.extend_from_slice(&after);
+				wrap(&sequence)
+			};
+			let then = self.sequence(body_idx, then, &|then| rebuild(then, otherwise.as_ref().map(|o| &o[..])));
+			let otherwise = otherwise.map(|otherwise| self.sequence(body_idx, otherwise, &|otherwise| rebuild(&then, Some(otherwise))));
+			let mut shrunk = vec![unit[0].clone()];
+			shrunk.extend(then);
+			if let Some(otherwise) = otherwise {
+				shrunk.push(Instruction::Else);
+				shrunk.extend(otherwise);
+			}
+			shrunk.push(Instruction::End);
+			units[idx] = shrunk;
+			idx += 1;
+		}
+		units.concat()
+	}
+}
+
+fn exports(module: &mut Module) -> Option<&mut Vec<ExportEntry>> {
+	module.export_section_mut().map(|es| es.entries_mut())
+}
+
+fn data_segments(module: &mut Module) -> Option<&mut Vec<DataSegment>> {
+	module.data_section_mut().map(|ds| ds.entries_mut())
+}
+
+fn element_segments(module: &mut Module) -> Option<&mut Vec<ElementSegment>> {
+	module.elements_section_mut().map(|es| es.entries_mut())
+}
+
+/// Splits `sequence` into structured instructions: single instructions, or
+/// blocks with their `end`.
+fn units(sequence: &[Instruction]) -> Vec<Vec<Instruction>> {
+	let mut units = Vec::new();
+	let mut depth = 0usize;
+	let mut start = 0;
+	for (idx, instruction) in sequence.iter().enumerate() {
+		match *instruction {
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => depth += 1,
+			Instruction::End => depth = depth.saturating_sub(1),
+			_ => {},
+		}
+		if depth == 0 {
+			units.push(sequence[start..=idx].to_vec());
+			start = idx + 1;
+		}
+	}
+	if start < sequence.len() {
+		units.push(sequence[start..].to_vec());
+	}
+	units
+}
+
+/// Position of the `else` of the interior of an `if`, if any.
+fn else_position(interior: &[Instruction]) -> Option<usize> {
+	let mut depth = 0usize;
+	for (idx, instruction) in interior.iter().enumerate() {
+		match *instruction {
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => depth += 1,
+			Instruction::End => depth = depth.saturating_sub(1),
+			Instruction::Else if depth == 0 => return Some(idx),
+			_ => {},
+		}
+	}
+	None
+}
+
+/// Removes chunks of `items`, of halving sizes, while `interesting` holds
+/// for the items kept. Returns the items kept.
+fn minimize<T: Clone, F: FnMut(&[T]) -> bool>(mut items: Vec<T>, mut interesting: F) -> Vec<T> {
+	let mut chunk = (items.len() / 2).max(1);
+	while !items.is_empty() {
+		let mut removed = false;
+		let mut start = 0;
+		while start < items.len() {
+			let end = (start + chunk).min(items.len());
+			let candidate: Vec<T> = items[..start].iter().chain(&items[end..]).cloned().collect();
+			if interesting(&candidate) {
+				items = candidate;
+				removed = true;
+			} else {
+				start = end;
+			}
+		}
+		if !removed {
+			if chunk == 1 {
+				break;
+			}
+			chunk /= 2;
+		}
+	}
+	items
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{reduce, minimize};
+	use builder::module;
+	use elements::{Instruction, Instructions, ValueType, DataSegment, InitExpr};
+	use elements::Instruction::*;
+
+	#[test]
+	fn minimize_items() {
+		let mut calls = 0;
+		let kept = minimize((0..16).collect(), |items| {
+			calls += 1;
+			items.contains(&3) && items.contains(&11)
+		});
+		assert_eq!(kept, vec![3, 11]);
+		assert!(calls < 40);
+	}
+
+	#[test]
+	fn reduce_module() {
+		let source = module()
+			.function().signature().with_param(ValueType::I32).build()
+				.body().with_instructions(Instructions::new(vec![
+					I32Const(1),
+					Drop,
+					Block(::elements::BlockType::NoResult),
+					Nop,
+					I32Const(7),
+					I32Const(8),
+					I32Add,
+					Drop,
+					End,
+					End,
+				])).build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Call(0), End])).build()
+				.build()
+			.export().field("a").internal().func(0).build()
+			.export().field("b").internal().func(1).build()
+			.with_data_segment(DataSegment::new(0, Some(InitExpr::new(vec![I32Const(0), End])), vec![1], false))
+			.memory().build()
+			.build();
+
+		// The "bug": adding 7 and 8 somewhere.
+		let reduced = reduce(source, |module| {
+			module.code_section().map_or(false, |cs| cs.bodies().iter().any(|body| {
+				body.code().elements().windows(3).any(|w| w == [I32Const(7), I32Const(8), I32Add])
+			}))
+		});
+		assert!(reduced.export_section().is_none_or(|es| es.entries().is_empty()));
+		assert!(reduced.data_section().is_none_or(|ds| ds.entries().is_empty()));
+		let bodies = reduced.code_section().expect("code section").bodies();
+		assert_eq!(bodies.len(), 1);
+		assert_eq!(bodies[0].code().elements(), &[I32Const(7), I32Const(8), I32Add, Instruction::End][..]);
+	}
+}
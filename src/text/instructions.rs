@@ -0,0 +1,505 @@
+//! Mnemonics of the instructions in the text format.
+
+use elements::{Instruction, MemArg};
+
+macro_rules! plain_instructions {
+	($($variant: ident => $mnemonic: literal,)*) => {
+		/// Mnemonic of an instruction without immediates.
+		pub(crate) fn plain_mnemonic(instruction: &Instruction) -> Option<&'static str> {
+			match *instruction {
+				$(Instruction::$variant => Some($mnemonic),)*
+				_ => None,
+			}
+		}
+
+		/// Instruction without immediates of `mnemonic`.
+		pub(crate) fn plain_instruction(mnemonic: &str) -> Option<Instruction> {
+			match mnemonic {
+				$($mnemonic => Some(Instruction::$variant),)*
+				_ => None,
+			}
+		}
+	}
+}
+
+macro_rules! index_instructions {
+	($($variant: ident => $mnemonic: literal,)*) => {
+		/// Mnemonic and immediate of an instruction with a single index
+		/// immediate.
+		pub(crate) fn index_mnemonic(instruction: &Instruction) -> Option<(&'static str, u32)> {
+			match *instruction {
+				$(Instruction::$variant(idx) => Some(($mnemonic, idx)),)*
+				_ => None,
+			}
+		}
+
+		/// Constructor of the instruction with a single index immediate of
+		/// `mnemonic`.
+		pub(crate) fn index_instruction(mnemonic: &str) -> Option<fn(u32) -> Instruction> {
+			match mnemonic {
+				$($mnemonic => Some(Instruction::$variant as fn(u32) -> Instruction),)*
+				_ => None,
+			}
+		}
+	}
+}
+
+macro_rules! lane_instructions {
+	($($variant: ident => $mnemonic: literal,)*) => {
+		/// Mnemonic and lane of a SIMD lane instruction.
+		pub(crate) fn lane_mnemonic(instruction: &Instruction) -> Option<(&'static str, u8)> {
+			match *instruction {
+				$(Instruction::$variant(lane) => Some(($mnemonic, lane)),)*
+				_ => None,
+			}
+		}
+
+		/// Constructor of the SIMD lane instruction of `mnemonic`.
+		pub(crate) fn lane_instruction(mnemonic: &str) -> Option<fn(u8) -> Instruction> {
+			match mnemonic {
+				$($mnemonic => Some(Instruction::$variant as fn(u8) -> Instruction),)*
+				_ => None,
+			}
+		}
+	}
+}
+
+macro_rules! memory_instructions {
+	(
+		$($variant: ident => $mnemonic: literal, $natural: literal,)*
+		;
+		$($arg_variant: ident => $arg_mnemonic: literal, $arg_natural: literal,)*
+	) => {
+		/// Mnemonic, natural alignment and memory immediate (as log2 of the
+		/// alignment and offset) of a memory access.
+		pub(crate) fn memory_mnemonic(instruction: &Instruction) -> Option<(&'static str, u32, MemArg)> {
+			match *instruction {
+				$(Instruction::$variant(align, offset) => Some(($mnemonic, $natural, MemArg { align: align as u8, offset })),)*
+				$(Instruction::$arg_variant(ref arg) => Some(($arg_mnemonic, $arg_natural, arg.clone())),)*
+				_ => None,
+			}
+		}
+
+		/// Natural alignment and constructor of the memory access of
+		/// `mnemonic`.
+		pub(crate) fn memory_instruction(mnemonic: &str) -> Option<(u32, fn(MemArg) -> Instruction)> {
+			match mnemonic {
+				$($mnemonic => Some(($natural, (|arg: MemArg| Instruction::$variant(u32::from(arg.align), arg.offset)) as fn(MemArg) -> Instruction)),)*
+				$($arg_mnemonic => Some(($arg_natural, Instruction::$arg_variant as fn(MemArg) -> Instruction)),)*
+				_ => None,
+			}
+		}
+	}
+}
+
+plain_instructions! {
+	Unreachable => "unreachable",
+	Nop => "nop",
+	Else => "else",
+	End => "end",
+	Return => "return",
+	Drop => "drop",
+
+	I32Eqz => "i32.eqz",
+	I32Eq => "i32.eq",
+	I32Ne => "i32.ne",
+	I32LtS => "i32.lt_s",
+	I32LtU => "i32.lt_u",
+	I32GtS => "i32.gt_s",
+	I32GtU => "i32.gt_u",
+	I32LeS => "i32.le_s",
+	I32LeU => "i32.le_u",
+	I32GeS => "i32.ge_s",
+	I32GeU => "i32.ge_u",
+
+	I64Eqz => "i64.eqz",
+	I64Eq => "i64.eq",
+	I64Ne => "i64.ne",
+	I64LtS => "i64.lt_s",
+	I64LtU => "i64.lt_u",
+	I64GtS => "i64.gt_s",
+	I64GtU => "i64.gt_u",
+	I64LeS => "i64.le_s",
+	I64LeU => "i64.le_u",
+	I64GeS => "i64.ge_s",
+	I64GeU => "i64.ge_u",
+
+	F32Eq => "f32.eq",
+	F32Ne => "f32.ne",
+	F32Lt => "f32.lt",
+	F32Gt => "f32.gt",
+	F32Le => "f32.le",
+	F32Ge => "f32.ge",
+
+	F64Eq => "f64.eq",
+	F64Ne => "f64.ne",
+	F64Lt => "f64.lt",
+	F64Gt => "f64.gt",
+	F64Le => "f64.le",
+	F64Ge => "f64.ge",
+
+	I32Clz => "i32.clz",
+	I32Ctz => "i32.ctz",
+	I32Popcnt => "i32.popcnt",
+	I32Add => "i32.add",
+	I32Sub => "i32.sub",
+	I32Mul => "i32.mul",
+	I32DivS => "i32.div_s",
+	I32DivU => "i32.div_u",
+	I32RemS => "i32.rem_s",
+	I32RemU => "i32.rem_u",
+	I32And => "i32.and",
+	I32Or => "i32.or",
+	I32Xor => "i32.xor",
+	I32Shl => "i32.shl",
+	I32ShrS => "i32.shr_s",
+	I32ShrU => "i32.shr_u",
+	I32Rotl => "i32.rotl",
+	I32Rotr => "i32.rotr",
+
+	I64Clz => "i64.clz",
+	I64Ctz => "i64.ctz",
+	I64Popcnt => "i64.popcnt",
+	I64Add => "i64.add",
+	I64Sub => "i64.sub",
+	I64Mul => "i64.mul",
+	I64DivS => "i64.div_s",
+	I64DivU => "i64.div_u",
+	I64RemS => "i64.rem_s",
+	I64RemU => "i64.rem_u",
+	I64And => "i64.and",
+	I64Or => "i64.or",
+	I64Xor => "i64.xor",
+	I64Shl => "i64.shl",
+	I64ShrS => "i64.shr_s",
+	I64ShrU => "i64.shr_u",
+	I64Rotl => "i64.rotl",
+	I64Rotr => "i64.rotr",
+
+	F32Abs => "f32.abs",
+	F32Neg => "f32.neg",
+	F32Ceil => "f32.ceil",
+	F32Floor => "f32.floor",
+	F32Trunc => "f32.trunc",
+	F32Nearest => "f32.nearest",
+	F32Sqrt => "f32.sqrt",
+	F32Add => "f32.add",
+	F32Sub => "f32.sub",
+	F32Mul => "f32.mul",
+	F32Div => "f32.div",
+	F32Min => "f32.min",
+	F32Max => "f32.max",
+	F32Copysign => "f32.copysign",
+
+	F64Abs => "f64.abs",
+	F64Neg => "f64.neg",
+	F64Ceil => "f64.ceil",
+	F64Floor => "f64.floor",
+	F64Trunc => "f64.trunc",
+	F64Nearest => "f64.nearest",
+	F64Sqrt => "f64.sqrt",
+	F64Add => "f64.add",
+	F64Sub => "f64.sub",
+	F64Mul => "f64.mul",
+	F64Div => "f64.div",
+	F64Min => "f64.min",
+	F64Max => "f64.max",
+	F64Copysign => "f64.copysign",
+
+	I32WrapI64 => "i32.wrap_i64",
+	I32TruncSF32 => "i32.trunc_f32_s",
+	I32TruncUF32 => "i32.trunc_f32_u",
+	I32TruncSF64 => "i32.trunc_f64_s",
+	I32TruncUF64 => "i32.trunc_f64_u",
+	I64ExtendSI32 => "i64.extend_i32_s",
+	I64ExtendUI32 => "i64.extend_i32_u",
+	I64TruncSF32 => "i64.trunc_f32_s",
+	I64TruncUF32 => "i64.trunc_f32_u",
+	I64TruncSF64 => "i64.trunc_f64_s",
+	I64TruncUF64 => "i64.trunc_f64_u",
+	F32ConvertSI32 => "f32.convert_i32_s",
+	F32ConvertUI32 => "f32.convert_i32_u",
+	F32ConvertSI64 => "f32.convert_i64_s",
+	F32ConvertUI64 => "f32.convert_i64_u",
+	F32DemoteF64 => "f32.demote_f64",
+	F64ConvertSI32 => "f64.convert_i32_s",
+	F64ConvertUI32 => "f64.convert_i32_u",
+	F64ConvertSI64 => "f64.convert_i64_s",
+	F64ConvertUI64 => "f64.convert_i64_u",
+	F64PromoteF32 => "f64.promote_f32",
+
+	I32ReinterpretF32 => "i32.reinterpret_f32",
+	I64ReinterpretF64 => "i64.reinterpret_f64",
+	F32ReinterpretI32 => "f32.reinterpret_i32",
+	F64ReinterpretI64 => "f64.reinterpret_i64",
+
+	I32Extend8S => "i32.extend8_s",
+	I32Extend16S => "i32.extend16_s",
+	I64Extend8S => "i64.extend8_s",
+	I64Extend16S => "i64.extend16_s",
+	I64Extend32S => "i64.extend32_s",
+
+	I8x16Splat => "i8x16.splat",
+	I16x8Splat => "i16x8.splat",
+	I32x4Splat => "i32x4.splat",
+	I64x2Splat => "i64x2.splat",
+	F32x4Splat => "f32x4.splat",
+	F64x2Splat => "f64x2.splat",
+	I8x16Add => "i8x16.add",
+	I16x8Add => "i16x8.add",
+	I32x4Add => "i32x4.add",
+	I64x2Add => "i64x2.add",
+	I8x16Sub => "i8x16.sub",
+	I16x8Sub => "i16x8.sub",
+	I32x4Sub => "i32x4.sub",
+	I64x2Sub => "i64x2.sub",
+	I8x16Mul => "i8x16.mul",
+	I16x8Mul => "i16x8.mul",
+	I32x4Mul => "i32x4.mul",
+	I8x16Neg => "i8x16.neg",
+	I16x8Neg => "i16x8.neg",
+	I32x4Neg => "i32x4.neg",
+	I64x2Neg => "i64x2.neg",
+	I8x16AddSaturateS => "i8x16.add_saturate_s",
+	I8x16AddSaturateU => "i8x16.add_saturate_u",
+	I16x8AddSaturateS => "i16x8.add_saturate_s",
+	I16x8AddSaturateU => "i16x8.add_saturate_u",
+	I8x16SubSaturateS => "i8x16.sub_saturate_s",
+	I8x16SubSaturateU => "i8x16.sub_saturate_u",
+	I16x8SubSaturateS => "i16x8.sub_saturate_s",
+	I16x8SubSaturateU => "i16x8.sub_saturate_u",
+	I8x16Shl => "i8x16.shl",
+	I16x8Shl => "i16x8.shl",
+	I32x4Shl => "i32x4.shl",
+	I64x2Shl => "i64x2.shl",
+	I8x16ShrS => "i8x16.shr_s",
+	I8x16ShrU => "i8x16.shr_u",
+	I16x8ShrS => "i16x8.shr_s",
+	I16x8ShrU => "i16x8.shr_u",
+	I32x4ShrS => "i32x4.shr_s",
+	I32x4ShrU => "i32x4.shr_u",
+	I64x2ShrS => "i64x2.shr_s",
+	I64x2ShrU => "i64x2.shr_u",
+	V128And => "v128.and",
+	V128Or => "v128.or",
+	V128Xor => "v128.xor",
+	V128Not => "v128.not",
+	V128Bitselect => "v128.bitselect",
+	I8x16AnyTrue => "i8x16.any_true",
+	I16x8AnyTrue => "i16x8.any_true",
+	I32x4AnyTrue => "i32x4.any_true",
+	I64x2AnyTrue => "i64x2.any_true",
+	I8x16AllTrue => "i8x16.all_true",
+	I16x8AllTrue => "i16x8.all_true",
+	I32x4AllTrue => "i32x4.all_true",
+	I64x2AllTrue => "i64x2.all_true",
+	I8x16Eq => "i8x16.eq",
+	I16x8Eq => "i16x8.eq",
+	I32x4Eq => "i32x4.eq",
+	F32x4Eq => "f32x4.eq",
+	F64x2Eq => "f64x2.eq",
+	I8x16Ne => "i8x16.ne",
+	I16x8Ne => "i16x8.ne",
+	I32x4Ne => "i32x4.ne",
+	F32x4Ne => "f32x4.ne",
+	F64x2Ne => "f64x2.ne",
+	I8x16LtS => "i8x16.lt_s",
+	I8x16LtU => "i8x16.lt_u",
+	I16x8LtS => "i16x8.lt_s",
+	I16x8LtU => "i16x8.lt_u",
+	I32x4LtS => "i32x4.lt_s",
+	I32x4LtU => "i32x4.lt_u",
+	F32x4Lt => "f32x4.lt",
+	F64x2Lt => "f64x2.lt",
+	I8x16LeS => "i8x16.le_s",
+	I8x16LeU => "i8x16.le_u",
+	I16x8LeS => "i16x8.le_s",
+	I16x8LeU => "i16x8.le_u",
+	I32x4LeS => "i32x4.le_s",
+	I32x4LeU => "i32x4.le_u",
+	F32x4Le => "f32x4.le",
+	F64x2Le => "f64x2.le",
+	I8x16GtS => "i8x16.gt_s",
+	I8x16GtU => "i8x16.gt_u",
+	I16x8GtS => "i16x8.gt_s",
+	I16x8GtU => "i16x8.gt_u",
+	I32x4GtS => "i32x4.gt_s",
+	I32x4GtU => "i32x4.gt_u",
+	F32x4Gt => "f32x4.gt",
+	F64x2Gt => "f64x2.gt",
+	I8x16GeS => "i8x16.ge_s",
+	I8x16GeU => "i8x16.ge_u",
+	I16x8GeS => "i16x8.ge_s",
+	I16x8GeU => "i16x8.ge_u",
+	I32x4GeS => "i32x4.ge_s",
+	I32x4GeU => "i32x4.ge_u",
+	F32x4Ge => "f32x4.ge",
+	F64x2Ge => "f64x2.ge",
+	F32x4Neg => "f32x4.neg",
+	F64x2Neg => "f64x2.neg",
+	F32x4Abs => "f32x4.abs",
+	F64x2Abs => "f64x2.abs",
+	F32x4Min => "f32x4.min",
+	F64x2Min => "f64x2.min",
+	F32x4Max => "f32x4.max",
+	F64x2Max => "f64x2.max",
+	F32x4Add => "f32x4.add",
+	F64x2Add => "f64x2.add",
+	F32x4Sub => "f32x4.sub",
+	F64x2Sub => "f64x2.sub",
+	F32x4Div => "f32x4.div",
+	F64x2Div => "f64x2.div",
+	F32x4Mul => "f32x4.mul",
+	F64x2Mul => "f64x2.mul",
+	F32x4Sqrt => "f32x4.sqrt",
+	F64x2Sqrt => "f64x2.sqrt",
+	F32x4ConvertSI32x4 => "f32x4.convert_i32x4_s",
+	F32x4ConvertUI32x4 => "f32x4.convert_i32x4_u",
+	F64x2ConvertSI64x2 => "f64x2.convert_i64x2_s",
+	F64x2ConvertUI64x2 => "f64x2.convert_i64x2_u",
+	I32x4TruncSF32x4Sat => "i32x4.trunc_sat_f32x4_s",
+	I32x4TruncUF32x4Sat => "i32x4.trunc_sat_f32x4_u",
+	I64x2TruncSF64x2Sat => "i64x2.trunc_sat_f64x2_s",
+	I64x2TruncUF64x2Sat => "i64x2.trunc_sat_f64x2_u",
+
+	MemoryCopy => "memory.copy",
+	MemoryFill => "memory.fill",
+
+	RefIsNull => "ref.is_null",
+}
+
+index_instructions! {
+	Br => "br",
+	BrIf => "br_if",
+	Call => "call",
+	GetLocal => "local.get",
+	SetLocal => "local.set",
+	TeeLocal => "local.tee",
+	GetGlobal => "global.get",
+	SetGlobal => "global.set",
+	MemoryInit => "memory.init",
+	MemoryDrop => "data.drop",
+	TableDrop => "elem.drop",
+	RefFunc => "ref.func",
+}
+
+lane_instructions! {
+	I8x16ExtractLaneS => "i8x16.extract_lane_s",
+	I8x16ExtractLaneU => "i8x16.extract_lane_u",
+	I16x8ExtractLaneS => "i16x8.extract_lane_s",
+	I16x8ExtractLaneU => "i16x8.extract_lane_u",
+	I32x4ExtractLane => "i32x4.extract_lane",
+	I64x2ExtractLane => "i64x2.extract_lane",
+	F32x4ExtractLane => "f32x4.extract_lane",
+	F64x2ExtractLane => "f64x2.extract_lane",
+	I8x16ReplaceLane => "i8x16.replace_lane",
+	I16x8ReplaceLane => "i16x8.replace_lane",
+	I32x4ReplaceLane => "i32x4.replace_lane",
+	I64x2ReplaceLane => "i64x2.replace_lane",
+	F32x4ReplaceLane => "f32x4.replace_lane",
+	F64x2ReplaceLane => "f64x2.replace_lane",
+}
+
+memory_instructions! {
+	I32Load => "i32.load", 2,
+	I64Load => "i64.load", 3,
+	F32Load => "f32.load", 2,
+	F64Load => "f64.load", 3,
+	I32Load8S => "i32.load8_s", 0,
+	I32Load8U => "i32.load8_u", 0,
+	I32Load16S => "i32.load16_s", 1,
+	I32Load16U => "i32.load16_u", 1,
+	I64Load8S => "i64.load8_s", 0,
+	I64Load8U => "i64.load8_u", 0,
+	I64Load16S => "i64.load16_s", 1,
+	I64Load16U => "i64.load16_u", 1,
+	I64Load32S => "i64.load32_s", 2,
+	I64Load32U => "i64.load32_u", 2,
+	I32Store => "i32.store", 2,
+	I64Store => "i64.store", 3,
+	F32Store => "f32.store", 2,
+	F64Store => "f64.store", 3,
+	I32Store8 => "i32.store8", 0,
+	I32Store16 => "i32.store16", 1,
+	I64Store8 => "i64.store8", 0,
+	I64Store16 => "i64.store16", 1,
+	I64Store32 => "i64.store32", 2,
+	;
+	AtomicWake => "memory.atomic.notify", 2,
+	I32AtomicWait => "memory.atomic.wait32", 2,
+	I64AtomicWait => "memory.atomic.wait64", 3,
+
+	I32AtomicLoad => "i32.atomic.load", 2,
+	I64AtomicLoad => "i64.atomic.load", 3,
+	I32AtomicLoad8u => "i32.atomic.load8_u", 0,
+	I32AtomicLoad16u => "i32.atomic.load16_u", 1,
+	I64AtomicLoad8u => "i64.atomic.load8_u", 0,
+	I64AtomicLoad16u => "i64.atomic.load16_u", 1,
+	I64AtomicLoad32u => "i64.atomic.load32_u", 2,
+	I32AtomicStore => "i32.atomic.store", 2,
+	I64AtomicStore => "i64.atomic.store", 3,
+	I32AtomicStore8u => "i32.atomic.store8", 0,
+	I32AtomicStore16u => "i32.atomic.store16", 1,
+	I64AtomicStore8u => "i64.atomic.store8", 0,
+	I64AtomicStore16u => "i64.atomic.store16", 1,
+	I64AtomicStore32u => "i64.atomic.store32", 2,
+
+	I32AtomicRmwAdd => "i32.atomic.rmw.add", 2,
+	I64AtomicRmwAdd => "i64.atomic.rmw.add", 3,
+	I32AtomicRmwAdd8u => "i32.atomic.rmw8.add_u", 0,
+	I32AtomicRmwAdd16u => "i32.atomic.rmw16.add_u", 1,
+	I64AtomicRmwAdd8u => "i64.atomic.rmw8.add_u", 0,
+	I64AtomicRmwAdd16u => "i64.atomic.rmw16.add_u", 1,
+	I64AtomicRmwAdd32u => "i64.atomic.rmw32.add_u", 2,
+
+	I32AtomicRmwSub => "i32.atomic.rmw.sub", 2,
+	I64AtomicRmwSub => "i64.atomic.rmw.sub", 3,
+	I32AtomicRmwSub8u => "i32.atomic.rmw8.sub_u", 0,
+	I32AtomicRmwSub16u => "i32.atomic.rmw16.sub_u", 1,
+	I64AtomicRmwSub8u => "i64.atomic.rmw8.sub_u", 0,
+	I64AtomicRmwSub16u => "i64.atomic.rmw16.sub_u", 1,
+	I64AtomicRmwSub32u => "i64.atomic.rmw32.sub_u", 2,
+
+	I32AtomicRmwAnd => "i32.atomic.rmw.and", 2,
+	I64AtomicRmwAnd => "i64.atomic.rmw.and", 3,
+	I32AtomicRmwAnd8u => "i32.atomic.rmw8.and_u", 0,
+	I32AtomicRmwAnd16u => "i32.atomic.rmw16.and_u", 1,
+	I64AtomicRmwAnd8u => "i64.atomic.rmw8.and_u", 0,
+	I64AtomicRmwAnd16u => "i64.atomic.rmw16.and_u", 1,
+	I64AtomicRmwAnd32u => "i64.atomic.rmw32.and_u", 2,
+
+	I32AtomicRmwOr => "i32.atomic.rmw.or", 2,
+	I64AtomicRmwOr => "i64.atomic.rmw.or", 3,
+	I32AtomicRmwOr8u => "i32.atomic.rmw8.or_u", 0,
+	I32AtomicRmwOr16u => "i32.atomic.rmw16.or_u", 1,
+	I64AtomicRmwOr8u => "i64.atomic.rmw8.or_u", 0,
+	I64AtomicRmwOr16u => "i64.atomic.rmw16.or_u", 1,
+	I64AtomicRmwOr32u => "i64.atomic.rmw32.or_u", 2,
+
+	I32AtomicRmwXor => "i32.atomic.rmw.xor", 2,
+	I64AtomicRmwXor => "i64.atomic.rmw.xor", 3,
+	I32AtomicRmwXor8u => "i32.atomic.rmw8.xor_u", 0,
+	I32AtomicRmwXor16u => "i32.atomic.rmw16.xor_u", 1,
+	I64AtomicRmwXor8u => "i64.atomic.rmw8.xor_u", 0,
+	I64AtomicRmwXor16u => "i64.atomic.rmw16.xor_u", 1,
+	I64AtomicRmwXor32u => "i64.atomic.rmw32.xor_u", 2,
+
+	I32AtomicRmwXchg => "i32.atomic.rmw.xchg", 2,
+	I64AtomicRmwXchg => "i64.atomic.rmw.xchg", 3,
+	I32AtomicRmwXchg8u => "i32.atomic.rmw8.xchg_u", 0,
+	I32AtomicRmwXchg16u => "i32.atomic.rmw16.xchg_u", 1,
+	I64AtomicRmwXchg8u => "i64.atomic.rmw8.xchg_u", 0,
+	I64AtomicRmwXchg16u => "i64.atomic.rmw16.xchg_u", 1,
+	I64AtomicRmwXchg32u => "i64.atomic.rmw32.xchg_u", 2,
+
+	I32AtomicRmwCmpxchg => "i32.atomic.rmw.cmpxchg", 2,
+	I64AtomicRmwCmpxchg => "i64.atomic.rmw.cmpxchg", 3,
+	I32AtomicRmwCmpxchg8u => "i32.atomic.rmw8.cmpxchg_u", 0,
+	I32AtomicRmwCmpxchg16u => "i32.atomic.rmw16.cmpxchg_u", 1,
+	I64AtomicRmwCmpxchg8u => "i64.atomic.rmw8.cmpxchg_u", 0,
+	I64AtomicRmwCmpxchg16u => "i64.atomic.rmw16.cmpxchg_u", 1,
+	I64AtomicRmwCmpxchg32u => "i64.atomic.rmw32.cmpxchg_u", 2,
+
+	V128Load => "v128.load", 4,
+	V128Store => "v128.store", 4,
+}
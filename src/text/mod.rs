@@ -0,0 +1,9 @@
+//! Text format of WebAssembly modules.
+
+mod instructions;
+mod printer;
+mod parser;
+mod sexpr;
+
+pub use self::printer::to_wat;
+pub use self::parser::from_wat;
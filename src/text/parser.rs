@@ -0,0 +1,1467 @@
+use std::vec::Vec;
+use std::string::{String, ToString};
+use std::boxed::Box;
+use elements::{
+	Module, Section, Error, Instruction, Instructions, InitExpr, BlockType, BrTableData, MemArg,
+	FunctionType, Type, ValueType, Func, FuncBody, Local, GlobalEntry, GlobalType, TableType, TableElementType,
+	MemoryType, ImportEntry, External, ExportEntry, Internal, ElementSegment, DataSegment,
+	TypeSection, ImportSection, FunctionSection, TableSection, MemorySection, GlobalSection,
+	ExportSection, ElementSection, CodeSection, DataSection, NameSection, ModuleNameSection,
+	FunctionNameSection, LocalNameSection, NameMap,
+};
+use super::instructions::{plain_instruction, index_instruction, lane_instruction, memory_instruction};
+use super::sexpr::{self, Sexpr, Items, ParseError};
+
+/// Parses a module in the text format.
+///
+/// The source is either a `(module ...)` or the bare fields of a module.
+/// Instructions can be written flat or folded, and functions, locals,
+/// labels, types, tables, memories, globals and segments referred to by
+/// their identifier. The identifiers of the module, of the functions and of
+/// the locals are recorded in the name section, so that printing the module
+/// with `to_wat` keeps them.
+///
+/// Block types and function types have at most one result, as for the rest
+/// of the crate, and element segments only hold function indices. Errors
+/// are reported with the line and column they occur at.
+pub fn from_wat(source: &str) -> Result<Module, Error> {
+	parse(source).map_err(|error| {
+		let before = &source[..error.offset];
+		let line = before.matches('\n').count() + 1;
+		let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+		Error::HeapOther(format!("{}:{}: {}", line, column, error.message))
+	})
+}
+
+fn parse(source: &str) -> Result<Module, ParseError> {
+	let sexprs = sexpr::read(source)?;
+	let (module_id, fields) = match sexprs.first() {
+		Some(&Sexpr::List(ref items, offset)) if sexprs.len() == 1 && is_list(items, "module") => {
+			let mut items = Items::new(&items[1..], offset);
+			let id = items.id();
+			(id, items)
+		},
+		_ => (None, Items::new(&sexprs, 0)),
+	};
+
+	let mut parser = ModuleParser { module_id, ..ModuleParser::default() };
+	let mut fields_list = Vec::new();
+	let mut remaining = fields;
+	while let Some(field) = remaining.next() {
+		match *field {
+			Sexpr::List(ref items, offset) => match items.first() {
+				Some(&Sexpr::Atom(keyword, _)) => fields_list.push((keyword, Items::new(&items[1..], offset))),
+				_ => return Err(ParseError::new(offset, "expected a module field")),
+			},
+			ref other => return Err(ParseError::new(other.offset(), "expected a module field")),
+		}
+	}
+	for &(keyword, ref items) in &fields_list {
+		parser.declare(keyword, items.clone())?;
+	}
+	for &(keyword, ref items) in &fields_list {
+		parser.define(keyword, items.clone())?;
+	}
+	parser.into_module()
+}
+
+fn is_list(items: &[Sexpr], keyword: &str) -> bool {
+	match items.first() {
+		Some(&Sexpr::Atom(atom, _)) => atom == keyword,
+		_ => false,
+	}
+}
+
+/// Whether `atom` is an index: a number or an identifier.
+fn is_index(atom: &str) -> bool {
+	atom.starts_with('$') || atom.bytes().next().map_or(false, |c| c.is_ascii_digit())
+}
+
+/// Identifiers of an index space, with the number of items in it.
+#[derive(Default)]
+struct Space<'a> {
+	ids: Vec<(&'a str, u32)>,
+	len: u32,
+}
+
+impl<'a> Space<'a> {
+	fn push(&mut self, id: Option<&'a str>, offset: usize) -> Result<u32, ParseError> {
+		if let Some(id) = id {
+			if self.ids.iter().any(|&(existing, _)| existing == id) {
+				return Err(ParseError::new(offset, format!("duplicate identifier {}", id)));
+			}
+			self.ids.push((id, self.len));
+		}
+		self.len += 1;
+		Ok(self.len - 1)
+	}
+
+	fn resolve(&self, atom: &str, offset: usize, what: &str) -> Result<u32, ParseError> {
+		if atom.starts_with('$') {
+			self.ids.iter()
+				.find(|&&(id, _)| id == atom)
+				.map(|&(_, idx)| idx)
+				.ok_or_else(|| ParseError::new(offset, format!("unknown {} {}", what, atom)))
+		} else {
+			parse_u32(atom).ok_or_else(|| ParseError::new(offset, format!("invalid {} index {}", what, atom)))
+		}
+	}
+}
+
+/// Locals, labels and code of the instructions being parsed.
+#[derive(Default)]
+struct Body<'a> {
+	locals: Space<'a>,
+	labels: Vec<Option<&'a str>>,
+	code: Vec<Instruction>,
+}
+
+#[derive(Default)]
+struct ModuleParser<'a> {
+	module_id: Option<&'a str>,
+	/// Whether a function, table, memory or global was defined, after which
+	/// nothing can be imported.
+	defined: bool,
+
+	type_ids: Space<'a>,
+	funcs: Space<'a>,
+	tables: Space<'a>,
+	memories: Space<'a>,
+	globals: Space<'a>,
+	elems: Space<'a>,
+	datas: Space<'a>,
+
+	types: Vec<FunctionType>,
+	imports: Vec<ImportEntry>,
+	functions: Vec<Func>,
+	bodies: Vec<FuncBody>,
+	table_entries: Vec<TableType>,
+	memory_entries: Vec<MemoryType>,
+	global_entries: Vec<GlobalEntry>,
+	exports: Vec<ExportEntry>,
+	start: Option<u32>,
+	elements: Vec<ElementSegment>,
+	data: Vec<DataSegment>,
+	uses_data_count: bool,
+	/// Identifiers of the locals, by function.
+	local_ids: Vec<(u32, Vec<(&'a str, u32)>)>,
+}
+
+impl<'a> ModuleParser<'a> {
+	/// Registers the identifiers of a field and the explicit types, so that
+	/// fields can refer to the ones coming after them.
+	fn declare<'s>(&mut self, keyword: &'a str, mut items: Items<'s, 'a>) -> Result<(), ParseError> {
+		let offset = items.offset();
+		match keyword {
+			"type" => {
+				let id = items.id();
+				let mut func = items.list("func").ok_or_else(|| ParseError::new(items.offset(), "expected a function type"))?;
+				items.end()?;
+				func.id();
+				let (_, func_type, _) = self.signature(&mut func)?;
+				func.end()?;
+				self.type_ids.push(id, offset)?;
+				self.types.push(func_type);
+			},
+			"import" => {
+				items.string()?;
+				items.string()?;
+				let (kind, mut desc) = match items.next() {
+					Some(&Sexpr::List(ref desc, offset)) => match desc.first() {
+						Some(&Sexpr::Atom(kind, _)) => (kind, Items::new(&desc[1..], offset)),
+						_ => return Err(ParseError::new(offset, "expected an import description")),
+					},
+					_ => return Err(ParseError::new(items.offset(), "expected an import description")),
+				};
+				if self.defined {
+					return Err(ParseError::new(offset, "imports must come before definitions"));
+				}
+				let id = desc.id();
+				self.space(kind, offset)?.push(id, offset)?;
+			},
+			"func" | "table" | "memory" | "global" => {
+				let id = items.id();
+				while items.list("export").is_some() {}
+				let imported = items.list("import").is_some();
+				if imported && self.defined {
+					return Err(ParseError::new(offset, "imports must come before definitions"));
+				}
+				self.defined |= !imported;
+				self.space(keyword, offset)?.push(id, offset)?;
+				if !imported && keyword == "table" && (items.keyword("funcref") || items.keyword("anyfunc")) && items.list("elem").is_some() {
+					self.elems.push(None, offset)?;
+				}
+				if !imported && keyword == "memory" && items.list("data").is_some() {
+					self.datas.push(None, offset)?;
+				}
+			},
+			"elem" => { self.elems.push(items.id(), offset)?; },
+			"data" => { self.datas.push(items.id(), offset)?; },
+			"export" | "start" => {},
+			_ => return Err(ParseError::new(offset, format!("unknown module field {}", keyword))),
+		}
+		Ok(())
+	}
+
+	fn space(&mut self, kind: &str, offset: usize) -> Result<&mut Space<'a>, ParseError> {
+		match kind {
+			"func" => Ok(&mut self.funcs),
+			"table" => Ok(&mut self.tables),
+			"memory" => Ok(&mut self.memories),
+			"global" => Ok(&mut self.globals),
+			_ => Err(ParseError::new(offset, format!("unknown kind {}", kind))),
+		}
+	}
+
+	/// Parses a field, now that every identifier is known.
+	fn define<'s>(&mut self, keyword: &'a str, mut items: Items<'s, 'a>) -> Result<(), ParseError> {
+		let offset = items.offset();
+		match keyword {
+			"import" => {
+				let module = items.name()?;
+				let field = items.name()?;
+				let (kind, mut desc, desc_offset) = match items.next() {
+					Some(&Sexpr::List(ref desc, offset)) => match desc.first() {
+						Some(&Sexpr::Atom(kind, _)) => (kind, Items::new(&desc[1..], offset), offset),
+						_ => unreachable!("checked when declared"),
+					},
+					_ => unreachable!("checked when declared"),
+				};
+				items.end()?;
+				desc.id();
+				let external = self.external(kind, &mut desc, desc_offset)?;
+				desc.end()?;
+				self.imports.push(ImportEntry::new(module, field, external));
+			},
+			"func" | "table" | "memory" | "global" => {
+				items.id();
+				let mut exports = Vec::new();
+				while let Some(mut export) = items.list("export") {
+					exports.push(export.name()?);
+					export.end()?;
+				}
+				let internal = match items.list("import") {
+					Some(mut import) => {
+						let module = import.name()?;
+						let field = import.name()?;
+						import.end()?;
+						let external = self.external(keyword, &mut items, offset)?;
+						let internal = match external {
+							External::Function(_) => Internal::Function(self.count(External::Function(0))),
+							External::Table(_) => Internal::Table(self.count(External::Table(TableType::new(0, None)))),
+							External::Memory(_) => Internal::Memory(self.count(External::Memory(MemoryType::new(0, None, false)))),
+							External::Global(_) => Internal::Global(self.count(External::Global(GlobalType::new(ValueType::I32, false)))),
+						};
+						self.imports.push(ImportEntry::new(module, field, external));
+						internal
+					},
+					None => match keyword {
+						"func" => self.func(&mut items, offset)?,
+						"table" => self.table(&mut items)?,
+						"memory" => self.memory(&mut items)?,
+						_ => self.global(&mut items)?,
+					},
+				};
+				items.end()?;
+				for field in exports {
+					self.exports.push(ExportEntry::new(field, internal));
+				}
+			},
+			"export" => {
+				let field = items.name()?;
+				let mut desc_items = None;
+				for kind in &["func", "table", "memory", "global"] {
+					if let Some(desc) = items.list(kind) {
+						desc_items = Some((*kind, desc));
+						break;
+					}
+				}
+				let (kind, mut desc) = desc_items.ok_or_else(|| ParseError::new(items.offset(), "expected an export description"))?;
+				items.end()?;
+				let atom_offset = desc.offset();
+				let atom = desc.atom()?;
+				desc.end()?;
+				let internal = match kind {
+					"func" => Internal::Function(self.funcs.resolve(atom, atom_offset, "function")?),
+					"table" => Internal::Table(self.tables.resolve(atom, atom_offset, "table")?),
+					"memory" => Internal::Memory(self.memories.resolve(atom, atom_offset, "memory")?),
+					_ => Internal::Global(self.globals.resolve(atom, atom_offset, "global")?),
+				};
+				self.exports.push(ExportEntry::new(field, internal));
+			},
+			"start" => {
+				if self.start.is_some() {
+					return Err(ParseError::new(offset, "multiple start functions"));
+				}
+				let atom_offset = items.offset();
+				let atom = items.atom()?;
+				items.end()?;
+				self.start = Some(self.funcs.resolve(atom, atom_offset, "function")?);
+			},
+			"elem" => {
+				items.id();
+				let segment = if items.keyword("declare") {
+					ElementSegment::new_declared(self.members(&mut items)?)
+				} else {
+					let table = match items.list("table") {
+						Some(mut table) => {
+							let atom_offset = table.offset();
+							let idx = self.tables.resolve(table.atom()?, atom_offset, "table")?;
+							table.end()?;
+							idx
+						},
+						None => match items.peek_atom() {
+							Some(atom) if is_index(atom) => {
+								items.next();
+								self.tables.resolve(atom, offset, "table")?
+							},
+							_ => 0,
+						},
+					};
+					let init = self.offset(&mut items)?;
+					let passive = init.is_none();
+					ElementSegment::new(table, init, self.members(&mut items)?, passive)
+				};
+				self.elements.push(segment);
+			},
+			"data" => {
+				items.id();
+				let memory = match items.list("memory") {
+					Some(mut memory) => {
+						let atom_offset = memory.offset();
+						let idx = self.memories.resolve(memory.atom()?, atom_offset, "memory")?;
+						memory.end()?;
+						idx
+					},
+					None => match items.peek_atom() {
+						Some(atom) if is_index(atom) => {
+							items.next();
+							self.memories.resolve(atom, offset, "memory")?
+						},
+						_ => 0,
+					},
+				};
+				let init = self.offset(&mut items)?;
+				let passive = init.is_none();
+				let value = strings(&mut items)?;
+				self.data.push(DataSegment::new(memory, init, value, passive));
+			},
+			_ => {},
+		}
+		Ok(())
+	}
+
+	/// Number of imports of the same kind as `external`.
+	fn count(&self, external: External) -> u32 {
+		self.imports.iter().filter(|entry| matches!((*entry.external(), external),
+			(External::Function(_), External::Function(_))
+				| (External::Table(_), External::Table(_))
+				| (External::Memory(_), External::Memory(_))
+				| (External::Global(_), External::Global(_))
+		)).count() as u32
+	}
+
+	/// Parses the description of an imported item of `kind`.
+	fn external<'s>(&mut self, kind: &str, items: &mut Items<'s, 'a>, offset: usize) -> Result<External, ParseError> {
+		match kind {
+			"func" => {
+				let (type_ref, param_ids) = self.type_use(items)?;
+				let func_idx = self.count(External::Function(0));
+				self.record_locals(func_idx, &param_ids);
+				Ok(External::Function(type_ref))
+			},
+			"table" => {
+				let limits = limits(items)?;
+				let elem_type = reftype(items)?;
+				Ok(External::Table(table_type(limits, elem_type)))
+			},
+			"memory" => Ok(External::Memory(memory_type(items)?)),
+			"global" => Ok(External::Global(global_type(items)?)),
+			_ => Err(ParseError::new(offset, format!("unknown kind {}", kind))),
+		}
+	}
+
+	/// Records the identifiers of the parameters (or locals) of `func_idx`.
+	fn record_locals(&mut self, func_idx: u32, ids: &[Option<&'a str>]) {
+		let ids: Vec<(&'a str, u32)> = ids.iter().enumerate()
+			.filter_map(|(idx, id)| id.map(|id| (id, idx as u32)))
+			.collect();
+		if !ids.is_empty() {
+			self.local_ids.push((func_idx, ids));
+		}
+	}
+
+	fn func<'s>(&mut self, items: &mut Items<'s, 'a>, offset: usize) -> Result<Internal, ParseError> {
+		let (type_ref, param_ids) = self.type_use(items)?;
+		let mut body = Body::default();
+		for &param_id in &param_ids {
+			body.locals.push(param_id, offset)?;
+		}
+		let mut locals: Vec<Local> = Vec::new();
+		while let Some(mut local) = items.list("local") {
+			let local_offset = local.offset();
+			let local_id = local.id();
+			let mut value_types = Vec::new();
+			while !local.is_empty() {
+				value_types.push(value_type(&mut local)?);
+			}
+			if local_id.is_some() && value_types.len() != 1 {
+				return Err(ParseError::new(local_offset, "a named local has a single type"));
+			}
+			for value_type in value_types {
+				body.locals.push(local_id, local_offset)?;
+				match locals.last_mut() {
+					Some(last) if last.value_type() == value_type => *last = Local::new(last.count() + 1, value_type),
+					_ => locals.push(Local::new(1, value_type)),
+				}
+			}
+		}
+		self.instructions(items, &mut body)?;
+		if !body.labels.is_empty() {
+			return Err(ParseError::new(items.offset(), "unclosed block"));
+		}
+		body.code.push(Instruction::End);
+
+		let func_idx = self.count(External::Function(0)) + self.functions.len() as u32;
+		if !body.locals.ids.is_empty() {
+			self.local_ids.push((func_idx, body.locals.ids.clone()));
+		}
+		self.functions.push(Func::new(type_ref));
+		self.bodies.push(FuncBody::new(locals, Instructions::new(body.code)));
+		Ok(Internal::Function(func_idx))
+	}
+
+	fn table<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<Internal, ParseError> {
+		let idx = self.count(External::Table(TableType::new(0, None))) + self.table_entries.len() as u32;
+		if items.keyword("funcref") || items.keyword("anyfunc") {
+			let mut elem = items.list("elem").ok_or_else(|| ParseError::new(items.offset(), "expected inline elements"))?;
+			let members = self.members(&mut elem)?;
+			let len = members.len() as u32;
+			self.table_entries.push(TableType::new(len, Some(len)));
+			let init = InitExpr::new(vec![Instruction::I32Const(0), Instruction::End]);
+			self.elements.push(ElementSegment::new(idx, Some(init), members, false));
+		} else {
+			let limits = limits(items)?;
+			let elem_type = reftype(items)?;
+			self.table_entries.push(table_type(limits, elem_type));
+		}
+		Ok(Internal::Table(idx))
+	}
+
+	fn memory<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<Internal, ParseError> {
+		let idx = self.count(External::Memory(MemoryType::new(0, None, false))) + self.memory_entries.len() as u32;
+		match items.list("data") {
+			Some(mut data) => {
+				let value = strings(&mut data)?;
+				let pages = ((value.len() + 0xffff) / 0x10000) as u32;
+				self.memory_entries.push(MemoryType::new(pages, Some(pages), false));
+				let init = InitExpr::new(vec![Instruction::I32Const(0), Instruction::End]);
+				self.data.push(DataSegment::new(idx, Some(init), value, false));
+			},
+			None => self.memory_entries.push(memory_type(items)?),
+		}
+		Ok(Internal::Memory(idx))
+	}
+
+	fn global<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<Internal, ParseError> {
+		let idx = self.count(External::Global(GlobalType::new(ValueType::I32, false))) + self.global_entries.len() as u32;
+		let global_type = global_type(items)?;
+		let init = self.const_expr(items)?;
+		self.global_entries.push(GlobalEntry::new(global_type, init));
+		Ok(Internal::Global(idx))
+	}
+
+	/// Function indices of an element segment.
+	fn members<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<Vec<u32>, ParseError> {
+		items.keyword("func");
+		let mut members = Vec::new();
+		while !items.is_empty() {
+			let offset = items.offset();
+			members.push(self.funcs.resolve(items.atom()?, offset, "function")?);
+		}
+		Ok(members)
+	}
+
+	/// Offset of an active segment, either `(offset ...)` or a single folded
+	/// instruction.
+	fn offset<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<Option<InitExpr>, ParseError> {
+		if let Some(mut offset) = items.list("offset") {
+			return self.const_expr(&mut offset).map(Some);
+		}
+		match items.peek() {
+			Some(&Sexpr::List(ref list, offset)) => {
+				items.next();
+				let mut body = Body::default();
+				self.folded(Items::new(list, offset), &mut body)?;
+				body.code.push(Instruction::End);
+				Ok(Some(InitExpr::new(body.code)))
+			},
+			_ => Ok(None),
+		}
+	}
+
+	fn const_expr<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<InitExpr, ParseError> {
+		let mut body = Body::default();
+		self.instructions(items, &mut body)?;
+		if !body.labels.is_empty() {
+			return Err(ParseError::new(items.offset(), "unclosed block"));
+		}
+		body.code.push(Instruction::End);
+		Ok(InitExpr::new(body.code))
+	}
+
+	/// Parameters (with their identifiers) and result of a function type.
+	/// Also tells whether any was given.
+	fn signature<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<(Vec<Option<&'a str>>, FunctionType, bool), ParseError> {
+		let mut ids = Vec::new();
+		let mut params = Vec::new();
+		let mut results = Vec::new();
+		let mut given = false;
+		while let Some(mut param) = items.list("param") {
+			given = true;
+			let param_offset = param.offset();
+			let id = param.id();
+			let count = params.len();
+			while !param.is_empty() {
+				params.push(value_type(&mut param)?);
+				ids.push(id);
+			}
+			if id.is_some() && params.len() != count + 1 {
+				return Err(ParseError::new(param_offset, "a named parameter has a single type"));
+			}
+		}
+		while let Some(mut result) = items.list("result") {
+			given = true;
+			while !result.is_empty() {
+				results.push(value_type(&mut result)?);
+			}
+		}
+		Ok((ids, FunctionType::with_results(params, results), given))
+	}
+
+	/// Type of a function, either explicit, inline or both, and the
+	/// identifiers of its parameters. Inline types which don't exist yet are
+	/// added after the others.
+	fn type_use<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<(u32, Vec<Option<&'a str>>), ParseError> {
+		let offset = items.offset();
+		let explicit = match items.list("type") {
+			Some(mut type_items) => {
+				let atom_offset = type_items.offset();
+				let idx = self.type_ids.resolve(type_items.atom()?, atom_offset, "type")?;
+				type_items.end()?;
+				Some(idx)
+			},
+			None => None,
+		};
+		let (ids, func_type, given) = self.signature(items)?;
+		match explicit {
+			Some(idx) => {
+				let existing = self.types.get(idx as usize)
+					.ok_or_else(|| ParseError::new(offset, format!("unknown type {}", idx)))?;
+				if given && *existing != func_type {
+					return Err(ParseError::new(offset, "inline function type doesn't match the type"));
+				}
+				let ids = if given { ids } else { vec![None; existing.params().len()] };
+				Ok((idx, ids))
+			},
+			None => match self.types.iter().position(|existing| *existing == func_type) {
+				Some(idx) => Ok((idx as u32, ids)),
+				None => {
+					self.types.push(func_type);
+					Ok((self.type_ids.push(None, offset)?, ids))
+				},
+			},
+		}
+	}
+
+	/// Type of a block: a single result if possible, a type use otherwise.
+	fn block_type<'s>(&mut self, items: &mut Items<'s, 'a>) -> Result<BlockType, ParseError> {
+		let mut inline = items.clone();
+		if inline.list("type").is_none() {
+			let (_, func_type, _) = self.signature(&mut inline)?;
+			if func_type.params().is_empty() && func_type.results().len() <= 1 {
+				*items = inline;
+				return Ok(match func_type.results().first() {
+					Some(&value_type) => BlockType::Value(value_type),
+					None => BlockType::NoResult,
+				});
+			}
+		}
+		let (idx, _) = self.type_use(items)?;
+		Ok(BlockType::TypeIndex(idx))
+	}
+
+	/// Parses a sequence of flat and folded instructions.
+	fn instructions<'s>(&mut self, items: &mut Items<'s, 'a>, body: &mut Body<'a>) -> Result<(), ParseError> {
+		while let Some(item) = items.next() {
+			match *item {
+				Sexpr::List(ref list, offset) => self.folded(Items::new(list, offset), body)?,
+				Sexpr::Atom(mnemonic, offset) => match mnemonic {
+					"block" | "loop" | "if" => {
+						let label = items.id();
+						let block_type = self.block_type(items)?;
+						body.labels.push(label);
+						body.code.push(block(mnemonic, block_type));
+					},
+					"else" | "end" => {
+						let label = match mnemonic {
+							"else" => body.labels.last().cloned(),
+							_ => body.labels.pop(),
+						}.ok_or_else(|| ParseError::new(offset, format!("`{}` outside of a block", mnemonic)))?;
+						if let Some(id) = items.id() {
+							if Some(id) != label {
+								return Err(ParseError::new(offset, format!("mismatched label {}", id)));
+							}
+						}
+						body.code.push(if mnemonic == "else" { Instruction::Else } else { Instruction::End });
+					},
+					_ => {
+						let instruction = self.instruction(mnemonic, offset, items, body)?;
+						body.code.push(instruction);
+					},
+				},
+				Sexpr::Str(_, offset) => return Err(ParseError::new(offset, "expected an instruction")),
+			}
+		}
+		Ok(())
+	}
+
+	/// Parses a folded instruction, whose operands come before it.
+	fn folded<'s>(&mut self, mut items: Items<'s, 'a>, body: &mut Body<'a>) -> Result<(), ParseError> {
+		let offset = items.offset();
+		let mnemonic = items.atom()?;
+		let depth = body.labels.len();
+		match mnemonic {
+			"block" | "loop" => {
+				let label = items.id();
+				let block_type = self.block_type(&mut items)?;
+				body.labels.push(label);
+				body.code.push(block(mnemonic, block_type));
+				self.instructions(&mut items, body)?;
+			},
+			"if" => {
+				let label = items.id();
+				let block_type = self.block_type(&mut items)?;
+				let mut then = loop {
+					if let Some(then) = items.list("then") {
+						break then;
+					}
+					match items.next() {
+						Some(&Sexpr::List(ref list, offset)) => self.folded(Items::new(list, offset), body)?,
+						_ => return Err(ParseError::new(items.offset(), "expected `then`")),
+					}
+				};
+				body.labels.push(label);
+				body.code.push(block(mnemonic, block_type));
+				self.instructions(&mut then, body)?;
+				if let Some(mut otherwise) = items.list("else") {
+					if body.labels.len() != depth + 1 {
+						return Err(ParseError::new(offset, "unclosed block"));
+					}
+					body.code.push(Instruction::Else);
+					self.instructions(&mut otherwise, body)?;
+				}
+				items.end()?;
+			},
+			"else" | "end" | "then" => return Err(ParseError::new(offset, format!("unexpected `{}`", mnemonic))),
+			_ => {
+				let instruction = self.instruction(mnemonic, offset, &mut items, body)?;
+				while let Some(item) = items.next() {
+					match *item {
+						Sexpr::List(ref list, offset) => self.folded(Items::new(list, offset), body)?,
+						ref other => return Err(ParseError::new(other.offset(), "expected a folded instruction")),
+					}
+				}
+				body.code.push(instruction);
+				return Ok(());
+			},
+		}
+		if body.labels.len() != depth + 1 {
+			return Err(ParseError::new(offset, "unclosed block"));
+		}
+		body.labels.pop();
+		body.code.push(Instruction::End);
+		Ok(())
+	}
+
+	/// Parses the immediates of the instruction `mnemonic`.
+	fn instruction<'s>(&mut self, mnemonic: &'a str, offset: usize, items: &mut Items<'s, 'a>, body: &Body<'a>) -> Result<Instruction, ParseError> {
+		if let Some(instruction) = plain_instruction(mnemonic) {
+			return Ok(instruction);
+		}
+		if let Some(constructor) = index_instruction(mnemonic) {
+			let atom_offset = items.offset();
+			let atom = items.atom()?;
+			let idx = match mnemonic {
+				"br" | "br_if" => label(body, atom, atom_offset)?,
+				"call" | "ref.func" => self.funcs.resolve(atom, atom_offset, "function")?,
+				"local.get" | "local.set" | "local.tee" => body.locals.resolve(atom, atom_offset, "local")?,
+				"global.get" | "global.set" => self.globals.resolve(atom, atom_offset, "global")?,
+				"memory.init" | "data.drop" => {
+					self.uses_data_count = true;
+					self.datas.resolve(atom, atom_offset, "data segment")?
+				},
+				_ => self.elems.resolve(atom, atom_offset, "element segment")?,
+			};
+			return Ok(constructor(idx));
+		}
+		if let Some(constructor) = lane_instruction(mnemonic) {
+			return Ok(constructor(lane(items)?));
+		}
+		if let Some((natural, constructor)) = memory_instruction(mnemonic) {
+			let mut arg = MemArg { align: natural as u8, offset: 0 };
+			if let Some(atom) = items.peek_atom().filter(|atom| atom.starts_with("offset=")) {
+				arg.offset = parse_u32(&atom["offset=".len()..])
+					.ok_or_else(|| ParseError::new(items.offset(), "invalid offset"))?;
+				items.next();
+			}
+			if let Some(atom) = items.peek_atom().filter(|atom| atom.starts_with("align=")) {
+				arg.align = parse_u32(&atom["align=".len()..])
+					.filter(|align| align.is_power_of_two())
+					.map(|align| align.trailing_zeros() as u8)
+					.ok_or_else(|| ParseError::new(items.offset(), "invalid alignment"))?;
+				items.next();
+			}
+			return Ok(constructor(arg));
+		}
+
+		let atom_offset = items.offset();
+		let instruction = match mnemonic {
+			"br_table" => {
+				let mut depths = Vec::new();
+				while let Some(atom) = items.peek_atom().filter(|atom| is_index(atom)) {
+					depths.push(label(body, atom, items.offset())?);
+					items.next();
+				}
+				let default = depths.pop().ok_or_else(|| ParseError::new(atom_offset, "expected a label"))?;
+				Instruction::BrTable(Box::new(BrTableData { table: depths.into_boxed_slice(), default }))
+			},
+			"call_indirect" => {
+				let table = match items.peek_atom().filter(|atom| is_index(atom)) {
+					Some(atom) => {
+						items.next();
+						self.tables.resolve(atom, atom_offset, "table")?
+					},
+					None => 0,
+				};
+				let (type_ref, _) = self.type_use(items)?;
+				Instruction::CallIndirect(type_ref, table)
+			},
+			"table.get" | "table.set" | "table.grow" | "table.size" | "table.fill" => {
+				let table = match items.peek_atom().filter(|atom| is_index(atom)) {
+					Some(atom) => {
+						items.next();
+						self.tables.resolve(atom, atom_offset, "table")?
+					},
+					None => 0,
+				};
+				match mnemonic {
+					"table.get" => Instruction::TableGet(table),
+					"table.set" => Instruction::TableSet(table),
+					"table.grow" => Instruction::TableGrow(table),
+					"table.size" => Instruction::TableSize(table),
+					_ => Instruction::TableFill(table),
+				}
+			},
+			"select" => {
+				let mut value_types = Vec::new();
+				while let Some(mut result) = items.list("result") {
+					while !result.is_empty() {
+						value_types.push(value_type(&mut result)?);
+					}
+				}
+				match value_types[..] {
+					[] => Instruction::Select,
+					[value_type] => Instruction::TypedSelect(value_type),
+					_ => return Err(ParseError::new(atom_offset, "select has a single result type")),
+				}
+			},
+			"table.init" => {
+				let first_offset = items.offset();
+				let first = items.atom()?;
+				match items.peek_atom().filter(|atom| is_index(atom)) {
+					Some(segment) => {
+						let segment_offset = items.offset();
+						items.next();
+						let table = self.tables.resolve(first, first_offset, "table")?;
+						Instruction::TableInit(self.elems.resolve(segment, segment_offset, "element segment")?, table)
+					},
+					None => Instruction::TableInit(self.elems.resolve(first, first_offset, "element segment")?, 0),
+				}
+			},
+			"table.copy" => match items.peek_atom().filter(|atom| is_index(atom)) {
+				Some(destination) => {
+					items.next();
+					let destination = self.tables.resolve(destination, atom_offset, "table")?;
+					let source_offset = items.offset();
+					let source = items.atom()?;
+					Instruction::TableCopy(destination, self.tables.resolve(source, source_offset, "table")?)
+				},
+				None => Instruction::TableCopy(0, 0),
+			},
+			"ref.null" => match items.atom()? {
+				"func" | "funcref" => Instruction::RefNull(TableElementType::AnyFunc),
+				"extern" | "externref" => Instruction::RefNull(TableElementType::ExternRef),
+				other => return Err(ParseError::new(atom_offset, format!("unknown reference type {}", other))),
+			},
+			"memory.size" | "memory.grow" => {
+				let memory = match items.peek_atom().filter(|atom| is_index(atom)) {
+					Some(atom) => {
+						items.next();
+						self.memories.resolve(atom, atom_offset, "memory")?
+					},
+					None => 0,
+				};
+				if memory > u32::from(u8::MAX) {
+					return Err(ParseError::new(atom_offset, "memory index is too large"));
+				}
+				if mnemonic == "memory.size" {
+					Instruction::CurrentMemory(memory as u8)
+				} else {
+					Instruction::GrowMemory(memory as u8)
+				}
+			},
+			"i32.const" => Instruction::I32Const(integer(items.atom()?, 32).ok_or_else(|| invalid(atom_offset))? as u32 as i32),
+			"i64.const" => Instruction::I64Const(integer(items.atom()?, 64).ok_or_else(|| invalid(atom_offset))? as i64),
+			"f32.const" => Instruction::F32Const(float(items.atom()?, 23, 8).ok_or_else(|| invalid(atom_offset))? as u32),
+			"f64.const" => Instruction::F64Const(float(items.atom()?, 52, 11).ok_or_else(|| invalid(atom_offset))?),
+			"v128.const" => {
+				let shape = items.atom()?;
+				let (lanes, lane_bytes) = match shape {
+					"i8x16" => (16, 1),
+					"i16x8" => (8, 2),
+					"i32x4" | "f32x4" => (4, 4),
+					"i64x2" | "f64x2" => (2, 8),
+					_ => return Err(ParseError::new(atom_offset, format!("unknown shape {}", shape))),
+				};
+				let mut bytes = [0u8; 16];
+				for lane in 0..lanes {
+					let lane_offset = items.offset();
+					let atom = items.atom()?;
+					let value = match shape {
+						"f32x4" => float(atom, 23, 8),
+						"f64x2" => float(atom, 52, 11),
+						_ => integer(atom, lane_bytes as u32 * 8),
+					}.ok_or_else(|| invalid(lane_offset))?;
+					for byte in 0..lane_bytes {
+						bytes[lane * lane_bytes + byte] = (value >> (byte * 8)) as u8;
+					}
+				}
+				Instruction::V128Const(Box::new(bytes))
+			},
+			"v8x16.shuffle" => {
+				let mut lanes = [0u8; 16];
+				for lane_value in lanes.iter_mut() {
+					*lane_value = lane(items)?;
+				}
+				Instruction::V8x16Shuffle(Box::new(lanes))
+			},
+			_ => return Err(ParseError::new(offset, format!("unknown instruction {}", mnemonic))),
+		};
+		Ok(instruction)
+	}
+
+	fn into_module(self) -> Result<Module, ParseError> {
+		let mut sections = Vec::new();
+		if !self.types.is_empty() {
+			sections.push(Section::Type(TypeSection::with_types(self.types.into_iter().map(Type::Function).collect())));
+		}
+		if !self.imports.is_empty() {
+			sections.push(Section::Import(ImportSection::with_entries(self.imports)));
+		}
+		if !self.functions.is_empty() {
+			sections.push(Section::Function(FunctionSection::with_entries(self.functions)));
+		}
+		if !self.table_entries.is_empty() {
+			sections.push(Section::Table(TableSection::with_entries(self.table_entries)));
+		}
+		if !self.memory_entries.is_empty() {
+			sections.push(Section::Memory(MemorySection::with_entries(self.memory_entries)));
+		}
+		if !self.global_entries.is_empty() {
+			sections.push(Section::Global(GlobalSection::with_entries(self.global_entries)));
+		}
+		if !self.exports.is_empty() {
+			sections.push(Section::Export(ExportSection::with_entries(self.exports)));
+		}
+		if let Some(start) = self.start {
+			sections.push(Section::Start(start));
+		}
+		if !self.elements.is_empty() {
+			sections.push(Section::Element(ElementSection::with_entries(self.elements)));
+		}
+		if self.uses_data_count {
+			sections.push(Section::DataCount(self.data.len() as u32));
+		}
+		if !self.bodies.is_empty() {
+			sections.push(Section::Code(CodeSection::with_bodies(self.bodies)));
+		}
+		if !self.data.is_empty() {
+			sections.push(Section::Data(DataSection::with_entries(self.data)));
+		}
+		let mut module = Module::new(sections);
+
+		let mut names = Vec::new();
+		if let Some(id) = self.module_id {
+			names.push(NameSection::Module(ModuleNameSection::new(&id[1..])));
+		}
+		if !self.funcs.ids.is_empty() {
+			let mut function_names = FunctionNameSection::default();
+			for &(id, idx) in &self.funcs.ids {
+				function_names.names_mut().insert(idx, id[1..].to_string());
+			}
+			names.push(NameSection::Function(function_names));
+		}
+		if !self.local_ids.is_empty() {
+			let mut local_names = LocalNameSection::default();
+			for &(func_idx, ref ids) in &self.local_ids {
+				let mut locals = NameMap::default();
+				for &(id, idx) in ids {
+					locals.insert(idx, id[1..].to_string());
+				}
+				local_names.local_names_mut().insert(func_idx, locals);
+			}
+			names.push(NameSection::Local(local_names));
+		}
+		if !names.is_empty() {
+			module.set_names(names).map_err(|_| ParseError::new(0, "names cannot be encoded"))?;
+		}
+		Ok(module)
+	}
+}
+
+fn invalid(offset: usize) -> ParseError {
+	ParseError::new(offset, "invalid constant")
+}
+
+fn block(mnemonic: &str, block_type: BlockType) -> Instruction {
+	match mnemonic {
+		"block" => Instruction::Block(block_type),
+		"loop" => Instruction::Loop(block_type),
+		_ => Instruction::If(block_type),
+	}
+}
+
+/// Depth of the label `atom`.
+fn label(body: &Body, atom: &str, offset: usize) -> Result<u32, ParseError> {
+	if atom.starts_with('$') {
+		body.labels.iter().rev()
+			.position(|&label| label == Some(atom))
+			.map(|depth| depth as u32)
+			.ok_or_else(|| ParseError::new(offset, format!("unknown label {}", atom)))
+	} else {
+		parse_u32(atom).ok_or_else(|| ParseError::new(offset, format!("invalid label {}", atom)))
+	}
+}
+
+fn lane(items: &mut Items) -> Result<u8, ParseError> {
+	let offset = items.offset();
+	parse_u32(items.atom()?)
+		.filter(|&lane| lane <= u32::from(u8::MAX))
+		.map(|lane| lane as u8)
+		.ok_or_else(|| ParseError::new(offset, "invalid lane"))
+}
+
+fn value_type(items: &mut Items) -> Result<ValueType, ParseError> {
+	let offset = items.offset();
+	match items.atom()? {
+		"i32" => Ok(ValueType::I32),
+		"i64" => Ok(ValueType::I64),
+		"f32" => Ok(ValueType::F32),
+		"f64" => Ok(ValueType::F64),
+		"v128" => Ok(ValueType::V128),
+		"funcref" => Ok(ValueType::FuncRef),
+		"externref" => Ok(ValueType::ExternRef),
+		other => Err(ParseError::new(offset, format!("unknown value type {}", other))),
+	}
+}
+
+fn global_type(items: &mut Items) -> Result<GlobalType, ParseError> {
+	match items.list("mut") {
+		Some(mut mutable) => {
+			let value_type = value_type(&mut mutable)?;
+			mutable.end()?;
+			Ok(GlobalType::new(value_type, true))
+		},
+		None => Ok(GlobalType::new(value_type(items)?, false)),
+	}
+}
+
+/// Limits, as whether they are 64-bit, initial and maximum size.
+fn limits(items: &mut Items) -> Result<(bool, u32, Option<u32>), ParseError> {
+	let is_64 = items.keyword("i64");
+	let offset = items.offset();
+	let initial = parse_u32(items.atom()?).ok_or_else(|| ParseError::new(offset, "invalid limit"))?;
+	let maximum = match items.peek_atom().filter(|atom| is_index(atom)) {
+		Some(atom) => {
+			let offset = items.offset();
+			items.next();
+			Some(parse_u32(atom).ok_or_else(|| ParseError::new(offset, "invalid limit"))?)
+		},
+		None => None,
+	};
+	Ok((is_64, initial, maximum))
+}
+
+fn reftype(items: &mut Items) -> Result<TableElementType, ParseError> {
+	if items.keyword("funcref") || items.keyword("anyfunc") {
+		Ok(TableElementType::AnyFunc)
+	} else if items.keyword("externref") {
+		Ok(TableElementType::ExternRef)
+	} else {
+		Err(ParseError::new(items.offset(), "expected `funcref` or `externref`"))
+	}
+}
+
+fn table_type((is_64, initial, maximum): (bool, u32, Option<u32>), elem_type: TableElementType) -> TableType {
+	let mut table_type = TableType::new(initial, maximum);
+	*table_type.limits_mut().is_64_mut() = is_64;
+	*table_type.elem_type_mut() = elem_type;
+	table_type
+}
+
+/// Memory type, as limits possibly followed by `shared` and the page size.
+fn memory_type(items: &mut Items) -> Result<MemoryType, ParseError> {
+	let (is_64, initial, maximum) = limits(items)?;
+	let mut memory_type = MemoryType::new(initial, maximum, items.keyword("shared"));
+	*memory_type.limits_mut().is_64_mut() = is_64;
+	if let Some(mut page_size) = items.list("pagesize") {
+		let offset = page_size.offset();
+		let size = parse_u32(page_size.atom()?).filter(|size| size.is_power_of_two())
+			.ok_or_else(|| ParseError::new(offset, "invalid page size"))?;
+		page_size.end()?;
+		*memory_type.limits_mut().page_size_log2_mut() = Some(size.trailing_zeros());
+	}
+	Ok(memory_type)
+}
+
+/// Concatenation of the strings of `items`, which holds nothing else.
+fn strings(items: &mut Items) -> Result<Vec<u8>, ParseError> {
+	let mut value = Vec::new();
+	while !items.is_empty() {
+		value.extend_from_slice(items.string()?);
+	}
+	Ok(value)
+}
+
+/// Digits of a number in `radix`, with optional underscores between them.
+fn digits(atom: &str, radix: u32) -> Option<u64> {
+	if atom.is_empty() || atom.starts_with('_') || atom.ends_with('_') || atom.contains("__") {
+		return None;
+	}
+	let mut value: u64 = 0;
+	for c in atom.chars().filter(|&c| c != '_') {
+		value = value.checked_mul(u64::from(radix))?.checked_add(u64::from(c.to_digit(radix)?))?;
+	}
+	Some(value)
+}
+
+fn parse_u32(atom: &str) -> Option<u32> {
+	let value = match atom.strip_prefix("0x") {
+		Some(hex) => digits(hex, 16)?,
+		None => digits(atom, 10)?,
+	};
+	if value > u64::from(u32::MAX) {
+		None
+	} else {
+		Some(value as u32)
+	}
+}
+
+/// Integer of `bits` bits, signed or not, as its two's complement.
+fn integer(atom: &str, bits: u32) -> Option<u64> {
+	let (negative, atom) = match atom.as_bytes().first() {
+		Some(&b'-') => (true, &atom[1..]),
+		Some(&b'+') => (false, &atom[1..]),
+		_ => (false, atom),
+	};
+	let magnitude = match atom.strip_prefix("0x") {
+		Some(hex) => digits(hex, 16)?,
+		None => digits(atom, 10)?,
+	};
+	let mask = u64::MAX >> (64 - bits);
+	if negative {
+		if magnitude > 1 << (bits - 1) {
+			return None;
+		}
+		Some(magnitude.wrapping_neg() & mask)
+	} else if magnitude > mask {
+		None
+	} else {
+		Some(magnitude)
+	}
+}
+
+/// Bits of a float with `mantissa_bits` and `exponent_bits`, rounded to
+/// nearest.
+fn float(atom: &str, mantissa_bits: u32, exponent_bits: u32) -> Option<u64> {
+	let (negative, atom) = match atom.as_bytes().first() {
+		Some(&b'-') => (true, &atom[1..]),
+		Some(&b'+') => (false, &atom[1..]),
+		_ => (false, atom),
+	};
+	let infinity = ((1u64 << exponent_bits) - 1) << mantissa_bits;
+	let magnitude = if atom == "inf" {
+		infinity
+	} else if atom == "nan" {
+		infinity | 1 << (mantissa_bits - 1)
+	} else if let Some(payload) = atom.strip_prefix("nan:0x") {
+		let payload = digits(payload, 16)?;
+		if payload == 0 || payload >> mantissa_bits != 0 {
+			return None;
+		}
+		infinity | payload
+	} else if let Some(hex) = atom.strip_prefix("0x") {
+		hex_float(hex, mantissa_bits, exponent_bits)?
+	} else {
+		if !atom.bytes().all(|c| c.is_ascii_digit() || b"._eE+-".contains(&c)) || !atom.bytes().next()?.is_ascii_digit() {
+			return None;
+		}
+		let decimal: String = atom.chars().filter(|&c| c != '_').collect();
+		let bits = if mantissa_bits == 23 {
+			u64::from(decimal.parse::<f32>().ok()?.to_bits())
+		} else {
+			decimal.parse::<f64>().ok()?.to_bits()
+		};
+		if bits == infinity {
+			return None;
+		}
+		bits
+	};
+	Some(if negative { magnitude | 1 << (mantissa_bits + exponent_bits) } else { magnitude })
+}
+
+/// Bits of the hexadecimal float `hex` (without its `0x` prefix and sign).
+fn hex_float(hex: &str, mantissa_bits: u32, exponent_bits: u32) -> Option<u64> {
+	let (significand, exponent) = match hex.find(['p', 'P']) {
+		Some(p) => {
+			let exponent = &hex[p + 1..];
+			let (negative, exponent) = match exponent.as_bytes().first() {
+				Some(&b'-') => (true, &exponent[1..]),
+				Some(&b'+') => (false, &exponent[1..]),
+				_ => (false, exponent),
+			};
+			let exponent = digits(exponent, 10)?.min(100_000) as i64;
+			(&hex[..p], if negative { -exponent } else { exponent })
+		},
+		None => (hex, 0),
+	};
+	let (integral, fractional) = match significand.find('.') {
+		Some(dot) => (&significand[..dot], &significand[dot + 1..]),
+		None => (significand, ""),
+	};
+	if integral.is_empty() {
+		return None;
+	}
+
+	// Significand as `mantissa * 2^exponent`, the digits which don't fit
+	// being kept as a sticky bit.
+	let mut mantissa: u64 = 0;
+	let mut exponent = exponent;
+	for (part, is_fraction) in [(integral, false), (fractional, true)].iter().cloned() {
+		if part.is_empty() {
+			continue;
+		}
+		if part.starts_with('_') || part.ends_with('_') || part.contains("__") {
+			return None;
+		}
+		for c in part.chars().filter(|&c| c != '_') {
+			let digit = u64::from(c.to_digit(16)?);
+			if mantissa >> 60 == 0 {
+				mantissa = mantissa << 4 | digit;
+				if is_fraction {
+					exponent -= 4;
+				}
+			} else {
+				mantissa |= (digit != 0) as u64;
+				if !is_fraction {
+					exponent += 4;
+				}
+			}
+		}
+	}
+	if mantissa == 0 {
+		return Some(0);
+	}
+
+	let leading_zeros = mantissa.leading_zeros();
+	let mantissa = mantissa << leading_zeros;
+	let exponent = exponent - i64::from(leading_zeros) + 63;
+	let bias = (1i64 << (exponent_bits - 1)) - 1;
+	if exponent >= 1 - bias {
+		let mut rounded = round_shift(mantissa, 63 - mantissa_bits);
+		let mut exponent = exponent;
+		if rounded >> (mantissa_bits + 1) != 0 {
+			rounded >>= 1;
+			exponent += 1;
+		}
+		if exponent > bias {
+			return None;
+		}
+		Some(((exponent + bias) as u64) << mantissa_bits | (rounded & ((1 << mantissa_bits) - 1)))
+	} else {
+		// Subnormal, rounding up to the smallest normal number if need be.
+		let shift = (1 - bias - i64::from(mantissa_bits)) - (exponent - 63);
+		Some(round_shift(mantissa, shift.min(65) as u32))
+	}
+}
+
+/// `value >> shift`, rounded to nearest, ties to even.
+fn round_shift(value: u64, shift: u32) -> u64 {
+	if shift == 0 {
+		return value;
+	}
+	if shift > 64 {
+		return 0;
+	}
+	if shift == 64 {
+		return (value > 1 << 63) as u64;
+	}
+	let truncated = value >> shift;
+	let rest = value & ((1 << shift) - 1);
+	let half = 1 << (shift - 1);
+	if rest > half || (rest == half && truncated & 1 == 1) {
+		truncated + 1
+	} else {
+		truncated
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{from_wat, float, integer};
+	use elements::{Instruction, BlockType};
+	use elements::Instruction::*;
+	#[cfg(feature = "validation")]
+	use elements::{
+		Internal, External, ValueType, NameSection, Type, FunctionType, TableElementType,
+		deserialize_buffer, serialize,
+	};
+	#[cfg(feature = "validation")]
+	use text::to_wat;
+	#[cfg(feature = "validation")]
+	use validation::{validate, Features};
+
+	#[test]
+	#[cfg(feature = "validation")]
+	fn module() {
+		let module = from_wat(r#"
+			(module $test
+				(type $log (func (param i32)))
+				(import "env" "log" (func $log (type $log)))
+				(import "env" "memory" (memory 1))
+				(func $main (export "main") (param $flag i32) (result i32)
+					(local $x f64) (local i64 i64)
+					(block $out (result i32)
+						(br_if $out (i32.const 1) (local.get $flag))
+						drop
+						(if (result i32) (local.get $flag)
+							(then (i32.const 2))
+							(else (i32.load offset=8 align=1 (i32.const 0)))))
+					(call $log (i32.const 3))
+					f64.const 1.5
+					local.set $x)
+				(table 2 funcref)
+				(global $g (mut i32) (i32.const -1))
+				(elem (i32.const 0) $main $log)
+				(data (i32.const 16) "hi" "\00"))
+		"#).expect("module to be parsed");
+
+		let imports = module.import_section().expect("import section").entries();
+		assert_eq!(*imports[0].external(), External::Function(0));
+		let body = &module.code_section().expect("code section").bodies()[0];
+		assert_eq!(body.locals().len(), 2);
+		assert_eq!(body.code().elements(), &[
+			Block(BlockType::Value(ValueType::I32)),
+			I32Const(1),
+			GetLocal(0),
+			BrIf(0),
+			Drop,
+			GetLocal(0),
+			If(BlockType::Value(ValueType::I32)),
+			I32Const(2),
+			Else,
+			I32Const(0),
+			I32Load(0, 8),
+			End,
+			End,
+			I32Const(3),
+			Call(0),
+			F64Const(1.5f64.to_bits()),
+			SetLocal(1),
+			End,
+		][..]);
+		assert_eq!(*module.export_section().expect("export section").entries()[0].internal(), Internal::Function(1));
+		assert_eq!(module.elements_section().expect("elements section").entries()[0].members(), &[1, 0]);
+		assert_eq!(module.data_section().expect("data section").entries()[0].value(), b"hi\0");
+
+		let names = module.names().expect("names to be parsed");
+		match names[0] {
+			NameSection::Module(ref name) => assert_eq!(name.name(), "test"),
+			_ => panic!("module name to come first"),
+		}
+
+		validate(module.clone(), &Features::default()).expect("module to be valid");
+		let reparsed = from_wat(&to_wat(&module)).expect("printed module to be parsed");
+		assert_eq!(to_wat(&reparsed), to_wat(&module));
+		let bytes = serialize(module).expect("module to be serialized");
+		deserialize_buffer::<::elements::Module>(&bytes).expect("module to be deserialized");
+	}
+
+	#[test]
+	fn labels_and_flat_blocks() {
+		let module = from_wat(r#"
+			(func (param i32)
+				block $a
+					loop $b
+						local.get 0
+						br_table $a $b 0
+					end $b
+				end
+				(if (local.get 0) (then (br 0))))
+		"#).expect("module to be parsed");
+		let code = module.code_section().expect("code section").bodies()[0].code().elements().to_vec();
+		match code[3] {
+			Instruction::BrTable(ref data) => {
+				assert_eq!(&data.table[..], &[1, 0]);
+				assert_eq!(data.default, 0);
+			},
+			ref other => panic!("unexpected {:?}", other),
+		}
+		assert_eq!(&code[4..], &[End, End, GetLocal(0), If(BlockType::NoResult), Br(0), End, End][..]);
+	}
+
+	#[test]
+	fn errors() {
+		assert_eq!(
+			format!("{}", from_wat("(module\n  (func (call $missing)))").unwrap_err()),
+			"2:15: unknown function $missing"
+		);
+		assert!(from_wat("(func (param $x i32 i32))").is_err());
+		assert!(from_wat("(func block)").is_err());
+		assert!(from_wat("(func end)").is_err());
+		assert!(from_wat("(func) (import \"a\" \"b\" (func))").is_err());
+		assert!(from_wat("(func $f) (func $f)").is_err());
+		assert!(from_wat("(func i32.const 2147483648)").is_ok());
+		assert!(from_wat("(func i32.const 4294967296)").is_err());
+	}
+
+	#[test]
+	#[cfg(feature = "validation")]
+	fn multi_value() {
+		let module = from_wat(r#"
+			(module
+				(func $swap (param i32 i64) (result i64 i32)
+					local.get 1
+					local.get 0)
+				(func (result i64 i32)
+					(i32.const 1)
+					(block (param i32) (result i64 i32)
+						(i64.const 2)
+						(call $swap))))
+		"#).expect("module to be parsed");
+		let types: Vec<_> = module.type_section().expect("type section").types().iter()
+			.map(|ty| match *ty { Type::Function(ref func_type) => func_type.clone() })
+			.collect();
+		assert_eq!(types, vec![
+			FunctionType::with_results(vec![ValueType::I32, ValueType::I64], vec![ValueType::I64, ValueType::I32]),
+			FunctionType::with_results(Vec::new(), vec![ValueType::I64, ValueType::I32]),
+			FunctionType::with_results(vec![ValueType::I32], vec![ValueType::I64, ValueType::I32]),
+		]);
+		let code = module.code_section().expect("code section").bodies()[1].code().elements();
+		assert_eq!(code[1], Block(BlockType::TypeIndex(2)));
+		assert!(validate(module.clone(), &Features::default()).is_err());
+		validate(module, &Features { multi_value: true, ..Default::default() }).expect("module to be valid");
+	}
+
+	#[test]
+	#[cfg(feature = "validation")]
+	fn reference_types() {
+		let module = from_wat(r#"
+			(module
+				(table $funcs 1 funcref)
+				(table $handles 1 externref)
+				(func $store (param externref) (result i32)
+					(table.set $handles (i32.const 0) (local.get 0))
+					(table.set $funcs (i32.const 0) (ref.func $store))
+					(table.grow $handles (ref.null extern) (i32.const 1))
+					drop
+					(table.copy $funcs $funcs (i32.const 0) (i32.const 0) (i32.const 1))
+					(select (result externref) (ref.null extern) (local.get 0) (i32.const 1))
+					drop
+					(ref.is_null (table.get $handles (i32.const 0)))))
+		"#).expect("module to be parsed");
+		let tables = module.table_section().expect("table section").entries();
+		assert_eq!(tables[1].elem_type(), TableElementType::ExternRef);
+		let code = module.code_section().expect("code section").bodies()[0].code().elements();
+		assert_eq!(code[4], RefFunc(0));
+		assert_eq!(code[6], RefNull(TableElementType::ExternRef));
+		assert_eq!(code[8], TableGrow(1));
+		assert_eq!(code[13], TableCopy(0, 0));
+		assert_eq!(code[17], TypedSelect(ValueType::ExternRef));
+		validate(module.clone(), &Features { reference_types: true, bulk_memory: true, ..Default::default() }).expect("module to be valid");
+
+		let reparsed = from_wat(&to_wat(&module)).expect("printed module to be parsed");
+		assert_eq!(reparsed.code_section(), module.code_section());
+		assert_eq!(reparsed.table_section(), module.table_section());
+	}
+
+	#[test]
+	#[cfg(feature = "validation")]
+	fn data_count() {
+		let module = from_wat(r#"
+			(module
+				(memory 1)
+				(data "ab")
+				(func
+					(memory.init 0 (i32.const 0) (i32.const 0) (i32.const 2))
+					data.drop 0))
+		"#).expect("module to be parsed");
+		assert_eq!(module.data_count_section(), Some(1));
+		validate(module, &Features { bulk_memory: true, ..Default::default() }).expect("module to be valid");
+
+		let module = from_wat(r#"(module (memory 1) (data "ab") (func))"#).expect("module to be parsed");
+		assert_eq!(module.data_count_section(), None);
+	}
+
+	#[test]
+	#[cfg(feature = "validation")]
+	fn custom_page_sizes() {
+		let module = from_wat(r#"
+			(module
+				(import "env" "small" (memory 2 4 (pagesize 1)))
+				(memory 1 (pagesize 65536)))
+		"#).expect("module to be parsed");
+		let imported = match *module.import_section().expect("import section").entries()[0].external() {
+			External::Memory(memory_type) => memory_type,
+			ref other => panic!("unexpected {:?}", other),
+		};
+		assert_eq!(imported.limits().page_size_log2(), Some(0));
+		assert_eq!(module.memory_section().expect("memory section").entries()[0].limits().page_size_log2(), Some(16));
+		validate(module.clone(), &Features { custom_page_sizes: true, multi_memory: true, ..Default::default() }).expect("module to be valid");
+
+		let reparsed = from_wat(&to_wat(&module)).expect("printed module to be parsed");
+		assert_eq!(reparsed.import_section(), module.import_section());
+		assert_eq!(reparsed.memory_section(), module.memory_section());
+		assert!(from_wat("(module (memory 1 (pagesize 3)))").is_err());
+	}
+
+	#[test]
+	fn numbers() {
+		assert_eq!(integer("-1", 32), Some(0xffff_ffff));
+		assert_eq!(integer("0x_1", 32), None);
+		assert_eq!(integer("1_000", 64), Some(1000));
+		assert_eq!(integer("-0x8000_0000", 32), Some(0x8000_0000));
+		assert_eq!(integer("-0x8000_0001", 32), None);
+		assert_eq!(float("0x1p-1", 52, 11), Some(0.5f64.to_bits()));
+		assert_eq!(float("-0x1.8p1", 23, 8), Some(u64::from((-3.0f32).to_bits())));
+		assert_eq!(float("0x1p-149", 23, 8), Some(1));
+		assert_eq!(float("0x1.fffffffffffff8p1023", 52, 11), None);
+		assert_eq!(float("0x1.000001p0", 23, 8), Some(u64::from(1.0f32.to_bits())));
+		assert_eq!(float("0x1.000003p0", 23, 8), Some(u64::from(1.0f32.to_bits()) + 2));
+		assert_eq!(float("1e400", 52, 11), None);
+		assert_eq!(float("-inf", 23, 8), Some(u64::from(f32::NEG_INFINITY.to_bits())));
+		assert_eq!(float("nan:0x1", 23, 8), Some(0x7f80_0001));
+		assert_eq!(float("1_0.5", 52, 11), Some(10.5f64.to_bits()));
+	}
+}
@@ -0,0 +1,567 @@
+use std::fmt::Write;
+use std::vec::Vec;
+use std::string::{String, ToString};
+use elements::{
+	Module, Instruction, InitExpr, External, Internal, BlockType, FunctionType, Type,
+	ResizableLimits, TableType, MemoryType, GlobalType, NameSection, IndexMap, NameMap,
+	ImportCountType, ValueType, TableElementType,
+};
+use super::instructions::{plain_mnemonic, index_mnemonic, lane_mnemonic, memory_mnemonic};
+
+/// Prints `module` in the text format.
+///
+/// Instructions are printed flat, one per line, and items are printed in the
+/// order of their sections, each with its index in a comment. Functions,
+/// locals and the module itself are given the identifiers of the name
+/// section (parsed or not) when these are valid and unique identifiers.
+/// Custom sections are not printed.
+pub fn to_wat(module: &Module) -> String {
+	let mut printer = Printer::new(module);
+	printer.module();
+	printer.out
+}
+
+struct Printer<'a> {
+	module: &'a Module,
+	out: String,
+	/// Identifier of the module, if any.
+	module_name: Option<String>,
+	/// Identifiers of the functions.
+	functions: IndexMap<String>,
+	/// Identifiers of the locals, by function.
+	locals: IndexMap<IndexMap<String>>,
+	/// Number of imported functions, globals, tables and memories.
+	imported: [u32; 4],
+}
+
+fn is_id_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c)
+}
+
+/// Identifier `$name`, if `name` is a valid one.
+fn id(name: &str) -> Option<String> {
+	if !name.is_empty() && name.chars().all(is_id_char) {
+		Some(format!("${}", name))
+	} else {
+		None
+	}
+}
+
+/// Valid identifiers of `names`, dropping the duplicates.
+fn ids(names: &NameMap) -> IndexMap<String> {
+	let mut ids = IndexMap::default();
+	let mut seen: Vec<String> = Vec::new();
+	for (idx, name) in names.iter() {
+		if let Some(id) = id(name) {
+			if !seen.contains(&id) {
+				seen.push(id.clone());
+				ids.insert(idx, id);
+			}
+		}
+	}
+	ids
+}
+
+/// Writes `bytes` as a string literal.
+pub(crate) fn write_string(out: &mut String, bytes: &[u8]) {
+	out.push('"');
+	for &byte in bytes {
+		match byte {
+			b'"' | b'\\' => { out.push('\\'); out.push(byte as char); },
+			0x20..=0x7e => out.push(byte as char),
+			_ => { let _ = write!(out, "\\{:02x}", byte); },
+		}
+	}
+	out.push('"');
+}
+
+fn write_f32(out: &mut String, bits: u32) {
+	let value = f32::from_bits(bits);
+	let sign = if bits >> 31 != 0 { "-" } else { "" };
+	if value.is_nan() {
+		let payload = bits & 0x7f_ffff;
+		if payload == 0x40_0000 {
+			let _ = write!(out, "{}nan", sign);
+		} else {
+			let _ = write!(out, "{}nan:0x{:x}", sign, payload);
+		}
+	} else if value.is_infinite() {
+		let _ = write!(out, "{}inf", sign);
+	} else {
+		let _ = write!(out, "{:?}", value);
+	}
+}
+
+fn write_f64(out: &mut String, bits: u64) {
+	let value = f64::from_bits(bits);
+	let sign = if bits >> 63 != 0 { "-" } else { "" };
+	if value.is_nan() {
+		let payload = bits & 0xf_ffff_ffff_ffff;
+		if payload == 0x8_0000_0000_0000 {
+			let _ = write!(out, "{}nan", sign);
+		} else {
+			let _ = write!(out, "{}nan:0x{:x}", sign, payload);
+		}
+	} else if value.is_infinite() {
+		let _ = write!(out, "{}inf", sign);
+	} else {
+		let _ = write!(out, "{:?}", value);
+	}
+}
+
+impl<'a> Printer<'a> {
+	fn new(module: &'a Module) -> Printer<'a> {
+		let mut module_name = None;
+		let mut functions = IndexMap::default();
+		let mut locals = IndexMap::default();
+		for name_section in module.names().unwrap_or_default() {
+			match name_section {
+				NameSection::Module(ref name) => module_name = id(name.name()),
+				NameSection::Function(ref names) => functions = ids(names.names()),
+				NameSection::Local(ref names) => {
+					for (idx, names) in names.local_names() {
+						locals.insert(idx, ids(names));
+					}
+				},
+				NameSection::Unparsed { .. } => {},
+			}
+		}
+		Printer {
+			module,
+			out: String::new(),
+			module_name,
+			functions,
+			locals,
+			imported: [
+				module.import_count(ImportCountType::Function) as u32,
+				module.import_count(ImportCountType::Global) as u32,
+				module.import_count(ImportCountType::Table) as u32,
+				module.import_count(ImportCountType::Memory) as u32,
+			],
+		}
+	}
+
+	fn function_ref(&self, idx: u32) -> String {
+		self.functions.get(idx).cloned().unwrap_or_else(|| idx.to_string())
+	}
+
+	fn local_ref(&self, func_idx: u32, idx: u32) -> String {
+		self.locals.get(func_idx).and_then(|locals| locals.get(idx)).cloned().unwrap_or_else(|| idx.to_string())
+	}
+
+	fn module(&mut self) {
+		self.out.push_str("(module");
+		if let Some(ref name) = self.module_name {
+			let _ = write!(self.out, " {}", name);
+		}
+		let module = self.module;
+
+		for (idx, Type::Function(func_type)) in module.type_section().map_or(&[][..], |ts| ts.types()).iter().enumerate() {
+			let _ = write!(self.out, "\n  (type (;{};) (func", idx);
+			self.signature(func_type, None);
+			self.out.push_str("))");
+		}
+
+		let mut counts = [0u32; 4];
+		for entry in module.import_section().map_or(&[][..], |is| is.entries()) {
+			self.out.push_str("\n  (import ");
+			write_string(&mut self.out, entry.module().as_bytes());
+			self.out.push(' ');
+			write_string(&mut self.out, entry.field().as_bytes());
+			self.out.push(' ');
+			match *entry.external() {
+				External::Function(type_ref) => {
+					self.out.push_str("(func");
+					if let Some(name) = self.functions.get(counts[0]) {
+						let _ = write!(self.out, " {}", name);
+					}
+					let _ = write!(self.out, " (;{};) (type {}))", counts[0], type_ref);
+					counts[0] += 1;
+				},
+				External::Global(ref global_type) => {
+					let _ = write!(self.out, "(global (;{};) ", counts[1]);
+					self.global_type(global_type);
+					self.out.push(')');
+					counts[1] += 1;
+				},
+				External::Table(ref table_type) => {
+					let _ = write!(self.out, "(table (;{};) ", counts[2]);
+					self.table_type(table_type);
+					self.out.push(')');
+					counts[2] += 1;
+				},
+				External::Memory(ref memory_type) => {
+					let _ = write!(self.out, "(memory (;{};) ", counts[3]);
+					self.memory_type(memory_type);
+					self.out.push(')');
+					counts[3] += 1;
+				},
+			}
+			self.out.push(')');
+		}
+
+		let funcs = module.function_section().map_or(&[][..], |fs| fs.entries());
+		let bodies = module.code_section().map_or(&[][..], |cs| cs.bodies());
+		for (n, (func, body)) in funcs.iter().zip(bodies).enumerate() {
+			let idx = self.imported[0] + n as u32;
+			self.out.push_str("\n  (func");
+			if let Some(name) = self.functions.get(idx) {
+				let _ = write!(self.out, " {}", name);
+			}
+			let _ = write!(self.out, " (;{};) (type {})", idx, func.type_ref());
+			let func_type = module.type_section()
+				.and_then(|ts| ts.types().get(func.type_ref() as usize))
+				.map(|t| { let Type::Function(ref func_type) = *t; func_type });
+			let params = func_type.map_or(0, |func_type| func_type.params().len() as u32);
+			if let Some(func_type) = func_type {
+				self.signature(func_type, Some(idx));
+			}
+
+			let mut local_idx = params;
+			let mut unnamed = Vec::new();
+			for local in body.locals() {
+				for _ in 0..local.count() {
+					match self.locals.get(idx).and_then(|locals| locals.get(local_idx)).cloned() {
+						Some(name) => {
+							self.group("\n    (local", &mut unnamed);
+							let _ = write!(self.out, "\n    (local {} {})", name, local.value_type());
+						},
+						None => unnamed.push(local.value_type()),
+					}
+					local_idx += 1;
+				}
+			}
+			self.group("\n    (local", &mut unnamed);
+
+			let code = body.code().elements();
+			let mut depth = 0;
+			for instruction in &code[..code.len().saturating_sub(1)] {
+				match *instruction {
+					Instruction::End | Instruction::Else => depth -= 1,
+					_ => {},
+				}
+				self.out.push_str("\n    ");
+				for _ in 0..depth {
+					self.out.push_str("  ");
+				}
+				self.instruction(instruction, idx);
+				match *instruction {
+					Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) | Instruction::Else => depth += 1,
+					_ => {},
+				}
+			}
+			self.out.push(')');
+		}
+
+		for (n, table_type) in module.table_section().map_or(&[][..], |ts| ts.entries()).iter().enumerate() {
+			let _ = write!(self.out, "\n  (table (;{};) ", self.imported[2] + n as u32);
+			self.table_type(table_type);
+			self.out.push(')');
+		}
+		for (n, memory_type) in module.memory_section().map_or(&[][..], |ms| ms.entries()).iter().enumerate() {
+			let _ = write!(self.out, "\n  (memory (;{};) ", self.imported[3] + n as u32);
+			self.memory_type(memory_type);
+			self.out.push(')');
+		}
+		for (n, global) in module.global_section().map_or(&[][..], |gs| gs.entries()).iter().enumerate() {
+			let _ = write!(self.out, "\n  (global (;{};) ", self.imported[1] + n as u32);
+			self.global_type(global.global_type());
+			self.out.push(' ');
+			self.init_expr(global.init_expr());
+			self.out.push(')');
+		}
+		for entry in module.export_section().map_or(&[][..], |es| es.entries()) {
+			self.out.push_str("\n  (export ");
+			write_string(&mut self.out, entry.field().as_bytes());
+			match *entry.internal() {
+				Internal::Function(idx) => { let _ = write!(self.out, " (func {}))", self.function_ref(idx)); },
+				Internal::Table(idx) => { let _ = write!(self.out, " (table {}))", idx); },
+				Internal::Memory(idx) => { let _ = write!(self.out, " (memory {}))", idx); },
+				Internal::Global(idx) => { let _ = write!(self.out, " (global {}))", idx); },
+			}
+		}
+		if let Some(start) = module.start_section() {
+			let _ = write!(self.out, "\n  (start {})", self.function_ref(start));
+		}
+		for (n, segment) in module.elements_section().map_or(&[][..], |es| es.entries()).iter().enumerate() {
+			let _ = write!(self.out, "\n  (elem (;{};)", n);
+			if segment.declared() {
+				self.out.push_str(" declare");
+			} else if let Some(ref offset) = *segment.offset() {
+				if segment.index() != 0 {
+					let _ = write!(self.out, " (table {})", segment.index());
+				}
+				self.out.push(' ');
+				self.init_expr(offset);
+			}
+			self.out.push_str(" func");
+			for &member in segment.members() {
+				let _ = write!(self.out, " {}", self.function_ref(member));
+			}
+			self.out.push(')');
+		}
+		for (n, segment) in module.data_section().map_or(&[][..], |ds| ds.entries()).iter().enumerate() {
+			let _ = write!(self.out, "\n  (data (;{};)", n);
+			if let Some(ref offset) = *segment.offset() {
+				if segment.index() != 0 {
+					let _ = write!(self.out, " (memory {})", segment.index());
+				}
+				self.out.push(' ');
+				self.init_expr(offset);
+			}
+			self.out.push(' ');
+			write_string(&mut self.out, segment.value());
+			self.out.push(')');
+		}
+		self.out.push_str(")\n");
+	}
+
+	/// Writes the parameters and result of `func_type`, with the names of
+	/// the parameters of `func_idx`, if any.
+	fn signature(&mut self, func_type: &FunctionType, func_idx: Option<u32>) {
+		let mut unnamed = Vec::new();
+		for (idx, value_type) in func_type.params().iter().enumerate() {
+			match func_idx.and_then(|func_idx| self.locals.get(func_idx)).and_then(|locals| locals.get(idx as u32)).cloned() {
+				Some(name) => {
+					self.group(" (param", &mut unnamed);
+					let _ = write!(self.out, " (param {} {})", name, value_type);
+				},
+				None => unnamed.push(*value_type),
+			}
+		}
+		self.group(" (param", &mut unnamed);
+		let mut results = func_type.results().to_vec();
+		self.group(" (result", &mut results);
+	}
+
+	/// Writes `unnamed` params, results or locals in a single group opened
+	/// by `open`.
+	fn group(&mut self, open: &str, unnamed: &mut Vec<ValueType>) {
+		if !unnamed.is_empty() {
+			self.out.push_str(open);
+			for value_type in unnamed.drain(..) {
+				let _ = write!(self.out, " {}", value_type);
+			}
+			self.out.push(')');
+		}
+	}
+
+	fn limits(&mut self, limits: &ResizableLimits) {
+		if limits.is_64() {
+			self.out.push_str("i64 ");
+		}
+		let _ = write!(self.out, "{}", limits.initial());
+		if let Some(maximum) = limits.maximum() {
+			let _ = write!(self.out, " {}", maximum);
+		}
+	}
+
+	fn table_type(&mut self, table_type: &TableType) {
+		self.limits(table_type.limits());
+		let _ = write!(self.out, " {}", table_type.elem_type());
+	}
+
+	fn memory_type(&mut self, memory_type: &MemoryType) {
+		self.limits(memory_type.limits());
+		if memory_type.limits().shared() {
+			self.out.push_str(" shared");
+		}
+		if memory_type.limits().page_size_log2().is_some() {
+			let _ = write!(self.out, " (pagesize {})", memory_type.limits().page_size());
+		}
+	}
+
+	fn global_type(&mut self, global_type: &GlobalType) {
+		if global_type.is_mutable() {
+			let _ = write!(self.out, "(mut {})", global_type.content_type());
+		} else {
+			let _ = write!(self.out, "{}", global_type.content_type());
+		}
+	}
+
+	/// Writes the instructions of `init_expr` (but the final `end`), folded.
+	fn init_expr(&mut self, init_expr: &InitExpr) {
+		let code = init_expr.code();
+		let code = match code.last() {
+			Some(&Instruction::End) => &code[..code.len() - 1],
+			_ => code,
+		};
+		for (n, instruction) in code.iter().enumerate() {
+			if n > 0 {
+				self.out.push(' ');
+			}
+			self.out.push('(');
+			self.instruction(instruction, 0);
+			self.out.push(')');
+		}
+	}
+
+	fn instruction(&mut self, instruction: &Instruction, func_idx: u32) {
+		if let Some(mnemonic) = plain_mnemonic(instruction) {
+			self.out.push_str(mnemonic);
+			return;
+		}
+		if let Some((mnemonic, idx)) = index_mnemonic(instruction) {
+			let immediate = match *instruction {
+				Instruction::Call(_) | Instruction::RefFunc(_) => self.function_ref(idx),
+				Instruction::GetLocal(_) | Instruction::SetLocal(_) | Instruction::TeeLocal(_) => self.local_ref(func_idx, idx),
+				_ => idx.to_string(),
+			};
+			let _ = write!(self.out, "{} {}", mnemonic, immediate);
+			return;
+		}
+		if let Some((mnemonic, lane)) = lane_mnemonic(instruction) {
+			let _ = write!(self.out, "{} {}", mnemonic, lane);
+			return;
+		}
+		if let Some((mnemonic, natural, arg)) = memory_mnemonic(instruction) {
+			self.out.push_str(mnemonic);
+			if arg.offset != 0 {
+				let _ = write!(self.out, " offset={}", arg.offset);
+			}
+			if u32::from(arg.align) != natural {
+				let _ = write!(self.out, " align={}", 1u64.checked_shl(u32::from(arg.align)).unwrap_or(0));
+			}
+			return;
+		}
+		match *instruction {
+			Instruction::Block(block_type) => self.block("block", block_type),
+			Instruction::Loop(block_type) => self.block("loop", block_type),
+			Instruction::If(block_type) => self.block("if", block_type),
+			Instruction::BrTable(ref data) => {
+				self.out.push_str("br_table");
+				for depth in data.table.iter() {
+					let _ = write!(self.out, " {}", depth);
+				}
+				let _ = write!(self.out, " {}", data.default);
+			},
+			Instruction::CallIndirect(type_ref, 0) => { let _ = write!(self.out, "call_indirect (type {})", type_ref); },
+			Instruction::CallIndirect(type_ref, table) => { let _ = write!(self.out, "call_indirect {} (type {})", table, type_ref); },
+			Instruction::Select => self.out.push_str("select"),
+			Instruction::TypedSelect(value_type) => { let _ = write!(self.out, "select (result {})", value_type); },
+			Instruction::TableInit(segment, 0) => { let _ = write!(self.out, "table.init {}", segment); },
+			Instruction::TableInit(segment, table) => { let _ = write!(self.out, "table.init {} {}", table, segment); },
+			Instruction::TableCopy(0, 0) => self.out.push_str("table.copy"),
+			Instruction::TableCopy(destination, source) => { let _ = write!(self.out, "table.copy {} {}", destination, source); },
+			Instruction::TableGet(table) => { let _ = write!(self.out, "table.get {}", table); },
+			Instruction::TableSet(table) => { let _ = write!(self.out, "table.set {}", table); },
+			Instruction::TableGrow(table) => { let _ = write!(self.out, "table.grow {}", table); },
+			Instruction::TableSize(table) => { let _ = write!(self.out, "table.size {}", table); },
+			Instruction::TableFill(table) => { let _ = write!(self.out, "table.fill {}", table); },
+			Instruction::RefNull(TableElementType::AnyFunc) => self.out.push_str("ref.null func"),
+			Instruction::RefNull(TableElementType::ExternRef) => self.out.push_str("ref.null extern"),
+			Instruction::CurrentMemory(_) => self.out.push_str("memory.size"),
+			Instruction::GrowMemory(_) => self.out.push_str("memory.grow"),
+			Instruction::I32Const(value) => { let _ = write!(self.out, "i32.const {}", value); },
+			Instruction::I64Const(value) => { let _ = write!(self.out, "i64.const {}", value); },
+			Instruction::F32Const(bits) => {
+				self.out.push_str("f32.const ");
+				write_f32(&mut self.out, bits);
+			},
+			Instruction::F64Const(bits) => {
+				self.out.push_str("f64.const ");
+				write_f64(&mut self.out, bits);
+			},
+			Instruction::V128Const(ref bytes) => {
+				self.out.push_str("v128.const i8x16");
+				for byte in bytes.iter() {
+					let _ = write!(self.out, " {}", byte);
+				}
+			},
+			Instruction::V8x16Shuffle(ref lanes) => {
+				self.out.push_str("v8x16.shuffle");
+				for lane in lanes.iter() {
+					let _ = write!(self.out, " {}", lane);
+				}
+			},
+			_ => { let _ = write!(self.out, "{:?}", instruction); },
+		}
+	}
+
+	fn block(&mut self, mnemonic: &str, block_type: BlockType) {
+		self.out.push_str(mnemonic);
+		match block_type {
+			BlockType::Value(value_type) => { let _ = write!(self.out, " (result {})", value_type); },
+			BlockType::TypeIndex(idx) => { let _ = write!(self.out, " (type {})", idx); },
+			BlockType::NoResult => {},
+		}
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::to_wat;
+	use builder::module;
+	use elements::{
+		Instructions, ValueType, DataSegment, InitExpr, NameSection, ModuleNameSection,
+		FunctionNameSection, LocalNameSection, NameMap, Local, BlockType,
+	};
+	use elements::Instruction::*;
+
+	#[test]
+	fn print() {
+		let mut built = module()
+			.import().module("env").field("log").external().func(0).build()
+			.memory().with_min(1).build()
+			.table().with_min(2).with_element(0, vec![1, 0]).build()
+			.global().value_type().i32().mutable().init_expr(I32Const(-1)).build()
+			.function().signature().with_param(ValueType::I32).build()
+				.body()
+					.with_locals(vec![Local::new(2, ValueType::F64)])
+					.with_instructions(Instructions::new(vec![
+						GetLocal(0),
+						If(BlockType::Value(ValueType::I32)),
+						I32Const(1),
+						Else,
+						I32Load(0, 8),
+						End,
+						Call(0),
+						F64Const(1.5f64.to_bits()),
+						SetLocal(1),
+						End,
+					]))
+					.build()
+				.build()
+			.export().field("main").internal().func(1).build()
+			.with_data_segment(DataSegment::new(0, Some(InitExpr::new(vec![I32Const(16), End])), b"hi\"\n".to_vec(), false))
+			.with_data_segment(DataSegment::new(0, None, vec![0xff], true))
+			.build();
+
+		let mut function_names = FunctionNameSection::default();
+		function_names.names_mut().insert(1, "main".to_string());
+		let mut local_names = LocalNameSection::default();
+		let mut locals = NameMap::default();
+		locals.insert(0, "flag".to_string());
+		locals.insert(1, "x".to_string());
+		local_names.local_names_mut().insert(1, locals);
+		built.set_names(vec![
+			NameSection::Module(ModuleNameSection::new("test")),
+			NameSection::Function(function_names),
+			NameSection::Local(local_names),
+		]).expect("names to be set");
+
+		assert_eq!(to_wat(&built), r#"(module $test
+  (type (;0;) (func (param i32)))
+  (import "env" "log" (func (;0;) (type 0)))
+  (func $main (;1;) (type 0) (param $flag i32)
+    (local $x f64)
+    (local f64)
+    local.get $flag
+    if (result i32)
+      i32.const 1
+    else
+      i32.load offset=8 align=1
+    end
+    call 0
+    f64.const 1.5
+    local.set $x)
+  (table (;0;) 2 funcref)
+  (memory (;0;) 1)
+  (global (;0;) (mut i32) (i32.const -1))
+  (export "main" (func $main))
+  (elem (;0;) (i32.const 0) func $main 0)
+  (data (;0;) (i32.const 16) "hi\"\0a")
+  (data (;1;) "\ff"))
+"#);
+	}
+}
@@ -0,0 +1,274 @@
+//! S-expressions the text format is made of.
+
+use std::vec::Vec;
+use std::string::String;
+
+/// S-expression, with the offset in the source where it starts.
+#[derive(Debug)]
+pub(crate) enum Sexpr<'a> {
+	/// Parenthesized list.
+	List(Vec<Sexpr<'a>>, usize),
+	/// Keyword, identifier (starting with `$`) or number.
+	Atom(&'a str, usize),
+	/// String literal, unescaped.
+	Str(Vec<u8>, usize),
+}
+
+impl<'a> Sexpr<'a> {
+	/// Offset in the source where the expression starts.
+	pub(crate) fn offset(&self) -> usize {
+		match *self {
+			Sexpr::List(_, offset) | Sexpr::Atom(_, offset) | Sexpr::Str(_, offset) => offset,
+		}
+	}
+}
+
+/// Error of the text format, at some offset in the source.
+#[derive(Debug)]
+pub(crate) struct ParseError {
+	pub(crate) offset: usize,
+	pub(crate) message: String,
+}
+
+impl ParseError {
+	pub(crate) fn new<M: Into<String>>(offset: usize, message: M) -> ParseError {
+		ParseError { offset, message: message.into() }
+	}
+}
+
+fn is_id_char(c: u8) -> bool {
+	c.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&c)
+}
+
+/// Reads the S-expressions of `source`, skipping whitespace and comments.
+pub(crate) fn read(source: &str) -> Result<Vec<Sexpr<'_>>, ParseError> {
+	let bytes = source.as_bytes();
+	let mut pos = 0;
+	// Items of the lists being read, with the offset of their opening paren.
+	let mut stack: Vec<(Vec<Sexpr>, usize)> = vec![(Vec::new(), 0)];
+	while pos < bytes.len() {
+		let start = pos;
+		match bytes[pos] {
+			b' ' | b'\t' | b'\n' | b'\r' => pos += 1,
+			b';' if bytes.get(pos + 1) == Some(&b';') => {
+				while pos < bytes.len() && bytes[pos] != b'\n' {
+					pos += 1;
+				}
+			},
+			b'(' if bytes.get(pos + 1) == Some(&b';') => {
+				let mut depth = 0;
+				loop {
+					if pos + 1 >= bytes.len() {
+						return Err(ParseError::new(start, "unterminated block comment"));
+					}
+					match (bytes[pos], bytes[pos + 1]) {
+						(b'(', b';') => { depth += 1; pos += 2; },
+						(b';', b')') => {
+							depth -= 1;
+							pos += 2;
+							if depth == 0 {
+								break;
+							}
+						},
+						_ => pos += 1,
+					}
+				}
+			},
+			b'(' => {
+				stack.push((Vec::new(), start));
+				pos += 1;
+			},
+			b')' => {
+				if stack.len() == 1 {
+					return Err(ParseError::new(start, "unexpected `)`"));
+				}
+				let (items, offset) = stack.pop().expect("stack has at least two lists; qed");
+				stack.last_mut().expect("stack has at least one list; qed").0.push(Sexpr::List(items, offset));
+				pos += 1;
+			},
+			b'"' => {
+				pos += 1;
+				let mut value = Vec::new();
+				loop {
+					match bytes.get(pos) {
+						None => return Err(ParseError::new(start, "unterminated string")),
+						Some(&b'"') => { pos += 1; break; },
+						Some(&b'\\') => {
+							let escape = pos;
+							pos += 1;
+							match bytes.get(pos).cloned() {
+								Some(b'n') => { value.push(b'\n'); pos += 1; },
+								Some(b't') => { value.push(b'\t'); pos += 1; },
+								Some(b'r') => { value.push(b'\r'); pos += 1; },
+								Some(b'"') => { value.push(b'"'); pos += 1; },
+								Some(b'\'') => { value.push(b'\''); pos += 1; },
+								Some(b'\\') => { value.push(b'\\'); pos += 1; },
+								Some(b'u') if bytes.get(pos + 1) == Some(&b'{') => {
+									let end = bytes[pos..].iter().position(|&c| c == b'}')
+										.ok_or_else(|| ParseError::new(escape, "unterminated unicode escape"))?;
+									let code = ::std::char::from_u32(
+										u32::from_str_radix(&source[pos + 2..pos + end], 16).unwrap_or(0xd800)
+									).ok_or_else(|| ParseError::new(escape, "invalid unicode escape"))?;
+									let mut buf = [0; 4];
+									value.extend_from_slice(code.encode_utf8(&mut buf).as_bytes());
+									pos += end + 1;
+								},
+								_ => {
+									let byte = source.get(pos..pos + 2)
+										.and_then(|hex| u8::from_str_radix(hex, 16).ok())
+										.ok_or_else(|| ParseError::new(escape, "invalid escape"))?;
+									value.push(byte);
+									pos += 2;
+								},
+							}
+						},
+						Some(_) => {
+							let end = bytes[pos..].iter().position(|&c| c == b'"' || c == b'\\').map_or(bytes.len(), |end| pos + end);
+							value.extend_from_slice(&bytes[pos..end]);
+							pos = end;
+						},
+					}
+				}
+				stack.last_mut().expect("stack has at least one list; qed").0.push(Sexpr::Str(value, start));
+			},
+			c if is_id_char(c) => {
+				while pos < bytes.len() && is_id_char(bytes[pos]) {
+					pos += 1;
+				}
+				stack.last_mut().expect("stack has at least one list; qed").0.push(Sexpr::Atom(&source[start..pos], start));
+			},
+			_ => return Err(ParseError::new(start, "unexpected character")),
+		}
+	}
+	if stack.len() > 1 {
+		return Err(ParseError::new(stack[stack.len() - 1].1, "unclosed `(`"));
+	}
+	Ok(stack.pop().expect("stack has one list; qed").0)
+}
+
+/// Cursor over the items of a list.
+#[derive(Clone)]
+pub(crate) struct Items<'s, 'a: 's> {
+	items: &'s [Sexpr<'a>],
+	/// Offset of the list, for errors about missing items.
+	offset: usize,
+}
+
+impl<'s, 'a> Items<'s, 'a> {
+	pub(crate) fn new(items: &'s [Sexpr<'a>], offset: usize) -> Items<'s, 'a> {
+		Items { items, offset }
+	}
+
+	/// Offset of the next item, or of the list if there is none.
+	pub(crate) fn offset(&self) -> usize {
+		self.items.first().map_or(self.offset, Sexpr::offset)
+	}
+
+	pub(crate) fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+
+	pub(crate) fn peek(&self) -> Option<&'s Sexpr<'a>> {
+		self.items.first()
+	}
+
+	pub(crate) fn next(&mut self) -> Option<&'s Sexpr<'a>> {
+		let (first, rest) = self.items.split_first()?;
+		self.items = rest;
+		Some(first)
+	}
+
+	/// Next item, which is an atom.
+	pub(crate) fn atom(&mut self) -> Result<&'a str, ParseError> {
+		match self.peek() {
+			Some(&Sexpr::Atom(atom, _)) => { self.next(); Ok(atom) },
+			_ => Err(ParseError::new(self.offset(), "expected a keyword or number")),
+		}
+	}
+
+	/// Next item, if it is an atom.
+	pub(crate) fn peek_atom(&self) -> Option<&'a str> {
+		match self.peek() {
+			Some(&Sexpr::Atom(atom, _)) => Some(atom),
+			_ => None,
+		}
+	}
+
+	/// Skips the next item if it is the atom `keyword`.
+	pub(crate) fn keyword(&mut self, keyword: &str) -> bool {
+		if self.peek_atom() == Some(keyword) {
+			self.next();
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Next item, if it is an identifier.
+	pub(crate) fn id(&mut self) -> Option<&'a str> {
+		match self.peek_atom() {
+			Some(atom) if atom.starts_with('$') => { self.next(); Some(atom) },
+			_ => None,
+		}
+	}
+
+	/// Next item, which is a string.
+	pub(crate) fn string(&mut self) -> Result<&'s [u8], ParseError> {
+		match self.peek() {
+			Some(Sexpr::Str(value, _)) => { self.next(); Ok(value) },
+			_ => Err(ParseError::new(self.offset(), "expected a string")),
+		}
+	}
+
+	/// Next item, which is a UTF-8 string.
+	pub(crate) fn name(&mut self) -> Result<String, ParseError> {
+		let offset = self.offset();
+		let value = self.string()?;
+		String::from_utf8(value.to_vec()).map_err(|_| ParseError::new(offset, "malformed UTF-8 name"))
+	}
+
+	/// Items of the next item, if it is a list starting with `keyword`.
+	pub(crate) fn list(&mut self, keyword: &str) -> Option<Items<'s, 'a>> {
+		match self.peek() {
+			Some(&Sexpr::List(ref items, offset)) => match items.first() {
+				Some(&Sexpr::Atom(atom, _)) if atom == keyword => {
+					self.next();
+					Some(Items::new(&items[1..], offset))
+				},
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+
+	/// Fails if there are items left.
+	pub(crate) fn end(&self) -> Result<(), ParseError> {
+		match self.peek() {
+			None => Ok(()),
+			Some(item) => Err(ParseError::new(item.offset(), "unexpected item")),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{read, Sexpr};
+
+	#[test]
+	fn comments_and_strings() {
+		let sexprs = read("(a (; (; nested ;) ;) \"x\\41\\n\\u{e9}\") ;; comment\n$b").expect("source to be read");
+		assert_eq!(sexprs.len(), 2);
+		match sexprs[0] {
+			Sexpr::List(ref items, 0) => {
+				match items[1] {
+					Sexpr::Str(ref value, _) => assert_eq!(&value[..], "xA\n\u{e9}".as_bytes()),
+					ref other => panic!("unexpected {:?}", other),
+				}
+			},
+			ref other => panic!("unexpected {:?}", other),
+		}
+		assert!(read("(a").is_err());
+		assert!(read("a)").is_err());
+		assert!(read("\"a").is_err());
+	}
+}
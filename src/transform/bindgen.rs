@@ -0,0 +1,144 @@
+use std::vec::Vec;
+use std::string::String;
+use std::borrow::ToOwned;
+use elements::{Module, Section, External, Internal};
+
+/// Prefix of the custom sections in which wasm-bindgen stores its
+/// descriptors (`__wasm_bindgen_unstable`).
+pub const BINDGEN_SECTION_PREFIX: &str = "__wasm_bindgen";
+
+/// Import modules of the placeholder functions wasm-bindgen replaces when
+/// generating bindings.
+pub const BINDGEN_PLACEHOLDER_MODULES: &[&str] = &["__wbindgen_placeholder__", "__wbindgen_externref_xform__"];
+
+/// Prefix of the exports wasm-bindgen calls to describe the types of the
+/// bindings.
+pub const BINDGEN_DESCRIBE_PREFIX: &str = "__wbindgen_describe_";
+
+/// Prefix of the functions Embind imports to register bindings.
+pub const EMBIND_IMPORT_PREFIX: &str = "_embind_";
+
+/// Binding generator artifacts found in a module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BindgenItems {
+	/// Positions and names of the wasm-bindgen custom sections.
+	pub sections: Vec<(usize, String)>,
+	/// Function indices of the wasm-bindgen placeholder imports.
+	pub placeholder_imports: Vec<u32>,
+	/// Function indices of the Embind registration imports.
+	pub embind_imports: Vec<u32>,
+	/// Names of the wasm-bindgen describe exports.
+	pub describe_exports: Vec<String>,
+}
+
+impl BindgenItems {
+	/// Whether the module has no binding generator artifacts.
+	pub fn is_empty(&self) -> bool {
+		self.sections.is_empty() && self.placeholder_imports.is_empty()
+			&& self.embind_imports.is_empty() && self.describe_exports.is_empty()
+	}
+}
+
+/// Lists the artifacts left by wasm-bindgen and Embind in `module`.
+///
+/// These are regular custom sections, imports and exports, so they are
+/// kept as they are through deserialization, transformations and
+/// serialization; this only recognizes them.
+pub fn bindgen_items(module: &Module) -> BindgenItems {
+	let mut items = BindgenItems::default();
+	for (position, section) in module.sections().iter().enumerate() {
+		if let Section::Custom(ref custom) = *section {
+			if custom.name().starts_with(BINDGEN_SECTION_PREFIX) {
+				items.sections.push((position, custom.name().to_owned()));
+			}
+		}
+	}
+
+	let imports = module.import_section().map_or(&[][..], |is| is.entries());
+	let functions = imports.iter().filter(|entry| matches!(*entry.external(), External::Function(_)));
+	for (func_idx, entry) in functions.enumerate() {
+		if BINDGEN_PLACEHOLDER_MODULES.contains(&entry.module()) {
+			items.placeholder_imports.push(func_idx as u32);
+		} else if entry.field().starts_with(EMBIND_IMPORT_PREFIX) {
+			items.embind_imports.push(func_idx as u32);
+		}
+	}
+
+	for entry in module.export_section().map_or(&[][..], |es| es.entries()) {
+		if matches!(*entry.internal(), Internal::Function(_)) && entry.field().starts_with(BINDGEN_DESCRIBE_PREFIX) {
+			items.describe_exports.push(entry.field().to_owned());
+		}
+	}
+	items
+}
+
+/// Removes the wasm-bindgen custom sections and describe exports, which are
+/// only used when generating the bindings. The describe functions stay in
+/// the module, unexported.
+///
+/// Returns the number of removed sections and exports.
+pub fn strip_bindgen(module: &mut Module) -> usize {
+	let mut removed = 0;
+	{
+		let sections = module.sections_mut();
+		let before = sections.len();
+		sections.retain(|section| match *section {
+			Section::Custom(ref custom) => !custom.name().starts_with(BINDGEN_SECTION_PREFIX),
+			_ => true,
+		});
+		removed += before - sections.len();
+	}
+	if let Some(exports) = module.export_section_mut() {
+		let entries = exports.entries_mut();
+		let before = entries.len();
+		entries.retain(|entry| !(matches!(*entry.internal(), Internal::Function(_))
+			&& entry.field().starts_with(BINDGEN_DESCRIBE_PREFIX)));
+		removed += before - entries.len();
+	}
+	removed
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{bindgen_items, strip_bindgen};
+	use builder::module;
+	use elements::{serialize, deserialize_buffer, Module, Section, CustomSection};
+
+	fn sample() -> Module {
+		module()
+			.import().module("__wbindgen_placeholder__").field("__wbindgen_describe").external().func(0).build()
+			.import().module("env").field("_embind_register_function").external().func(0).build()
+			.import().module("env").field("abort").external().func(0).build()
+			.function().signature().build().body().build().build()
+			.export().field("__wbindgen_describe_greet").internal().func(3).build()
+			.export().field("greet").internal().func(3).build()
+			.with_section(Section::Custom(CustomSection::new("__wasm_bindgen_unstable".into(), vec![0, 0xff, 1])))
+			.build()
+	}
+
+	#[test]
+	fn items() {
+		let module = sample();
+		let items = bindgen_items(&module);
+		assert_eq!(items.placeholder_imports, vec![0]);
+		assert_eq!(items.embind_imports, vec![1]);
+		assert_eq!(items.describe_exports, vec!["__wbindgen_describe_greet".to_owned()]);
+		assert_eq!(items.sections.len(), 1);
+
+		// The descriptors survive a round trip untouched.
+		let bytes = serialize(module.clone()).expect("module to serialize");
+		let reparsed: Module = deserialize_buffer(&bytes).expect("module to deserialize");
+		assert_eq!(reparsed, module);
+		assert_eq!(serialize(reparsed).expect("module to serialize"), bytes);
+	}
+
+	#[test]
+	fn strip() {
+		let mut module = sample();
+		assert_eq!(strip_bindgen(&mut module), 2);
+		let items = bindgen_items(&module);
+		assert!(items.sections.is_empty() && items.describe_exports.is_empty());
+		assert_eq!(items.placeholder_imports, vec![0]);
+		assert_eq!(module.export_section().expect("export section").entries().len(), 1);
+	}
+}
@@ -0,0 +1,220 @@
+use std::vec::Vec;
+use elements::{
+	Module, Section, Error, External, GlobalEntry, GlobalType, InitExpr, Instruction,
+	ValueType, DataSegment, DataSection, ImportCountType,
+};
+use super::remap::{remap_globals, insert_section};
+
+/// Value of a configuration entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+	/// Value of an `i32` global.
+	I32(i32),
+	/// Value of an `i64` global.
+	I64(i64),
+	/// Value of an `f32` global.
+	F32(f32),
+	/// Value of an `f64` global.
+	F64(f64),
+	/// Bytes copied to memory 0, whose address is the value of an `i32`
+	/// global.
+	Blob(Vec<u8>),
+}
+
+/// Resolves the immutable globals imported from `config_module` with the
+/// values of `config`, turning them into globals defined in the module.
+///
+/// This lets an embedder hand per-instance configuration to a guest without
+/// host functions: the guest imports a global per entry and the embedder
+/// injects the values before instantiating it. Blobs are laid out one after
+/// the other in memory 0 from `blob_base`, in data segments, and the global
+/// holds their address (their length has to be passed in another entry).
+///
+/// Initializer expressions of globals and segments reading an injected
+/// global are replaced by its value, since they can only read imported
+/// globals.
+///
+/// Returns the number of injected globals. Fails, leaving the module
+/// untouched, if an imported global has no entry in `config`, is mutable or
+/// doesn't have the type of its entry, or if blobs are injected in a module
+/// without memory.
+pub fn inject_config(module: &mut Module, config_module: &str, config: &[(&str, ConfigValue)], blob_base: u32) -> Result<usize, Error> {
+	let mut injected = Vec::new();
+	let mut blobs = Vec::new();
+	let mut blob_end = blob_base;
+	let mut global_idx = 0u32;
+	for entry in module.import_section().map_or(&[][..], |is| is.entries()) {
+		let global_type = match *entry.external() {
+			External::Global(ref global_type) => global_type,
+			_ => continue,
+		};
+		global_idx += 1;
+		if entry.module() != config_module {
+			continue;
+		}
+
+		let value = config.iter()
+			.find(|&&(field, _)| field == entry.field())
+			.map(|(_, value)| value)
+			.ok_or_else(|| Error::HeapOther(format!("no configuration entry for {}.{}", entry.module(), entry.field())))?;
+		if global_type.is_mutable() {
+			return Err(Error::HeapOther(format!("configuration global {}.{} is mutable", entry.module(), entry.field())));
+		}
+		let init = match (global_type.content_type(), value) {
+			(ValueType::I32, &ConfigValue::I32(value)) => Instruction::I32Const(value),
+			(ValueType::I64, &ConfigValue::I64(value)) => Instruction::I64Const(value),
+			(ValueType::F32, &ConfigValue::F32(value)) => Instruction::F32Const(value.to_bits()),
+			(ValueType::F64, &ConfigValue::F64(value)) => Instruction::F64Const(value.to_bits()),
+			(ValueType::I32, ConfigValue::Blob(bytes)) => {
+				let address = blob_end;
+				blob_end = (bytes.len() as u32).checked_add(address)
+					.ok_or(Error::Other("configuration blobs overflow the memory"))?;
+				blobs.push(DataSegment::new(0, Some(InitExpr::new(vec![Instruction::I32Const(address as i32), Instruction::End])), bytes.clone(), false));
+				Instruction::I32Const(address as i32)
+			},
+			_ => return Err(Error::HeapOther(format!("configuration global {}.{} doesn't have the type of its value", entry.module(), entry.field()))),
+		};
+		injected.push((global_idx - 1, GlobalEntry::new(GlobalType::new(global_type.content_type(), false), InitExpr::new(vec![init, Instruction::End]))));
+	}
+	if !blobs.is_empty() && module.memory_space() == 0 {
+		return Err(Error::Other("configuration blobs require a memory"));
+	}
+	if injected.is_empty() {
+		return Ok(0);
+	}
+
+	// Injected globals come first among the defined ones, in import order.
+	let old_imported = module.import_count(ImportCountType::Global) as u32;
+	let new_imported = old_imported - injected.len() as u32;
+	let targets: Vec<u32> = {
+		let mut kept = 0;
+		let mut defined = new_imported;
+		(0..old_imported).map(|idx| if injected.iter().any(|&(injected_idx, _)| injected_idx == idx) {
+			defined += 1;
+			defined - 1
+		} else {
+			kept += 1;
+			kept - 1
+		}).collect()
+	};
+
+	if let Some(imports) = module.import_section_mut() {
+		let mut global_idx = 0;
+		imports.entries_mut().retain(|entry| match *entry.external() {
+			External::Global(_) => {
+				global_idx += 1;
+				!injected.iter().any(|&(injected_idx, _)| injected_idx == global_idx - 1)
+			},
+			_ => true,
+		});
+	}
+	remap_globals(module, |idx| match targets.get(idx as usize) {
+		Some(&target) => target,
+		None => idx,
+	});
+
+	let count = injected.len();
+	let values: Vec<Instruction> = injected.iter().map(|(_, global)| global.init_expr().code()[0].clone()).collect();
+	let entries = injected.into_iter().map(|(_, global)| global);
+	match module.global_section_mut() {
+		Some(globals) => {
+			let rest = globals.entries_mut().split_off(0);
+			globals.entries_mut().extend(entries.chain(rest));
+		},
+		None => insert_section(module, Section::Global(::elements::GlobalSection::with_entries(entries.collect()))),
+	}
+	for_each_init_expr_mut(module, |init| {
+		let value = match init.code() {
+			[Instruction::GetGlobal(idx), Instruction::End] if *idx >= new_imported => values.get((*idx - new_imported) as usize).cloned(),
+			_ => None,
+		};
+		if let Some(value) = value {
+			*init = InitExpr::new(vec![value, Instruction::End]);
+		}
+	});
+
+	if !blobs.is_empty() {
+		match module.data_section_mut() {
+			Some(data) => data.entries_mut().extend(blobs),
+			None => insert_section(module, Section::Data(DataSection::with_entries(blobs))),
+		}
+		if module.data_count_section().is_some() {
+			let segments = module.data_section().map_or(0, |ds| ds.entries().len());
+			module.set_data_count_section(segments as u32);
+		}
+	}
+
+	Ok(count)
+}
+
+fn for_each_init_expr_mut<F: FnMut(&mut InitExpr)>(module: &mut Module, mut f: F) {
+	if let Some(globals) = module.global_section_mut() {
+		for entry in globals.entries_mut() {
+			f(entry.init_expr_mut());
+		}
+	}
+	if let Some(data) = module.data_section_mut() {
+		for segment in data.entries_mut() {
+			if let Some(ref mut offset) = *segment.offset_mut() {
+				f(offset);
+			}
+		}
+	}
+	if let Some(elements) = module.elements_section_mut() {
+		for segment in elements.entries_mut() {
+			if let Some(ref mut offset) = *segment.offset_mut() {
+				f(offset);
+			}
+		}
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{inject_config, ConfigValue};
+	use builder::module;
+	use elements::{Instruction, Instructions, Internal, ValueType};
+
+	#[test]
+	fn inject() {
+		let mut module = module()
+			.import().module("config").field("level").external().global(ValueType::I32, false).build()
+			.import().module("env").field("g").external().global(ValueType::I64, false).build()
+			.import().module("config").field("motd").external().global(ValueType::I32, false).build()
+			.memory().build()
+			.global().value_type().i32().init_expr(Instruction::GetGlobal(0)).build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![
+					Instruction::GetGlobal(2),
+					Instruction::GetGlobal(1),
+					Instruction::GetGlobal(3),
+					Instruction::Drop,
+					Instruction::Drop,
+					Instruction::Drop,
+					Instruction::End,
+				])).build()
+				.build()
+			.export().field("level").internal().global(0).build()
+			.build();
+
+		assert!(inject_config(&mut module, "config", &[("level", ConfigValue::I64(3))], 0).is_err());
+		assert!(inject_config(&mut module, "config", &[("level", ConfigValue::I32(3))], 0).is_err());
+
+		let config = [("motd", ConfigValue::Blob(b"hello".to_vec())), ("level", ConfigValue::I32(3))];
+		assert_eq!(inject_config(&mut module, "config", &config, 1024).expect("configuration to be injected"), 2);
+
+		let imports = module.import_section().expect("import section").entries();
+		assert_eq!(imports.len(), 1);
+		assert_eq!(imports[0].field(), "g");
+		let globals = module.global_section().expect("global section").entries();
+		assert_eq!(globals[0].init_expr().code(), &[Instruction::I32Const(3), Instruction::End]);
+		assert_eq!(globals[1].init_expr().code(), &[Instruction::I32Const(1024), Instruction::End]);
+		assert_eq!(globals[2].init_expr().code(), &[Instruction::I32Const(3), Instruction::End]);
+		assert_eq!(
+			&module.code_section().expect("code section").bodies()[0].code().elements()[..3],
+			&[Instruction::GetGlobal(2), Instruction::GetGlobal(0), Instruction::GetGlobal(3)]
+		);
+		assert_eq!(*module.export_section().expect("export section").entries()[0].internal(), Internal::Global(1));
+		assert_eq!(module.data_section().expect("data section").entries()[0].value(), b"hello");
+	}
+}
@@ -0,0 +1,508 @@
+use io;
+use std::vec::Vec;
+use elements::{
+	serialize, CountedList, Deserialize, Error, Instruction, Local, Module, RelocationEntry, Section,
+	VarUint32, LINKING_SECTION_NAME,
+};
+use super::remap::for_each_instruction_mut;
+
+/// How `dedup_data` treats references to the merged data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDedupMode {
+	/// Only find the duplicates, the module is left untouched.
+	Analyze,
+	/// Remove the duplicated segments and rewrite the addresses located by the
+	/// `reloc.CODE` and `reloc.DATA` sections of the module.
+	RewriteRelocations,
+}
+
+/// Data segment merged into another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataMerge {
+	/// Address of the duplicated segment.
+	pub from: u32,
+	/// Length of the duplicated segment.
+	pub len: u32,
+	/// Address of the same bytes within the segment which is kept.
+	pub to: u32,
+}
+
+impl DataMerge {
+	/// Translates `address` if it points into the duplicated segment.
+	pub fn translate(&self, address: u32) -> Option<u32> {
+		if address >= self.from && address - self.from < self.len {
+			Some(self.to + (address - self.from))
+		} else {
+			None
+		}
+	}
+}
+
+/// Result of `dedup_data`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DataDedup {
+	merges: Vec<DataMerge>,
+}
+
+impl DataDedup {
+	/// Duplicated segments found, in the order of the data section.
+	pub fn merges(&self) -> &[DataMerge] {
+		&self.merges
+	}
+
+	/// Number of data bytes saved (or which would be saved in the
+	/// `Analyze` mode).
+	pub fn bytes_saved(&self) -> usize {
+		self.merges.iter().map(|m| m.len as usize).sum()
+	}
+
+	/// Translates an address of the original module into the address of the
+	/// same byte in the transformed module.
+	pub fn translate(&self, address: u32) -> u32 {
+		self.merges.iter()
+			.filter_map(|merge| merge.translate(address))
+			.next()
+			.unwrap_or(address)
+	}
+}
+
+struct Candidate {
+	/// Position of the segment in the data section.
+	position: usize,
+	address: u32,
+	bytes: Vec<u8>,
+}
+
+fn candidates(module: &Module) -> Vec<Candidate> {
+	let segments = match module.data_section() {
+		Some(data) => data.entries(),
+		None => return Vec::new(),
+	};
+
+	let mut candidates: Vec<Candidate> = segments.iter().enumerate()
+		.filter(|&(_, segment)| segment.index() == 0 && !segment.value().is_empty())
+		.filter_map(|(position, segment)| {
+			let address = match segment.offset().as_ref().map(|offset| offset.code()) {
+				Some(&[Instruction::I32Const(address), Instruction::End]) => address as u32,
+				_ => return None,
+			};
+			Some(Candidate { position, address, bytes: segment.value().to_vec() })
+		})
+		.collect();
+
+	// Segments overlapping in memory shadow each other at instantiation, keep
+	// away from them.
+	let overlapping: Vec<usize> = candidates.iter()
+		.filter(|a| candidates.iter().any(|b| {
+			a.position != b.position
+				&& (a.address as u64) < b.address as u64 + b.bytes.len() as u64
+				&& (b.address as u64) < a.address as u64 + a.bytes.len() as u64
+		}))
+		.map(|c| c.position)
+		.collect();
+	candidates.retain(|c| !overlapping.contains(&c.position));
+	candidates
+}
+
+/// Address rewritten by `dedup_data`.
+enum Site {
+	/// `i32.const` immediate, or offset of a load or store, of an instruction
+	/// of a function body.
+	Instruction { body: usize, instruction: usize },
+	/// Little endian `u32` within the value of a data segment.
+	Data { segment: usize, offset: usize },
+}
+
+fn is_relocation_section(section: &Section) -> bool {
+	match *section {
+		Section::Reloc(_) => true,
+		Section::Custom(ref custom) => custom.name().starts_with("reloc.") || custom.name() == LINKING_SECTION_NAME,
+		_ => false,
+	}
+}
+
+fn memarg_offset_mut(instruction: &mut Instruction) -> Option<&mut u32> {
+	use elements::Instruction::*;
+	match *instruction {
+		I32Load(_, ref mut offset) | I64Load(_, ref mut offset) | F32Load(_, ref mut offset)
+		| F64Load(_, ref mut offset) | I32Load8S(_, ref mut offset) | I32Load8U(_, ref mut offset)
+		| I32Load16S(_, ref mut offset) | I32Load16U(_, ref mut offset) | I64Load8S(_, ref mut offset)
+		| I64Load8U(_, ref mut offset) | I64Load16S(_, ref mut offset) | I64Load16U(_, ref mut offset)
+		| I64Load32S(_, ref mut offset) | I64Load32U(_, ref mut offset) | I32Store(_, ref mut offset)
+		| I64Store(_, ref mut offset) | F32Store(_, ref mut offset) | F64Store(_, ref mut offset)
+		| I32Store8(_, ref mut offset) | I32Store16(_, ref mut offset) | I64Store8(_, ref mut offset)
+		| I64Store16(_, ref mut offset) | I64Store32(_, ref mut offset) => Some(offset),
+		_ => None,
+	}
+}
+
+/// Maps `(offset, is_memarg)` relocations of the code section to instructions.
+///
+/// Offsets are relative to the payload of the code section as currently
+/// encoded, which matches the original encoding when the module was decoded
+/// with `deserialize_buffer_with_raw_bodies` (relocated immediates are padded).
+fn code_sites(module: &Module, mut relocations: Vec<(u32, bool)>) -> Result<Vec<Site>, Error> {
+	relocations.sort();
+	let bodies = module.code_section().map(|code| code.bodies()).unwrap_or(&[]);
+	let mut sites = Vec::new();
+	let mut next = 0;
+	let mut position = serialize(VarUint32::from(bodies.len()))?.len();
+	for (body_index, body) in bodies.iter().enumerate() {
+		let encoded = serialize(body.clone())?;
+		let mut rdr = io::Cursor::new(&encoded[..]);
+		VarUint32::deserialize(&mut rdr)?;
+		CountedList::<Local>::deserialize(&mut rdr)?;
+		let mut instruction_index = 0;
+		while rdr.position() < encoded.len() {
+			let start = rdr.position();
+			let mut instruction = Instruction::deserialize(&mut rdr)?;
+			let end = position + rdr.position();
+			let has_memarg = memarg_offset_mut(&mut instruction).is_some();
+			while next < relocations.len() && (relocations[next].0 as usize) < end {
+				let (offset, is_memarg) = relocations[next];
+				let immediate = match (is_memarg, has_memarg, &instruction) {
+					(false, _, &Instruction::I32Const(_)) => start + 1,
+					(true, true, _) => {
+						let mut align = io::Cursor::new(&encoded[start + 1..]);
+						VarUint32::deserialize(&mut align)?;
+						start + 1 + align.position()
+					},
+					_ => return Err(Error::HeapOther(format!("unsupported memory address relocation at {}", offset))),
+				};
+				if offset as usize != position + immediate {
+					return Err(Error::HeapOther(format!("memory address relocation at {} doesn't match the code section", offset)));
+				}
+				sites.push(Site::Instruction { body: body_index, instruction: instruction_index });
+				next += 1;
+			}
+			instruction_index += 1;
+		}
+		position += encoded.len();
+	}
+	if next < relocations.len() {
+		return Err(Error::Other("memory address relocation out of the code section"));
+	}
+	Ok(sites)
+}
+
+/// Maps relocations of the data section to the `u32` they locate.
+fn data_sites(module: &Module, offsets: Vec<u32>) -> Result<Vec<Site>, Error> {
+	let segments = module.data_section().map(|data| data.entries()).unwrap_or(&[]);
+	let mut sites = Vec::new();
+	for offset in offsets {
+		let mut position = serialize(VarUint32::from(segments.len()))?.len();
+		let mut site = None;
+		for (index, segment) in segments.iter().enumerate() {
+			position += serialize(segment.clone())?.len();
+			let start = position - segment.value().len();
+			if offset as usize >= start && offset as usize + 4 <= position {
+				site = Some(Site::Data { segment: index, offset: offset as usize - start });
+				break;
+			}
+		}
+		match site {
+			Some(site) => sites.push(site),
+			None => return Err(Error::HeapOther(format!("memory address relocation at {} doesn't match the data section", offset))),
+		}
+	}
+	Ok(sites)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+	bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+/// Finds data segments whose bytes are identical to, or a suffix of, the
+/// bytes of another segment (typical for duplicated string literals) and
+/// merges them.
+///
+/// Only active segments of memory 0 with a constant offset are considered.
+/// In `RewriteRelocations` mode the duplicated segments are removed and the
+/// memory addresses located by the relocations of the module are redirected to
+/// the kept copy: `i32.const` immediates and load/store offsets in
+/// `reloc.CODE`, pointers stored in data segments in `reloc.DATA`. Nothing else
+/// is rewritten, so an unrelated constant which happens to fall into a removed
+/// segment keeps its value. The module should be decoded with
+/// `deserialize_buffer_with_raw_bodies` so the relocation offsets match the
+/// encoding of the function bodies. The merged data must never be written to,
+/// which holds for read-only data (string literals).
+///
+/// Data segment indices used by `memory.init` and `data.drop`, and the data
+/// count section, are updated. The relocation and linking sections, whose
+/// offsets and segment indices would no longer be accurate, are removed.
+///
+/// An error is returned, and the module is left untouched, if the module has no
+/// relocation sections, if a relocation can not be mapped, if a global is
+/// initialized to an address within a removed segment, or if a removed segment
+/// is used by `memory.init` or `data.drop`.
+pub fn dedup_data(module: &mut Module, mode: DataDedupMode) -> Result<DataDedup, Error> {
+	let mut candidates = candidates(module);
+	// Longest first so a segment can only be merged into one which is kept.
+	candidates.sort_by(|a, b| b.bytes.len().cmp(&a.bytes.len()).then(a.position.cmp(&b.position)));
+
+	let mut kept: Vec<&Candidate> = Vec::new();
+	let mut merged: Vec<(usize, DataMerge)> = Vec::new();
+	for candidate in &candidates {
+		let target = kept.iter().find(|k| k.bytes.ends_with(&candidate.bytes));
+		match target {
+			Some(target) => merged.push((candidate.position, DataMerge {
+				from: candidate.address,
+				len: candidate.bytes.len() as u32,
+				to: target.address + (target.bytes.len() - candidate.bytes.len()) as u32,
+			})),
+			None => kept.push(candidate),
+		}
+	}
+	merged.sort_by_key(|&(position, _)| position);
+
+	let result = DataDedup { merges: merged.iter().map(|&(_, merge)| merge).collect() };
+	if mode == DataDedupMode::Analyze || result.merges.is_empty() {
+		return Ok(result);
+	}
+	let removed: Vec<usize> = merged.iter().map(|&(position, _)| position).collect();
+
+	let relocations = module.relocations()?;
+	if relocations.is_empty() {
+		return Err(Error::Other("data deduplication requires relocations to find the memory addresses"));
+	}
+	let mut code_relocations = Vec::new();
+	let mut data_relocations = Vec::new();
+	for relocation in &relocations {
+		for entry in relocation.entries() {
+			match (relocation.name(), *entry) {
+				("reloc.CODE", RelocationEntry::MemoryAddressSleb { offset, .. }) => code_relocations.push((offset, false)),
+				("reloc.CODE", RelocationEntry::MemoryAddressLeb { offset, .. }) => code_relocations.push((offset, true)),
+				("reloc.DATA", RelocationEntry::MemoryAddressI32 { offset, .. }) => data_relocations.push(offset),
+				("reloc.CODE", RelocationEntry::MemoryAddressI32 { .. })
+				| ("reloc.CODE", RelocationEntry::MemoryAddressRelSleb { .. })
+				| ("reloc.DATA", RelocationEntry::MemoryAddressLeb { .. })
+				| ("reloc.DATA", RelocationEntry::MemoryAddressSleb { .. })
+				| ("reloc.DATA", RelocationEntry::MemoryAddressRelSleb { .. }) =>
+					return Err(Error::Other("unsupported memory address relocation")),
+				_ => {},
+			}
+		}
+	}
+	let mut sites = code_sites(module, code_relocations)?;
+	sites.extend(data_sites(module, data_relocations)?);
+
+	// Globals have no relocations, an address in their initializer can not be
+	// told apart from an unrelated constant.
+	let globals = module.global_section().map(|globals| globals.entries()).unwrap_or(&[]);
+	for global in globals {
+		for instruction in global.init_expr().code() {
+			if let Instruction::I32Const(value) = *instruction {
+				if result.translate(value as u32) != value as u32 {
+					return Err(Error::Other("a global is initialized to an address within a merged segment"));
+				}
+			}
+		}
+	}
+
+	let mut segment_uses = Vec::new();
+	if let Some(code) = module.code_section() {
+		for body in code.bodies() {
+			for instruction in body.code().elements() {
+				match *instruction {
+					Instruction::MemoryInit(index) | Instruction::MemoryDrop(index) => segment_uses.push(index as usize),
+					_ => {},
+				}
+			}
+		}
+	}
+	if segment_uses.iter().any(|index| removed.binary_search(index).is_ok()) {
+		return Err(Error::Other("a merged segment is used by memory.init or data.drop"));
+	}
+
+	for site in sites {
+		match site {
+			Site::Instruction { body, instruction } => {
+				let body = &mut module.code_section_mut().expect("sites are only found in the code section; qed").bodies_mut()[body];
+				let mut rewritten = body.code().elements()[instruction].clone();
+				match rewritten {
+					Instruction::I32Const(ref mut value) => *value = result.translate(*value as u32) as i32,
+					ref mut other => {
+						let offset = memarg_offset_mut(other).expect("relocated instructions have an address; qed");
+						*offset = result.translate(*offset);
+					},
+				}
+				if rewritten != body.code().elements()[instruction] {
+					body.code_mut().elements_mut()[instruction] = rewritten;
+				}
+			},
+			Site::Data { segment, offset } => {
+				if removed.binary_search(&segment).is_ok() {
+					continue;
+				}
+				let value = &mut module.data_section_mut().expect("sites are only found in the data section; qed")
+					.entries_mut()[segment].value_mut()[offset..offset + 4];
+				let translated = result.translate(read_u32(value));
+				value.copy_from_slice(&[translated as u8, (translated >> 8) as u8, (translated >> 16) as u8, (translated >> 24) as u8]);
+			},
+		}
+	}
+
+	let mut segment_count = 0;
+	if let Some(data) = module.data_section_mut() {
+		let mut position = 0;
+		data.entries_mut().retain(|_| {
+			let keep = removed.binary_search(&position).is_err();
+			position += 1;
+			keep
+		});
+		segment_count = data.entries().len();
+	}
+	if !segment_uses.is_empty() {
+		for_each_instruction_mut(module, |instruction| match *instruction {
+			Instruction::MemoryInit(ref mut index) | Instruction::MemoryDrop(ref mut index) => {
+				*index -= removed.iter().filter(|&&position| position < *index as usize).count() as u32;
+			},
+			_ => {},
+		});
+	}
+	module.sections_mut().retain(|section| !is_relocation_section(section));
+	for section in module.sections_mut() {
+		if let Section::DataCount(ref mut count) = *section {
+			*count = segment_count as u32;
+		}
+	}
+
+	Ok(result)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{dedup_data, DataDedupMode, DataMerge};
+	use super::super::remap::insert_section;
+	use builder::module;
+	use elements::{CustomSection, DataSegment, Instructions, Module, Section};
+	use elements::Instruction::*;
+
+	const MEMORY_ADDR_LEB: u8 = 3;
+	const MEMORY_ADDR_SLEB: u8 = 4;
+	const MEMORY_ADDR_I32: u8 = 5;
+
+	fn sample() -> Module {
+		module()
+			.memory().with_min(1)
+				.with_data(16, b"hello world\0".to_vec())
+				.with_data(64, b"world\0".to_vec())
+				.with_data(96, b"hello world\0".to_vec())
+				.with_data(128, b"other\0".to_vec())
+				.with_data(160, vec![64, 0, 0, 0, 98, 0, 0, 0])
+				.build()
+			.with_data_segment(DataSegment::new(0, None, b"passive".to_vec(), true))
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						I32Const(64), Drop,
+						I32Const(98), Drop,
+						// Not an address, happens to fall into the "world\0" segment.
+						I32Const(66), Drop,
+						I32Const(0), I32Load(2, 96), Drop,
+						I32Const(128), Drop,
+						End,
+					]))
+					.build()
+				.build()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						I32Const(0), I32Const(0), I32Const(7), MemoryInit(5),
+						MemoryDrop(5),
+						End,
+					]))
+					.build()
+				.build()
+			.build()
+	}
+
+	fn add_relocations(module: &mut Module, name: &str, section: u8, entries: &[(u8, u8)]) {
+		let mut payload = vec![section, entries.len() as u8];
+		for &(kind, offset) in entries {
+			payload.extend_from_slice(&[kind, offset, 0, 0]);
+		}
+		module.sections_mut().push(Section::Custom(CustomSection::new(name.into(), payload)));
+	}
+
+	fn section_index<F: Fn(&Section) -> bool>(module: &Module, f: F) -> u8 {
+		module.sections().iter().position(f).expect("section to exist") as u8
+	}
+
+	/// `sample` with the relocations a linker would emit for the addresses.
+	fn relocated() -> Module {
+		let mut module = sample();
+		let code = section_index(&module, |section| matches!(*section, Section::Code(_)));
+		let data = section_index(&module, |section| matches!(*section, Section::Data(_)));
+		// Offsets of the immediates in the payload of the code section.
+		add_relocations(&mut module, "reloc.CODE", code, &[
+			(MEMORY_ADDR_SLEB, 4),
+			(MEMORY_ADDR_SLEB, 8),
+			(MEMORY_ADDR_LEB, 19),
+			(MEMORY_ADDR_SLEB, 22),
+		]);
+		// Offsets of the pointers in the payload of the data section.
+		add_relocations(&mut module, "reloc.DATA", data, &[(MEMORY_ADDR_I32, 66), (MEMORY_ADDR_I32, 70)]);
+		module
+	}
+
+	#[test]
+	fn analyze() {
+		let mut module = sample();
+		let result = dedup_data(&mut module, DataDedupMode::Analyze).expect("analysis never fails");
+		assert_eq!(result.bytes_saved(), 18);
+		assert_eq!(result.merges(), &[
+			DataMerge { from: 64, len: 6, to: 22 },
+			DataMerge { from: 96, len: 12, to: 16 },
+		][..]);
+		assert_eq!(module, sample());
+	}
+
+	#[test]
+	fn rewrite() {
+		let mut module = relocated();
+		insert_section(&mut module, Section::DataCount(6));
+		let result = dedup_data(&mut module, DataDedupMode::RewriteRelocations).expect("relocations to be mapped");
+		assert_eq!(result.bytes_saved(), 18);
+
+		let segments = module.data_section().expect("data section").entries();
+		assert_eq!(segments.len(), 4);
+		assert_eq!(segments[0].value(), b"hello world\0");
+		assert_eq!(segments[1].value(), b"other\0");
+		assert_eq!(segments[2].value(), &[22, 0, 0, 0, 18, 0, 0, 0]);
+		assert_eq!(segments[3].value(), b"passive");
+
+		let bodies = module.code_section().expect("code section").bodies();
+		assert_eq!(
+			bodies[0].code().elements(),
+			&[I32Const(22), Drop, I32Const(18), Drop, I32Const(66), Drop, I32Const(0), I32Load(2, 16), Drop, I32Const(128), Drop, End][..]
+		);
+		assert_eq!(
+			bodies[1].code().elements(),
+			&[I32Const(0), I32Const(0), I32Const(7), MemoryInit(3), MemoryDrop(3), End][..]
+		);
+
+		assert!(module.sections().contains(&Section::DataCount(4)));
+		assert!(module.relocations().expect("no relocations").is_empty());
+	}
+
+	#[test]
+	fn no_relocations() {
+		let mut module = sample();
+		assert!(dedup_data(&mut module, DataDedupMode::RewriteRelocations).is_err());
+		assert_eq!(module, sample());
+	}
+
+	#[test]
+	fn mismatched_relocation() {
+		let mut module = sample();
+		// Points into the opcode of an `i32.const` instead of its immediate.
+		let code = section_index(&module, |section| matches!(*section, Section::Code(_)));
+		add_relocations(&mut module, "reloc.CODE", code, &[(MEMORY_ADDR_SLEB, 3)]);
+		let expected = module.clone();
+		assert!(dedup_data(&mut module, DataDedupMode::RewriteRelocations).is_err());
+		assert_eq!(module, expected);
+	}
+}
@@ -0,0 +1,242 @@
+use std::vec::Vec;
+use elements::{Module, Instruction};
+use elements::Instruction::*;
+
+fn bool_i32(value: bool) -> Instruction {
+	I32Const(value as i32)
+}
+
+/// Evaluates a unary operation applied to a constant.
+fn fold_unary(operand: &Instruction, op: &Instruction) -> Option<Instruction> {
+	Some(match (operand, op) {
+		(&I32Const(a), &I32Eqz) => bool_i32(a == 0),
+		(&I32Const(a), &I32Clz) => I32Const(a.leading_zeros() as i32),
+		(&I32Const(a), &I32Ctz) => I32Const(a.trailing_zeros() as i32),
+		(&I32Const(a), &I32Popcnt) => I32Const(a.count_ones() as i32),
+		(&I32Const(a), &I32Extend8S) => I32Const(a as i8 as i32),
+		(&I32Const(a), &I32Extend16S) => I32Const(a as i16 as i32),
+		(&I32Const(a), &I64ExtendSI32) => I64Const(a as i64),
+		(&I32Const(a), &I64ExtendUI32) => I64Const(a as u32 as i64),
+
+		(&I64Const(a), &I64Eqz) => bool_i32(a == 0),
+		(&I64Const(a), &I64Clz) => I64Const(a.leading_zeros() as i64),
+		(&I64Const(a), &I64Ctz) => I64Const(a.trailing_zeros() as i64),
+		(&I64Const(a), &I64Popcnt) => I64Const(a.count_ones() as i64),
+		(&I64Const(a), &I64Extend8S) => I64Const(a as i8 as i64),
+		(&I64Const(a), &I64Extend16S) => I64Const(a as i16 as i64),
+		(&I64Const(a), &I64Extend32S) => I64Const(a as i32 as i64),
+		(&I64Const(a), &I32WrapI64) => I32Const(a as i32),
+
+		_ => return None,
+	})
+}
+
+/// Evaluates a binary operation applied to two constants.
+///
+/// Operations which would trap (division by zero, signed overflow in
+/// division) are left alone so the trap still happens at runtime.
+fn fold_binary(lhs: &Instruction, rhs: &Instruction, op: &Instruction) -> Option<Instruction> {
+	Some(match (lhs, rhs, op) {
+		(&I32Const(a), &I32Const(b), op) => {
+			let (ua, ub) = (a as u32, b as u32);
+			match *op {
+				I32Eq => bool_i32(a == b),
+				I32Ne => bool_i32(a != b),
+				I32LtS => bool_i32(a < b),
+				I32LtU => bool_i32(ua < ub),
+				I32GtS => bool_i32(a > b),
+				I32GtU => bool_i32(ua > ub),
+				I32LeS => bool_i32(a <= b),
+				I32LeU => bool_i32(ua <= ub),
+				I32GeS => bool_i32(a >= b),
+				I32GeU => bool_i32(ua >= ub),
+
+				I32Add => I32Const(a.wrapping_add(b)),
+				I32Sub => I32Const(a.wrapping_sub(b)),
+				I32Mul => I32Const(a.wrapping_mul(b)),
+				I32DivS => I32Const(a.checked_div(b)?),
+				I32DivU => I32Const(ua.checked_div(ub)? as i32),
+				I32RemS if b != 0 => I32Const(a.wrapping_rem(b)),
+				I32RemU => I32Const(ua.checked_rem(ub)? as i32),
+				I32And => I32Const(a & b),
+				I32Or => I32Const(a | b),
+				I32Xor => I32Const(a ^ b),
+				I32Shl => I32Const(a.wrapping_shl(ub)),
+				I32ShrS => I32Const(a.wrapping_shr(ub)),
+				I32ShrU => I32Const(ua.wrapping_shr(ub) as i32),
+				I32Rotl => I32Const(ua.rotate_left(ub % 32) as i32),
+				I32Rotr => I32Const(ua.rotate_right(ub % 32) as i32),
+				_ => return None,
+			}
+		},
+		(&I64Const(a), &I64Const(b), op) => {
+			let (ua, ub) = (a as u64, b as u64);
+			match *op {
+				I64Eq => bool_i32(a == b),
+				I64Ne => bool_i32(a != b),
+				I64LtS => bool_i32(a < b),
+				I64LtU => bool_i32(ua < ub),
+				I64GtS => bool_i32(a > b),
+				I64GtU => bool_i32(ua > ub),
+				I64LeS => bool_i32(a <= b),
+				I64LeU => bool_i32(ua <= ub),
+				I64GeS => bool_i32(a >= b),
+				I64GeU => bool_i32(ua >= ub),
+
+				I64Add => I64Const(a.wrapping_add(b)),
+				I64Sub => I64Const(a.wrapping_sub(b)),
+				I64Mul => I64Const(a.wrapping_mul(b)),
+				I64DivS => I64Const(a.checked_div(b)?),
+				I64DivU => I64Const(ua.checked_div(ub)? as i64),
+				I64RemS if b != 0 => I64Const(a.wrapping_rem(b)),
+				I64RemU => I64Const(ua.checked_rem(ub)? as i64),
+				I64And => I64Const(a & b),
+				I64Or => I64Const(a | b),
+				I64Xor => I64Const(a ^ b),
+				I64Shl => I64Const(a.wrapping_shl(ub as u32)),
+				I64ShrS => I64Const(a.wrapping_shr(ub as u32)),
+				I64ShrU => I64Const(ua.wrapping_shr(ub as u32) as i64),
+				I64Rotl => I64Const(ua.rotate_left((ub % 64) as u32) as i64),
+				I64Rotr => I64Const(ua.rotate_right((ub % 64) as u32) as i64),
+				_ => return None,
+			}
+		},
+		_ => return None,
+	})
+}
+
+/// Tries to simplify the instructions at the end of `code`.
+///
+/// Returns `false` when nothing could be simplified.
+fn fold_tail(code: &mut Vec<Instruction>) -> bool {
+	let len = code.len();
+
+	if len >= 3 {
+		if let Some(folded) = fold_binary(&code[len - 3], &code[len - 2], &code[len - 1]) {
+			code.truncate(len - 3);
+			code.push(folded);
+			return true;
+		}
+	}
+
+	if len >= 2 {
+		if let Some(folded) = fold_unary(&code[len - 2], &code[len - 1]) {
+			code.truncate(len - 2);
+			code.push(folded);
+			return true;
+		}
+
+		match (&code[len - 2], &code[len - 1]) {
+			(&I32Const(condition), &BrIf(depth)) => {
+				code.truncate(len - 2);
+				if condition != 0 {
+					code.push(Br(depth));
+				}
+				return true;
+			},
+			(&I32Const(_), &Drop) | (&I64Const(_), &Drop) => {
+				code.truncate(len - 2);
+				return true;
+			},
+			_ => {},
+		}
+	}
+
+	false
+}
+
+/// Folds constant expressions in a sequence of instructions.
+///
+/// Returns the simplified sequence.
+pub fn fold_instructions(code: Vec<Instruction>) -> Vec<Instruction> {
+	let mut folded = Vec::with_capacity(code.len());
+	for instruction in code {
+		folded.push(instruction);
+		while fold_tail(&mut folded) {}
+	}
+	folded
+}
+
+/// Conservative constant folding over all function bodies.
+///
+/// Integer operations whose operands are constants are evaluated at
+/// transformation time, `br_if` with a constant condition is turned into an
+/// unconditional `br` (or removed when the condition is zero), and constants
+/// which are immediately dropped are removed. Operations which may trap are
+/// kept as they are; floating point operations are not touched.
+///
+/// Returns the number of instructions removed from the module.
+pub fn fold_constants(module: &mut Module) -> usize {
+	let mut removed = 0;
+	if let Some(code) = module.code_section_mut() {
+		for body in code.bodies_mut() {
+			let instructions = body.code_mut().elements_mut();
+			let old = ::std::mem::take(instructions);
+			let old_len = old.len();
+			*instructions = fold_instructions(old);
+			removed += old_len - instructions.len();
+		}
+	}
+	removed
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{fold_instructions, fold_constants};
+	use builder::module;
+	use elements::Instructions;
+	use elements::Instruction::*;
+
+	#[test]
+	fn arithmetic() {
+		assert_eq!(
+			fold_instructions(vec![I32Const(2), I32Const(3), I32Add, I32Const(4), I32Mul, End]),
+			vec![I32Const(20), End]
+		);
+		assert_eq!(
+			fold_instructions(vec![I64Const(-1), I64Const(60), I64ShrU, I32WrapI64, I32Eqz, End]),
+			vec![I32Const(0), End]
+		);
+		assert_eq!(
+			fold_instructions(vec![I32Const(1), I32Const(33), I32Shl, End]),
+			vec![I32Const(2), End]
+		);
+	}
+
+	#[test]
+	fn traps_are_kept() {
+		let code = vec![I32Const(1), I32Const(0), I32DivS, End];
+		assert_eq!(fold_instructions(code.clone()), code);
+
+		let code = vec![I32Const(i32::MIN), I32Const(-1), I32DivS, End];
+		assert_eq!(fold_instructions(code.clone()), code);
+
+		assert_eq!(
+			fold_instructions(vec![I32Const(i32::MIN), I32Const(-1), I32RemS, End]),
+			vec![I32Const(0), End]
+		);
+	}
+
+	#[test]
+	fn branches() {
+		let mut module = module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						I32Const(0), BrIf(0),
+						I32Const(1), I32Const(1), I32Eq, BrIf(0),
+						GetLocal(0), I32Const(7), I32Add, Drop,
+						End,
+					]))
+					.build()
+				.build()
+			.build();
+
+		assert_eq!(fold_constants(&mut module), 5);
+		assert_eq!(
+			module.code_section().expect("code section to exist").bodies()[0].code().elements(),
+			&[Br(0), GetLocal(0), I32Const(7), I32Add, Drop, End][..]
+		);
+	}
+}
@@ -0,0 +1,157 @@
+use std::string::String;
+use elements::{
+	Module, Section, Error, External, FunctionType, ImportEntry, ImportSection, ImportCountType,
+	Instruction, Internal, NameSection, Type, TypeSection,
+};
+use super::remap::{insert_section, remap_functions};
+
+/// Imports the function `module_name.field` with signature `func_type` and
+/// returns its index.
+///
+/// The import comes after the other function imports, so the functions
+/// defined in the module are shifted by one and every reference to them
+/// (calls, exports, element segments, start section and parsed names) is
+/// updated. The signature reuses an identical type if there is one.
+pub fn insert_import_function(module: &mut Module, module_name: &str, field: &str, func_type: FunctionType) -> u32 {
+	let func_type = Type::Function(func_type);
+	let type_ref = match module.type_section().and_then(|ts| ts.types().iter().position(|t| *t == func_type)) {
+		Some(position) => position as u32,
+		None => match module.type_section_mut() {
+			Some(types) => {
+				types.types_mut().push(func_type);
+				types.types().len() as u32 - 1
+			},
+			None => {
+				insert_section(module, Section::Type(TypeSection::with_types(vec![func_type])));
+				0
+			},
+		},
+	};
+
+	let index = module.import_count(ImportCountType::Function) as u32;
+	remap_functions(module, |idx| if idx >= index { idx + 1 } else { idx });
+
+	let entry = ImportEntry::new(String::from(module_name), String::from(field), External::Function(type_ref));
+	match module.import_section_mut() {
+		Some(imports) => imports.entries_mut().push(entry),
+		None => insert_section(module, Section::Import(ImportSection::with_entries(vec![entry]))),
+	}
+	index
+}
+
+/// Removes the function `func_idx` (in the function index space, imports
+/// included), shifting the following functions down by one.
+///
+/// Every reference to the shifted functions (calls, exports, element
+/// segments, start section and parsed names) is updated, and the names of
+/// the removed function are dropped. Types are left as they are.
+///
+/// Fails, leaving the module untouched, if the function doesn't exist or is
+/// still referenced outside of its own body.
+pub fn remove_function(module: &mut Module, func_idx: u32) -> Result<(), Error> {
+	if func_idx as usize >= module.functions_space() {
+		return Err(Error::HeapOther(format!("function {} does not exist", func_idx)));
+	}
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	if is_referenced(module, func_idx, imported) {
+		return Err(Error::HeapOther(format!("function {} is still referenced", func_idx)));
+	}
+
+	if func_idx < imported {
+		let imports = module.import_section_mut().expect("function is imported; qed");
+		let mut function_idx = 0;
+		let position = imports.entries().iter()
+			.position(|entry| match *entry.external() {
+				External::Function(_) => {
+					function_idx += 1;
+					function_idx - 1 == func_idx
+				},
+				_ => false,
+			})
+			.expect("function is imported; qed");
+		imports.entries_mut().remove(position);
+	} else {
+		let body_idx = (func_idx - imported) as usize;
+		module.function_section_mut().expect("function is defined; qed").entries_mut().remove(body_idx);
+		if let Some(code) = module.code_section_mut() {
+			if body_idx < code.bodies().len() {
+				code.bodies_mut().remove(body_idx);
+			}
+		}
+	}
+
+	for section in module.sections_mut() {
+		match *section {
+			Section::Name(NameSection::Function(ref mut names)) => { names.names_mut().remove(func_idx); },
+			Section::Name(NameSection::Local(ref mut names)) => { names.local_names_mut().remove(func_idx); },
+			_ => {},
+		}
+	}
+	remap_functions(module, |idx| if idx > func_idx { idx - 1 } else { idx });
+	Ok(())
+}
+
+/// Whether `func_idx` is referenced anywhere but in its own body.
+fn is_referenced(module: &Module, func_idx: u32, imported: u32) -> bool {
+	let exported = module.export_section().map_or(false, |es| es.entries().iter()
+		.any(|entry| *entry.internal() == Internal::Function(func_idx)));
+	let in_table = module.elements_section().map_or(false, |es| es.entries().iter()
+		.any(|segment| segment.members().contains(&func_idx)));
+	let called = module.code_section().map_or(false, |cs| cs.bodies().iter().enumerate()
+		.filter(|&(body_idx, _)| body_idx as u32 + imported != func_idx)
+		.any(|(_, body)| body.code().elements().iter()
+			.any(|instruction| *instruction == Instruction::Call(func_idx) || *instruction == Instruction::RefFunc(func_idx))));
+	let in_global = module.global_section().map_or(false, |gs| gs.entries().iter()
+		.any(|entry| entry.init_expr().code().contains(&Instruction::RefFunc(func_idx))));
+	exported || in_table || called || in_global || module.start_section() == Some(func_idx)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{insert_import_function, remove_function};
+	use builder::module;
+	use elements::{FunctionType, Instruction, Instructions, Internal, ValueType};
+	use elements::Instruction::*;
+
+	fn calls(module: &::elements::Module) -> Vec<Vec<Instruction>> {
+		module.code_section().expect("code section").bodies().iter()
+			.map(|body| body.code().elements().to_vec())
+			.collect()
+	}
+
+	#[test]
+	fn insert_and_remove() {
+		let mut module = module()
+			.import().module("env").field("log").external().func(0).build()
+			.function().signature().with_param(ValueType::I32).build()
+				.body().with_instructions(Instructions::new(vec![Call(2), End])).build()
+				.build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![I32Const(1), Call(0), Call(1), End])).build()
+				.build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(2).build()
+			.build();
+
+		let index = insert_import_function(&mut module, "env", "gas", FunctionType::with_results(vec![ValueType::I32], Vec::new()));
+		assert_eq!(index, 1);
+		assert_eq!(module.type_section().expect("type section").types().len(), 2);
+		assert_eq!(calls(&module), vec![
+			vec![Call(3), End],
+			vec![I32Const(1), Call(0), Call(2), End],
+			vec![End],
+		]);
+		assert_eq!(*module.export_section().expect("export section").entries()[0].internal(), Internal::Function(3));
+
+		assert!(remove_function(&mut module, 3).is_err());
+		assert!(remove_function(&mut module, 5).is_err());
+		remove_function(&mut module, 1).expect("unused import to be removed");
+		remove_function(&mut module, 3).expect("unused function to be removed");
+		assert_eq!(module.import_section().expect("import section").entries().len(), 1);
+		assert_eq!(calls(&module), vec![
+			vec![Call(2), End],
+			vec![I32Const(1), Call(0), Call(1), End],
+		]);
+		assert_eq!(*module.export_section().expect("export section").entries()[0].internal(), Internal::Function(2));
+	}
+}
@@ -0,0 +1,320 @@
+use std::vec::Vec;
+use std::cmp::Ordering;
+use elements::{Module, ImportEntry, External, FunctionType};
+use super::remap::{remap_functions, remap_globals, remap_tables, remap_memories, function_type};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	Function,
+	Table,
+	Memory,
+	Global,
+}
+
+const KINDS: usize = 4;
+
+fn kind_of(external: &External) -> Kind {
+	match *external {
+		External::Function(_) => Kind::Function,
+		External::Table(_) => Kind::Table,
+		External::Memory(_) => Kind::Memory,
+		External::Global(_) => Kind::Global,
+	}
+}
+
+/// Import entry together with its position in the index space of its kind
+/// before the transformation.
+struct Slot {
+	entry: ImportEntry,
+	kind: Kind,
+	old_index: u32,
+}
+
+fn slots(module: &Module) -> Vec<Slot> {
+	let mut counters = [0u32; KINDS];
+	module.import_section()
+		.map(|is| is.entries().to_vec())
+		.unwrap_or_default()
+		.into_iter()
+		.map(|entry| {
+			let kind = kind_of(entry.external());
+			let old_index = counters[kind as usize];
+			counters[kind as usize] += 1;
+			Slot { entry, kind, old_index }
+		})
+		.collect()
+}
+
+/// Two imports are interchangeable when they name the same field of the same
+/// module with the same external type. Function imports are compared by their
+/// resolved signature rather than by type index.
+fn same_import(module: &Module, a: &ImportEntry, b: &ImportEntry) -> bool {
+	if a.module() != b.module() || a.field() != b.field() {
+		return false;
+	}
+	match (*a.external(), *b.external()) {
+		(External::Function(ta), External::Function(tb)) => {
+			ta == tb || {
+				let fa: Option<&FunctionType> = function_type(module, ta);
+				let fb: Option<&FunctionType> = function_type(module, tb);
+				fa.is_some() && fa == fb
+			}
+		},
+		(ea, eb) => ea == eb,
+	}
+}
+
+/// Rebuilds the import section from `slots` and rewrites all index spaces.
+///
+/// `targets[kind][old_index]` is the new index of the import with `old_index`,
+/// several old indices may map to the same new one.
+fn apply(module: &mut Module, slots: Vec<Slot>, targets: [Vec<u32>; KINDS]) {
+	let mut imported = [0u32; KINDS];
+	for slot in &slots {
+		imported[slot.kind as usize] += 1;
+	}
+	let old_imported: Vec<u32> = targets.iter().map(|t| t.len() as u32).collect();
+
+	let mapper = |kind: Kind| {
+		let kind = kind as usize;
+		let targets = &targets[kind];
+		let old_count = old_imported[kind];
+		let new_count = imported[kind];
+		move |idx: u32| {
+			if idx < old_count {
+				targets[idx as usize]
+			} else {
+				idx - old_count + new_count
+			}
+		}
+	};
+
+	if let Some(section) = module.import_section_mut() {
+		*section.entries_mut() = slots.into_iter().map(|slot| slot.entry).collect();
+	}
+
+	remap_functions(module, mapper(Kind::Function));
+	remap_tables(module, mapper(Kind::Table));
+	remap_memories(module, mapper(Kind::Memory));
+	remap_globals(module, mapper(Kind::Global));
+}
+
+/// Merges duplicate imports.
+///
+/// Imports of the same `(module, field)` pair with the same type are
+/// collapsed into the first occurrence and every reference to the removed
+/// entries (calls, exports, element segments, global accesses, names...) is
+/// rewritten accordingly.
+///
+/// Returns the number of removed import entries.
+pub fn dedup_imports(module: &mut Module) -> usize {
+	let all = slots(module);
+	let total = all.len();
+
+	let mut kept: Vec<Slot> = Vec::with_capacity(total);
+	let mut new_counts = [0u32; KINDS];
+	let mut targets: [Vec<u32>; KINDS] = Default::default();
+
+	for slot in all {
+		let kind = slot.kind as usize;
+		let existing = kept.iter()
+			.find(|k| k.kind == slot.kind && same_import(module, &k.entry, &slot.entry))
+			.map(|k| targets[kind][k.old_index as usize]);
+
+		match existing {
+			Some(target) => targets[kind].push(target),
+			None => {
+				targets[kind].push(new_counts[kind]);
+				new_counts[kind] += 1;
+				kept.push(slot);
+			},
+		}
+	}
+
+	let removed = total - kept.len();
+	if removed > 0 {
+		apply(module, kept, targets);
+	}
+	removed
+}
+
+/// Sorts imports canonically by module name and then by field name.
+///
+/// The sort is stable, so imports with equal names keep their relative
+/// order. All references to the imported entities are rewritten.
+pub fn sort_imports(module: &mut Module) {
+	let mut all = slots(module);
+	all.sort_by(|a, b| match a.entry.module().cmp(b.entry.module()) {
+		Ordering::Equal => a.entry.field().cmp(b.entry.field()),
+		ordering => ordering,
+	});
+
+	let mut targets: [Vec<u32>; KINDS] = Default::default();
+	let mut new_counts = [0u32; KINDS];
+	for slot in &all {
+		let kind = slot.kind as usize;
+		if targets[kind].len() <= slot.old_index as usize {
+			targets[kind].resize(slot.old_index as usize + 1, 0);
+		}
+		targets[kind][slot.old_index as usize] = new_counts[kind];
+		new_counts[kind] += 1;
+	}
+
+	apply(module, all, targets);
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{dedup_imports, sort_imports};
+	use builder::module;
+	use elements::{Instruction, Instructions, Internal, TableType, TableElementType};
+
+	fn sample() -> ::elements::Module {
+		module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::Call(0),
+						Instruction::Call(1),
+						Instruction::Call(2),
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.import().module("env").field("b").external().func(0).build()
+			.import().module("env").field("a").external().func(0).build()
+			.import().module("env").field("b").external().func(0).build()
+			.export().field("main").internal().func(3).build()
+			.build()
+	}
+
+	#[test]
+	fn dedup() {
+		let mut module = sample();
+		assert_eq!(dedup_imports(&mut module), 1);
+
+		let imports = module.import_section().expect("import section to exist").entries();
+		assert_eq!(imports.len(), 2);
+		assert_eq!(imports[0].field(), "b");
+		assert_eq!(imports[1].field(), "a");
+
+		assert_eq!(
+			module.code_section().expect("code section to exist").bodies()[0].code().elements(),
+			&[Instruction::Call(0), Instruction::Call(1), Instruction::Call(0), Instruction::End][..]
+		);
+		assert_eq!(
+			*module.export_section().expect("export section to exist").entries()[0].internal(),
+			Internal::Function(2)
+		);
+	}
+
+	#[test]
+	fn dedup_nothing() {
+		let mut module = module()
+			.import().module("env").field("a").external().func(0).build()
+			.import().module("env").field("a").external().global(::elements::ValueType::I32, false).build()
+			.build();
+		assert_eq!(dedup_imports(&mut module), 0);
+	}
+
+	fn with_tables() -> ::elements::Module {
+		let mut externref = TableType::new(1, None);
+		*externref.elem_type_mut() = TableElementType::ExternRef;
+		module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::I32Const(0),
+						Instruction::TableGet(0),
+						Instruction::Drop,
+						Instruction::I32Const(0),
+						Instruction::I32Const(0),
+						Instruction::I32Const(0),
+						Instruction::TableCopy(2, 0),
+						Instruction::I32Const(0),
+						Instruction::CallIndirect(0, 1),
+						Instruction::TableSize(1),
+						Instruction::Drop,
+						Instruction::End,
+					]))
+					.build()
+				.build()
+			.import().module("env").field("b").external().table_type(externref).build()
+			.import().module("env").field("a").external().table(1, None).build()
+			.import().module("env").field("b").external().table_type(externref).build()
+			.build()
+	}
+
+	fn table_refs(module: &::elements::Module) -> Vec<Instruction> {
+		module.code_section().expect("code section to exist").bodies()[0].code().elements().iter()
+			.filter(|instruction| matches!(**instruction,
+				Instruction::TableGet(_) | Instruction::TableCopy(..) | Instruction::CallIndirect(..) | Instruction::TableSize(_)))
+			.cloned()
+			.collect()
+	}
+
+	#[test]
+	fn tables() {
+		let mut module = with_tables();
+		sort_imports(&mut module);
+		assert_eq!(module.import_section().expect("import section to exist").entries()[0].field(), "a");
+		assert_eq!(table_refs(&module), vec![
+			Instruction::TableGet(1),
+			Instruction::TableCopy(2, 1),
+			Instruction::CallIndirect(0, 0),
+			Instruction::TableSize(0),
+		]);
+
+		let mut module = with_tables();
+		assert_eq!(dedup_imports(&mut module), 1);
+		assert_eq!(table_refs(&module), vec![
+			Instruction::TableGet(0),
+			Instruction::TableCopy(0, 0),
+			Instruction::CallIndirect(0, 1),
+			Instruction::TableSize(1),
+		]);
+	}
+
+	#[test]
+	fn many_tables() {
+		let mut builder = module()
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						Instruction::I32Const(0),
+						Instruction::CallIndirect(0, 0),
+						Instruction::End,
+					]))
+					.build()
+				.build();
+		for i in (0..300).rev() {
+			builder = builder.import().module("env").field(&format!("t{:03}", i)).external().table(1, None).build();
+		}
+		let mut module = builder.build();
+		sort_imports(&mut module);
+		assert_eq!(table_refs(&module), vec![Instruction::CallIndirect(0, 299)]);
+	}
+
+	#[test]
+	fn sort() {
+		let mut module = sample();
+		sort_imports(&mut module);
+
+		let imports = module.import_section().expect("import section to exist").entries();
+		assert_eq!(imports[0].field(), "a");
+		assert_eq!(imports[1].field(), "b");
+		assert_eq!(imports[2].field(), "b");
+
+		assert_eq!(
+			module.code_section().expect("code section to exist").bodies()[0].code().elements(),
+			&[Instruction::Call(1), Instruction::Call(0), Instruction::Call(2), Instruction::End][..]
+		);
+		assert_eq!(
+			*module.export_section().expect("export section to exist").entries()[0].internal(),
+			Internal::Function(3)
+		);
+	}
+}
@@ -0,0 +1,287 @@
+use std::vec::Vec;
+use std::boxed::Box;
+use elements::{
+	Module, FuncBody, Instruction, Local, ValueType, BlockType, FunctionType,
+	ImportCountType, TableElementType,
+};
+use super::remap::function_type;
+
+/// Configuration of the inlining transformation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InlineOptions {
+	/// Maximum number of instructions (not counting the final `end`) in a
+	/// function body for it to be considered for inlining.
+	pub max_instructions: usize,
+	/// Whether functions containing `loop` instructions can be inlined.
+	pub allow_loops: bool,
+}
+
+impl Default for InlineOptions {
+	fn default() -> Self {
+		InlineOptions {
+			max_instructions: 16,
+			allow_loops: false,
+		}
+	}
+}
+
+/// Inlinable function: its signature and its body as found before the
+/// transformation started.
+struct Candidate {
+	signature: FunctionType,
+	body: FuncBody,
+}
+
+fn is_candidate(func_idx: u32, body: &FuncBody, options: &InlineOptions) -> bool {
+	let code = body.code().elements();
+	if code.len().saturating_sub(1) > options.max_instructions {
+		return false;
+	}
+	code.iter().all(|instruction| match *instruction {
+		Instruction::Loop(_) => options.allow_loops,
+		Instruction::Call(callee) => callee != func_idx,
+		_ => true,
+	})
+}
+
+fn zero_const(value_type: ValueType) -> Instruction {
+	match value_type {
+		ValueType::I32 => Instruction::I32Const(0),
+		ValueType::I64 => Instruction::I64Const(0),
+		ValueType::F32 => Instruction::F32Const(0),
+		ValueType::F64 => Instruction::F64Const(0),
+		ValueType::V128 => Instruction::V128Const(Box::new([0u8; 16])),
+		ValueType::FuncRef => Instruction::RefNull(TableElementType::AnyFunc),
+		ValueType::ExternRef => Instruction::RefNull(TableElementType::ExternRef),
+	}
+}
+
+fn local_count(signature: &FunctionType, body: &FuncBody) -> u32 {
+	signature.params().len() as u32 + body.locals().iter().map(|l| l.count()).sum::<u32>()
+}
+
+/// Expands a call to `candidate`, whose locals are mapped starting at `base`.
+fn expand(candidate: &Candidate, base: u32, out: &mut Vec<Instruction>) {
+	let params = candidate.signature.params().len() as u32;
+
+	// Arguments are on the stack in order, so the last one is on top.
+	for idx in (0..params).rev() {
+		out.push(Instruction::SetLocal(base + idx));
+	}
+
+	// Locals of a real call start zeroed, while here they may be reused by
+	// several expansions, so reset them explicitly.
+	let mut idx = base + params;
+	for local in candidate.body.locals() {
+		for _ in 0..local.count() {
+			out.push(zero_const(local.value_type()));
+			out.push(Instruction::SetLocal(idx));
+			idx += 1;
+		}
+	}
+
+	let block_type = match candidate.signature.results().first() {
+		Some(&value_type) => BlockType::Value(value_type),
+		None => BlockType::NoResult,
+	};
+	out.push(Instruction::Block(block_type));
+
+	// The wrapping block plays the role of the function's implicit block, so
+	// branch depths inside the body stay the same and `return` becomes a
+	// branch to the wrapping block.
+	let mut depth = 0u32;
+	for instruction in candidate.body.code().elements() {
+		let instruction = match *instruction {
+			Instruction::GetLocal(idx) => Instruction::GetLocal(base + idx),
+			Instruction::SetLocal(idx) => Instruction::SetLocal(base + idx),
+			Instruction::TeeLocal(idx) => Instruction::TeeLocal(base + idx),
+			Instruction::Return => Instruction::Br(depth),
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => {
+				depth += 1;
+				instruction.clone()
+			},
+			Instruction::End => {
+				depth = depth.wrapping_sub(1);
+				Instruction::End
+			},
+			ref other => other.clone(),
+		};
+		out.push(instruction);
+	}
+}
+
+/// Inlines calls to small functions into their callers.
+///
+/// A function defined in the module is inlined when it returns at most one
+/// value, its body has at most `options.max_instructions` instructions, it
+/// doesn't call itself and (unless `options.allow_loops` is set) it has no
+/// loops. Every call to it is replaced
+/// by a block containing its body: parameters and locals of the callee become
+/// new locals of the caller and `return` is turned into a branch out of the
+/// block.
+///
+/// Inlined functions themselves are kept in the module (they may still be
+/// exported or referenced from tables). Returns the number of call sites
+/// which were inlined.
+pub fn inline_functions(module: &mut Module, options: &InlineOptions) -> usize {
+	let imported = module.import_count(ImportCountType::Function) as u32;
+
+	let signatures: Vec<FunctionType> = match module.function_section() {
+		Some(fs) => fs.entries().iter()
+			.map(|func| function_type(module, func.type_ref()).cloned().unwrap_or_default())
+			.collect(),
+		None => return 0,
+	};
+
+	let candidates: Vec<Option<Candidate>> = match module.code_section() {
+		Some(cs) => cs.bodies().iter().enumerate().map(|(idx, body)| {
+			let func_idx = imported + idx as u32;
+			// The wrapping block of functions with several results would
+			// need a type of its own.
+			match signatures.get(idx) {
+				Some(signature) if signature.results().len() <= 1 && is_candidate(func_idx, body, options) => Some(Candidate {
+					signature: signature.clone(),
+					body: body.clone(),
+				}),
+				_ => None,
+			}
+		}).collect(),
+		None => return 0,
+	};
+
+	let mut inlined = 0;
+	let bodies = module.code_section_mut().expect("code section checked above; qed").bodies_mut();
+	for (idx, body) in bodies.iter_mut().enumerate() {
+		let caller_signature = match signatures.get(idx) {
+			Some(signature) => signature,
+			None => continue,
+		};
+
+		let mut next_local = local_count(caller_signature, body);
+		// Base local index allocated for each callee in this caller.
+		let mut bases: Vec<(u32, u32)> = Vec::new();
+		let mut new_locals: Vec<Local> = Vec::new();
+
+		let old_code = ::std::mem::take(body.code_mut().elements_mut());
+		let mut new_code = Vec::with_capacity(old_code.len());
+		for instruction in old_code {
+			let callee = match instruction {
+				Instruction::Call(callee) if callee >= imported => callee,
+				other => {
+					new_code.push(other);
+					continue;
+				}
+			};
+			let candidate = match candidates.get((callee - imported) as usize) {
+				Some(Some(candidate)) => candidate,
+				_ => {
+					new_code.push(instruction);
+					continue;
+				}
+			};
+
+			let base = match bases.iter().find(|&&(f, _)| f == callee) {
+				Some(&(_, base)) => base,
+				None => {
+					let base = next_local;
+					for param in candidate.signature.params() {
+						new_locals.push(Local::new(1, *param));
+					}
+					new_locals.extend(candidate.body.locals().iter().cloned());
+					next_local += local_count(&candidate.signature, &candidate.body);
+					bases.push((callee, base));
+					base
+				}
+			};
+
+			expand(candidate, base, &mut new_code);
+			inlined += 1;
+		}
+
+		*body.code_mut().elements_mut() = new_code;
+		body.locals_mut().extend(new_locals);
+	}
+
+	inlined
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{inline_functions, InlineOptions};
+	use builder::module;
+	use elements::{Instruction, Instructions, Local, ValueType, BlockType};
+	use elements::Instruction::*;
+
+	fn sample(callee_code: Vec<Instruction>, callee_locals: Vec<Local>) -> ::elements::Module {
+		module()
+			.function()
+				.signature().param().i32().return_type().i32().build()
+				.body()
+					.with_locals(callee_locals)
+					.with_instructions(Instructions::new(callee_code))
+					.build()
+				.build()
+			.function()
+				.signature().return_type().i32().build()
+				.body()
+					.with_instructions(Instructions::new(vec![
+						I32Const(5),
+						Call(0),
+						End,
+					]))
+					.build()
+				.build()
+			.build()
+	}
+
+	#[test]
+	fn simple() {
+		let mut module = sample(vec![GetLocal(0), I32Const(1), I32Add, Return, End], vec![]);
+		assert_eq!(inline_functions(&mut module, &InlineOptions::default()), 1);
+
+		let body = &module.code_section().expect("code section to exist").bodies()[1];
+		assert_eq!(body.locals(), &[Local::new(1, ValueType::I32)][..]);
+		assert_eq!(
+			body.code().elements(),
+			&[
+				I32Const(5),
+				SetLocal(0),
+				Block(BlockType::Value(ValueType::I32)),
+				GetLocal(0),
+				I32Const(1),
+				I32Add,
+				Br(0),
+				End,
+				End,
+			][..]
+		);
+	}
+
+	#[test]
+	fn locals_are_reset() {
+		let mut module = sample(
+			vec![GetLocal(1), GetLocal(0), I32Add, End],
+			vec![Local::new(1, ValueType::I32)],
+		);
+		assert_eq!(inline_functions(&mut module, &InlineOptions::default()), 1);
+
+		let body = &module.code_section().expect("code section to exist").bodies()[1];
+		assert_eq!(body.locals().len(), 2);
+		assert_eq!(&body.code().elements()[..4], &[I32Const(5), SetLocal(0), I32Const(0), SetLocal(1)][..]);
+	}
+
+	#[test]
+	fn loops_and_size() {
+		let code = vec![Loop(BlockType::NoResult), End, GetLocal(0), End];
+		let mut module = sample(code.clone(), vec![]);
+		assert_eq!(inline_functions(&mut module, &InlineOptions::default()), 0);
+
+		let mut module = sample(code.clone(), vec![]);
+		let options = InlineOptions { allow_loops: true, ..Default::default() };
+		assert_eq!(inline_functions(&mut module, &options), 1);
+
+		let mut module = sample(code, vec![]);
+		let options = InlineOptions { allow_loops: true, max_instructions: 2 };
+		assert_eq!(inline_functions(&mut module, &options), 0);
+	}
+}
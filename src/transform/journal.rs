@@ -0,0 +1,291 @@
+use std::mem;
+use std::vec::Vec;
+use std::boxed::Box;
+use elements::{Module, Section, Instruction, Error};
+
+/// Reversible edit of a module.
+///
+/// `revert` is only called on the module `apply` succeeded on, in the state
+/// `apply` left it in, so it can assume the edit is still in place.
+pub trait Edit {
+	/// Performs the edit.
+	fn apply(&mut self, module: &mut Module) -> Result<(), Error>;
+
+	/// Undoes the edit.
+	fn revert(&mut self, module: &mut Module);
+}
+
+/// Inserts a section at the given position in the section list.
+#[derive(Debug, Clone)]
+pub struct InsertSection {
+	position: usize,
+	section: Option<Section>,
+}
+
+impl InsertSection {
+	/// Edit inserting `section` at `position`.
+	pub fn new(position: usize, section: Section) -> Self {
+		InsertSection { position, section: Some(section) }
+	}
+}
+
+impl Edit for InsertSection {
+	fn apply(&mut self, module: &mut Module) -> Result<(), Error> {
+		if self.position > module.sections().len() {
+			return Err(Error::Other("section position out of range"));
+		}
+		let section = self.section.take().expect("apply and revert alternate; qed");
+		module.sections_mut().insert(self.position, section);
+		Ok(())
+	}
+
+	fn revert(&mut self, module: &mut Module) {
+		self.section = Some(module.sections_mut().remove(self.position));
+	}
+}
+
+/// Removes the section at the given position in the section list.
+#[derive(Debug, Clone)]
+pub struct RemoveSection {
+	position: usize,
+	section: Option<Section>,
+}
+
+impl RemoveSection {
+	/// Edit removing the section at `position`.
+	pub fn new(position: usize) -> Self {
+		RemoveSection { position, section: None }
+	}
+}
+
+impl Edit for RemoveSection {
+	fn apply(&mut self, module: &mut Module) -> Result<(), Error> {
+		if self.position >= module.sections().len() {
+			return Err(Error::Other("section position out of range"));
+		}
+		self.section = Some(module.sections_mut().remove(self.position));
+		Ok(())
+	}
+
+	fn revert(&mut self, module: &mut Module) {
+		let section = self.section.take().expect("apply and revert alternate; qed");
+		module.sections_mut().insert(self.position, section);
+	}
+}
+
+/// Replaces the section at the given position in the section list.
+#[derive(Debug, Clone)]
+pub struct ReplaceSection {
+	position: usize,
+	section: Section,
+}
+
+impl ReplaceSection {
+	/// Edit replacing the section at `position` by `section`.
+	pub fn new(position: usize, section: Section) -> Self {
+		ReplaceSection { position, section }
+	}
+}
+
+impl Edit for ReplaceSection {
+	fn apply(&mut self, module: &mut Module) -> Result<(), Error> {
+		match module.sections_mut().get_mut(self.position) {
+			Some(section) => {
+				mem::swap(section, &mut self.section);
+				Ok(())
+			},
+			None => Err(Error::Other("section position out of range")),
+		}
+	}
+
+	fn revert(&mut self, module: &mut Module) {
+		mem::swap(&mut module.sections_mut()[self.position], &mut self.section);
+	}
+}
+
+/// Replaces the instructions of a function body.
+#[derive(Debug, Clone)]
+pub struct ReplaceInstructions {
+	body: usize,
+	instructions: Vec<Instruction>,
+}
+
+impl ReplaceInstructions {
+	/// Edit replacing the instructions of the body number `body` in the
+	/// code section.
+	pub fn new(body: usize, instructions: Vec<Instruction>) -> Self {
+		ReplaceInstructions { body, instructions }
+	}
+
+	fn swap(&mut self, module: &mut Module) -> Result<(), Error> {
+		let body = module.code_section_mut()
+			.and_then(|cs| cs.bodies_mut().get_mut(self.body))
+			.ok_or(Error::Other("function body out of range"))?;
+		mem::swap(body.code_mut().elements_mut(), &mut self.instructions);
+		Ok(())
+	}
+}
+
+impl Edit for ReplaceInstructions {
+	fn apply(&mut self, module: &mut Module) -> Result<(), Error> {
+		self.swap(module)
+	}
+
+	fn revert(&mut self, module: &mut Module) {
+		self.swap(module).expect("the body was found when applying; qed");
+	}
+}
+
+/// Arbitrary edit performed by a closure (typically a whole module
+/// transformation), reverted by restoring a copy of the module taken before
+/// running it.
+pub struct Snapshot<F> {
+	edit: F,
+	saved: Option<Module>,
+}
+
+impl<F: FnMut(&mut Module) -> Result<(), Error>> Snapshot<F> {
+	/// Edit running `edit` on the module.
+	pub fn new(edit: F) -> Self {
+		Snapshot { edit, saved: None }
+	}
+}
+
+impl<F: FnMut(&mut Module) -> Result<(), Error>> Edit for Snapshot<F> {
+	fn apply(&mut self, module: &mut Module) -> Result<(), Error> {
+		let saved = module.clone();
+		match (self.edit)(module) {
+			Ok(()) => {
+				self.saved = Some(saved);
+				Ok(())
+			},
+			Err(e) => {
+				*module = saved;
+				Err(e)
+			},
+		}
+	}
+
+	fn revert(&mut self, module: &mut Module) {
+		*module = self.saved.take().expect("apply and revert alternate; qed");
+	}
+}
+
+/// Undo/redo history of the edits applied to a module.
+///
+/// The module must only be modified through the journal while edits are
+/// recorded, otherwise undoing them has unspecified results.
+#[derive(Default)]
+pub struct Journal {
+	done: Vec<Box<dyn Edit>>,
+	undone: Vec<Box<dyn Edit>>,
+}
+
+impl Journal {
+	/// New empty journal.
+	pub fn new() -> Self {
+		Journal::default()
+	}
+
+	/// Applies `edit` to `module` and records it, forgetting the edits which
+	/// were undone. Failed edits leave the module and the journal untouched.
+	pub fn apply<E: Edit + 'static>(&mut self, module: &mut Module, mut edit: E) -> Result<(), Error> {
+		edit.apply(module)?;
+		self.done.push(Box::new(edit));
+		self.undone.clear();
+		Ok(())
+	}
+
+	/// Reverts the last applied edit. Returns `false` if there is none.
+	pub fn undo(&mut self, module: &mut Module) -> bool {
+		match self.done.pop() {
+			Some(mut edit) => {
+				edit.revert(module);
+				self.undone.push(edit);
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Applies again the last undone edit. Returns `false` if there is none.
+	pub fn redo(&mut self, module: &mut Module) -> Result<bool, Error> {
+		match self.undone.pop() {
+			Some(mut edit) => {
+				if let Err(e) = edit.apply(module) {
+					self.undone.push(edit);
+					return Err(e);
+				}
+				self.done.push(edit);
+				Ok(true)
+			},
+			None => Ok(false),
+		}
+	}
+
+	/// Number of edits which can be undone.
+	pub fn undo_len(&self) -> usize {
+		self.done.len()
+	}
+
+	/// Number of edits which can be redone.
+	pub fn redo_len(&self) -> usize {
+		self.undone.len()
+	}
+
+	/// Forgets all recorded edits, keeping the module as it is.
+	pub fn clear(&mut self) {
+		self.done.clear();
+		self.undone.clear();
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{Journal, InsertSection, RemoveSection, ReplaceInstructions, Snapshot};
+	use builder::module;
+	use elements::{Module, Section, CustomSection, Instruction};
+	use elements::Instruction::*;
+	use transform::fold_constants;
+
+	fn code(module: &Module) -> Vec<Instruction> {
+		module.code_section().expect("code section").bodies()[0].code().elements().to_vec()
+	}
+
+	#[test]
+	fn undo_redo() {
+		let mut module = module()
+			.function().signature().build()
+				.body().with_instructions(::elements::Instructions::new(vec![I32Const(1), Drop, End])).build()
+				.build()
+			.build();
+		let original = module.clone();
+		let mut journal = Journal::new();
+
+		let custom = Section::Custom(CustomSection::new("hello".into(), vec![1]));
+		journal.apply(&mut module, InsertSection::new(0, custom)).expect("insertion to succeed");
+		journal.apply(&mut module, ReplaceInstructions::new(0, vec![Nop, I32Const(2), Drop, End]))
+			.expect("replacement to succeed");
+		journal.apply(&mut module, Snapshot::new(|m: &mut Module| { fold_constants(m); Ok(()) }))
+			.expect("folding to succeed");
+		assert_eq!(code(&module), vec![Nop, End]);
+		assert!(journal.apply(&mut module, RemoveSection::new(10)).is_err());
+		assert_eq!(journal.undo_len(), 3);
+
+		assert!(journal.undo(&mut module));
+		assert_eq!(code(&module), vec![Nop, I32Const(2), Drop, End]);
+		assert!(journal.undo(&mut module));
+		assert!(journal.undo(&mut module));
+		assert!(!journal.undo(&mut module));
+		assert_eq!(module, original);
+
+		assert!(journal.redo(&mut module).expect("redo to succeed"));
+		assert_eq!(module.sections().len(), original.sections().len() + 1);
+
+		// A new edit discards the redo history.
+		journal.apply(&mut module, RemoveSection::new(0)).expect("removal to succeed");
+		assert_eq!(journal.redo_len(), 0);
+		assert!(!journal.redo(&mut module).expect("redo to succeed"));
+		assert_eq!(module, original);
+	}
+}
@@ -0,0 +1,84 @@
+use std::string::String;
+use elements::{Module, Section, NameSection, LocalNameSection, NameMap, Local, ValueType};
+
+/// Declares a new local of type `value_type` in the function `func_idx` (in
+/// the function index space, imports included) and returns its index.
+///
+/// If `name` is given, it is recorded in the local name section, which is
+/// created if needed. Names are left untouched when the name section has
+/// not been parsed (see `Module::parse_names`).
+///
+/// Returns `None` if `func_idx` doesn't refer to a function defined in the
+/// module.
+pub fn add_local(module: &mut Module, func_idx: u32, value_type: ValueType, name: Option<String>) -> Option<u32> {
+	let variables = ::analysis::function_locals(module, func_idx)?;
+	let index = variables.len() as u32;
+
+	let body_idx = (func_idx as usize).checked_sub(module.import_count(::elements::ImportCountType::Function))?;
+	let body = module.code_section_mut()?.bodies_mut().get_mut(body_idx)?;
+	let locals = body.locals_mut();
+	match locals.last_mut() {
+		Some(last) if last.value_type() == value_type => *last = Local::new(last.count() + 1, value_type),
+		_ => locals.push(Local::new(1, value_type)),
+	}
+
+	if let Some(name) = name {
+		let unparsed_names = module.sections().iter()
+			.any(|section| matches!(*section, Section::Custom(ref custom) if custom.name() == "name"));
+		let local_names = module.sections_mut().iter_mut()
+			.filter_map(|section| match *section {
+				Section::Name(NameSection::Local(ref mut local_names)) => Some(local_names),
+				_ => None,
+			})
+			.next();
+		match local_names {
+			Some(local_names) => {
+				let map = local_names.local_names_mut();
+				let mut names = map.remove(func_idx).unwrap_or_default();
+				names.insert(index, name);
+				map.insert(func_idx, names);
+			},
+			None if !unparsed_names => {
+				let mut names = NameMap::default();
+				names.insert(index, name);
+				let mut local_names = LocalNameSection::default();
+				local_names.local_names_mut().insert(func_idx, names);
+				module.sections_mut().push(Section::Name(NameSection::Local(local_names)));
+			},
+			None => {},
+		}
+	}
+
+	Some(index)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::add_local;
+	use analysis::function_locals;
+	use builder::module;
+	use elements::{Local, ValueType};
+
+	#[test]
+	fn names_follow_new_locals() {
+		let mut module = module()
+			.import().module("env").field("f").external().func(0).build()
+			.function()
+				.signature().with_param(ValueType::I32).build()
+				.body().with_locals(vec![Local::new(1, ValueType::I64)]).build()
+				.build()
+			.build();
+
+		assert_eq!(add_local(&mut module, 1, ValueType::I64, Some("counter".into())), Some(2));
+		assert_eq!(add_local(&mut module, 1, ValueType::F32, None), Some(3));
+		assert_eq!(add_local(&mut module, 1, ValueType::F32, Some("x".into())), Some(4));
+		assert_eq!(add_local(&mut module, 0, ValueType::I32, None), None);
+
+		let body = &module.code_section().expect("code section").bodies()[0];
+		assert_eq!(body.locals(), &[Local::new(2, ValueType::I64), Local::new(2, ValueType::F32)][..]);
+
+		let names: Vec<_> = function_locals(&module, 1).expect("function to exist")
+			.iter().map(|l| l.name().map(|n| n.to_owned())).collect();
+		assert_eq!(names, vec![None, None, Some("counter".to_owned()), None, Some("x".to_owned())]);
+	}
+}
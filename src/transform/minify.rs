@@ -0,0 +1,337 @@
+use std::vec::Vec;
+use std::string::String;
+use std::borrow::ToOwned;
+use elements::{Module, Section, Error};
+
+/// Module and field names of an import.
+pub type ImportName = (String, String);
+
+/// Renaming of exports and imports, which also maps minified names back to
+/// the original ones.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameMapping {
+	exports: Vec<(String, String)>,
+	imports: Vec<(ImportName, ImportName)>,
+}
+
+fn escape(name: &str, out: &mut String) {
+	for c in name.chars() {
+		match c {
+			'\\' => out.push_str("\\\\"),
+			'\t' => out.push_str("\\t"),
+			'\n' => out.push_str("\\n"),
+			c => out.push(c),
+		}
+	}
+}
+
+fn unescape(field: &str) -> Result<String, Error> {
+	let mut name = String::new();
+	let mut chars = field.chars();
+	while let Some(c) = chars.next() {
+		name.push(match c {
+			'\\' => match chars.next() {
+				Some('\\') => '\\',
+				Some('t') => '\t',
+				Some('n') => '\n',
+				_ => return Err(Error::Other("invalid escape in name mapping")),
+			},
+			c => c,
+		});
+	}
+	Ok(name)
+}
+
+impl NameMapping {
+	/// New empty mapping.
+	pub fn new() -> Self {
+		NameMapping::default()
+	}
+
+	/// Renames the export `original` to `renamed`.
+	pub fn add_export<S: Into<String>, T: Into<String>>(&mut self, original: S, renamed: T) {
+		self.exports.push((original.into(), renamed.into()));
+	}
+
+	/// Renames the imports of the field `original.1` of the module
+	/// `original.0` to the field `renamed.1` of the module `renamed.0`.
+	pub fn add_import(&mut self, original: ImportName, renamed: ImportName) {
+		self.imports.push((original, renamed));
+	}
+
+	/// Renamed exports, as `(original, renamed)` pairs.
+	pub fn exports(&self) -> &[(String, String)] {
+		&self.exports
+	}
+
+	/// Renamed imports, as `((module, field), (renamed module, renamed field))`.
+	pub fn imports(&self) -> &[(ImportName, ImportName)] {
+		&self.imports
+	}
+
+	/// Original name of the export renamed to `renamed`.
+	pub fn original_export(&self, renamed: &str) -> Option<&str> {
+		self.exports.iter().find(|(_, r)| r == renamed).map(|(original, _)| original.as_str())
+	}
+
+	/// Original module and field names of the import renamed to
+	/// `module`/`field`.
+	pub fn original_import(&self, module: &str, field: &str) -> Option<(&str, &str)> {
+		self.imports.iter()
+			.find(|(_, (m, f))| m == module && f == field)
+			.map(|((m, f), _)| (m.as_str(), f.as_str()))
+	}
+
+	/// Text form of the mapping, one entry per line:
+	/// `export<TAB>original<TAB>renamed` or
+	/// `import<TAB>module<TAB>field<TAB>renamed module<TAB>renamed field`,
+	/// with backslashes, tabs and newlines in names escaped as `\\`, `\t`
+	/// and `\n`.
+	pub fn to_text(&self) -> String {
+		let mut text = String::new();
+		for (original, renamed) in &self.exports {
+			text.push_str("export");
+			for name in &[original, renamed] {
+				text.push('\t');
+				escape(name, &mut text);
+			}
+			text.push('\n');
+		}
+		for ((module, field), (renamed_module, renamed_field)) in &self.imports {
+			text.push_str("import");
+			for name in &[module, field, renamed_module, renamed_field] {
+				text.push('\t');
+				escape(name, &mut text);
+			}
+			text.push('\n');
+		}
+		text
+	}
+
+	/// Parses the text form produced by `to_text`.
+	pub fn from_text(text: &str) -> Result<NameMapping, Error> {
+		let mut mapping = NameMapping::new();
+		for line in text.lines().filter(|line| !line.is_empty()) {
+			let fields = line.split('\t').map(unescape).collect::<Result<Vec<_>, _>>()?;
+			match (fields[0].as_str(), fields.len()) {
+				("export", 3) => mapping.add_export(fields[1].clone(), fields[2].clone()),
+				("import", 5) => mapping.add_import(
+					(fields[1].clone(), fields[2].clone()),
+					(fields[3].clone(), fields[4].clone()),
+				),
+				_ => return Err(Error::HeapOther(format!("invalid name mapping entry: {}", line))),
+			}
+		}
+		Ok(mapping)
+	}
+}
+
+/// What `minify_names` renames.
+#[derive(Debug, Clone)]
+pub struct MinifyOptions {
+	/// Rename exports. Defaults to `true`.
+	pub exports: bool,
+	/// Rename imports (module and field names), which requires the host to
+	/// provide them under the new names. Defaults to `false`.
+	pub imports: bool,
+	/// Exports keeping their name (e.g. entry points the host looks up).
+	pub keep_exports: Vec<String>,
+	/// Remove the name section. Defaults to `true`.
+	pub strip_names: bool,
+}
+
+impl Default for MinifyOptions {
+	fn default() -> Self {
+		MinifyOptions {
+			exports: true,
+			imports: false,
+			keep_exports: Vec::new(),
+			strip_names: true,
+		}
+	}
+}
+
+/// Generates the `n`-th short name: `a` to `z`, `A` to `Z`, then `aa`, ...
+fn short_name(mut n: usize) -> String {
+	const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+	let mut name = Vec::new();
+	loop {
+		name.push(ALPHABET[n % ALPHABET.len()]);
+		n /= ALPHABET.len();
+		if n == 0 {
+			break;
+		}
+		n -= 1;
+	}
+	name.reverse();
+	String::from_utf8(name).expect("alphabet is ASCII; qed")
+}
+
+/// Generator of short names avoiding a set of reserved names.
+struct Names<'a> {
+	next: usize,
+	reserved: &'a [String],
+}
+
+impl<'a> Names<'a> {
+	fn next(&mut self) -> String {
+		loop {
+			let name = short_name(self.next);
+			self.next += 1;
+			if !self.reserved.contains(&name) {
+				return name;
+			}
+		}
+	}
+}
+
+/// Removes the name section, parsed or not.
+///
+/// Returns the number of removed sections.
+pub fn strip_names(module: &mut Module) -> usize {
+	let sections = module.sections_mut();
+	let before = sections.len();
+	sections.retain(|section| match *section {
+		Section::Name(_) => false,
+		Section::Custom(ref custom) => custom.name() != "name",
+		_ => true,
+	});
+	before - sections.len()
+}
+
+/// Renames exports and imports according to `mapping`. Exports and imports
+/// which are not in the mapping keep their name.
+///
+/// Fails if two exports end up with the same name.
+pub fn rename(module: &mut Module, mapping: &NameMapping) -> Result<(), Error> {
+	if let Some(exports) = module.export_section_mut() {
+		for entry in exports.entries_mut() {
+			if let Some((_, renamed)) = mapping.exports.iter().find(|(original, _)| original == entry.field()) {
+				*entry.field_mut() = renamed.clone();
+			}
+		}
+		let mut names: Vec<&str> = exports.entries().iter().map(|entry| entry.field()).collect();
+		names.sort_unstable();
+		if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+			return Err(Error::HeapOther(format!("duplicate export name {} after renaming", pair[0])));
+		}
+	}
+	if let Some(imports) = module.import_section_mut() {
+		for entry in imports.entries_mut() {
+			let renamed = mapping.imports.iter()
+				.find(|((module, field), _)| module == entry.module() && field == entry.field())
+				.map(|(_, renamed)| renamed.clone());
+			if let Some((module, field)) = renamed {
+				*entry.module_mut() = module;
+				*entry.field_mut() = field;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Gives short generated names to exports (and optionally imports), and
+/// strips the name section, returning the mapping to the original names.
+///
+/// Import module names are renamed consistently across imports, and field
+/// names consistently within a module.
+pub fn minify_names(module: &mut Module, options: &MinifyOptions) -> Result<NameMapping, Error> {
+	let mut mapping = NameMapping::new();
+
+	if options.exports {
+		let mut names = Names { next: 0, reserved: &options.keep_exports };
+		for entry in module.export_section().map_or(&[][..], |es| es.entries()) {
+			if !options.keep_exports.iter().any(|keep| keep == entry.field()) {
+				mapping.add_export(entry.field(), names.next());
+			}
+		}
+	}
+
+	if options.imports {
+		let mut modules: Vec<(String, String, Names)> = Vec::new();
+		let mut module_names = Names { next: 0, reserved: &[] };
+		for entry in module.import_section().map_or(&[][..], |is| is.entries()) {
+			let original = (entry.module().to_owned(), entry.field().to_owned());
+			if mapping.imports.iter().any(|(o, _)| *o == original) {
+				continue;
+			}
+			let position = match modules.iter().position(|(m, _, _)| m == entry.module()) {
+				Some(position) => position,
+				None => {
+					modules.push((entry.module().to_owned(), module_names.next(), Names { next: 0, reserved: &[] }));
+					modules.len() - 1
+				},
+			};
+			let (_, ref renamed_module, ref mut fields) = modules[position];
+			mapping.add_import(original, (renamed_module.clone(), fields.next()));
+		}
+	}
+
+	rename(module, &mapping)?;
+	if options.strip_names {
+		strip_names(module);
+	}
+	Ok(mapping)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{minify_names, short_name, MinifyOptions, NameMapping};
+	use builder::module;
+	use elements::{Section, CustomSection};
+
+	#[test]
+	fn names() {
+		assert_eq!(short_name(0), "a");
+		assert_eq!(short_name(51), "Z");
+		assert_eq!(short_name(52), "aa");
+		assert_eq!(short_name(52 + 52 * 52), "aaa");
+	}
+
+	#[test]
+	fn minify() {
+		let mut module = module()
+			.import().module("env").field("memcpy").external().func(0).build()
+			.import().module("env").field("abort").external().func(0).build()
+			.import().module("wasi").field("fd_write").external().func(0).build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(3).build()
+			.export().field("helper_one").internal().func(3).build()
+			.export().field("helper_two").internal().func(3).build()
+			.with_section(Section::Custom(CustomSection::new("name".into(), vec![0, 1, 0])))
+			.build();
+
+		let options = MinifyOptions { imports: true, keep_exports: vec!["a".into()], ..Default::default() };
+		let mapping = minify_names(&mut module, &options).expect("names to be minified");
+
+		let exports: Vec<&str> = module.export_section().expect("exports").entries().iter().map(|e| e.field()).collect();
+		assert_eq!(exports, vec!["b", "c", "d"]);
+		let imports: Vec<(&str, &str)> = module.import_section().expect("imports").entries().iter()
+			.map(|e| (e.module(), e.field())).collect();
+		assert_eq!(imports, vec![("a", "a"), ("a", "b"), ("b", "a")]);
+		assert!(module.sections().iter().all(|s| !matches!(*s, Section::Custom(_))));
+
+		assert_eq!(mapping.original_export("c"), Some("helper_one"));
+		assert_eq!(mapping.original_import("b", "a"), Some(("wasi", "fd_write")));
+
+		let text = mapping.to_text();
+		assert!(text.starts_with("export\tmain\tb\n"));
+		assert_eq!(NameMapping::from_text(&text).expect("mapping to parse"), mapping);
+	}
+
+	#[test]
+	fn rename_conflict() {
+		let mut module = module()
+			.function().signature().build().body().build().build()
+			.export().field("a").internal().func(0).build()
+			.export().field("b").internal().func(0).build()
+			.build();
+		let mut mapping = NameMapping::new();
+		mapping.add_export("a", "b");
+		assert!(super::rename(&mut module, &mapping).is_err());
+
+		let mut mapping = NameMapping::new();
+		mapping.add_export("tab\there", "new\\line\n");
+		assert_eq!(NameMapping::from_text(&mapping.to_text()).expect("mapping to parse"), mapping);
+	}
+}
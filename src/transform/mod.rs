@@ -0,0 +1,32 @@
+//! Transformations of the whole module (rewriting index spaces, merging entries, ...)
+
+mod remap;
+mod imports;
+mod inline;
+mod fold;
+mod split;
+mod data;
+mod locals;
+mod journal;
+mod minify;
+mod splice;
+mod bindgen;
+mod config;
+mod functions;
+mod names;
+
+pub use self::imports::{dedup_imports, sort_imports};
+pub use self::inline::{inline_functions, InlineOptions};
+pub use self::fold::{fold_constants, fold_instructions};
+pub use self::split::{split_module, SPLIT_PRIMARY_MODULE};
+pub use self::data::{dedup_data, DataDedupMode, DataDedup, DataMerge};
+pub use self::locals::add_local;
+pub use self::journal::{Journal, Edit, InsertSection, RemoveSection, ReplaceSection, ReplaceInstructions, Snapshot};
+pub use self::minify::{minify_names, rename, strip_names, MinifyOptions, NameMapping, ImportName};
+pub use self::bindgen::{
+	bindgen_items, strip_bindgen, BindgenItems, BINDGEN_SECTION_PREFIX, BINDGEN_PLACEHOLDER_MODULES,
+	BINDGEN_DESCRIBE_PREFIX, EMBIND_IMPORT_PREFIX,
+};
+pub use self::config::{inject_config, ConfigValue};
+pub use self::functions::{insert_import_function, remove_function};
+pub use self::names::synthesize_names;
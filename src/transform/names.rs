@@ -0,0 +1,100 @@
+use std::vec::Vec;
+use std::string::String;
+use elements::{Module, Error, External, Internal, NameSection, FunctionNameSection};
+
+/// Adds function names to a module whose name section has none, so that
+/// dumps and backtraces of stripped modules stay readable.
+///
+/// Imported functions are named `module.field`, defined functions after
+/// their first export and the others `func{index}`. A name already given
+/// to a previous function gets `#{index}` appended. The other subsections
+/// of the name section are kept.
+///
+/// Returns whether names were added.
+pub fn synthesize_names(module: &mut Module) -> Result<bool, Error> {
+	let mut names = module.names()?;
+	if names.iter().any(|name_section| match *name_section {
+		NameSection::Function(_) => true,
+		NameSection::Unparsed { name_type, .. } => name_type == 1,
+		_ => false,
+	}) {
+		return Ok(false);
+	}
+
+	let mut candidates: Vec<Option<String>> = vec![None; module.functions_space()];
+	let mut func_idx = 0;
+	for entry in module.import_section().map_or(&[][..], |is| is.entries()) {
+		if let External::Function(_) = *entry.external() {
+			candidates[func_idx] = Some(format!("{}.{}", entry.module(), entry.field()));
+			func_idx += 1;
+		}
+	}
+	for entry in module.export_section().map_or(&[][..], |es| es.entries()) {
+		if let Internal::Function(idx) = *entry.internal() {
+			if let Some(candidate) = candidates.get_mut(idx as usize) {
+				candidate.get_or_insert_with(|| entry.field().into());
+			}
+		}
+	}
+	if candidates.is_empty() {
+		return Ok(false);
+	}
+
+	// Names given so far, sorted.
+	let mut taken: Vec<String> = Vec::new();
+	let mut functions = FunctionNameSection::default();
+	for (idx, candidate) in candidates.into_iter().enumerate() {
+		let mut name = candidate.unwrap_or_else(|| format!("func{}", idx));
+		if taken.binary_search(&name).is_ok() {
+			name = format!("{}#{}", name, idx);
+		}
+		let position = taken.binary_search(&name).unwrap_or_else(|position| position);
+		taken.insert(position, name.clone());
+		functions.names_mut().insert(idx as u32, name);
+	}
+
+	// Subsections are ordered by id: the module name comes first.
+	let position = names.iter().position(|name_section| match *name_section {
+		NameSection::Module(_) => false,
+		NameSection::Unparsed { name_type, .. } => name_type > 1,
+		_ => true,
+	}).unwrap_or(names.len());
+	names.insert(position, NameSection::Function(functions));
+	module.set_names(names)?;
+	Ok(true)
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::synthesize_names;
+	use builder::module;
+	use elements::{NameSection, ModuleNameSection};
+
+	#[test]
+	fn synthesize() {
+		let mut built = module()
+			.import().module("env").field("log").external().func(0).build()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.function().signature().build().body().build().build()
+			.export().field("main").internal().func(1).build()
+			.export().field("start").internal().func(1).build()
+			.export().field("func3").internal().func(2).build()
+			.build();
+		built.set_names(vec![NameSection::Module(ModuleNameSection::new("app"))]).expect("names to be set");
+
+		assert!(synthesize_names(&mut built).expect("names to be synthesized"));
+		let names = built.names().expect("names to be parsed");
+		assert_eq!(names[0], NameSection::Module(ModuleNameSection::new("app")));
+		match names[1] {
+			NameSection::Function(ref functions) => {
+				let names: Vec<(u32, &str)> = functions.names().iter().map(|(idx, name)| (idx, name.as_str())).collect();
+				assert_eq!(names, vec![(0, "env.log"), (1, "main"), (2, "func3"), (3, "func3#3")]);
+			},
+			ref other => panic!("unexpected {:?}", other),
+		}
+
+		assert!(!synthesize_names(&mut built).expect("names to be kept"));
+		assert!(!synthesize_names(&mut module().build()).expect("nothing to name"));
+	}
+}
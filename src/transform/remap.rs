@@ -0,0 +1,220 @@
+//! Helpers for rewriting index references across a whole module.
+//!
+//! Every transformation that adds, removes or reorders entries in one of the
+//! index spaces (functions, globals, tables, memories, types) has to rewrite
+//! all the places that refer to that space. These helpers keep that logic in
+//! one place.
+
+use std::mem;
+use elements::{
+	Module, Section, Instruction, Internal, NameSection, IndexMap, Type,
+};
+
+/// Calls `f` for every instruction of the module: function bodies and all
+/// initializer expressions (globals, data and element segment offsets).
+pub(crate) fn for_each_instruction_mut<F: FnMut(&mut Instruction)>(module: &mut Module, mut f: F) {
+	for section in module.sections_mut() {
+		match *section {
+			Section::Code(ref mut code) => {
+				for body in code.bodies_mut() {
+					for instruction in body.code_mut().elements_mut() {
+						f(instruction);
+					}
+				}
+			},
+			Section::Global(ref mut globals) => {
+				for entry in globals.entries_mut() {
+					for instruction in entry.init_expr_mut().code_mut() {
+						f(instruction);
+					}
+				}
+			},
+			Section::Data(ref mut data) => {
+				for segment in data.entries_mut() {
+					if let Some(ref mut offset) = *segment.offset_mut() {
+						for instruction in offset.code_mut() {
+							f(instruction);
+						}
+					}
+				}
+			},
+			Section::Element(ref mut elements) => {
+				for segment in elements.entries_mut() {
+					if let Some(ref mut offset) = *segment.offset_mut() {
+						for instruction in offset.code_mut() {
+							f(instruction);
+						}
+					}
+				}
+			},
+			_ => {},
+		}
+	}
+}
+
+fn remap_index_map<T, F: Fn(u32) -> u32>(map: &mut IndexMap<T>, f: &F) {
+	let old = mem::replace(map, IndexMap::with_capacity(0));
+	for (idx, value) in old {
+		let new_idx = f(idx);
+		// When several entries collapse into one, the first one wins.
+		if !map.contains_key(new_idx) {
+			map.insert(new_idx, value);
+		}
+	}
+}
+
+/// Rewrites every reference to the function index space through `f`.
+///
+/// Covers calls, function references, exports, element segments, the start
+/// section and parsed name sections.
+pub(crate) fn remap_functions<F: Fn(u32) -> u32>(module: &mut Module, f: F) {
+	for_each_instruction_mut(module, |instruction| {
+		if let Instruction::Call(ref mut idx) | Instruction::RefFunc(ref mut idx) = *instruction {
+			*idx = f(*idx);
+		}
+	});
+
+	for section in module.sections_mut() {
+		match *section {
+			Section::Export(ref mut exports) => {
+				for entry in exports.entries_mut() {
+					if let Internal::Function(ref mut idx) = *entry.internal_mut() {
+						*idx = f(*idx);
+					}
+				}
+			},
+			Section::Element(ref mut elements) => {
+				for segment in elements.entries_mut() {
+					for member in segment.members_mut() {
+						*member = f(*member);
+					}
+				}
+			},
+			Section::Start(ref mut idx) => {
+				*idx = f(*idx);
+			},
+			Section::Name(NameSection::Function(ref mut names)) => {
+				remap_index_map(names.names_mut(), &f);
+			},
+			Section::Name(NameSection::Local(ref mut names)) => {
+				remap_index_map(names.local_names_mut(), &f);
+			},
+			_ => {},
+		}
+	}
+}
+
+/// Rewrites every reference to the global index space through `f`.
+pub(crate) fn remap_globals<F: Fn(u32) -> u32>(module: &mut Module, f: F) {
+	for_each_instruction_mut(module, |instruction| {
+		match *instruction {
+			Instruction::GetGlobal(ref mut idx) | Instruction::SetGlobal(ref mut idx) => {
+				*idx = f(*idx);
+			},
+			_ => {},
+		}
+	});
+
+	if let Some(exports) = module.export_section_mut() {
+		for entry in exports.entries_mut() {
+			if let Internal::Global(ref mut idx) = *entry.internal_mut() {
+				*idx = f(*idx);
+			}
+		}
+	}
+}
+
+/// Rewrites every reference to the table index space through `f`.
+///
+/// Covers indirect calls, table instructions, exports and element segments.
+pub(crate) fn remap_tables<F: Fn(u32) -> u32>(module: &mut Module, f: F) {
+	for_each_instruction_mut(module, |instruction| {
+		match *instruction {
+			Instruction::CallIndirect(_, ref mut idx) | Instruction::TableGet(ref mut idx) | Instruction::TableSet(ref mut idx)
+				| Instruction::TableGrow(ref mut idx) | Instruction::TableSize(ref mut idx)
+				| Instruction::TableFill(ref mut idx) | Instruction::TableInit(_, ref mut idx) => {
+				*idx = f(*idx);
+			},
+			Instruction::TableCopy(ref mut destination, ref mut source) => {
+				*destination = f(*destination);
+				*source = f(*source);
+			},
+			_ => {},
+		}
+	});
+
+	for section in module.sections_mut() {
+		match *section {
+			Section::Export(ref mut exports) => {
+				for entry in exports.entries_mut() {
+					if let Internal::Table(ref mut idx) = *entry.internal_mut() {
+						*idx = f(*idx);
+					}
+				}
+			},
+			Section::Element(ref mut elements) => {
+				for segment in elements.entries_mut() {
+					// Declared segments don't refer to a table.
+					if segment.declared() {
+						continue;
+					}
+					let new_idx = f(segment.index());
+					*segment = ::elements::ElementSegment::new(
+						new_idx,
+						segment.offset().clone(),
+						mem::take(segment.members_mut()),
+						segment.passive(),
+					);
+				}
+			},
+			_ => {},
+		}
+	}
+}
+
+/// Rewrites every reference to the memory index space through `f`.
+pub(crate) fn remap_memories<F: Fn(u32) -> u32>(module: &mut Module, f: F) {
+	for section in module.sections_mut() {
+		match *section {
+			Section::Export(ref mut exports) => {
+				for entry in exports.entries_mut() {
+					if let Internal::Memory(ref mut idx) = *entry.internal_mut() {
+						*idx = f(*idx);
+					}
+				}
+			},
+			Section::Data(ref mut data) => {
+				for segment in data.entries_mut() {
+					let new_idx = f(segment.index());
+					*segment = ::elements::DataSegment::new(
+						new_idx,
+						segment.offset().clone(),
+						mem::take(segment.value_mut()),
+						segment.passive(),
+					);
+				}
+			},
+			_ => {},
+		}
+	}
+}
+
+/// Resolves a type index to the function type it refers to, if present.
+pub(crate) fn function_type(module: &Module, type_ref: u32) -> Option<&::elements::FunctionType> {
+	module.type_section()
+		.and_then(|ts| ts.types().get(type_ref as usize))
+		.map(|t| match *t { Type::Function(ref func_type) => func_type })
+}
+
+/// Inserts a known (non-custom) section at its place according to the section
+/// order mandated by the binary format.
+///
+/// Custom sections preceding the next known section stay before it.
+pub(crate) fn insert_section(module: &mut Module, section: Section) {
+	let order = section.order();
+	let sections = module.sections_mut();
+	let position = sections.iter()
+		.position(|s| s.order() > order)
+		.unwrap_or(sections.len());
+	sections.insert(position, section);
+}
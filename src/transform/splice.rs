@@ -0,0 +1,133 @@
+use std::ops::Range;
+use std::vec::Vec;
+use elements::{FuncBody, Instruction, Error};
+use analysis::StackEffects;
+
+/// Nesting of `code`, which must be a balanced sequence of blocks, and the
+/// deepest branch relative to the start of `code` (0 meaning the innermost
+/// block enclosing the sequence).
+fn structure(code: &[Instruction]) -> Result<Option<u32>, Error> {
+	let mut depth = 0u32;
+	let mut deepest: Option<u32> = None;
+	let mut branch = |target: u32, depth: u32| {
+		if let Some(outside) = target.checked_sub(depth) {
+			deepest = Some(deepest.map_or(outside, |deepest| ::std::cmp::max(deepest, outside)));
+		}
+	};
+	for instruction in code {
+		match *instruction {
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => depth += 1,
+			Instruction::Else if depth == 0 => return Err(Error::Other("else outside of an if in spliced code")),
+			Instruction::End => {
+				depth = depth.checked_sub(1).ok_or(Error::Other("spliced code closes an enclosing block"))?;
+			},
+			Instruction::Br(target) | Instruction::BrIf(target) => branch(target, depth),
+			Instruction::BrTable(ref table) => {
+				for &target in table.table.iter().chain(Some(&table.default)) {
+					branch(target, depth);
+				}
+			},
+			_ => {},
+		}
+	}
+	if depth != 0 {
+		return Err(Error::Other("spliced code leaves a block open"));
+	}
+	Ok(deepest)
+}
+
+impl FuncBody {
+	/// Replaces the instructions at positions `range` by `instructions`,
+	/// returning the replaced instructions.
+	///
+	/// The replacement is checked to fit in place of the replaced code:
+	///
+	/// - both have to be balanced sequences of blocks, and the final `end`
+	///   of the body can't be replaced, so that the structure of enclosing
+	///   blocks is kept;
+	/// - branches of `instructions` can't target blocks which don't enclose
+	///   `range`;
+	/// - both have to pop and push the same number of operands, according
+	///   to `effects` (which needs to know the module to resolve calls).
+	///   Branching out of either sequence is not supported.
+	///
+	/// The body is left untouched on failure.
+	pub fn splice(&mut self, range: Range<usize>, instructions: Vec<Instruction>, effects: &StackEffects)
+		-> Result<Vec<Instruction>, Error>
+	{
+		let code = self.code().elements();
+		if range.start > range.end || range.end >= code.len() {
+			return Err(Error::Other("splice range out of bounds"));
+		}
+
+		structure(&code[range.clone()])?;
+		let open = code[..range.start].iter().fold(0u32, |depth, instruction| match *instruction {
+			Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => depth + 1,
+			Instruction::End => depth.saturating_sub(1),
+			_ => depth,
+		});
+		if let Some(deepest) = structure(&instructions)? {
+			// Labels available are the open blocks and the function itself.
+			if deepest > open {
+				return Err(Error::HeapOther(format!("spliced code branches to label {} out of {} enclosing labels", deepest, open + 1)));
+			}
+		}
+
+		let before = effects.sequence(&code[range.clone()])
+			.ok_or(Error::Other("stack effect of the replaced code is unknown"))?;
+		let after = effects.sequence(&instructions)
+			.ok_or(Error::Other("stack effect of the spliced code is unknown"))?;
+		if before != after {
+			return Err(Error::HeapOther(format!(
+				"spliced code pops {} and pushes {} operands where the replaced code pops {} and pushes {}",
+				after.0, after.1, before.0, before.1,
+			)));
+		}
+
+		Ok(self.code_mut().elements_mut().splice(range, instructions).collect())
+	}
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use analysis::StackEffects;
+	use builder::module;
+	use elements::{FuncBody, Instructions, BlockType, ValueType};
+	use elements::Instruction::*;
+
+	#[test]
+	fn splice() {
+		let module = module()
+			.function().signature().with_param(ValueType::I32).build().body().build().build()
+			.build();
+		let effects = StackEffects::new(&module);
+		let mut body = FuncBody::new(Vec::new(), Instructions::new(vec![
+			Block(BlockType::NoResult),
+				GetLocal(0),
+				I32Const(1),
+				I32Add,
+				Drop,
+			End,
+			End,
+		]));
+
+		// Instrument the addition with a call, keeping `(2, 1)`.
+		let removed = body.splice(3..4, vec![I32Add, Call(0), GetLocal(0)], &effects).expect("splice to succeed");
+		assert_eq!(removed, vec![I32Add]);
+		assert_eq!(body.code().elements()[3..6], [I32Add, Call(0), GetLocal(0)]);
+
+		// Branches may target enclosing blocks and the function.
+		body.splice(1..1, vec![Block(BlockType::NoResult), GetLocal(0), BrIf(2), End], &effects)
+			.expect("branch to the function to be allowed");
+		assert!(body.splice(1..1, vec![Block(BlockType::NoResult), GetLocal(0), BrIf(3), End], &effects).is_err());
+
+		let snapshot = body.clone();
+		assert!(body.splice(1..1, vec![I32Const(0)], &effects).is_err());
+		assert!(body.splice(0..1, vec![], &effects).is_err());
+		assert!(body.splice(1..1, vec![Block(BlockType::NoResult)], &effects).is_err());
+		assert!(body.splice(11..12, vec![], &effects).is_err());
+		assert!(body.splice(12..13, vec![], &effects).is_err());
+		assert!(body.splice(1..1, vec![Call(3)], &effects).is_err());
+		assert_eq!(body, snapshot);
+	}
+}
@@ -0,0 +1,347 @@
+use std::vec::Vec;
+use std::string::String;
+use elements::{
+	Module, Section, Error, FuncBody, Instruction, Instructions, InitExpr,
+	ImportEntry, ExportEntry, External, Internal, GlobalType, MemoryType, TableType,
+	ImportSection, ExportSection, FunctionSection, CodeSection, ElementSection,
+	ElementSegment, TableSection, ImportCountType,
+};
+use super::remap::{function_type, insert_section};
+
+/// Name of the module the secondary modules import their dependencies from.
+pub const SPLIT_PRIMARY_MODULE: &str = "primary";
+
+/// Prefix of the exports added to the primary module.
+const EXPORT_PREFIX: &str = "__split_";
+
+fn table_export() -> String { format!("{}table", EXPORT_PREFIX) }
+fn memory_export() -> String { format!("{}memory", EXPORT_PREFIX) }
+fn function_export(idx: u32) -> String { format!("{}func_{}", EXPORT_PREFIX, idx) }
+fn global_export(idx: u32) -> String { format!("{}global_{}", EXPORT_PREFIX, idx) }
+
+fn global_type(module: &Module, idx: u32) -> Option<GlobalType> {
+	let imported = module.import_section()
+		.map(|is| is.entries().iter().filter_map(|entry| match *entry.external() {
+			External::Global(global_type) => Some(global_type),
+			_ => None,
+		}).collect::<Vec<_>>())
+		.unwrap_or_default();
+	match imported.get(idx as usize) {
+		Some(global_type) => Some(*global_type),
+		None => module.global_section()
+			.and_then(|gs| gs.entries().get(idx as usize - imported.len()))
+			.map(|entry| *entry.global_type()),
+	}
+}
+
+fn memory_type(module: &Module) -> Option<MemoryType> {
+	let imported = module.import_section().and_then(|is| is.entries().iter().filter_map(|entry| match *entry.external() {
+		External::Memory(memory_type) => Some(memory_type),
+		_ => None,
+	}).next());
+	imported.or_else(|| module.memory_section().and_then(|ms| ms.entries().first().cloned()))
+}
+
+fn push_unique(list: &mut Vec<u32>, idx: u32) {
+	if let Err(position) = list.binary_search(&idx) {
+		list.insert(position, idx);
+	}
+}
+
+fn position(list: &[u32], idx: u32) -> u32 {
+	list.binary_search(&idx).expect("all referenced indices were collected; qed") as u32
+}
+
+/// Body of a moved function left in the primary module: forwards its
+/// arguments to the table slot which the secondary module fills in.
+fn stub(module: &Module, type_ref: u32, slot: u32) -> FuncBody {
+	let params = function_type(module, type_ref).map(|t| t.params().len() as u32).unwrap_or(0);
+	let mut code: Vec<Instruction> = (0..params).map(Instruction::GetLocal).collect();
+	code.push(Instruction::I32Const(slot as i32));
+	code.push(Instruction::CallIndirect(type_ref, 0));
+	code.push(Instruction::End);
+	FuncBody::new(Vec::new(), Instructions::new(code))
+}
+
+/// Splits `module` into a primary module and one secondary module per entry
+/// of `partition`, which lists the (non-imported) functions to move out.
+///
+/// The primary module keeps all functions, so their indices do not change,
+/// but the body of each moved function is replaced by a stub calling through
+/// table 0 (which is created or grown as needed). Every secondary module
+/// imports from [`SPLIT_PRIMARY_MODULE`] the table, the memory, the globals
+/// and the functions it needs (all of them exported by the primary module
+/// under `__split_*` names), defines its functions and installs them in the
+/// table slots reserved for it with an element segment. Until a secondary
+/// module is instantiated, calling one of its functions traps.
+///
+/// Fails if a function is listed twice, is imported or out of range, is the
+/// start function, uses data or element segments or a table other than
+/// table 0, or if the module imports its table.
+///
+/// [`SPLIT_PRIMARY_MODULE`]: constant.SPLIT_PRIMARY_MODULE.html
+pub fn split_module(mut module: Module, partition: &[Vec<u32>]) -> Result<(Module, Vec<Module>), Error> {
+	let imported = module.import_count(ImportCountType::Function) as u32;
+	let total = module.functions_space() as u32;
+	let start = module.start_section();
+
+	let mut moved: Vec<u32> = Vec::new();
+	for &idx in partition.iter().flat_map(|part| part.iter()) {
+		if idx < imported || idx >= total {
+			return Err(Error::Other("only functions defined in the module can be moved"));
+		}
+		if Some(idx) == start {
+			return Err(Error::Other("the start function cannot be moved"));
+		}
+		if moved.binary_search(&idx).is_ok() {
+			return Err(Error::Other("function listed more than once in the partition"));
+		}
+		push_unique(&mut moved, idx);
+	}
+
+	if module.import_count(ImportCountType::Table) > 0 {
+		return Err(Error::Other("cannot split a module with an imported table"));
+	}
+
+	// Reserve table slots after the existing ones.
+	let count = moved.len() as u32;
+	let (base, table_type) = match module.table_section().and_then(|ts| ts.entries().first().cloned()) {
+		Some(table) => {
+			let limits = table.limits();
+			let initial = limits.initial().checked_add(count)
+				.ok_or(Error::Other("table is too large"))?;
+			let maximum = match limits.maximum() {
+				Some(max) => Some(max.checked_add(count).ok_or(Error::Other("table is too large"))?),
+				None => None,
+			};
+			(limits.initial(), TableType::new(initial, maximum))
+		},
+		None => (0, TableType::new(count, None)),
+	};
+	match module.table_section_mut() {
+		Some(ts) if !ts.entries().is_empty() => ts.entries_mut()[0] = table_type,
+		Some(ts) => ts.entries_mut().push(table_type),
+		None => insert_section(&mut module, Section::Table(TableSection::with_entries(vec![table_type]))),
+	}
+
+	let memory = memory_type(&module);
+	let types = module.type_section().cloned().unwrap_or_default();
+	let mut exported_functions: Vec<u32> = Vec::new();
+	let mut exported_globals: Vec<u32> = Vec::new();
+	let mut secondaries = Vec::with_capacity(partition.len());
+	let mut slot = base;
+
+	for part in partition {
+		let mut funcs = Vec::with_capacity(part.len());
+		let mut bodies = Vec::with_capacity(part.len());
+		for &idx in part {
+			let local = (idx - imported) as usize;
+			funcs.push(module.function_section().expect("function in range; qed").entries()[local]);
+			bodies.push(module.code_section().expect("function in range; qed").bodies()[local].clone());
+		}
+
+		// Collect what the moved functions refer to.
+		let mut functions: Vec<u32> = Vec::new();
+		let mut globals: Vec<u32> = Vec::new();
+		for body in &bodies {
+			for instruction in body.code().elements() {
+				match *instruction {
+					Instruction::Call(idx) | Instruction::RefFunc(idx) if !part.contains(&idx) => push_unique(&mut functions, idx),
+					Instruction::GetGlobal(idx) | Instruction::SetGlobal(idx) => push_unique(&mut globals, idx),
+					// Only the first table and the memory are shared with the
+					// secondary modules, which have no data or element
+					// segments of the primary module.
+					Instruction::MemoryInit(_) | Instruction::MemoryDrop(_) => {
+						return Err(Error::Other("functions using data segments cannot be moved"));
+					},
+					Instruction::TableInit(..) | Instruction::TableDrop(_) => {
+						return Err(Error::Other("functions using element segments cannot be moved"));
+					},
+					Instruction::CallIndirect(_, table) | Instruction::TableGet(table) | Instruction::TableSet(table)
+						| Instruction::TableGrow(table) | Instruction::TableSize(table)
+						| Instruction::TableFill(table) if table != 0 => {
+						return Err(Error::Other("functions using a table other than the first cannot be moved"));
+					},
+					Instruction::TableCopy(destination, source) if destination != 0 || source != 0 => {
+						return Err(Error::Other("functions using a table other than the first cannot be moved"));
+					},
+					_ => {},
+				}
+			}
+		}
+
+		let mut imports = Vec::new();
+		for &idx in &functions {
+			let type_ref = match idx.checked_sub(imported) {
+				Some(local) => module.function_section().expect("function in range; qed").entries()[local as usize].type_ref(),
+				None => module.import_section().expect("function in range; qed").entries().iter()
+					.filter_map(|entry| match *entry.external() {
+						External::Function(type_ref) => Some(type_ref),
+						_ => None,
+					})
+					.nth(idx as usize)
+					.expect("function in range; qed"),
+			};
+			imports.push(ImportEntry::new(SPLIT_PRIMARY_MODULE.into(), function_export(idx), External::Function(type_ref)));
+			push_unique(&mut exported_functions, idx);
+		}
+		imports.push(ImportEntry::new(SPLIT_PRIMARY_MODULE.into(), table_export(), External::Table(table_type)));
+		if let Some(memory) = memory {
+			imports.push(ImportEntry::new(SPLIT_PRIMARY_MODULE.into(), memory_export(), External::Memory(memory)));
+		}
+		for &idx in &globals {
+			let global_type = global_type(&module, idx).ok_or(Error::Other("global index out of range"))?;
+			imports.push(ImportEntry::new(SPLIT_PRIMARY_MODULE.into(), global_export(idx), External::Global(global_type)));
+			push_unique(&mut exported_globals, idx);
+		}
+
+		let function_imports = functions.len() as u32;
+		for body in &mut bodies {
+			for instruction in body.code_mut().elements_mut() {
+				match *instruction {
+					Instruction::Call(ref mut idx) | Instruction::RefFunc(ref mut idx) => {
+						*idx = match part.iter().position(|f| f == idx) {
+							Some(local) => function_imports + local as u32,
+							None => position(&functions, *idx),
+						};
+					},
+					Instruction::GetGlobal(ref mut idx) | Instruction::SetGlobal(ref mut idx) => {
+						*idx = position(&globals, *idx);
+					},
+					_ => {},
+				}
+			}
+		}
+
+		let members = (function_imports..function_imports + part.len() as u32).collect();
+		let offset = InitExpr::new(vec![Instruction::I32Const(slot as i32), Instruction::End]);
+
+		let mut sections = Vec::new();
+		if !types.types().is_empty() {
+			sections.push(Section::Type(types.clone()));
+		}
+		sections.push(Section::Import(ImportSection::with_entries(imports)));
+		sections.push(Section::Function(FunctionSection::with_entries(funcs)));
+		sections.push(Section::Element(ElementSection::with_entries(vec![
+			ElementSegment::new(0, Some(offset), members, false),
+		])));
+		sections.push(Section::Code(CodeSection::with_bodies(bodies)));
+		secondaries.push(Module::new(sections));
+
+		// Leave stubs behind in the primary module.
+		for &idx in part {
+			let local = (idx - imported) as usize;
+			let type_ref = module.function_section().expect("function in range; qed").entries()[local].type_ref();
+			let body = stub(&module, type_ref, slot);
+			module.code_section_mut().expect("function in range; qed").bodies_mut()[local] = body;
+			slot += 1;
+		}
+	}
+
+	let mut exports = vec![ExportEntry::new(table_export(), Internal::Table(0))];
+	if memory.is_some() {
+		exports.push(ExportEntry::new(memory_export(), Internal::Memory(0)));
+	}
+	exports.extend(exported_functions.into_iter().map(|idx| ExportEntry::new(function_export(idx), Internal::Function(idx))));
+	exports.extend(exported_globals.into_iter().map(|idx| ExportEntry::new(global_export(idx), Internal::Global(idx))));
+	match module.export_section_mut() {
+		Some(es) => es.entries_mut().extend(exports),
+		None => insert_section(&mut module, Section::Export(ExportSection::with_entries(exports))),
+	}
+
+	Ok((module, secondaries))
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::split_module;
+	use builder::module;
+	use elements::{Instructions, Instruction, External, Internal, ValueType, Module, serialize, deserialize_buffer};
+	use elements::Instruction::*;
+
+	fn sample() -> Module {
+		module()
+			.global().value_type().i32().mutable().init_expr(I32Const(0)).build()
+			.memory().with_min(1).build()
+			// 0: main, calls the lazily loaded function
+			.function()
+				.signature().build()
+				.body()
+					.with_instructions(Instructions::new(vec![I32Const(1), I32Const(2), Call(1), Drop, End]))
+					.build()
+				.build()
+			// 1: moved, calls a helper staying in the primary module
+			.function()
+				.signature().param().i32().param().i32().return_type().i32().build()
+				.body()
+					.with_instructions(Instructions::new(vec![GetLocal(0), GetLocal(1), Call(2), SetGlobal(0), GetGlobal(0), End]))
+					.build()
+				.build()
+			// 2: helper
+			.function()
+				.signature().param().i32().param().i32().return_type().i32().build()
+				.body()
+					.with_instructions(Instructions::new(vec![GetLocal(0), GetLocal(1), I32Add, End]))
+					.build()
+				.build()
+			.export().field("main").internal().func(0).build()
+			.build()
+	}
+
+	fn roundtrip(module: Module) -> Module {
+		deserialize_buffer(&serialize(module).expect("module to serialize")).expect("module to deserialize")
+	}
+
+	#[test]
+	fn split() {
+		let (primary, secondaries) = split_module(sample(), &[vec![1]]).expect("split to succeed");
+		let primary = roundtrip(primary);
+		let secondary = roundtrip(secondaries.into_iter().next().expect("one secondary module"));
+
+		assert_eq!(primary.table_section().expect("table section").entries()[0].limits().initial(), 1);
+		assert_eq!(
+			primary.code_section().expect("code section").bodies()[1].code().elements(),
+			&[GetLocal(0), GetLocal(1), I32Const(0), CallIndirect(1, 0), End][..]
+		);
+		let exports: Vec<_> = primary.export_section().expect("export section").entries().iter()
+			.map(|e| (e.field().to_owned(), *e.internal()))
+			.collect();
+		assert!(exports.contains(&("__split_func_2".to_owned(), Internal::Function(2))));
+		assert!(exports.contains(&("__split_global_0".to_owned(), Internal::Global(0))));
+		assert!(exports.contains(&("__split_memory".to_owned(), Internal::Memory(0))));
+
+		let imports = secondary.import_section().expect("import section").entries();
+		assert_eq!(imports.len(), 4);
+		assert_eq!(imports[0].field(), "__split_func_2");
+		match *imports[3].external() {
+			External::Global(global_type) => assert_eq!(global_type.content_type(), ValueType::I32),
+			_ => panic!("global import expected"),
+		}
+		assert_eq!(
+			secondary.code_section().expect("code section").bodies()[0].code().elements(),
+			&[GetLocal(0), GetLocal(1), Call(0), SetGlobal(0), GetGlobal(0), End][..]
+		);
+		let segment = &secondary.elements_section().expect("element section").entries()[0];
+		assert_eq!(segment.members(), &[1][..]);
+		assert_eq!(segment.offset().as_ref().expect("active segment").code(), &[Instruction::I32Const(0), End][..]);
+	}
+
+	#[test]
+	fn invalid_partitions() {
+		assert!(split_module(sample(), &[vec![1], vec![1]]).is_err());
+		assert!(split_module(sample(), &[vec![3]]).is_err());
+
+		let using = |instruction| {
+			let mut module = sample();
+			module.code_section_mut().expect("code section").bodies_mut()[1].code_mut().elements_mut().insert(0, instruction);
+			module
+		};
+		for instruction in &[
+			MemoryInit(0), MemoryDrop(0), TableInit(0, 0), TableDrop(0),
+			CallIndirect(1, 1), TableSize(1), TableCopy(0, 1),
+		] {
+			assert!(split_module(using(instruction.clone()), &[vec![1]]).is_err());
+		}
+		assert!(split_module(using(TableSize(0)), &[vec![1]]).is_ok());
+	}
+}
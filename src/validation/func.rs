@@ -0,0 +1,965 @@
+use std::vec::Vec;
+use std::string::String;
+use elements::{Module, Instruction, BlockType, ValueType, FunctionType, Type, External, FuncBody, InitExpr, TableElementType};
+use elements::Instruction::*;
+use elements::ValueType::*;
+use super::{Error, Features};
+
+/// Maximum number of pages of a memory.
+const MAX_PAGES: u32 = 65_536;
+
+/// Index spaces of a module, as seen by its function bodies.
+pub(crate) struct Context<'a> {
+	features: &'a Features,
+	types: Vec<&'a FunctionType>,
+	/// Type of each function, imported ones first.
+	functions: Vec<u32>,
+	/// Type and mutability of each global, imported ones first.
+	globals: Vec<(ValueType, bool)>,
+	imported_globals: usize,
+	/// Element type of each table, imported ones first.
+	tables: Vec<TableElementType>,
+	memories: usize,
+	data_count: Option<u32>,
+	element_segments: usize,
+}
+
+impl<'a> Context<'a> {
+	pub fn new(module: &'a Module, features: &'a Features) -> Context<'a> {
+		let types = module.type_section().map_or(&[][..], |ts| ts.types()).iter()
+			.map(|ty| match *ty { Type::Function(ref signature) => signature })
+			.collect();
+		let imports = module.import_section().map_or(&[][..], |is| is.entries());
+		let functions = imports.iter()
+			.filter_map(|entry| match *entry.external() {
+				External::Function(type_ref) => Some(type_ref),
+				_ => None,
+			})
+			.chain(module.function_section().map_or(&[][..], |fs| fs.entries()).iter().map(|func| func.type_ref()))
+			.collect();
+		let mut globals: Vec<(ValueType, bool)> = imports.iter()
+			.filter_map(|entry| match *entry.external() {
+				External::Global(ref global_type) => Some((global_type.content_type(), global_type.is_mutable())),
+				_ => None,
+			})
+			.collect();
+		let imported_globals = globals.len();
+		let tables = imports.iter()
+			.filter_map(|entry| match *entry.external() {
+				External::Table(ref table_type) => Some(table_type.elem_type()),
+				_ => None,
+			})
+			.chain(module.table_section().map_or(&[][..], |ts| ts.entries()).iter().map(|table| table.elem_type()))
+			.collect();
+		globals.extend(module.global_section().map_or(&[][..], |gs| gs.entries()).iter()
+			.map(|global| (global.global_type().content_type(), global.global_type().is_mutable())));
+		Context {
+			features,
+			types,
+			functions,
+			globals,
+			imported_globals,
+			tables,
+			memories: module.memory_space(),
+			data_count: module.data_count_section(),
+			element_segments: module.elements_section().map_or(0, |es| es.entries().len()),
+		}
+	}
+
+	fn signature(&self, type_ref: u32) -> Result<&'a FunctionType, String> {
+		self.types.get(type_ref as usize).cloned().ok_or_else(|| format!("type {} does not exist", type_ref))
+	}
+
+	/// Signature of the function `index`.
+	pub fn function(&self, index: u32) -> Result<&'a FunctionType, String> {
+		let type_ref = *self.functions.get(index as usize).ok_or_else(|| format!("function {} does not exist", index))?;
+		self.signature(type_ref)
+	}
+
+	/// Type of the constant expression `init`, which may only refer to
+	/// imported globals.
+	pub fn const_expr(&self, init: &InitExpr) -> Result<ValueType, String> {
+		let value_type = match init.code() {
+			[I32Const(_), End] => I32,
+			[I64Const(_), End] => I64,
+			[F32Const(_), End] => F32,
+			[F64Const(_), End] => F64,
+			[V128Const(_), End] => V128,
+			[RefNull(elem_type), End] if self.features.reference_types => (*elem_type).into(),
+			[RefFunc(index), End] if self.features.reference_types => {
+				self.function(*index)?;
+				FuncRef
+			},
+			[GetGlobal(index), End] => match self.globals.get(*index as usize) {
+				Some(&(value_type, false)) if (*index as usize) < self.imported_globals => value_type,
+				_ => return Err(format!("constant expression refers to global {} which is not an immutable import", index)),
+			},
+			_ => return Err(String::from("constant expression must be a single constant instruction")),
+		};
+		Ok(value_type)
+	}
+}
+
+/// Checks that the `initial` size doesn't exceed the `maximum` one, and
+/// that neither exceeds `max`.
+pub(crate) fn check_limits(initial: u32, maximum: Option<u32>, max: Option<u32>) -> Result<(), String> {
+	if let Some(maximum) = maximum {
+		if initial > maximum {
+			return Err(format!("minimum size {} is greater than maximum size {}", initial, maximum));
+		}
+	}
+	if let Some(max) = max {
+		if let Some(size) = Some(initial).into_iter().chain(maximum).find(|&size| size > max) {
+			return Err(format!("size {} is greater than {}", size, max));
+		}
+	}
+	Ok(())
+}
+
+/// Checks the limits of a memory, in pages.
+pub(crate) fn check_memory_limits(initial: u32, maximum: Option<u32>) -> Result<(), String> {
+	check_limits(initial, maximum, Some(MAX_PAGES))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameKind {
+	Block,
+	Loop,
+	If,
+	Else,
+	Function,
+}
+
+/// Control frame of the validation algorithm of the specification.
+struct Frame {
+	kind: FrameKind,
+	params: Vec<ValueType>,
+	results: Vec<ValueType>,
+	/// Height of the operand stack when entering the frame.
+	height: usize,
+	/// Whether the rest of the frame is unreachable, making the operand
+	/// stack polymorphic.
+	unreachable: bool,
+}
+
+/// Type checker of a function body.
+struct Checker<'a> {
+	context: &'a Context<'a>,
+	/// Locals, parameters first, as their type and the index following the
+	/// last local of this type.
+	locals: Vec<(u64, ValueType)>,
+	/// Operand stack, `None` being an operand of unknown type popped from
+	/// a polymorphic stack.
+	operands: Vec<Option<ValueType>>,
+	frames: Vec<Frame>,
+}
+
+/// Operand stack types inferred by `validate_with_operand_types` at each
+/// program point of a function body.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OperandTypes {
+	/// Operand stacks of all program points, bottom first, concatenated.
+	types: Vec<Option<ValueType>>,
+	/// End of the stack of each program point in `types`.
+	ends: Vec<usize>,
+}
+
+impl OperandTypes {
+	fn stack(&self, point: usize) -> Option<&[Option<ValueType>]> {
+		let end = *self.ends.get(point)?;
+		let start = if point == 0 { 0 } else { self.ends[point - 1] };
+		Some(&self.types[start..end])
+	}
+
+	/// Operand stack before the instruction `position` of the body, bottom
+	/// first. `None` is an operand of unknown type, only found in unreachable
+	/// code.
+	pub fn before(&self, position: usize) -> Option<&[Option<ValueType>]> {
+		if position + 1 < self.ends.len() { self.stack(position) } else { None }
+	}
+
+	/// Operand stack after the instruction `position` of the body, the
+	/// results of the function after its final `end`.
+	pub fn after(&self, position: usize) -> Option<&[Option<ValueType>]> {
+		self.stack(position + 1)
+	}
+}
+
+/// Type checks the body of the function `index` (in the function space).
+pub(crate) fn check_body(context: &Context, index: u32, body: &FuncBody) -> Result<(), Error> {
+	check_body_with(context, index, body, |_| {})
+}
+
+/// Type checks the body of the function `index` like `check_body`,
+/// recording the operand stack at each program point.
+pub(crate) fn operand_types(context: &Context, index: u32, body: &FuncBody) -> Result<OperandTypes, Error> {
+	let mut operand_types = OperandTypes::default();
+	check_body_with(context, index, body, |stack| {
+		operand_types.types.extend_from_slice(stack);
+		operand_types.ends.push(operand_types.types.len());
+	})?;
+	Ok(operand_types)
+}
+
+/// Type checks the body of the function `index`, calling `record` with the
+/// operand stack before each instruction and with the results of the
+/// function at the end.
+fn check_body_with<F: FnMut(&[Option<ValueType>])>(context: &Context, index: u32, body: &FuncBody, mut record: F) -> Result<(), Error> {
+	let signature = context.function(index).map_err(|message| Error(format!("function {}: {}", index, message)))?;
+
+	let mut locals = Vec::new();
+	let mut count = 0u64;
+	for &param in signature.params() {
+		count += 1;
+		locals.push((count, param));
+	}
+	for local in body.locals() {
+		count += local.count() as u64;
+		locals.push((count, local.value_type()));
+	}
+	if count > u32::MAX as u64 {
+		return Err(Error(format!("function {}: too many locals ({})", index, count)));
+	}
+
+	let mut checker = Checker {
+		context,
+		locals,
+		operands: Vec::new(),
+		frames: vec![Frame {
+			kind: FrameKind::Function,
+			params: Vec::new(),
+			results: signature.results().to_vec(),
+			height: 0,
+			unreachable: false,
+		}],
+	};
+	let instructions = body.code().elements();
+	for (position, instruction) in instructions.iter().enumerate() {
+		if checker.frames.is_empty() {
+			return Err(Error(format!("function {} instruction {}: instruction after the end of the function", index, position)));
+		}
+		record(&checker.operands);
+		checker.step(instruction)
+			.map_err(|message| Error(format!("function {} instruction {} ({}): {}", index, position, instruction, message)))?;
+	}
+	if !checker.frames.is_empty() {
+		return Err(Error(format!("function {}: missing end of the function", index)));
+	}
+	record(&signature.results().iter().cloned().map(Some).collect::<Vec<_>>());
+	Ok(())
+}
+
+impl<'a> Checker<'a> {
+	fn push(&mut self, operand: Option<ValueType>) {
+		self.operands.push(operand);
+	}
+
+	fn pop(&mut self) -> Result<Option<ValueType>, String> {
+		let frame = self.frames.last().expect("frames are checked not to be empty before each instruction; qed");
+		if self.operands.len() == frame.height {
+			if frame.unreachable {
+				return Ok(None);
+			}
+			return Err(String::from("operand stack underflow"));
+		}
+		Ok(self.operands.pop().expect("operands are above the height of the frame; qed"))
+	}
+
+	fn pop_expect(&mut self, expected: ValueType) -> Result<(), String> {
+		match self.pop()? {
+			Some(actual) if actual != expected => Err(format!("expected {} operand, found {}", expected, actual)),
+			_ => Ok(()),
+		}
+	}
+
+	fn pop_all(&mut self, value_types: &[ValueType]) -> Result<(), String> {
+		for &value_type in value_types.iter().rev() {
+			self.pop_expect(value_type)?;
+		}
+		Ok(())
+	}
+
+	fn push_all(&mut self, value_types: &[ValueType]) {
+		for &value_type in value_types {
+			self.push(Some(value_type));
+		}
+	}
+
+	/// Enters a block of type `block_type`, popping its parameters.
+	fn push_frame(&mut self, kind: FrameKind, block_type: BlockType) -> Result<(), String> {
+		let (params, results) = match block_type {
+			BlockType::Value(value_type) => (Vec::new(), vec![value_type]),
+			BlockType::NoResult => (Vec::new(), Vec::new()),
+			BlockType::TypeIndex(type_ref) => {
+				self.feature(self.context.features.multi_value, "multi-value")?;
+				let signature = self.context.signature(type_ref)?;
+				(signature.params().to_vec(), signature.results().to_vec())
+			},
+		};
+		self.pop_all(&params)?;
+		self.push_frame_with_types(kind, params, results);
+		Ok(())
+	}
+
+	/// Enters a block whose parameters were popped, pushing them back in
+	/// the block.
+	fn push_frame_with_types(&mut self, kind: FrameKind, params: Vec<ValueType>, results: Vec<ValueType>) {
+		let height = self.operands.len();
+		self.push_all(&params);
+		self.frames.push(Frame { kind, params, results, height, unreachable: false });
+	}
+
+	fn pop_frame(&mut self) -> Result<Frame, String> {
+		let results = self.frames.last().expect("frames are checked not to be empty before each instruction; qed").results.clone();
+		self.pop_all(&results)?;
+		let frame = self.frames.pop().expect("frames are checked not to be empty before each instruction; qed");
+		if self.operands.len() != frame.height {
+			return Err(format!("{} operands left on the stack at the end of the block", self.operands.len() - frame.height));
+		}
+		Ok(frame)
+	}
+
+	/// Types of the operands expected by a branch to the label `depth`.
+	fn label(&self, depth: u32) -> Result<Vec<ValueType>, String> {
+		let frame = self.frames.iter().rev().nth(depth as usize)
+			.ok_or_else(|| format!("branch depth {} is too large", depth))?;
+		Ok(if frame.kind == FrameKind::Loop { frame.params.clone() } else { frame.results.clone() })
+	}
+
+	fn set_unreachable(&mut self) {
+		let frame = self.frames.last_mut().expect("frames are checked not to be empty before each instruction; qed");
+		self.operands.truncate(frame.height);
+		frame.unreachable = true;
+	}
+
+	fn local(&self, index: u32) -> Result<ValueType, String> {
+		self.locals.iter()
+			.find(|&&(end, _)| (index as u64) < end)
+			.map(|&(_, value_type)| value_type)
+			.ok_or_else(|| format!("local {} does not exist", index))
+	}
+
+	fn global(&self, index: u32) -> Result<(ValueType, bool), String> {
+		self.context.globals.get(index as usize).cloned().ok_or_else(|| format!("global {} does not exist", index))
+	}
+
+	fn call(&mut self, signature: &FunctionType) -> Result<(), String> {
+		self.pop_all(signature.params())?;
+		self.push_all(signature.results());
+		Ok(())
+	}
+
+	fn step(&mut self, instruction: &Instruction) -> Result<(), String> {
+		match *instruction {
+			Unreachable => self.set_unreachable(),
+			Block(block_type) => self.push_frame(FrameKind::Block, block_type)?,
+			Loop(block_type) => self.push_frame(FrameKind::Loop, block_type)?,
+			If(block_type) => {
+				self.pop_expect(I32)?;
+				self.push_frame(FrameKind::If, block_type)?;
+			},
+			Else => {
+				if self.frames.last().map(|frame| frame.kind) != Some(FrameKind::If) {
+					return Err(String::from("else outside of an if"));
+				}
+				let frame = self.pop_frame()?;
+				self.push_frame_with_types(FrameKind::Else, frame.params, frame.results);
+			},
+			End => {
+				let frame = self.pop_frame()?;
+				if frame.kind == FrameKind::If && frame.params != frame.results {
+					return Err(String::from("if without else must have the same parameters and results"));
+				}
+				if !self.frames.is_empty() {
+					self.push_all(&frame.results);
+				}
+			},
+			Br(depth) => {
+				let label = self.label(depth)?;
+				self.pop_all(&label)?;
+				self.set_unreachable();
+			},
+			BrIf(depth) => {
+				self.pop_expect(I32)?;
+				let label = self.label(depth)?;
+				self.pop_all(&label)?;
+				self.push_all(&label);
+			},
+			BrTable(ref data) => {
+				self.pop_expect(I32)?;
+				let label = self.label(data.default)?;
+				for &depth in data.table.iter() {
+					if self.label(depth)? != label {
+						return Err(format!("branch targets {} and {} expect different operands", depth, data.default));
+					}
+				}
+				self.pop_all(&label)?;
+				self.set_unreachable();
+			},
+			Return => {
+				let results = self.frames[0].results.clone();
+				self.pop_all(&results)?;
+				self.set_unreachable();
+			},
+			Call(index) => {
+				let signature = self.context.function(index)?;
+				self.call(signature)?;
+			},
+			CallIndirect(type_ref, table) => {
+				if self.context.tables.is_empty() {
+					return Err(String::from("call_indirect requires a table"));
+				}
+				self.table_index(table)?;
+				if self.context.tables[table as usize] != TableElementType::AnyFunc {
+					return Err(format!("table {} does not hold function references", table));
+				}
+				let signature = self.context.signature(type_ref)?;
+				self.pop_expect(I32)?;
+				self.call(signature)?;
+			},
+			Drop => { self.pop()?; },
+			Select => {
+				self.pop_expect(I32)?;
+				let first = self.pop()?;
+				let second = self.pop()?;
+				match (first, second) {
+					(Some(first), Some(second)) if first != second => {
+						return Err(format!("select operands have different types {} and {}", second, first));
+					},
+					(Some(FuncRef), _) | (Some(ExternRef), _) | (_, Some(FuncRef)) | (_, Some(ExternRef)) => {
+						return Err(String::from("select of reference operands requires a result type"));
+					},
+					_ => self.push(first.or(second)),
+				}
+			},
+			TypedSelect(value_type) => {
+				self.feature(self.context.features.reference_types, "reference types")?;
+				self.pop_all(&[value_type, value_type, I32])?;
+				self.push(Some(value_type));
+			},
+			GetLocal(index) => {
+				let value_type = self.local(index)?;
+				self.push(Some(value_type));
+			},
+			SetLocal(index) => {
+				let value_type = self.local(index)?;
+				self.pop_expect(value_type)?;
+			},
+			TeeLocal(index) => {
+				let value_type = self.local(index)?;
+				self.pop_expect(value_type)?;
+				self.push(Some(value_type));
+			},
+			GetGlobal(index) => {
+				let (value_type, _) = self.global(index)?;
+				self.push(Some(value_type));
+			},
+			SetGlobal(index) => {
+				let (value_type, mutable) = self.global(index)?;
+				if !mutable {
+					return Err(format!("global {} is immutable", index));
+				}
+				self.pop_expect(value_type)?;
+			},
+			RefNull(elem_type) => {
+				self.feature(self.context.features.reference_types, "reference types")?;
+				self.push(Some(elem_type.into()));
+			},
+			RefIsNull => {
+				self.feature(self.context.features.reference_types, "reference types")?;
+				match self.pop()? {
+					None | Some(FuncRef) | Some(ExternRef) => {},
+					Some(other) => return Err(format!("expected reference operand, found {}", other)),
+				}
+				self.push(Some(I32));
+			},
+			RefFunc(index) => {
+				self.feature(self.context.features.reference_types, "reference types")?;
+				self.context.function(index)?;
+				self.push(Some(FuncRef));
+			},
+			TableGet(index) => {
+				let elem_type = self.table_type(index)?;
+				self.pop_expect(I32)?;
+				self.push(Some(elem_type));
+			},
+			TableSet(index) => {
+				let elem_type = self.table_type(index)?;
+				self.pop_all(&[I32, elem_type])?;
+			},
+			TableGrow(index) => {
+				let elem_type = self.table_type(index)?;
+				self.pop_all(&[elem_type, I32])?;
+				self.push(Some(I32));
+			},
+			TableSize(index) => {
+				self.table_type(index)?;
+				self.push(Some(I32));
+			},
+			TableFill(index) => {
+				let elem_type = self.table_type(index)?;
+				self.pop_all(&[I32, elem_type, I32])?;
+			},
+			_ => {
+				let (params, result) = self.operator(instruction)?;
+				for &param in params.iter().rev() {
+					self.pop_expect(param)?;
+				}
+				if let Some(result) = result {
+					self.push(Some(result));
+				}
+			},
+		}
+		Ok(())
+	}
+
+	fn memory(&self) -> Result<(), String> {
+		if self.context.memories == 0 {
+			return Err(String::from("memory access requires a memory"));
+		}
+		Ok(())
+	}
+
+	/// Checks a memory access with the alignment `align` (log2) of an
+	/// operand of `natural` bytes (log2).
+	fn access(&self, align: u32, natural: u32) -> Result<(), String> {
+		self.memory()?;
+		if align > natural {
+			return Err(format!("alignment 2^{} is larger than natural alignment 2^{}", align, natural));
+		}
+		Ok(())
+	}
+
+	/// Checks an atomic memory access, which must be naturally aligned.
+	fn atomic(&self, align: u32, natural: u32) -> Result<(), String> {
+		self.feature(self.context.features.threads, "threads")?;
+		self.memory()?;
+		if align != natural {
+			return Err(format!("atomic access alignment 2^{} is not the natural alignment 2^{}", align, natural));
+		}
+		Ok(())
+	}
+
+	fn feature(&self, enabled: bool, proposal: &str) -> Result<(), String> {
+		if !enabled {
+			return Err(format!("instruction requires the {} proposal", proposal));
+		}
+		Ok(())
+	}
+
+	fn lane(&self, lane: u8, lanes: u8) -> Result<(), String> {
+		self.feature(self.context.features.simd, "simd")?;
+		if lane >= lanes {
+			return Err(format!("lane {} is out of range", lane));
+		}
+		Ok(())
+	}
+
+	fn data_segment(&self, index: u32) -> Result<(), String> {
+		match self.context.data_count {
+			Some(count) if index < count => Ok(()),
+			Some(_) => Err(format!("data segment {} does not exist", index)),
+			None => Err(String::from("instruction requires the data count section")),
+		}
+	}
+
+	fn element_segment(&self, index: u32) -> Result<(), String> {
+		if index as usize >= self.context.element_segments {
+			return Err(format!("element segment {} does not exist", index));
+		}
+		Ok(())
+	}
+
+	/// Checks that the table `index` exists, only table 0 being allowed
+	/// without reference types.
+	fn table_index(&self, index: u32) -> Result<(), String> {
+		if self.context.tables.is_empty() {
+			return Err(String::from("instruction requires a table"));
+		}
+		if index != 0 {
+			self.feature(self.context.features.reference_types, "reference types")?;
+		}
+		if index as usize >= self.context.tables.len() {
+			return Err(format!("table {} does not exist", index));
+		}
+		Ok(())
+	}
+
+	/// Type of the elements of the table `index`.
+	fn table_type(&self, index: u32) -> Result<ValueType, String> {
+		self.feature(self.context.features.reference_types, "reference types")?;
+		self.context.tables.get(index as usize)
+			.map(|&elem_type| elem_type.into())
+			.ok_or_else(|| format!("table {} does not exist", index))
+	}
+
+	/// Operands popped and result pushed by an instruction whose type
+	/// doesn't depend on the control flow or the index spaces.
+	fn operator(&self, instruction: &Instruction) -> Result<(&'static [ValueType], Option<ValueType>), String> {
+		let features = self.context.features;
+		Ok(match *instruction {
+			Nop => (&[], None),
+
+			I32Load(align, _) => { self.access(align, 2)?; (&[I32], Some(I32)) },
+			I64Load(align, _) => { self.access(align, 3)?; (&[I32], Some(I64)) },
+			F32Load(align, _) => { self.access(align, 2)?; (&[I32], Some(F32)) },
+			F64Load(align, _) => { self.access(align, 3)?; (&[I32], Some(F64)) },
+			I32Load8S(align, _) | I32Load8U(align, _) => { self.access(align, 0)?; (&[I32], Some(I32)) },
+			I32Load16S(align, _) | I32Load16U(align, _) => { self.access(align, 1)?; (&[I32], Some(I32)) },
+			I64Load8S(align, _) | I64Load8U(align, _) => { self.access(align, 0)?; (&[I32], Some(I64)) },
+			I64Load16S(align, _) | I64Load16U(align, _) => { self.access(align, 1)?; (&[I32], Some(I64)) },
+			I64Load32S(align, _) | I64Load32U(align, _) => { self.access(align, 2)?; (&[I32], Some(I64)) },
+			I32Store(align, _) => { self.access(align, 2)?; (&[I32, I32], None) },
+			I64Store(align, _) => { self.access(align, 3)?; (&[I32, I64], None) },
+			F32Store(align, _) => { self.access(align, 2)?; (&[I32, F32], None) },
+			F64Store(align, _) => { self.access(align, 3)?; (&[I32, F64], None) },
+			I32Store8(align, _) => { self.access(align, 0)?; (&[I32, I32], None) },
+			I32Store16(align, _) => { self.access(align, 1)?; (&[I32, I32], None) },
+			I64Store8(align, _) => { self.access(align, 0)?; (&[I32, I64], None) },
+			I64Store16(align, _) => { self.access(align, 1)?; (&[I32, I64], None) },
+			I64Store32(align, _) => { self.access(align, 2)?; (&[I32, I64], None) },
+			CurrentMemory(_) => { self.memory()?; (&[], Some(I32)) },
+			GrowMemory(_) => { self.memory()?; (&[I32], Some(I32)) },
+
+			I32Const(_) => (&[], Some(I32)),
+			I64Const(_) => (&[], Some(I64)),
+			F32Const(_) => (&[], Some(F32)),
+			F64Const(_) => (&[], Some(F64)),
+
+			I32Eqz => (&[I32], Some(I32)),
+			I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU => (&[I32, I32], Some(I32)),
+			I64Eqz => (&[I64], Some(I32)),
+			I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU => (&[I64, I64], Some(I32)),
+			F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge => (&[F32, F32], Some(I32)),
+			F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge => (&[F64, F64], Some(I32)),
+
+			I32Clz | I32Ctz | I32Popcnt | I32Extend8S | I32Extend16S => (&[I32], Some(I32)),
+			I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU
+				| I32And | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr => (&[I32, I32], Some(I32)),
+			I64Clz | I64Ctz | I64Popcnt | I64Extend8S | I64Extend16S | I64Extend32S => (&[I64], Some(I64)),
+			I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU
+				| I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr => (&[I64, I64], Some(I64)),
+			F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt => (&[F32], Some(F32)),
+			F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign => (&[F32, F32], Some(F32)),
+			F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt => (&[F64], Some(F64)),
+			F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => (&[F64, F64], Some(F64)),
+
+			I32WrapI64 => (&[I64], Some(I32)),
+			I32TruncSF32 | I32TruncUF32 | I32ReinterpretF32 => (&[F32], Some(I32)),
+			I32TruncSF64 | I32TruncUF64 => (&[F64], Some(I32)),
+			I64ExtendSI32 | I64ExtendUI32 => (&[I32], Some(I64)),
+			I64TruncSF32 | I64TruncUF32 => (&[F32], Some(I64)),
+			I64TruncSF64 | I64TruncUF64 | I64ReinterpretF64 => (&[F64], Some(I64)),
+			F32ConvertSI32 | F32ConvertUI32 | F32ReinterpretI32 => (&[I32], Some(F32)),
+			F32ConvertSI64 | F32ConvertUI64 => (&[I64], Some(F32)),
+			F32DemoteF64 => (&[F64], Some(F32)),
+			F64ConvertSI32 | F64ConvertUI32 => (&[I32], Some(F64)),
+			F64ConvertSI64 | F64ConvertUI64 | F64ReinterpretI64 => (&[I64], Some(F64)),
+			F64PromoteF32 => (&[F32], Some(F64)),
+
+			AtomicWake(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32, I32], Some(I32)) },
+			I32AtomicWait(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32, I32, I64], Some(I32)) },
+			I64AtomicWait(ref arg) => { self.atomic(arg.align as u32, 3)?; (&[I32, I64, I64], Some(I32)) },
+			I32AtomicLoad(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32], Some(I32)) },
+			I32AtomicLoad8u(ref arg) => { self.atomic(arg.align as u32, 0)?; (&[I32], Some(I32)) },
+			I32AtomicLoad16u(ref arg) => { self.atomic(arg.align as u32, 1)?; (&[I32], Some(I32)) },
+			I64AtomicLoad(ref arg) => { self.atomic(arg.align as u32, 3)?; (&[I32], Some(I64)) },
+			I64AtomicLoad8u(ref arg) => { self.atomic(arg.align as u32, 0)?; (&[I32], Some(I64)) },
+			I64AtomicLoad16u(ref arg) => { self.atomic(arg.align as u32, 1)?; (&[I32], Some(I64)) },
+			I64AtomicLoad32u(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32], Some(I64)) },
+			I32AtomicStore(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32, I32], None) },
+			I32AtomicStore8u(ref arg) => { self.atomic(arg.align as u32, 0)?; (&[I32, I32], None) },
+			I32AtomicStore16u(ref arg) => { self.atomic(arg.align as u32, 1)?; (&[I32, I32], None) },
+			I64AtomicStore(ref arg) => { self.atomic(arg.align as u32, 3)?; (&[I32, I64], None) },
+			I64AtomicStore8u(ref arg) => { self.atomic(arg.align as u32, 0)?; (&[I32, I64], None) },
+			I64AtomicStore16u(ref arg) => { self.atomic(arg.align as u32, 1)?; (&[I32, I64], None) },
+			I64AtomicStore32u(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32, I64], None) },
+			I32AtomicRmwAdd(ref arg) | I32AtomicRmwSub(ref arg) | I32AtomicRmwAnd(ref arg)
+				| I32AtomicRmwOr(ref arg) | I32AtomicRmwXor(ref arg) | I32AtomicRmwXchg(ref arg) => {
+				self.atomic(arg.align as u32, 2)?;
+				(&[I32, I32], Some(I32))
+			},
+			I32AtomicRmwAdd8u(ref arg) | I32AtomicRmwSub8u(ref arg) | I32AtomicRmwAnd8u(ref arg)
+				| I32AtomicRmwOr8u(ref arg) | I32AtomicRmwXor8u(ref arg) | I32AtomicRmwXchg8u(ref arg) => {
+				self.atomic(arg.align as u32, 0)?;
+				(&[I32, I32], Some(I32))
+			},
+			I32AtomicRmwAdd16u(ref arg) | I32AtomicRmwSub16u(ref arg) | I32AtomicRmwAnd16u(ref arg)
+				| I32AtomicRmwOr16u(ref arg) | I32AtomicRmwXor16u(ref arg) | I32AtomicRmwXchg16u(ref arg) => {
+				self.atomic(arg.align as u32, 1)?;
+				(&[I32, I32], Some(I32))
+			},
+			I64AtomicRmwAdd(ref arg) | I64AtomicRmwSub(ref arg) | I64AtomicRmwAnd(ref arg)
+				| I64AtomicRmwOr(ref arg) | I64AtomicRmwXor(ref arg) | I64AtomicRmwXchg(ref arg) => {
+				self.atomic(arg.align as u32, 3)?;
+				(&[I32, I64], Some(I64))
+			},
+			I64AtomicRmwAdd8u(ref arg) | I64AtomicRmwSub8u(ref arg) | I64AtomicRmwAnd8u(ref arg)
+				| I64AtomicRmwOr8u(ref arg) | I64AtomicRmwXor8u(ref arg) | I64AtomicRmwXchg8u(ref arg) => {
+				self.atomic(arg.align as u32, 0)?;
+				(&[I32, I64], Some(I64))
+			},
+			I64AtomicRmwAdd16u(ref arg) | I64AtomicRmwSub16u(ref arg) | I64AtomicRmwAnd16u(ref arg)
+				| I64AtomicRmwOr16u(ref arg) | I64AtomicRmwXor16u(ref arg) | I64AtomicRmwXchg16u(ref arg) => {
+				self.atomic(arg.align as u32, 1)?;
+				(&[I32, I64], Some(I64))
+			},
+			I64AtomicRmwAdd32u(ref arg) | I64AtomicRmwSub32u(ref arg) | I64AtomicRmwAnd32u(ref arg)
+				| I64AtomicRmwOr32u(ref arg) | I64AtomicRmwXor32u(ref arg) | I64AtomicRmwXchg32u(ref arg) => {
+				self.atomic(arg.align as u32, 2)?;
+				(&[I32, I64], Some(I64))
+			},
+			I32AtomicRmwCmpxchg(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32, I32, I32], Some(I32)) },
+			I32AtomicRmwCmpxchg8u(ref arg) => { self.atomic(arg.align as u32, 0)?; (&[I32, I32, I32], Some(I32)) },
+			I32AtomicRmwCmpxchg16u(ref arg) => { self.atomic(arg.align as u32, 1)?; (&[I32, I32, I32], Some(I32)) },
+			I64AtomicRmwCmpxchg(ref arg) => { self.atomic(arg.align as u32, 3)?; (&[I32, I64, I64], Some(I64)) },
+			I64AtomicRmwCmpxchg8u(ref arg) => { self.atomic(arg.align as u32, 0)?; (&[I32, I64, I64], Some(I64)) },
+			I64AtomicRmwCmpxchg16u(ref arg) => { self.atomic(arg.align as u32, 1)?; (&[I32, I64, I64], Some(I64)) },
+			I64AtomicRmwCmpxchg32u(ref arg) => { self.atomic(arg.align as u32, 2)?; (&[I32, I64, I64], Some(I64)) },
+
+			V128Load(ref arg) => {
+				self.feature(features.simd, "simd")?;
+				self.access(arg.align as u32, 4)?;
+				(&[I32], Some(V128))
+			},
+			V128Store(ref arg) => {
+				self.feature(features.simd, "simd")?;
+				self.access(arg.align as u32, 4)?;
+				(&[I32, V128], None)
+			},
+			I8x16ExtractLaneS(lane) | I8x16ExtractLaneU(lane) => { self.lane(lane, 16)?; (&[V128], Some(I32)) },
+			I16x8ExtractLaneS(lane) | I16x8ExtractLaneU(lane) => { self.lane(lane, 8)?; (&[V128], Some(I32)) },
+			I32x4ExtractLane(lane) => { self.lane(lane, 4)?; (&[V128], Some(I32)) },
+			I64x2ExtractLane(lane) => { self.lane(lane, 2)?; (&[V128], Some(I64)) },
+			F32x4ExtractLane(lane) => { self.lane(lane, 4)?; (&[V128], Some(F32)) },
+			F64x2ExtractLane(lane) => { self.lane(lane, 2)?; (&[V128], Some(F64)) },
+			I8x16ReplaceLane(lane) => { self.lane(lane, 16)?; (&[V128, I32], Some(V128)) },
+			I16x8ReplaceLane(lane) => { self.lane(lane, 8)?; (&[V128, I32], Some(V128)) },
+			I32x4ReplaceLane(lane) => { self.lane(lane, 4)?; (&[V128, I32], Some(V128)) },
+			I64x2ReplaceLane(lane) => { self.lane(lane, 2)?; (&[V128, I64], Some(V128)) },
+			F32x4ReplaceLane(lane) => { self.lane(lane, 4)?; (&[V128, F32], Some(V128)) },
+			F64x2ReplaceLane(lane) => { self.lane(lane, 2)?; (&[V128, F64], Some(V128)) },
+			V8x16Shuffle(ref lanes) => {
+				for &lane in lanes.iter() {
+					self.lane(lane, 32)?;
+				}
+				(&[V128, V128], Some(V128))
+			},
+			_ => match self.simd_operator(instruction) {
+				Some(signature) => {
+					self.feature(features.simd, "simd")?;
+					signature
+				},
+				None => return self.bulk_operator(instruction),
+			},
+		})
+	}
+
+	fn simd_operator(&self, instruction: &Instruction) -> Option<(&'static [ValueType], Option<ValueType>)> {
+		Some(match *instruction {
+			V128Const(_) => (&[], Some(V128)),
+			I8x16Splat | I16x8Splat | I32x4Splat => (&[I32], Some(V128)),
+			I64x2Splat => (&[I64], Some(V128)),
+			F32x4Splat => (&[F32], Some(V128)),
+			F64x2Splat => (&[F64], Some(V128)),
+
+			I8x16Add | I16x8Add | I32x4Add | I64x2Add
+				| I8x16Sub | I16x8Sub | I32x4Sub | I64x2Sub
+				| I8x16Mul | I16x8Mul | I32x4Mul
+				| I8x16AddSaturateS | I8x16AddSaturateU | I16x8AddSaturateS | I16x8AddSaturateU
+				| I8x16SubSaturateS | I8x16SubSaturateU | I16x8SubSaturateS | I16x8SubSaturateU
+				| V128And | V128Or | V128Xor
+				| I8x16Eq | I16x8Eq | I32x4Eq | F32x4Eq | F64x2Eq
+				| I8x16Ne | I16x8Ne | I32x4Ne | F32x4Ne | F64x2Ne
+				| I8x16LtS | I8x16LtU | I16x8LtS | I16x8LtU | I32x4LtS | I32x4LtU | F32x4Lt | F64x2Lt
+				| I8x16LeS | I8x16LeU | I16x8LeS | I16x8LeU | I32x4LeS | I32x4LeU | F32x4Le | F64x2Le
+				| I8x16GtS | I8x16GtU | I16x8GtS | I16x8GtU | I32x4GtS | I32x4GtU | F32x4Gt | F64x2Gt
+				| I8x16GeS | I8x16GeU | I16x8GeS | I16x8GeU | I32x4GeS | I32x4GeU | F32x4Ge | F64x2Ge
+				| F32x4Min | F64x2Min | F32x4Max | F64x2Max
+				| F32x4Add | F64x2Add | F32x4Sub | F64x2Sub
+				| F32x4Div | F64x2Div | F32x4Mul | F64x2Mul => (&[V128, V128], Some(V128)),
+			I8x16Neg | I16x8Neg | I32x4Neg | I64x2Neg | V128Not
+				| F32x4Neg | F64x2Neg | F32x4Abs | F64x2Abs | F32x4Sqrt | F64x2Sqrt
+				| F32x4ConvertSI32x4 | F32x4ConvertUI32x4 | F64x2ConvertSI64x2 | F64x2ConvertUI64x2
+				| I32x4TruncSF32x4Sat | I32x4TruncUF32x4Sat | I64x2TruncSF64x2Sat | I64x2TruncUF64x2Sat => (&[V128], Some(V128)),
+			I8x16Shl | I16x8Shl | I32x4Shl | I64x2Shl
+				| I8x16ShrS | I8x16ShrU | I16x8ShrS | I16x8ShrU
+				| I32x4ShrS | I32x4ShrU | I64x2ShrS | I64x2ShrU => (&[V128, I32], Some(V128)),
+			V128Bitselect => (&[V128, V128, V128], Some(V128)),
+			I8x16AnyTrue | I16x8AnyTrue | I32x4AnyTrue | I64x2AnyTrue
+				| I8x16AllTrue | I16x8AllTrue | I32x4AllTrue | I64x2AllTrue => (&[V128], Some(I32)),
+			_ => return None,
+		})
+	}
+
+	fn bulk_operator(&self, instruction: &Instruction) -> Result<(&'static [ValueType], Option<ValueType>), String> {
+		self.feature(self.context.features.bulk_memory, "bulk memory")?;
+		match *instruction {
+			MemoryInit(index) => {
+				self.memory()?;
+				self.data_segment(index)?;
+			},
+			MemoryDrop(index) => self.data_segment(index)?,
+			MemoryCopy | MemoryFill => self.memory()?,
+			TableInit(index, table) => {
+				self.table_index(table)?;
+				self.element_segment(index)?;
+				// Element segments hold function indices.
+				if self.context.tables[table as usize] != TableElementType::AnyFunc {
+					return Err(format!("table {} does not hold function references", table));
+				}
+			},
+			TableDrop(index) => self.element_segment(index)?,
+			TableCopy(destination, source) => {
+				self.table_index(destination)?;
+				self.table_index(source)?;
+				if self.context.tables[destination as usize] != self.context.tables[source as usize] {
+					return Err(format!("tables {} and {} have different element types", destination, source));
+				}
+			},
+			_ => unreachable!("control and variable instructions are handled by `step`; qed"),
+		}
+		Ok(match *instruction {
+			MemoryDrop(_) | TableDrop(_) => (&[], None),
+			_ => (&[I32, I32, I32], None),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::vec::Vec;
+	use elements::{Module, Section, TypeSection, FunctionSection, Func, FuncBody, Instructions, Instruction, BrTableData, BlockType, FunctionType, Type, ValueType};
+	use elements::Instruction::*;
+	use elements::ValueType::*;
+	use super::super::{Error, Features};
+	use super::{check_body, Context};
+
+	/// Type checks `code` as the body of a function of the first of `types`.
+	fn check(types: Vec<FunctionType>, features: Features, code: Vec<Instruction>) -> Result<(), Error> {
+		let module = Module::new(vec![
+			Section::Type(TypeSection::with_types(types.into_iter().map(Type::Function).collect())),
+			Section::Function(FunctionSection::with_entries(vec![Func::new(0)])),
+		]);
+		let context = Context::new(&module, &features);
+		check_body(&context, 0, &FuncBody::new(Vec::new(), Instructions::new(code)))
+	}
+
+	fn returning(results: Vec<ValueType>) -> Vec<FunctionType> {
+		vec![FunctionType::with_results(vec![I32], results)]
+	}
+
+	#[test]
+	fn blocks() {
+		let ok = |results, code| check(returning(results), Features::default(), code).is_ok();
+
+		assert!(ok(vec![I32], vec![Block(BlockType::Value(I32)), I32Const(1), End, End]));
+		assert!(!ok(vec![I32], vec![Block(BlockType::Value(I64)), I32Const(1), End, End]));
+		assert!(!ok(vec![], vec![Block(BlockType::NoResult), I32Const(1), End, End]));
+		// Branches to a loop go to its start, which takes no operand.
+		assert!(ok(vec![], vec![Loop(BlockType::NoResult), GetLocal(0), BrIf(0), End, End]));
+		assert!(ok(vec![I32], vec![Loop(BlockType::Value(I32)), Br(0), End, End]));
+		assert!(!ok(vec![I32], vec![Block(BlockType::Value(I32)), Br(0), End, End]));
+		assert!(ok(vec![I32], vec![GetLocal(0), If(BlockType::Value(I32)), I32Const(1), Else, I32Const(2), End, End]));
+		assert!(!ok(vec![I32], vec![GetLocal(0), If(BlockType::Value(I32)), I32Const(1), Else, I64Const(2), End, End]));
+		// An if without else must leave its parameters as results.
+		assert!(!ok(vec![I32], vec![GetLocal(0), If(BlockType::Value(I32)), I32Const(1), End, End]));
+		assert!(!ok(vec![], vec![I64Const(0), If(BlockType::NoResult), End, End]));
+		assert!(!ok(vec![], vec![Else, End]));
+		assert!(!ok(vec![], vec![Block(BlockType::NoResult), End]));
+		assert!(!ok(vec![], vec![End, Nop]));
+	}
+
+	#[test]
+	fn unreachable() {
+		let ok = |results, code| check(returning(results), Features::default(), code).is_ok();
+
+		// The stack is polymorphic after an unconditional branch.
+		assert!(ok(vec![I32], vec![Unreachable, End]));
+		assert!(ok(vec![I32], vec![Unreachable, I32Add, End]));
+		assert!(ok(vec![I32], vec![Block(BlockType::Value(I64)), Unreachable, End, Drop, I32Const(0), End]));
+		assert!(ok(vec![], vec![Return, Select, Drop, End]));
+		// Operands pushed after the branch are still type checked.
+		assert!(!ok(vec![I32], vec![Unreachable, I64Const(0), End]));
+		assert!(!ok(vec![I32], vec![Unreachable, I64Const(0), I32Add, End]));
+		// Only the rest of the block is unreachable.
+		assert!(!ok(vec![I32], vec![Block(BlockType::NoResult), Unreachable, End, End]));
+	}
+
+	#[test]
+	fn br_table() {
+		let ok = |code| check(returning(vec![I32]), Features::default(), code).is_ok();
+		let table = |table: Vec<u32>, default| BrTable(Box::new(BrTableData { table: table.into_boxed_slice(), default }));
+
+		assert!(ok(vec![
+			Block(BlockType::Value(I32)), I32Const(1), GetLocal(0), table(vec![0, 1], 0), End, End,
+		]));
+		// The targets expect a different number of operands.
+		assert!(!ok(vec![
+			Block(BlockType::NoResult), I32Const(1), GetLocal(0), table(vec![0], 1), End, I32Const(0), End,
+		]));
+		assert!(!ok(vec![
+			Block(BlockType::Value(I32)), Block(BlockType::NoResult), I32Const(1), GetLocal(0), table(vec![1], 0), End, End, End,
+		]));
+		// Or operands of different types.
+		assert!(!ok(vec![
+			Block(BlockType::Value(I64)), I64Const(1), GetLocal(0), table(vec![0], 1), End, Drop, I32Const(0), End,
+		]));
+		assert!(!ok(vec![Block(BlockType::NoResult), GetLocal(0), table(vec![0], 2), End, I32Const(0), End]));
+		assert!(!ok(vec![I32Const(1), I64Const(0), table(Vec::new(), 0), End]));
+	}
+
+	#[test]
+	fn multi_value() {
+		let types = vec![
+			FunctionType::with_results(vec![I32], vec![I32, I64]),
+			FunctionType::with_results(vec![I32, I64], vec![I64]),
+		];
+		let features = Features { multi_value: true, ..Default::default() };
+		let ok = |code| check(types.clone(), features, code).is_ok();
+
+		// The parameters of a block are popped before it and pushed in it.
+		assert!(ok(vec![GetLocal(0), I64Const(1), Block(BlockType::TypeIndex(1)), Drop, Drop, I64Const(2), End, Drop, GetLocal(0), I64Const(3), End]));
+		assert!(ok(vec![GetLocal(0), Block(BlockType::TypeIndex(0)), I64Const(1), End, End]));
+		assert!(!ok(vec![GetLocal(0), I64Const(1), Block(BlockType::TypeIndex(1)), Drop, End, Drop, GetLocal(0), I64Const(2), End]));
+		assert!(!ok(vec![I64Const(1), GetLocal(0), Block(BlockType::TypeIndex(1)), Drop, End, Drop, GetLocal(0), I64Const(2), End]));
+		assert!(!ok(vec![Block(BlockType::TypeIndex(1)), Drop, End, Drop, GetLocal(0), I64Const(2), End]));
+		// Branches to a loop take its parameters.
+		assert!(ok(vec![GetLocal(0), Loop(BlockType::TypeIndex(0)), GetLocal(0), Br(0), End, End]));
+		assert!(!ok(vec![GetLocal(0), Loop(BlockType::TypeIndex(0)), I64Const(0), Br(0), End, End]));
+		// An if without else forwards its parameters, which must match its results.
+		assert!(!ok(vec![GetLocal(0), GetLocal(0), If(BlockType::TypeIndex(0)), End, End]));
+		assert!(ok(vec![GetLocal(0), GetLocal(0), If(BlockType::TypeIndex(0)), I64Const(0), Else, I64Const(1), End, End]));
+		assert!(check(types, Features::default(), vec![GetLocal(0), GetLocal(0), If(BlockType::TypeIndex(0)), I64Const(0), Else, I64Const(1), End, End]).is_err());
+	}
+
+	#[test]
+	fn operand_types() {
+		let module = Module::new(vec![
+			Section::Type(TypeSection::with_types(vec![Type::Function(FunctionType::with_results(vec![I32], vec![I32]))])),
+			Section::Function(FunctionSection::with_entries(vec![Func::new(0)])),
+		]);
+		let features = Features::default();
+		let context = Context::new(&module, &features);
+		let body = |code| FuncBody::new(Vec::new(), Instructions::new(code));
+
+		let types = super::operand_types(&context, 0, &body(vec![GetLocal(0), I64Const(2), Drop, End])).expect("valid body");
+		assert_eq!(types.before(0), Some(&[][..]));
+		assert_eq!(types.before(1), Some(&[Some(I32)][..]));
+		assert_eq!(types.before(2), Some(&[Some(I32), Some(I64)][..]));
+		assert_eq!(types.after(2), Some(&[Some(I32)][..]));
+		assert_eq!(types.after(3), Some(&[Some(I32)][..]));
+		assert_eq!(types.before(4), None);
+		assert_eq!(types.after(4), None);
+
+		// Operands popped from the polymorphic stack of unreachable code have no type.
+		let types = super::operand_types(&context, 0, &body(vec![
+			Block(BlockType::NoResult), Unreachable, Select, Drop, End, GetLocal(0), End,
+		])).expect("valid body");
+		assert_eq!(types.before(2), Some(&[][..]));
+		assert_eq!(types.before(3), Some(&[None][..]));
+		assert_eq!(types.before(6), Some(&[Some(I32)][..]));
+
+		assert!(super::operand_types(&context, 0, &body(vec![I64Const(0), End])).is_err());
+	}
+}
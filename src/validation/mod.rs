@@ -0,0 +1,727 @@
+//! Validation of modules against the rules of the specification.
+
+use std::fmt;
+use std::ops::Deref;
+use std::string::String;
+use std::vec::Vec;
+use elements::{Module, Type, External, Internal, ImportCountType, Instruction, ValueType, InitExpr, FuncBody, TableElementType};
+
+mod func;
+#[cfg(feature = "std")]
+mod report;
+
+pub use self::func::OperandTypes;
+#[cfg(feature = "std")]
+pub use self::report::{load_with_report, LoadError, LoadReport, SectionReport, FunctionReport};
+
+/// Error produced by validation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(String);
+
+impl Error {
+	/// Description of the rule which was violated.
+	pub fn message(&self) -> &str {
+		&self.0
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {
+	fn description(&self) -> &str {
+		&self.0
+	}
+}
+
+/// WebAssembly proposals which relax the rules checked by the validator.
+///
+/// The default is the MVP specification with no proposal enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Features {
+	/// Import and export of mutable globals.
+	pub mutable_globals: bool,
+	/// Passive data and element segments.
+	pub bulk_memory: bool,
+	/// More than one table and declared element segments.
+	pub reference_types: bool,
+	/// More than one memory.
+	pub multi_memory: bool,
+	/// Functions and blocks returning several values, and blocks taking
+	/// parameters.
+	pub multi_value: bool,
+	/// 128-bit SIMD instructions.
+	pub simd: bool,
+	/// Atomic memory instructions.
+	pub threads: bool,
+	/// Memories with one byte pages.
+	pub custom_page_sizes: bool,
+}
+
+macro_rules! bail {
+	($($arg:tt)*) => { return Err(Error(format!($($arg)*))) }
+}
+
+/// Validates the module-level structure of `module` against the MVP
+/// specification extended with `features`.
+///
+/// Checks that function types have at most one result (unless multi-value
+/// is enabled) and no reference values (unless reference types are
+/// enabled), that the start function exists and takes and returns nothing,
+/// that export names are unique and exported or imported globals are
+/// immutable (unless mutable globals are enabled), that there is at most one
+/// table and one memory and no externref table (unless reference types or
+/// multi-memory are enabled), that data and element segments only use the features
+/// enabled and refer to existing memories and tables, and that the data
+/// count section matches the data section and is present when function
+/// bodies refer to data segments.
+pub fn validate_module(module: &Module, features: &Features) -> Result<(), Error> {
+	validate_types(module, features)?;
+	validate_start(module)?;
+	validate_imports_exports(module, features)?;
+
+	let tables = module.table_space();
+	if tables > 1 && !features.reference_types {
+		bail!("multiple tables ({}) require the reference types proposal", tables);
+	}
+	let externref_table = module.import_section().map_or(&[][..], |is| is.entries()).iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Table(ref table_type) => Some(table_type.elem_type()),
+			_ => None,
+		})
+		.chain(module.table_section().map_or(&[][..], |ts| ts.entries()).iter().map(|table| table.elem_type()))
+		.any(|elem_type| elem_type == TableElementType::ExternRef);
+	if externref_table && !features.reference_types {
+		bail!("externref tables require the reference types proposal");
+	}
+	let memories = module.memory_space();
+	if memories > 1 && !features.multi_memory {
+		bail!("multiple memories ({}) require the multi-memory proposal", memories);
+	}
+
+	if let Some(data) = module.data_section() {
+		for (idx, segment) in data.entries().iter().enumerate() {
+			if segment.passive() {
+				if !features.bulk_memory {
+					bail!("data segment {} is passive, which requires the bulk memory proposal", idx);
+				}
+			} else if segment.index() as usize >= memories {
+				bail!("data segment {} refers to memory {} which does not exist", idx, segment.index());
+			}
+		}
+	}
+
+	validate_data_count(module, features)?;
+
+	if let Some(elements) = module.elements_section() {
+		let functions = module.functions_space();
+		for (idx, segment) in elements.entries().iter().enumerate() {
+			if segment.declared() {
+				if !features.reference_types {
+					bail!("element segment {} is declared, which requires the reference types proposal", idx);
+				}
+			} else if segment.passive() {
+				if !features.bulk_memory {
+					bail!("element segment {} is passive, which requires the bulk memory proposal", idx);
+				}
+			} else if segment.index() as usize >= tables {
+				bail!("element segment {} refers to table {} which does not exist", idx, segment.index());
+			}
+			if let Some(&func) = segment.members().iter().find(|&&func| func as usize >= functions) {
+				bail!("element segment {} refers to function {} which does not exist", idx, func);
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn validate_types(module: &Module, features: &Features) -> Result<(), Error> {
+	for (idx, ty) in module.type_section().map_or(&[][..], |ts| ts.types()).iter().enumerate() {
+		let Type::Function(ref signature) = *ty;
+		if signature.results().len() > 1 && !features.multi_value {
+			bail!("type {} has several results, which requires the multi-value proposal", idx);
+		}
+		let mut value_types = signature.params().iter().chain(signature.results());
+		if value_types.any(|&value_type| value_type == ValueType::FuncRef || value_type == ValueType::ExternRef) && !features.reference_types {
+			bail!("type {} has reference values, which requires the reference types proposal", idx);
+		}
+	}
+	Ok(())
+}
+
+fn validate_start(module: &Module) -> Result<(), Error> {
+	let start = match module.start_section() {
+		Some(start) => start,
+		None => return Ok(()),
+	};
+
+	let imported = module.import_count(ImportCountType::Function);
+	let type_ref = if (start as usize) < imported {
+		module.import_section()
+			.and_then(|is| is.entries().iter()
+				.filter_map(|entry| match *entry.external() {
+					External::Function(type_ref) => Some(type_ref),
+					_ => None,
+				})
+				.nth(start as usize))
+	} else {
+		module.function_section()
+			.and_then(|fs| fs.entries().get(start as usize - imported))
+			.map(|func| func.type_ref())
+	};
+	let type_ref = match type_ref {
+		Some(type_ref) => type_ref,
+		None => bail!("start function {} does not exist", start),
+	};
+
+	let Type::Function(ref signature) = *module.type_section()
+		.and_then(|ts| ts.types().get(type_ref as usize))
+		.ok_or_else(|| Error(format!("type {} of the start function does not exist", type_ref)))?;
+	if !signature.params().is_empty() || !signature.results().is_empty() {
+		bail!("start function {} must take no arguments and return nothing", start);
+	}
+	Ok(())
+}
+
+/// Module which passed `validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidatedModule {
+	module: Module,
+	operand_types: Option<Vec<OperandTypes>>,
+}
+
+impl ValidatedModule {
+	/// Validated module.
+	pub fn module(&self) -> &Module {
+		&self.module
+	}
+
+	/// Operand stack types of each function body, in the order of the code
+	/// section, if recorded by `validate_with_operand_types`.
+	pub fn operand_types(&self) -> Option<&[OperandTypes]> {
+		self.operand_types.as_ref().map(|types| &types[..])
+	}
+
+	/// Destructures into the validated module.
+	pub fn into_module(self) -> Module {
+		self.module
+	}
+}
+
+impl Deref for ValidatedModule {
+	type Target = Module;
+
+	fn deref(&self) -> &Module {
+		&self.module
+	}
+}
+
+/// Validates `module` against the MVP specification extended with
+/// `features`, type checking its function bodies.
+///
+/// Besides the module-level structure checked by `validate_module`, checks
+/// the limits of the memories and tables, the constant expressions of
+/// globals and segments, and runs the type checking algorithm of the
+/// specification on every function body. Errors in function bodies give the
+/// index of the function (imported functions included) and of the
+/// offending instruction.
+pub fn validate(module: Module, features: &Features) -> Result<ValidatedModule, Error> {
+	validate_with(module, features, false, |_, _, check| check())
+}
+
+/// Same as `validate`, also recording the types of the operand stack at
+/// each program point of the function bodies, see
+/// `ValidatedModule::operand_types`.
+pub fn validate_with_operand_types(module: Module, features: &Features) -> Result<ValidatedModule, Error> {
+	validate_with(module, features, true, |_, _, check| check())
+}
+
+/// Same as `validate`, but type checks function bodies through
+/// `check_function`, called with the index of the function, its body and
+/// the closure type checking it. Operand types are recorded if `record`.
+fn validate_with<F>(module: Module, features: &Features, record: bool, mut check_function: F) -> Result<ValidatedModule, Error>
+	where F: FnMut(u32, &FuncBody, &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error>
+{
+	validate_module(&module, features)?;
+	validate_limits(&module, features)?;
+
+	let context = func::Context::new(&module, features);
+	validate_const_exprs(&module, &context)?;
+
+	let signatures = module.function_section().map_or(0, |fs| fs.entries().len());
+	let bodies = module.code_section().map_or(&[][..], |cs| cs.bodies());
+	if signatures != bodies.len() {
+		bail!("{} function signatures but {} function bodies", signatures, bodies.len());
+	}
+	let imported = module.import_count(ImportCountType::Function);
+	let mut operand_types = Vec::new();
+	for (idx, body) in bodies.iter().enumerate() {
+		let index = (imported + idx) as u32;
+		let mut types = None;
+		check_function(index, body, &mut || if record {
+			types = Some(func::operand_types(&context, index, body)?);
+			Ok(())
+		} else {
+			func::check_body(&context, index, body)
+		})?;
+		operand_types.extend(types);
+	}
+
+	let operand_types = if record { Some(operand_types) } else { None };
+	Ok(ValidatedModule { module, operand_types })
+}
+
+fn validate_limits(module: &Module, features: &Features) -> Result<(), Error> {
+	let imports = module.import_section().map_or(&[][..], |is| is.entries());
+	let memories = imports.iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Memory(ref memory_type) => Some(memory_type.limits()),
+			_ => None,
+		})
+		.chain(module.memory_section().map_or(&[][..], |ms| ms.entries()).iter().map(|memory_type| memory_type.limits()));
+	for (idx, limits) in memories.enumerate() {
+		if limits.is_64() {
+			bail!("memory {} is 64-bit, which is not supported", idx);
+		}
+		let checked = match limits.page_size_log2() {
+			None => func::check_memory_limits(limits.initial(), limits.maximum()),
+			Some(_) if !features.custom_page_sizes => bail!("memory {} has a custom page size, which is not enabled", idx),
+			Some(16) => func::check_memory_limits(limits.initial(), limits.maximum()),
+			// Sizes of one byte pages are bounded by the 32-bit encoding.
+			Some(0) => func::check_limits(limits.initial(), limits.maximum(), None),
+			Some(log2) => bail!("memory {} has a page size of 2^{} bytes, which is neither 1 nor 65536", idx, log2),
+		};
+		if let Err(message) = checked {
+			bail!("memory {}: {}", idx, message);
+		}
+	}
+
+	let tables = imports.iter()
+		.filter_map(|entry| match *entry.external() {
+			External::Table(ref table_type) => Some(table_type.limits()),
+			_ => None,
+		})
+		.chain(module.table_section().map_or(&[][..], |ts| ts.entries()).iter().map(|table_type| table_type.limits()));
+	for (idx, limits) in tables.enumerate() {
+		if limits.is_64() {
+			bail!("table {} is 64-bit, which is not supported", idx);
+		}
+		if let Err(message) = func::check_limits(limits.initial(), limits.maximum(), None) {
+			bail!("table {}: {}", idx, message);
+		}
+	}
+	Ok(())
+}
+
+fn validate_const_exprs(module: &Module, context: &func::Context) -> Result<(), Error> {
+	if let Some(globals) = module.global_section() {
+		let imported = module.import_count(ImportCountType::Global);
+		for (idx, global) in globals.entries().iter().enumerate() {
+			let expected = global.global_type().content_type();
+			match context.const_expr(global.init_expr()) {
+				Ok(actual) if actual == expected => {},
+				Ok(actual) => bail!("global {} of type {} is initialized with {}", imported + idx, expected, actual),
+				Err(message) => bail!("global {}: {}", imported + idx, message),
+			}
+		}
+	}
+
+	if let Some(data) = module.data_section() {
+		for (idx, segment) in data.entries().iter().enumerate() {
+			validate_offset(context, "data", idx, segment.offset())?;
+		}
+	}
+	if let Some(elements) = module.elements_section() {
+		for (idx, segment) in elements.entries().iter().enumerate() {
+			validate_offset(context, "element", idx, segment.offset())?;
+		}
+	}
+	Ok(())
+}
+
+fn validate_offset(context: &func::Context, kind: &str, idx: usize, offset: &Option<InitExpr>) -> Result<(), Error> {
+	if let Some(ref offset) = *offset {
+		match context.const_expr(offset) {
+			Ok(ValueType::I32) => {},
+			Ok(actual) => bail!("{} segment {} has an offset of type {}", kind, idx, actual),
+			Err(message) => bail!("{} segment {}: {}", kind, idx, message),
+		}
+	}
+	Ok(())
+}
+
+fn validate_data_count(module: &Module, features: &Features) -> Result<(), Error> {
+	let segments = module.data_section().map(|ds| ds.entries().len()).unwrap_or(0);
+	if let Some(count) = module.data_count_section() {
+		if !features.bulk_memory {
+			bail!("data count section requires the bulk memory proposal");
+		}
+		if count as usize != segments {
+			bail!("data count section declares {} data segments but there are {}", count, segments);
+		}
+		return Ok(());
+	}
+
+	let bodies = module.code_section().map(|cs| cs.bodies()).unwrap_or(&[]);
+	for (idx, body) in bodies.iter().enumerate() {
+		let refers_to_data = body.code().elements().iter()
+			.any(|instruction| matches!(*instruction, Instruction::MemoryInit(_) | Instruction::MemoryDrop(_)));
+		if refers_to_data {
+			bail!("function body {} refers to data segments, which requires the data count section", idx);
+		}
+	}
+	Ok(())
+}
+
+fn validate_imports_exports(module: &Module, features: &Features) -> Result<(), Error> {
+	if let Some(imports) = module.import_section() {
+		for entry in imports.entries() {
+			if let External::Global(ref global_type) = *entry.external() {
+				if global_type.is_mutable() && !features.mutable_globals {
+					bail!("import of mutable global {}.{} requires the mutable globals proposal", entry.module(), entry.field());
+				}
+			}
+		}
+	}
+
+	let exports = match module.export_section() {
+		Some(exports) => exports.entries(),
+		None => return Ok(()),
+	};
+
+	let imported_globals: Vec<bool> = module.import_section()
+		.map(|is| is.entries().iter().filter_map(|entry| match *entry.external() {
+			External::Global(ref global_type) => Some(global_type.is_mutable()),
+			_ => None,
+		}).collect())
+		.unwrap_or_default();
+
+	for (idx, entry) in exports.iter().enumerate() {
+		if exports[..idx].iter().any(|other| other.field() == entry.field()) {
+			bail!("duplicate export name {}", entry.field());
+		}
+
+		let (index, space) = match *entry.internal() {
+			Internal::Function(index) => (index, module.functions_space()),
+			Internal::Table(index) => (index, module.table_space()),
+			Internal::Memory(index) => (index, module.memory_space()),
+			Internal::Global(index) => (index, module.globals_space()),
+		};
+		if index as usize >= space {
+			bail!("export {} refers to an entity which does not exist", entry.field());
+		}
+
+		if let Internal::Global(index) = *entry.internal() {
+			let mutable = match imported_globals.get(index as usize) {
+				Some(&mutable) => mutable,
+				None => module.global_section()
+					.and_then(|gs| gs.entries().get(index as usize - imported_globals.len()))
+					.map(|global| global.global_type().is_mutable())
+					.unwrap_or(false),
+			};
+			if mutable && !features.mutable_globals {
+				bail!("export of mutable global {} requires the mutable globals proposal", entry.field());
+			}
+		}
+	}
+	Ok(())
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use super::{validate_module, validate, validate_with_operand_types, Features};
+	use builder;
+	use elements::{ValueType, Instruction, Instructions, DataSegment, InitExpr, Section, ElementSection, ElementSegment};
+
+	#[test]
+	fn start_signature() {
+		let module = builder::module()
+			.function().main().signature().param().i32().build().body().build().build()
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+
+		let module = builder::module()
+			.function().main().signature().build().body().build().build()
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_ok());
+	}
+
+	#[test]
+	fn mutable_global_export() {
+		let module = builder::module()
+			.global().value_type().i32().mutable().init_expr(Instruction::I32Const(0)).build()
+			.export().field("g").internal().global(0).build()
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+		let features = Features { mutable_globals: true, ..Default::default() };
+		assert!(validate_module(&module, &features).is_ok());
+
+		let module = builder::module()
+			.import().module("env").field("g").external().global(ValueType::I32, true).build()
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+	}
+
+	#[test]
+	fn duplicate_exports() {
+		let module = builder::module()
+			.function().signature().build().body().build().build()
+			.export().field("f").internal().func(0).build()
+			.export().field("f").internal().func(0).build()
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+	}
+
+	#[test]
+	fn memories_and_segments() {
+		let module = builder::module()
+			.memory().build()
+			.memory().build()
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+		let features = Features { multi_memory: true, ..Default::default() };
+		assert!(validate_module(&module, &features).is_ok());
+
+		let module = builder::module()
+			.memory().build()
+			.with_data_segment(DataSegment::new(0, None, vec![1], true))
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+		let features = Features { bulk_memory: true, ..Default::default() };
+		assert!(validate_module(&module, &features).is_ok());
+
+		let module = builder::module()
+			.with_data_segment(DataSegment::new(0, Some(InitExpr::empty()), vec![1], false))
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+	}
+
+	#[test]
+	fn declared_segments() {
+		let declared = |members| Section::Element(ElementSection::with_entries(vec![ElementSegment::new_declared(members)]));
+		let module = builder::module()
+			.function().signature().build().body().build().build()
+			.with_section(declared(vec![0]))
+			.build();
+		assert!(validate_module(&module, &Features::default()).is_err());
+		let features = Features { reference_types: true, ..Default::default() };
+		assert!(validate_module(&module, &features).is_ok());
+
+		let module = builder::module().with_section(declared(vec![0])).build();
+		assert!(validate_module(&module, &features).is_err());
+	}
+
+	#[test]
+	fn data_count() {
+		let features = Features { bulk_memory: true, ..Default::default() };
+		let module = builder::module()
+			.memory().build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Instruction::MemoryDrop(0), Instruction::End])).build()
+				.build()
+			.with_data_segment(DataSegment::new(0, None, vec![1], true));
+		assert!(validate_module(&module.build(), &features).is_ok());
+
+		let mut built = builder::module()
+			.memory().build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Instruction::MemoryDrop(0), Instruction::End])).build()
+				.build()
+			.with_data_count(None)
+			.build();
+		assert!(validate_module(&built, &features).is_err());
+		built.set_data_count_section(1);
+		assert!(validate_module(&built, &features).is_err());
+		built.set_data_count_section(0);
+		assert!(validate_module(&built, &features).is_ok());
+		assert!(validate_module(&built, &Features::default()).is_err());
+	}
+
+	fn body(instructions: Vec<Instruction>) -> Result<(), String> {
+		use elements::Instruction::*;
+		let module = builder::module()
+			.global().value_type().i32().init_expr(I32Const(0)).build()
+			.function().signature().param().i32().return_type().i32().build()
+				.body().with_instructions(Instructions::new(instructions)).build()
+				.build()
+			.build();
+		validate(module, &Features::default()).map(|_| ()).map_err(|e| e.message().to_owned())
+	}
+
+	#[test]
+	fn function_bodies() {
+		use elements::Instruction::*;
+		use elements::BlockType;
+
+		assert_eq!(body(vec![GetLocal(0), End]), Ok(()));
+		assert_eq!(body(vec![I64Const(0), End]),
+			Err("function 0 instruction 1 (end): expected i32 operand, found i64".to_owned()));
+		assert!(body(vec![End]).is_err());
+		assert!(body(vec![GetLocal(0), GetLocal(0), End]).is_err());
+		assert!(body(vec![GetLocal(1), End]).is_err());
+		assert!(body(vec![GetLocal(0), SetGlobal(0), GetLocal(0), End]).is_err());
+		assert!(body(vec![GetLocal(0), End, Nop]).is_err());
+		assert!(body(vec![GetLocal(0)]).is_err());
+
+		// Unreachable code is type checked against a polymorphic stack.
+		assert_eq!(body(vec![Unreachable, I32Add, End]), Ok(()));
+		assert_eq!(body(vec![I32Const(1), Return, F32Add, Drop, I64Const(0), Drop, I32Const(0), End]), Ok(()));
+		assert!(body(vec![Unreachable, I64Const(0), I32Add, End]).is_err());
+
+		// Branches pass the operands expected by their label.
+		assert_eq!(body(vec![Block(BlockType::Value(ValueType::I32)), I32Const(1), Br(0), End, End]), Ok(()));
+		assert!(body(vec![Block(BlockType::Value(ValueType::I32)), Br(0), End, End]).is_err());
+		assert_eq!(body(vec![Loop(BlockType::Value(ValueType::I32)), GetLocal(0), BrIf(0), I32Const(2), End, End]), Ok(()));
+		assert!(body(vec![Block(BlockType::NoResult), GetLocal(0), BrTable(Box::new(::elements::BrTableData {
+			table: Box::new([0]),
+			default: 1,
+		})), End, I32Const(0), End]).is_err());
+		assert!(body(vec![Br(1), End]).is_err());
+
+		assert_eq!(body(vec![GetLocal(0), If(BlockType::Value(ValueType::I32)), I32Const(1), Else, I32Const(2), End, End]), Ok(()));
+		assert!(body(vec![GetLocal(0), If(BlockType::Value(ValueType::I32)), I32Const(1), End, End]).is_err());
+
+		// Memory instructions need a memory.
+		assert!(body(vec![GetLocal(0), I32Load(2, 0), End]).is_err());
+	}
+
+	#[test]
+	fn multi_value() {
+		use elements::Instruction::*;
+		use elements::BlockType;
+
+		let module = |code: Vec<Instruction>| builder::module()
+			.function().signature().param().i32().results().i32().i32().build().build()
+				.body().with_instructions(Instructions::new(code)).build()
+				.build()
+			.function().signature().param().i32().return_type().i32().build()
+				.body().with_instructions(Instructions::new(vec![GetLocal(0), End])).build()
+				.build()
+			.build();
+		let features = Features { multi_value: true, ..Default::default() };
+
+		// Branches to a loop pass its parameters.
+		let looping = module(vec![GetLocal(0), Loop(BlockType::TypeIndex(1)), GetLocal(0), BrIf(0), End, GetLocal(0), End]);
+		assert!(validate(looping.clone(), &Features::default()).is_err());
+		assert!(validate(looping, &features).is_ok());
+
+		let branching = module(vec![GetLocal(0), GetLocal(0), If(BlockType::TypeIndex(1)), End, GetLocal(0), End]);
+		assert!(validate(branching, &features).is_ok());
+		let branching = module(vec![GetLocal(0), If(BlockType::TypeIndex(0)), GetLocal(0), End, End]);
+		assert!(validate(branching, &features).is_err());
+		assert!(validate(module(vec![GetLocal(0), End]), &features).is_err());
+	}
+
+	#[test]
+	fn reference_types() {
+		use elements::Instruction::*;
+		use elements::TableElementType;
+
+		let module = |code: Vec<Instruction>| {
+			let mut module = builder::module()
+				.table().with_min(1).build()
+				.table().with_min(1).build()
+				.function().signature().with_param(ValueType::ExternRef).with_result(ValueType::I32).build()
+					.body().with_instructions(Instructions::new(code)).build()
+					.build()
+				.build();
+			*module.table_section_mut().expect("table section").entries_mut()[1].elem_type_mut() = TableElementType::ExternRef;
+			module
+		};
+		let features = Features { reference_types: true, ..Default::default() };
+
+		let storing = module(vec![
+			I32Const(0), GetLocal(0), TableSet(1),
+			I32Const(0), RefFunc(0), TableSet(0),
+			RefNull(TableElementType::ExternRef), I32Const(1), TableGrow(1), Drop,
+			I32Const(0), TableGet(1), RefIsNull,
+			End,
+		]);
+		assert!(validate(storing.clone(), &Features::default()).is_err());
+		assert!(validate(storing, &features).is_ok());
+		assert!(validate(module(vec![I32Const(0), GetLocal(0), TableSet(0), I32Const(0), End]), &features).is_err());
+		assert!(validate(module(vec![RefFunc(1), RefIsNull, End]), &features).is_err());
+		assert!(validate(module(vec![I32Const(0), RefIsNull, End]), &features).is_err());
+		assert!(validate(module(vec![TableSize(2), End]), &features).is_err());
+		assert!(validate(module(vec![GetLocal(0), RefNull(TableElementType::ExternRef), I32Const(1), TypedSelect(ValueType::ExternRef), RefIsNull, End]), &features).is_ok());
+		assert!(validate(module(vec![GetLocal(0), GetLocal(0), I32Const(1), Select, RefIsNull, End]), &features).is_err());
+		let bulk = Features { bulk_memory: true, ..features };
+		assert!(validate(module(vec![I32Const(0), I32Const(0), I32Const(0), TableCopy(0, 0), I32Const(0), End]), &bulk).is_ok());
+		assert!(validate(module(vec![I32Const(0), I32Const(0), I32Const(0), TableCopy(1, 0), I32Const(0), End]), &bulk).is_err());
+
+		let global = builder::module()
+			.global().with_type(ValueType::FuncRef).init_expr(RefNull(TableElementType::AnyFunc)).build()
+			.build();
+		assert!(validate(global.clone(), &Features::default()).is_err());
+		assert!(validate(global, &features).is_ok());
+	}
+
+	#[test]
+	fn limits_and_const_exprs() {
+		use elements::Instruction::*;
+
+		let module = builder::module()
+			.memory().with_min(2).with_max(Some(1)).build()
+			.build();
+		assert!(validate(module, &Features::default()).is_err());
+
+		let module = builder::module()
+			.memory().with_min(1).build()
+			.function().signature().param().i32().return_type().i32().build()
+				.body().with_instructions(Instructions::new(vec![GetLocal(0), I32Load(2, 0), End])).build()
+				.build()
+			.data().offset(I32Const(0)).value(vec![1]).build()
+			.build();
+		assert!(validate(module.clone(), &Features::default()).is_ok());
+		let mut invalid = module.clone();
+		invalid.code_section_mut().expect("code section").bodies_mut()[0].code_mut().elements_mut()[1] = I32Load(3, 0);
+		assert!(validate(invalid, &Features::default()).is_err());
+		let mut invalid = module;
+		*invalid.data_section_mut().expect("data section").entries_mut()[0].offset_mut() = Some(InitExpr::new(vec![I64Const(0), End]));
+		assert!(validate(invalid, &Features::default()).is_err());
+
+		let module = builder::module()
+			.global().value_type().i64().init_expr(I32Const(0)).build()
+			.build();
+		assert!(validate(module, &Features::default()).is_err());
+	}
+
+	#[test]
+	fn operand_types() {
+		use elements::Instruction::*;
+
+		let module = builder::module()
+			.import().module("env").field("f").external().func(0).build()
+			.function().signature().param().i32().return_type().i64().build()
+				.body().with_instructions(Instructions::new(vec![GetLocal(0), I64ExtendSI32, End])).build()
+				.build()
+			.build();
+		assert_eq!(validate(module.clone(), &Features::default()).expect("valid module").operand_types(), None);
+
+		let validated = validate_with_operand_types(module, &Features::default()).expect("valid module");
+		let types = validated.operand_types().expect("recorded operand types");
+		assert_eq!(types.len(), 1);
+		assert_eq!(types[0].before(1), Some(&[Some(ValueType::I32)][..]));
+		assert_eq!(types[0].after(1), Some(&[Some(ValueType::I64)][..]));
+	}
+
+	#[test]
+	fn custom_page_sizes() {
+		let paged = |page_size_log2, initial| {
+			let mut module = builder::module().memory().with_min(initial).build().build();
+			*module.memory_section_mut().expect("memory section").entries_mut()[0].limits_mut().page_size_log2_mut() = page_size_log2;
+			module
+		};
+		let features = Features { custom_page_sizes: true, ..Default::default() };
+		assert!(validate(paged(Some(0), 100_000), &Features::default()).is_err());
+		assert!(validate(paged(Some(0), 100_000), &features).is_ok());
+		assert!(validate(paged(Some(16), 1), &features).is_ok());
+		assert!(validate(paged(Some(16), 100_000), &features).is_err());
+		assert!(validate(paged(Some(12), 1), &features).is_err());
+	}
+}
@@ -0,0 +1,265 @@
+//! Loading of modules reporting where the time goes.
+
+use std::cell::Cell;
+use std::fmt;
+use std::time::{Duration, Instant};
+use elements::{self, Module, Section};
+use io;
+use super::{validate_with, Error, Features, ValidatedModule};
+
+/// Error while loading a module with `load_with_report`.
+#[derive(Debug, Clone)]
+pub enum LoadError {
+	/// The module could not be decoded.
+	Deserialize(elements::Error),
+	/// The module is invalid.
+	Invalid(Error),
+}
+
+impl fmt::Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LoadError::Deserialize(ref e) => write!(f, "Failed to decode module: {}", e),
+			LoadError::Invalid(ref e) => write!(f, "Invalid module: {}", e),
+		}
+	}
+}
+
+impl ::std::error::Error for LoadError {
+	fn description(&self) -> &str {
+		match *self {
+			LoadError::Deserialize(_) => "Failed to decode module",
+			LoadError::Invalid(_) => "Invalid module",
+		}
+	}
+}
+
+/// Section read by `load_with_report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionReport {
+	id: u8,
+	name: Option<String>,
+	size: usize,
+	parse_time: Duration,
+}
+
+impl SectionReport {
+	/// Id of the section, `0` for custom sections.
+	pub fn id(&self) -> u8 {
+		self.id
+	}
+
+	/// Name of the section, if it is a custom section.
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_ref().map(|name| &name[..])
+	}
+
+	/// Size of the section in bytes, header included.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// Time spent decoding the section.
+	pub fn parse_time(&self) -> Duration {
+		self.parse_time
+	}
+}
+
+/// Function whose type checking took at least the threshold given to
+/// `load_with_report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionReport {
+	index: u32,
+	instructions: usize,
+	time: Duration,
+}
+
+impl FunctionReport {
+	/// Index of the function, imported functions included.
+	pub fn index(&self) -> u32 {
+		self.index
+	}
+
+	/// Number of instructions of the body.
+	pub fn instructions(&self) -> usize {
+		self.instructions
+	}
+
+	/// Time spent type checking the body.
+	pub fn time(&self) -> Duration {
+		self.time
+	}
+}
+
+/// Timing and size breakdown of loading a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadReport {
+	size: usize,
+	parse_time: Duration,
+	sections: Vec<SectionReport>,
+	validation_time: Duration,
+	functions: usize,
+	functions_time: Duration,
+	slow_functions: Vec<FunctionReport>,
+}
+
+impl LoadReport {
+	/// Size of the binary in bytes.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// Time spent decoding the module.
+	pub fn parse_time(&self) -> Duration {
+		self.parse_time
+	}
+
+	/// Sections of the module, in the order of the binary.
+	pub fn sections(&self) -> &[SectionReport] {
+		&self.sections
+	}
+
+	/// Time spent validating the module, type checking of the function
+	/// bodies included.
+	pub fn validation_time(&self) -> Duration {
+		self.validation_time
+	}
+
+	/// Number of function bodies type checked.
+	pub fn functions(&self) -> usize {
+		self.functions
+	}
+
+	/// Time spent type checking the function bodies.
+	pub fn functions_time(&self) -> Duration {
+		self.functions_time
+	}
+
+	/// Functions whose type checking took at least the threshold, in index
+	/// order.
+	pub fn slow_functions(&self) -> &[FunctionReport] {
+		&self.slow_functions
+	}
+
+	/// Total time spent loading the module.
+	pub fn total_time(&self) -> Duration {
+		self.parse_time + self.validation_time
+	}
+}
+
+/// Reader keeping track of its position in `position`, which stays
+/// accessible while the reader is borrowed by the deserializer.
+struct Tracked<'a> {
+	cursor: io::Cursor<&'a [u8]>,
+	position: &'a Cell<usize>,
+}
+
+impl<'a> io::Read for Tracked<'a> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+		self.cursor.read(buf)?;
+		self.position.set(self.cursor.position());
+		Ok(())
+	}
+}
+
+/// Decodes and validates the module `bytes`, reporting the size and
+/// decoding time of every section, the time spent validating and the
+/// functions whose type checking took at least `threshold`.
+///
+/// Build pipelines can record the report to track how the complexity of
+/// their modules evolves.
+pub fn load_with_report(bytes: &[u8], features: &Features, threshold: Duration) -> Result<(ValidatedModule, LoadReport), LoadError> {
+	let position = Cell::new(0);
+	let mut reader = Tracked { cursor: io::Cursor::new(bytes), position: &position };
+	let mut sections = Vec::new();
+	let start = Instant::now();
+	// Sections start after the magic number and the version.
+	let mut section_start = (start, 8);
+	let module = Module::deserialize_observed(&mut reader, |section| {
+		let now = Instant::now();
+		sections.push(SectionReport {
+			id: section.id(),
+			name: match *section {
+				Section::Custom(ref custom) => Some(custom.name().into()),
+				_ => None,
+			},
+			size: position.get() - section_start.1,
+			parse_time: now - section_start.0,
+		});
+		section_start = (now, position.get());
+	}).map_err(LoadError::Deserialize)?;
+	if position.get() != bytes.len() {
+		return Err(LoadError::Deserialize(io::Error::TrailingData.into()));
+	}
+	let parse_time = start.elapsed();
+
+	let mut functions = 0;
+	let mut functions_time = Duration::from_secs(0);
+	let mut slow_functions = Vec::new();
+	let start = Instant::now();
+	let module = validate_with(module, features, false, |index, body, check| {
+		let start = Instant::now();
+		let result = check();
+		let time = start.elapsed();
+		functions += 1;
+		functions_time += time;
+		if time >= threshold {
+			slow_functions.push(FunctionReport { index, instructions: body.code().elements().len(), time });
+		}
+		result
+	}).map_err(LoadError::Invalid)?;
+	let validation_time = start.elapsed();
+
+	Ok((module, LoadReport {
+		size: bytes.len(),
+		parse_time,
+		sections,
+		validation_time,
+		functions,
+		functions_time,
+		slow_functions,
+	}))
+}
+
+#[cfg(all(test, feature = "builder"))]
+mod tests {
+	use std::time::Duration;
+	use super::{load_with_report, LoadError};
+	use super::super::Features;
+	use builder;
+	use elements::{serialize, Instruction, Instructions, CustomSection, Section};
+
+	#[test]
+	fn report() {
+		let mut module = builder::module()
+			.function().signature().build().body().build().build()
+			.function().signature().build()
+				.body().with_instructions(Instructions::new(vec![Instruction::Nop, Instruction::End])).build()
+				.build()
+			.build();
+		module.sections_mut().push(Section::Custom(CustomSection::new("producers".into(), vec![1, 2, 3])));
+		let bytes = serialize(module).expect("module to be serialized");
+
+		let (module, report) = load_with_report(&bytes, &Features::default(), Duration::from_secs(0))
+			.expect("module to be loaded");
+		assert_eq!(report.size(), bytes.len());
+		assert_eq!(report.sections().iter().map(|section| section.size()).sum::<usize>(), bytes.len() - 8);
+		assert_eq!(report.sections().iter().map(|section| section.id()).collect::<Vec<_>>(), vec![1, 3, 10, 0]);
+		assert_eq!(report.sections()[3].name(), Some("producers"));
+		assert_eq!(report.functions(), 2);
+		assert_eq!(report.slow_functions().iter().map(|f| (f.index(), f.instructions())).collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+		assert!(report.functions_time() <= report.validation_time());
+		assert_eq!(module.sections().len(), 4);
+
+		let (_, report) = load_with_report(&bytes, &Features::default(), Duration::from_secs(60))
+			.expect("module to be loaded");
+		assert!(report.slow_functions().is_empty());
+
+		let mut trailing = bytes.clone();
+		trailing.push(0);
+		match load_with_report(&trailing, &Features::default(), Duration::from_secs(0)) {
+			Err(LoadError::Deserialize(_)) => {},
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+}